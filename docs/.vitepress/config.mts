@@ -26,6 +26,8 @@ export default defineConfig({
         items: [
           { text: "分流控制", link: "/flow" },
           { text: "eBPF 路由", link: "/feature/route.md" },
+          { text: "DNS 服务", link: "/dns/index.md" },
+          { text: "NAT", link: "/feature/nat.md" },
         ],
       },
       {
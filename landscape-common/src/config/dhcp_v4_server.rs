@@ -11,7 +11,8 @@ use crate::LANDSCAPE_DEFAULT_LAN_NAME;
 
 use crate::{
     LANDSCAPE_DEFAULE_LAN_DHCP_RANGE_START, LANDSCAPE_DEFAULE_LAN_DHCP_SERVER_IP,
-    LANDSCAPE_DEFAULT_LAN_DHCP_SERVER_NETMASK, LANDSCAPE_DHCP_DEFAULT_ADDRESS_LEASE_TIME,
+    LANDSCAPE_DEFAULT_LAN_DHCP_SERVER_NETMASK, LANDSCAPE_DEFAULT_LOCAL_DOMAIN,
+    LANDSCAPE_DHCP_DEFAULT_ADDRESS_LEASE_TIME,
 };
 
 #[derive(Debug, Clone, Serialize, Deserialize, TS)]
@@ -73,6 +74,10 @@ pub struct DHCPv4ServerConfig {
     #[serde(default)]
     /// Static MAC --> IP address binding
     pub mac_binding_records: Vec<MacBindingRecord>,
+
+    /// 本地域名 e.g. lan, 设置后将客户端主机名注册到 DNS 中
+    #[serde(default)]
+    pub local_domain: Option<String>,
 }
 
 impl DHCPv4ServerConfig {
@@ -112,6 +117,7 @@ impl Default for DHCPv4ServerConfig {
             network_mask: LANDSCAPE_DEFAULT_LAN_DHCP_SERVER_NETMASK,
             address_lease_time: Some(LANDSCAPE_DHCP_DEFAULT_ADDRESS_LEASE_TIME),
             mac_binding_records: vec![],
+            local_domain: Some(LANDSCAPE_DEFAULT_LOCAL_DOMAIN.into()),
         }
     }
 }
@@ -123,6 +129,9 @@ pub struct MacBindingRecord {
     pub ip: Ipv4Addr,
     #[serde(default = "default_binding_record")]
     pub expire_time: u32,
+    /// 注册到本地域名中的主机名, 未设置时使用客户端上报的主机名
+    #[serde(default)]
+    pub hostname: Option<String>,
 }
 
 const fn default_binding_record() -> u32 {
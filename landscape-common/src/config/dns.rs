@@ -1,10 +1,11 @@
-use std::net::IpAddr;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 
 use serde::{Deserialize, Serialize};
 use ts_rs::TS;
 use uuid::Uuid;
 
 use crate::database::repository::LandscapeDBStore;
+use crate::ip_mark::{IpConfig, WanIPRuleSource};
 use crate::utils::time::get_f64_timestamp;
 use crate::{flow::mark::FlowDnsMark, store::storev2::LandscapeStore};
 
@@ -38,6 +39,9 @@ pub struct DNSRuleConfig {
 
     #[serde(default = "get_f64_timestamp")]
     pub update_at: f64,
+    /// 上游应答过滤
+    #[serde(default)]
+    pub answer_filter: DnsAnswerFilter,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Default)]
@@ -56,8 +60,33 @@ pub struct DNSRuntimeRule {
     pub mark: FlowDnsMark,
     /// 匹配规则列表
     pub source: Vec<DomainConfig>,
+    /// `source` 中每一段对应的配置来源, 用于追踪匹配结果
+    pub source_origins: Vec<RuntimeSourceOrigin>,
+    /// 例外列表, 匹配的域名不由该规则处理 (adblock 中的 `@@` 规则)
+    pub exclude: Vec<DomainConfig>,
 
     pub flow_id: u32,
+    /// `DNSResolveMode::Group` 引用的上游组, 在转换规则时填充
+    pub upstream_group: Option<DnsUpstreamGroupConfig>,
+    /// 上游应答过滤
+    pub answer_filter: DnsAnswerFilter,
+    /// `answer_filter.bogus_sources` 展开后的网段, 在转换规则时填充
+    pub bogus_nets: Vec<IpConfig>,
+}
+
+/// 运行时规则中一段连续的 `source` 对应的配置来源
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RuntimeSourceOrigin {
+    pub source: RuleSource,
+    /// 在 `DNSRuntimeRule.source` 中的结束位置 (不包含)
+    pub end: usize,
+}
+
+impl DNSRuntimeRule {
+    /// 找到 `source` 中第 `index` 条所属的配置来源
+    pub fn source_origin(&self, index: usize) -> Option<&RuleSource> {
+        self.source_origins.iter().find(|origin| index < origin.end).map(|origin| &origin.source)
+    }
 }
 
 fn default_flow_id() -> u32 {
@@ -89,6 +118,7 @@ impl Default for DNSRuleConfig {
             resolve_mode: DNSResolveMode::default(),
             flow_id: default_flow_id(),
             update_at: get_f64_timestamp(),
+            answer_filter: DnsAnswerFilter::default(),
         }
     }
 }
@@ -100,6 +130,14 @@ impl Default for DNSRuleConfig {
 pub enum RuleSource {
     GeoKey(GeoConfigKey),
     Config(DomainConfig),
+    /// 从 URL 下载的 hosts 文件, 定时刷新
+    Hosts {
+        url: String,
+    },
+    /// 从 URL 下载的 adblock 语法列表 (`||example.com^`, `@@` 例外), 定时刷新
+    Adblock {
+        url: String,
+    },
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Hash, PartialEq, Eq, TS)]
@@ -129,8 +167,78 @@ pub enum DomainMatchType {
 #[serde(rename_all = "snake_case")]
 pub enum DNSResolveMode {
     Redirect { ips: Vec<IpAddr> },
-    Upstream { upstream: DnsUpstreamType, ips: Vec<IpAddr>, port: Option<u16> },
+    Upstream {
+        upstream: DnsUpstreamType,
+        ips: Vec<IpAddr>,
+        port: Option<u16>,
+        /// EDNS Client Subnet 处理方式
+        #[serde(default)]
+        ecs: DnsEcsMode,
+    },
     Cloudflare { mode: CloudflareMode },
+    /// 使用上游服务器组
+    Group {
+        group_id: Uuid,
+        /// EDNS Client Subnet 处理方式
+        #[serde(default)]
+        ecs: DnsEcsMode,
+    },
+    /// 拦截, 不向上游查询
+    Block {
+        #[serde(default)]
+        response: DnsBlockResponse,
+    },
+    /// 本地权威记录, 可返回多条任意类型的记录
+    RecordSet {
+        records: Vec<DnsRecordConfig>,
+        #[serde(default = "default_record_ttl")]
+        ttl: u32,
+    },
+}
+
+fn default_record_ttl() -> u32 {
+    300
+}
+
+/// 拦截时返回的应答
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq, TS)]
+#[ts(export, export_to = "common/dns.d.ts")]
+#[serde(rename_all = "snake_case")]
+pub enum DnsBlockResponse {
+    /// 域名不存在
+    #[default]
+    NxDomain,
+    /// 拒绝查询
+    Refused,
+    /// A 返回 0.0.0.0, AAAA 返回 ::
+    Unspecified,
+    /// 返回空应答
+    NoData,
+}
+
+/// 本地记录
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, TS)]
+#[ts(export, export_to = "common/dns.d.ts")]
+pub struct DnsRecordConfig {
+    /// 记录所属的域名, 为空或 `@` 时表示匹配到当前规则的任意域名
+    #[serde(default)]
+    pub name: String,
+    /// 记录内容
+    pub data: DnsRecordData,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, TS)]
+#[ts(export, export_to = "common/dns.d.ts")]
+#[serde(tag = "t")]
+#[serde(rename_all = "snake_case")]
+pub enum DnsRecordData {
+    A { ip: Ipv4Addr },
+    Aaaa { ip: Ipv6Addr },
+    Cname { target: String },
+    Txt { values: Vec<String> },
+    Mx { preference: u16, exchange: String },
+    Srv { priority: u16, weight: u16, port: u16, target: String },
+    Ptr { target: String },
 }
 
 impl Default for DNSResolveMode {
@@ -152,6 +260,101 @@ pub enum DnsUpstreamType {
     Https {
         domain: String,
     }, // DNS over HTTPS (DoH)
+    Quic {
+        domain: String,
+    }, // DNS over QUIC (DoQ, RFC 9250)
+    Http3 {
+        domain: String,
+    }, // DNS over HTTP/3 (DoH3)
+}
+
+impl DnsUpstreamType {
+    pub fn default_port(&self) -> u16 {
+        match self {
+            DnsUpstreamType::Plaintext => 53,
+            DnsUpstreamType::Tls { .. } => 853,
+            DnsUpstreamType::Https { .. } => 443,
+            DnsUpstreamType::Quic { .. } => 853,
+            DnsUpstreamType::Http3 { .. } => 443,
+        }
+    }
+}
+
+/// 上游 DNS 服务器组
+#[derive(Serialize, Deserialize, Debug, Clone, TS)]
+#[ts(export, export_to = "common/dns.d.ts")]
+pub struct DnsUpstreamGroupConfig {
+    pub id: Option<Uuid>,
+    pub name: String,
+    /// 服务器选择策略
+    #[serde(default)]
+    pub strategy: DnsUpstreamStrategy,
+    /// 上游服务器, Failover 时按照列表顺序尝试
+    #[serde(default)]
+    pub servers: Vec<DnsUpstreamServer>,
+    #[serde(default = "get_f64_timestamp")]
+    pub update_at: f64,
+}
+
+impl LandscapeDBStore<Uuid> for DnsUpstreamGroupConfig {
+    fn get_id(&self) -> Uuid {
+        self.id.unwrap_or(Uuid::new_v4())
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq, TS)]
+#[ts(export, export_to = "common/dns.d.ts")]
+#[serde(rename_all = "snake_case")]
+pub enum DnsUpstreamStrategy {
+    /// 按顺序使用第一个健康的服务器, 失败后切换至下一个
+    #[default]
+    Failover,
+    /// 同时向所有健康的服务器发送请求, 使用最快的结果
+    Race,
+    /// 轮流使用组内的服务器
+    RoundRobin,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, TS)]
+#[ts(export, export_to = "common/dns.d.ts")]
+pub struct DnsUpstreamServer {
+    pub upstream: DnsUpstreamType,
+    pub ip: IpAddr,
+    pub port: Option<u16>,
+}
+
+impl DnsUpstreamServer {
+    pub fn port(&self) -> u16 {
+        self.port.unwrap_or(self.upstream.default_port())
+    }
+}
+
+impl std::fmt::Display for DnsUpstreamServer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let addr = std::net::SocketAddr::new(self.ip, self.port());
+        match &self.upstream {
+            DnsUpstreamType::Plaintext => write!(f, "udp://{addr}"),
+            DnsUpstreamType::Tls { domain } => write!(f, "tls://{domain}@{addr}"),
+            DnsUpstreamType::Https { domain } => write!(f, "https://{domain}@{addr}"),
+            DnsUpstreamType::Quic { domain } => write!(f, "quic://{domain}@{addr}"),
+            DnsUpstreamType::Http3 { domain } => write!(f, "h3://{domain}@{addr}"),
+        }
+    }
+}
+
+/// 向上游转发请求时 EDNS Client Subnet (RFC 7871) 的处理方式
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq, TS)]
+#[ts(export, export_to = "common/dns.d.ts")]
+#[serde(rename_all = "snake_case")]
+#[serde(tag = "t")]
+pub enum DnsEcsMode {
+    /// 不向上游发送 ECS
+    #[default]
+    Strip,
+    /// 转发客户端请求中携带的 ECS
+    Forward,
+    /// 使用指定的子网
+    Inject { ip: IpAddr, prefix: u8 },
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, TS)]
@@ -173,6 +376,77 @@ pub enum FilterResult {
     OnlyIPv4,
     #[serde(rename = "only_ipv6")]
     OnlyIPv6,
+    /// 上游没有 AAAA 记录时使用 A 记录合成 (DNS64, RFC 6147), 不过滤记录
+    #[serde(rename = "dns64")]
+    Dns64,
+}
+
+/// 对上游返回的 A / AAAA 记录进行过滤, 被过滤的记录不会写入缓存以及 flow 的标记
+#[derive(Serialize, Deserialize, Debug, Clone, Default, TS)]
+#[ts(export, export_to = "common/dns.d.ts")]
+pub struct DnsAnswerFilter {
+    /// DNS 重绑定保护: 丢弃非本地域名解析到的私有, 回环以及链路本地地址
+    #[serde(default)]
+    pub rebind_protection: bool,
+    /// 允许解析到私有地址的域名, 包含其子域名
+    #[serde(default)]
+    pub rebind_allow_domains: Vec<String>,
+    /// 丢弃落在这些 geoip / CIDR 中的应答, 用于防御 DNS 污染
+    #[serde(default)]
+    pub bogus_sources: Vec<WanIPRuleSource>,
+}
+
+/// flow 内 DNS 请求的限速以及按查询类型处理的策略
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, TS)]
+#[ts(export, export_to = "common/dns.d.ts")]
+pub struct FlowDnsPolicy {
+    /// 每个客户端 IP 每秒允许的请求数, 0 表示不限制
+    #[serde(default)]
+    pub client_qps: u32,
+    /// 每个客户端允许的突发请求数, 0 时与 `client_qps` 相同
+    #[serde(default)]
+    pub client_burst: u32,
+    /// 整个 flow 每秒允许的请求数, 0 表示不限制
+    #[serde(default)]
+    pub flow_qps: u32,
+    /// 整个 flow 允许的突发请求数, 0 时与 `flow_qps` 相同
+    #[serde(default)]
+    pub flow_burst: u32,
+    /// 超过限速后的处理方式
+    #[serde(default)]
+    pub rate_limit_action: DnsRateLimitAction,
+    /// 按查询类型拒绝或者返回空应答
+    #[serde(default)]
+    pub qtype_rules: Vec<DnsQtypeRule>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, TS)]
+#[ts(export, export_to = "common/dns.d.ts")]
+#[serde(rename_all = "snake_case")]
+pub enum DnsRateLimitAction {
+    /// 直接丢弃, 不进行应答
+    #[default]
+    Drop,
+    Refused,
+    ServFail,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, TS)]
+#[ts(export, export_to = "common/dns.d.ts")]
+pub struct DnsQtypeRule {
+    /// 查询类型, 例如 `HTTPS`, `SVCB`, `ANY`, `AAAA`
+    pub qtype: String,
+    pub action: DnsQtypeAction,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, TS)]
+#[ts(export, export_to = "common/dns.d.ts")]
+#[serde(rename_all = "snake_case")]
+pub enum DnsQtypeAction {
+    /// 返回 REFUSED
+    Refused,
+    /// 返回没有记录的 NOERROR (NODATA)
+    Empty,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, TS)]
@@ -181,6 +455,31 @@ pub enum FilterResult {
 pub enum LandscapeDnsRecordType {
     A,
     AAAA,
+    CNAME,
+    TXT,
+    MX,
+    SRV,
+    PTR,
+}
+
+/// 本地区域中自动生成的记录 (DHCP 租约, Docker 容器等)
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, TS)]
+#[ts(export, export_to = "common/dns.d.ts")]
+pub struct LocalZoneRecord {
+    /// 完整域名, 不带末尾的 `.`
+    pub name: String,
+    pub ip: IpAddr,
+    pub source: LocalZoneSource,
+}
+
+/// 本地区域记录的来源, 同一来源的记录会被整体替换
+#[derive(Serialize, Deserialize, Debug, Clone, Hash, PartialEq, Eq, TS)]
+#[ts(export, export_to = "common/dns.d.ts")]
+#[serde(tag = "t")]
+#[serde(rename_all = "snake_case")]
+pub enum LocalZoneSource {
+    Dhcp { iface_name: String },
+    Docker { container_name: String },
 }
 
 #[cfg(test)]
@@ -195,8 +494,53 @@ mod tests {
             upstream: super::DnsUpstreamType::Https { domain: "cloudflare-dns.com".to_string() },
             ips: vec![IpAddr::V4(Ipv4Addr::new(1, 1, 1, 1))],
             port: Some(443),
+            ecs: super::DnsEcsMode::Strip,
         };
         let result = serde_json::to_string(&value).unwrap();
         println!("{result}");
     }
+
+    #[test]
+    fn test_record_set_default_ttl() {
+        let value: DNSResolveMode = serde_json::from_str(
+            r#"{"t":"record_set","records":[{"name":"_sip._tcp.lan","data":{"t":"srv","priority":0,"weight":5,"port":5060,"target":"pbx.lan"}}]}"#,
+        )
+        .unwrap();
+        let DNSResolveMode::RecordSet { records, ttl } = value else {
+            panic!("unexpected resolve mode");
+        };
+        assert_eq!(ttl, 300);
+        assert_eq!(records.len(), 1);
+    }
+
+    #[test]
+    fn test_upstream_default_ecs() {
+        let value: DNSResolveMode = serde_json::from_str(
+            r#"{"t":"upstream","upstream":{"t":"plaintext"},"ips":["1.1.1.1"],"port":53}"#,
+        )
+        .unwrap();
+        let DNSResolveMode::Upstream { ecs, .. } = value else {
+            panic!("unexpected resolve mode");
+        };
+        assert_eq!(ecs, super::DnsEcsMode::Strip);
+    }
+
+    #[test]
+    fn test_upstream_server_label() {
+        let server = super::DnsUpstreamServer {
+            upstream: super::DnsUpstreamType::Tls { domain: "one.one.one.one".to_string() },
+            ip: IpAddr::V4(Ipv4Addr::new(1, 1, 1, 1)),
+            port: None,
+        };
+        assert_eq!(server.to_string(), "tls://one.one.one.one@1.1.1.1:853");
+    }
+
+    #[test]
+    fn test_block_default_response() {
+        let value: DNSResolveMode = serde_json::from_str(r#"{"t":"block"}"#).unwrap();
+        let DNSResolveMode::Block { response } = value else {
+            panic!("unexpected resolve mode");
+        };
+        assert_eq!(response, super::DnsBlockResponse::NxDomain);
+    }
 }
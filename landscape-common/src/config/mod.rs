@@ -16,19 +16,19 @@ pub mod route_lan;
 pub mod route_wan;
 
 use std::{
-    net::{IpAddr, Ipv6Addr},
+    net::{IpAddr, Ipv6Addr, SocketAddr},
     path::PathBuf,
 };
 
 use dhcp_v4_server::DHCPv4ServiceConfig;
 use dhcp_v6_client::IPV6PDServiceConfig;
-use dns::DNSRuleConfig;
+use dns::{DNSRuleConfig, DnsUpstreamGroupConfig, FlowDnsPolicy};
 use firewall::FirewallServiceConfig;
 use flow::FlowWanServiceConfig;
 use iface::NetworkIfaceConfig;
 use iface_ip::IfaceIpServiceConfig;
 use mss_clamp::MSSClampServiceConfig;
-use nat::NatServiceConfig;
+use nat::{NatServiceConfig, PortForwardRuleConfig};
 use ppp::PPPDServiceConfig;
 use ra::IPV6RAServiceConfig;
 use serde::{Deserialize, Serialize};
@@ -46,8 +46,8 @@ use crate::{
     firewall::FirewallRuleConfig,
     flow::FlowConfig,
     ip_mark::WanIpRuleConfig,
-    LANDSCAPE_CONFIG_DIR_NAME, LANDSCAPE_DB_SQLITE_NAME, LANDSCAPE_LOG_DIR_NAME,
-    LANDSCAPE_WEBROOT_DIR_NAME, LAND_CONFIG,
+    LANDSCAPE_CONFIG_DIR_NAME, LANDSCAPE_DB_SQLITE_NAME, LANDSCAPE_DNS_CACHE_DIR_NAME,
+    LANDSCAPE_LOG_DIR_NAME, LANDSCAPE_WEBROOT_DIR_NAME, LAND_CONFIG,
 };
 
 pub type FlowId = u32;
@@ -67,6 +67,8 @@ pub struct InitConfig {
     #[serde(skip_serializing_if = "Vec::is_empty")]
     pub nats: Vec<NatServiceConfig>,
     #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub port_forwards: Vec<PortForwardRuleConfig>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
     pub marks: Vec<FlowWanServiceConfig>,
     #[serde(skip_serializing_if = "Vec::is_empty")]
     pub pppds: Vec<PPPDServiceConfig>,
@@ -75,6 +77,8 @@ pub struct InitConfig {
     #[serde(skip_serializing_if = "Vec::is_empty")]
     pub dns_rules: Vec<DNSRuleConfig>,
     #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub dns_upstream_groups: Vec<DnsUpstreamGroupConfig>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
     pub dst_ip_mark: Vec<WanIpRuleConfig>,
 
     #[serde(skip_serializing_if = "Vec::is_empty")]
@@ -149,6 +153,73 @@ pub struct LandscapeStoreConfig {
     pub database_path: Option<String>,
 }
 
+/// dns realte config
+#[derive(Debug, Serialize, Deserialize, Clone, Default, TS)]
+#[ts(export, export_to = "common/config.d.ts")]
+pub struct LandscapeDnsConfig {
+    /// Enable DNS over TLS for LAN clients
+    pub dot_enable: Option<bool>,
+
+    /// Listen DNS over TLS port
+    pub dot_port: Option<u16>,
+
+    /// Enable DNS over HTTPS (`/dns-query` on the web HTTPS port, LAN clients only)
+    pub doh_enable: Option<bool>,
+
+    /// Prefetch frequently queried cache entries once this fraction of
+    /// their TTL has elapsed, `1.0` or greater disables prefetch
+    pub cache_prefetch_ratio: Option<f32>,
+
+    /// Answer with expired cache entries while refreshing them (RFC 8767)
+    pub cache_serve_stale: Option<bool>,
+
+    /// How long (seconds) an expired entry may still be served
+    pub cache_stale_max_ttl: Option<u32>,
+
+    /// Max cached domains of each flow
+    pub cache_capacity: Option<u32>,
+
+    /// Lower bound (seconds) of cached record TTLs
+    pub cache_min_ttl: Option<u32>,
+
+    /// Upper bound (seconds) of cached record TTLs, also caps negative answers
+    pub cache_max_ttl: Option<u32>,
+
+    /// NAT64 prefix used by DNS64 rules, default `64:ff9b::`
+    pub dns64_prefix: Option<Ipv6Addr>,
+
+    /// NAT64 prefix length, one of 32, 40, 48, 56, 64, 96 (RFC 6052)
+    pub dns64_prefix_len: Option<u8>,
+
+    /// Save the DNS cache to disk on shutdown and periodically, and
+    /// restore it (with the flow DNS marks) on startup
+    pub cache_persist: Option<bool>,
+
+    /// Interval (seconds) between periodic DNS cache snapshots
+    pub cache_snapshot_interval: Option<u64>,
+
+    /// Addresses the DNS server listens on, default `[::]`
+    pub listen_addresses: Option<Vec<IpAddr>>,
+
+    /// Port the DNS server listens on (UDP and TCP), default `53`
+    pub listen_port: Option<u16>,
+
+    /// Only accept queries arriving on these interfaces (`SO_BINDTODEVICE`),
+    /// empty means all interfaces
+    pub listen_interfaces: Option<Vec<String>>,
+
+    /// Point `/etc/resolv.conf` to this DNS server while it is running,
+    /// the original file is restored when the DNS server stops
+    pub takeover_resolv_conf: Option<bool>,
+
+    /// Flow used for lookups made by the router itself (queries from loopback)
+    pub local_flow_id: Option<u32>,
+
+    /// Rate limit and query type policy of the default flow (flow 0),
+    /// other flows are configured in the flow config
+    pub default_flow_policy: Option<FlowDnsPolicy>,
+}
+
 /// Read & Write <CONFIG_PATH>/config.toml
 #[derive(Debug, Serialize, Deserialize, Clone, Default, TS)]
 #[ts(export, export_to = "common/config.d.ts")]
@@ -161,6 +232,8 @@ pub struct LandscapeConfig {
     pub log: LandscapeLogConfig,
     #[serde(default)]
     pub store: LandscapeStoreConfig,
+    #[serde(default)]
+    pub dns: LandscapeDnsConfig,
 }
 
 ///
@@ -174,6 +247,7 @@ pub struct RuntimeConfig {
     pub log: LogRuntimeConfig,
     pub web: WebRuntimeConfig,
     pub store: StoreRuntimeConfig,
+    pub dns: DnsRuntimeConfig,
 }
 
 fn default_home_path() -> PathBuf {
@@ -248,12 +322,68 @@ impl RuntimeConfig {
                 StoreRuntimeConfig::create_default_db_store(&home_path),
             ),
         };
+        let default_dns = DnsRuntimeConfig::default();
+        let dns = DnsRuntimeConfig {
+            dot_enable: config.dns.dot_enable.unwrap_or(default_dns.dot_enable),
+            dot_port: config.dns.dot_port.unwrap_or(default_dns.dot_port),
+            doh_enable: config.dns.doh_enable.unwrap_or(default_dns.doh_enable),
+            cache_prefetch_ratio: config
+                .dns
+                .cache_prefetch_ratio
+                .unwrap_or(default_dns.cache_prefetch_ratio),
+            cache_serve_stale: config
+                .dns
+                .cache_serve_stale
+                .unwrap_or(default_dns.cache_serve_stale),
+            cache_stale_max_ttl: config
+                .dns
+                .cache_stale_max_ttl
+                .unwrap_or(default_dns.cache_stale_max_ttl),
+            cache_capacity: config.dns.cache_capacity.unwrap_or(default_dns.cache_capacity),
+            cache_min_ttl: config.dns.cache_min_ttl.unwrap_or(default_dns.cache_min_ttl),
+            cache_max_ttl: config.dns.cache_max_ttl.unwrap_or(default_dns.cache_max_ttl),
+            dns64_prefix: config.dns.dns64_prefix.unwrap_or(default_dns.dns64_prefix),
+            dns64_prefix_len: config.dns.dns64_prefix_len.unwrap_or(default_dns.dns64_prefix_len),
+            cache_snapshot_dir: if config.dns.cache_persist.unwrap_or(true) {
+                Some(home_path.join(LANDSCAPE_DNS_CACHE_DIR_NAME))
+            } else {
+                None
+            },
+            cache_snapshot_interval: config
+                .dns
+                .cache_snapshot_interval
+                .unwrap_or(default_dns.cache_snapshot_interval),
+            listen_addresses: config
+                .dns
+                .listen_addresses
+                .clone()
+                .filter(|addresses| !addresses.is_empty())
+                .unwrap_or(default_dns.listen_addresses),
+            listen_port: config.dns.listen_port.unwrap_or(default_dns.listen_port),
+            listen_interfaces: config
+                .dns
+                .listen_interfaces
+                .clone()
+                .unwrap_or(default_dns.listen_interfaces),
+            takeover_resolv_conf: config
+                .dns
+                .takeover_resolv_conf
+                .unwrap_or(default_dns.takeover_resolv_conf),
+            local_flow_id: config.dns.local_flow_id.unwrap_or(default_dns.local_flow_id),
+            default_flow_policy: config
+                .dns
+                .default_flow_policy
+                .clone()
+                .unwrap_or(default_dns.default_flow_policy),
+        };
+
         let runtime_config = RuntimeConfig {
             home_path,
             auth,
             log,
             web,
             store,
+            dns,
             file_config: config,
         };
 
@@ -289,7 +419,15 @@ impl RuntimeConfig {
          Listen HTTPS on: https://{}\n\
          \n\
          [Store]\n\
-         Database Connect: {}\n",
+         Database Connect: {}\n\
+         \n\
+         [DNS]\n\
+         DNS Listen on: {}\n\
+         Takeover resolv.conf: {}\n\
+         DNS over TLS: {}\n\
+         DNS over HTTPS: {}\n\
+         DNS Cache Serve Stale: {}\n\
+         DNS Cache Capacity: {}, TTL: {}s ~ {}s\n",
             self.home_path.display(),
             self.auth.admin_user,
             self.auth.admin_pass,
@@ -301,6 +439,26 @@ impl RuntimeConfig {
             address_http_str,
             address_https_str,
             self.store.database_path,
+            self.dns.listen_summary(),
+            self.dns.takeover_resolv_conf,
+            if self.dns.dot_enable {
+                format!("enable, port: {}", self.dns.dot_port)
+            } else {
+                "disable".to_string()
+            },
+            if self.dns.doh_enable {
+                format!("enable, https://{}/dns-query", address_https_str)
+            } else {
+                "disable".to_string()
+            },
+            if self.dns.cache_serve_stale {
+                format!("enable, max ttl: {}s", self.dns.cache_stale_max_ttl)
+            } else {
+                "disable".to_string()
+            },
+            self.dns.cache_capacity,
+            self.dns.cache_min_ttl,
+            self.dns.cache_max_ttl,
         )
     }
 }
@@ -337,6 +495,110 @@ pub struct WebRuntimeConfig {
     pub address: IpAddr,
 }
 
+#[derive(Clone, Debug)]
+pub struct DnsRuntimeConfig {
+    /// Enable DNS over TLS
+    pub dot_enable: bool,
+
+    /// Listen DNS over TLS port
+    pub dot_port: u16,
+
+    /// Enable DNS over HTTPS
+    pub doh_enable: bool,
+
+    /// 缓存预取的 TTL 比例
+    pub cache_prefetch_ratio: f32,
+
+    /// 是否返回过期的缓存
+    pub cache_serve_stale: bool,
+
+    /// 过期缓存最长可返回的时间
+    pub cache_stale_max_ttl: u32,
+
+    /// 每个 flow 的缓存容量
+    pub cache_capacity: u32,
+
+    /// 缓存 TTL 下限
+    pub cache_min_ttl: u32,
+
+    /// 缓存 TTL 上限
+    pub cache_max_ttl: u32,
+
+    /// DNS64 合成 AAAA 记录使用的 NAT64 前缀
+    pub dns64_prefix: Ipv6Addr,
+
+    /// NAT64 前缀长度
+    pub dns64_prefix_len: u8,
+
+    /// DNS 缓存快照的保存目录, 为空时不保存
+    pub cache_snapshot_dir: Option<PathBuf>,
+
+    /// 定时保存缓存快照的间隔
+    pub cache_snapshot_interval: u64,
+
+    /// DNS 服务监听的地址
+    pub listen_addresses: Vec<IpAddr>,
+
+    /// DNS 服务监听的端口
+    pub listen_port: u16,
+
+    /// 仅接收这些网卡上的请求, 为空时不限制
+    pub listen_interfaces: Vec<String>,
+
+    /// 运行期间是否接管 /etc/resolv.conf
+    pub takeover_resolv_conf: bool,
+
+    /// 路由器自身 (来自回环地址) 的查询使用的 flow
+    pub local_flow_id: u32,
+
+    /// 默认 flow 的限速以及查询类型策略
+    pub default_flow_policy: FlowDnsPolicy,
+}
+
+impl DnsRuntimeConfig {
+    /// 例如 `[::]:53 (eth1, eth2)`
+    pub fn listen_summary(&self) -> String {
+        let addresses: Vec<String> = self
+            .listen_addresses
+            .iter()
+            .map(|addr| SocketAddr::new(*addr, self.listen_port).to_string())
+            .collect();
+        if self.listen_interfaces.is_empty() {
+            addresses.join(", ")
+        } else {
+            format!("{} ({})", addresses.join(", "), self.listen_interfaces.join(", "))
+        }
+    }
+}
+
+impl Default for DnsRuntimeConfig {
+    fn default() -> Self {
+        Self {
+            dot_enable: false,
+            dot_port: 853,
+            doh_enable: false,
+            cache_prefetch_ratio: 0.9,
+            cache_serve_stale: false,
+            // RFC 8767 建议 1 ~ 3 天
+            cache_stale_max_ttl: 60 * 60 * 24,
+            cache_capacity: 2048,
+            cache_min_ttl: 0,
+            cache_max_ttl: 60 * 60 * 24,
+            // RFC 6052 Well-Known Prefix 64:ff9b::/96
+            dns64_prefix: Ipv6Addr::new(0x64, 0xff9b, 0, 0, 0, 0, 0, 0),
+            dns64_prefix_len: 96,
+            cache_snapshot_dir: None,
+            cache_snapshot_interval: 60 * 5,
+            listen_addresses: vec![IpAddr::V6(Ipv6Addr::UNSPECIFIED)],
+            listen_port: 53,
+            listen_interfaces: vec![],
+            takeover_resolv_conf: false,
+            local_flow_id: 0,
+            default_flow_policy: FlowDnsPolicy::default(),
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct StoreRuntimeConfig {
     pub database_path: String,
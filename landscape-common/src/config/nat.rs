@@ -1,6 +1,8 @@
-use core::ops::Range;
+use core::ops::{Range, RangeInclusive};
 use serde::{Deserialize, Serialize};
+use std::net::{Ipv4Addr, Ipv6Addr};
 use ts_rs::TS;
+use uuid::Uuid;
 
 use crate::database::repository::LandscapeDBStore;
 use crate::store::storev2::LandscapeStore;
@@ -13,10 +15,29 @@ pub struct NatServiceConfig {
     pub enable: bool,
     #[serde(default)]
     pub nat_config: NatConfig,
+    #[serde(default)]
+    pub nat6_mode: Nat6Mode,
+    /// 动态映射使用的 SNAT 地址池, 为空时使用 WAN 网卡的地址
+    #[serde(default)]
+    pub snat_pool: SnatPoolConfig,
     #[serde(default = "get_f64_timestamp")]
     pub update_at: f64,
 }
 
+impl NatServiceConfig {
+    /// 与旧配置相比只修改了连接跟踪参数 (超时, 映射数量限制, 映射与过滤行为)
+    /// 可以直接更新到运行中的服务
+    pub fn is_ct_only_change(&self, old: &NatServiceConfig) -> bool {
+        self.iface_name == old.iface_name
+            && self.enable == old.enable
+            && self.nat6_mode == old.nat6_mode
+            && self.snat_pool == old.snat_pool
+            && self.nat_config.tcp_range == old.nat_config.tcp_range
+            && self.nat_config.udp_range == old.nat_config.udp_range
+            && self.nat_config.icmp_in_range == old.nat_config.icmp_in_range
+    }
+}
+
 impl LandscapeStore for NatServiceConfig {
     fn get_store_key(&self) -> String {
         self.iface_name.clone()
@@ -35,6 +56,70 @@ pub struct NatConfig {
     pub tcp_range: Range<u16>,
     pub udp_range: Range<u16>,
     pub icmp_in_range: Range<u16>,
+    /// 未完成握手的 TCP 连接超时, 单位: 秒
+    #[serde(default = "default_tcp_syn_timeout")]
+    pub tcp_syn_timeout: u32,
+    /// unit: s
+    #[serde(default = "default_tcp_established_timeout")]
+    pub tcp_established_timeout: u32,
+    /// 收到 FIN 之后的 TCP 连接超时, 单位: 秒
+    #[serde(default = "default_tcp_closing_timeout")]
+    pub tcp_closing_timeout: u32,
+    /// unit: s
+    #[serde(default = "default_udp_timeout")]
+    pub udp_timeout: u32,
+    /// unit: s
+    #[serde(default = "default_icmp_timeout")]
+    pub icmp_timeout: u32,
+    /// 单个内网主机可同时占用的动态映射数量, 0 表示不限制
+    #[serde(default)]
+    pub max_mappings_per_host: u32,
+    /// 动态映射的映射与过滤行为, 可被 Flow 配置覆盖
+    #[serde(default)]
+    pub behavior: NatBehavior,
+}
+
+/// 各连接超时允许的最小值, 单位: 秒
+const MIN_TCP_SYN_TIMEOUT: u32 = 1;
+const MIN_TCP_ESTABLISHED_TIMEOUT: u32 = 60;
+const MIN_TCP_CLOSING_TIMEOUT: u32 = 1;
+const MIN_UDP_TIMEOUT: u32 = 10;
+const MIN_ICMP_TIMEOUT: u32 = 1;
+
+impl NatConfig {
+    pub fn check(&self) -> Result<(), String> {
+        let timeouts = [
+            ("tcp_syn_timeout", self.tcp_syn_timeout, MIN_TCP_SYN_TIMEOUT),
+            ("tcp_established_timeout", self.tcp_established_timeout, MIN_TCP_ESTABLISHED_TIMEOUT),
+            ("tcp_closing_timeout", self.tcp_closing_timeout, MIN_TCP_CLOSING_TIMEOUT),
+            ("udp_timeout", self.udp_timeout, MIN_UDP_TIMEOUT),
+            ("icmp_timeout", self.icmp_timeout, MIN_ICMP_TIMEOUT),
+        ];
+        if let Some((name, value, min)) = timeouts.iter().find(|(_, value, min)| value < min) {
+            return Err(format!("{name} must be at least {min}s, got {value}s"));
+        }
+        Ok(())
+    }
+}
+
+fn default_tcp_syn_timeout() -> u32 {
+    6
+}
+
+fn default_tcp_established_timeout() -> u32 {
+    60 * 10
+}
+
+fn default_tcp_closing_timeout() -> u32 {
+    60
+}
+
+fn default_udp_timeout() -> u32 {
+    60 * 5
+}
+
+fn default_icmp_timeout() -> u32 {
+    60 * 5
 }
 
 impl Default for NatConfig {
@@ -43,6 +128,682 @@ impl Default for NatConfig {
             tcp_range: 32768..65535,
             udp_range: 32768..65535,
             icmp_in_range: 32768..65535,
+            tcp_syn_timeout: default_tcp_syn_timeout(),
+            tcp_established_timeout: default_tcp_established_timeout(),
+            tcp_closing_timeout: default_tcp_closing_timeout(),
+            udp_timeout: default_udp_timeout(),
+            icmp_timeout: default_icmp_timeout(),
+            max_mappings_per_host: 0,
+            behavior: NatBehavior::default(),
+        }
+    }
+}
+
+/// 地址池中最多的地址数量, 与 eBPF 中的 SNAT_POOL_MAX_ADDRS 保持一致
+pub const SNAT_POOL_MAX_ADDRS: usize = 16;
+
+/// 从地址池中选择 SNAT 地址的方式
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq, TS)]
+#[ts(export, export_to = "common/nat.d.ts")]
+#[serde(rename_all = "snake_case")]
+pub enum SnatPoolPolicy {
+    /// 按内网地址哈希, 同一个内网主机总是使用同一个地址
+    #[default]
+    SourceHash,
+    /// 每个新映射轮流使用下一个地址
+    RoundRobin,
+}
+
+impl SnatPoolPolicy {
+    /// eBPF 中使用的值
+    pub fn as_u8(&self) -> u8 {
+        match self {
+            SnatPoolPolicy::SourceHash => 0,
+            SnatPoolPolicy::RoundRobin => 1,
+        }
+    }
+}
+
+/// 指定 flow 使用的 SNAT 地址
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, TS)]
+#[ts(export, export_to = "common/nat.d.ts")]
+pub struct SnatFlowOverride {
+    pub flow_id: u32,
+    pub addr: Ipv4Addr,
+}
+
+/// 指定内网网段使用的 SNAT 地址
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, TS)]
+#[ts(export, export_to = "common/nat.d.ts")]
+pub struct SnatSubnetOverride {
+    pub subnet: Ipv4Addr,
+    pub prefix_len: u8,
+    pub addr: Ipv4Addr,
+}
+
+/// SNAT 地址池
+/// 地址需要已经配置在 WAN 网卡上, 或由上游路由到 WAN 网卡
+/// 优先级: flow 指定 > 网段指定 > 地址池 > WAN 网卡地址
+/// 路由器自身发出的数据包始终使用 WAN 网卡地址
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq, TS)]
+#[ts(export, export_to = "common/nat.d.ts")]
+pub struct SnatPoolConfig {
+    #[serde(default)]
+    pub addrs: Vec<Ipv4Addr>,
+    #[serde(default)]
+    pub policy: SnatPoolPolicy,
+    #[serde(default)]
+    pub flow_overrides: Vec<SnatFlowOverride>,
+    #[serde(default)]
+    pub subnet_overrides: Vec<SnatSubnetOverride>,
+}
+
+impl SnatPoolConfig {
+    pub fn check(&self) -> Result<(), String> {
+        if self.addrs.len() > SNAT_POOL_MAX_ADDRS {
+            return Err(format!("snat pool supports at most {SNAT_POOL_MAX_ADDRS} addresses"));
+        }
+        if let Some(item) = self.subnet_overrides.iter().find(|item| item.prefix_len > 32) {
+            return Err(format!("invalid subnet prefix: {}/{}", item.subnet, item.prefix_len));
+        }
+        Ok(())
+    }
+}
+
+/// RFC 4787 中映射与过滤行为所依赖的对端范围
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, TS)]
+#[ts(export, export_to = "common/nat.d.ts")]
+#[serde(rename_all = "snake_case")]
+pub enum NatEndpointPolicy {
+    EndpointIndependent,
+    AddressDependent,
+    AddressAndPortDependent,
+}
+
+impl NatEndpointPolicy {
+    /// eBPF 中使用的值
+    pub fn as_u8(&self) -> u8 {
+        match self {
+            NatEndpointPolicy::EndpointIndependent => 0,
+            NatEndpointPolicy::AddressDependent => 1,
+            NatEndpointPolicy::AddressAndPortDependent => 2,
+        }
+    }
+}
+
+/// NAT 的映射与过滤行为
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, TS)]
+#[ts(export, export_to = "common/nat.d.ts")]
+pub struct NatBehavior {
+    /// 同一个内网端点访问哪些目的端点时使用同一个映射
+    /// 不同的目的端点会分配各自的映射以及外部端口
+    /// Flow 的 AllowReusePort 标记总是使用 Endpoint-Independent 映射
+    pub mapping: NatEndpointPolicy,
+    /// 允许哪些外部端点通过已有映射访问内网端点
+    pub filtering: NatEndpointPolicy,
+}
+
+impl Default for NatBehavior {
+    /// 默认不同的目的端点不复用端口
+    fn default() -> Self {
+        NatBehavior {
+            mapping: NatEndpointPolicy::AddressAndPortDependent,
+            filtering: NatEndpointPolicy::EndpointIndependent,
+        }
+    }
+}
+
+/// NAT 行为检测的请求
+#[derive(Debug, Serialize, Deserialize, Clone, TS)]
+#[ts(export, export_to = "common/nat.d.ts")]
+pub struct NatBehaviorTestRequest {
+    /// 支持 RFC 5780 (OTHER-ADDRESS / CHANGE-REQUEST) 的 STUN 服务器, 例如 `stun.example.com:3478`
+    pub server: String,
+}
+
+/// 通过 STUN 观察到的 NAT 行为
+/// 服务器不支持 RFC 5780 时只能得到映射地址
+#[derive(Debug, Serialize, Deserialize, Clone, TS)]
+#[ts(export, export_to = "common/nat.d.ts")]
+pub struct NatBehaviorTestResult {
+    pub server_addr: String,
+    pub local_port: u16,
+    pub mapped_addr: Option<String>,
+    pub other_addr: Option<String>,
+    /// 向其他地址发送的请求没有响应时无法判断, 为空
+    pub mapping: Option<NatEndpointPolicy>,
+    pub filtering: Option<NatEndpointPolicy>,
+}
+
+/// 当前的动态 NAT 映射
+#[derive(Debug, Serialize, Deserialize, Clone, TS)]
+#[ts(export, export_to = "common/nat.d.ts")]
+pub struct NatSession {
+    /// TCP / UDP / ICMP
+    pub l4proto: u8,
+    pub internal_addr: Ipv4Addr,
+    pub internal_port: u16,
+    pub mapped_addr: Ipv4Addr,
+    pub mapped_port: u16,
+    /// 触发创建映射的对端
+    pub remote_addr: Ipv4Addr,
+    pub remote_port: u16,
+    pub flow_id: u8,
+    /// 创建至今的时间, 单位: 秒
+    pub age: u64,
+    /// 距离超时的时间, 单位: 秒
+    pub remaining_timeout: u64,
+}
+
+/// NAT 会话的过滤条件, 未设置的条件不参与过滤
+#[derive(Debug, Serialize, Deserialize, Clone, Default, TS)]
+#[ts(export, export_to = "common/nat.d.ts")]
+pub struct NatSessionQuery {
+    /// 匹配内网, 映射或对端地址
+    pub ip: Option<Ipv4Addr>,
+    /// 匹配内网, 映射或对端端口
+    pub port: Option<u16>,
+    pub l4proto: Option<u8>,
+}
+
+impl NatSessionQuery {
+    pub fn matches(&self, session: &NatSession) -> bool {
+        if let Some(l4proto) = self.l4proto {
+            if session.l4proto != l4proto {
+                return false;
+            }
+        }
+        if let Some(ip) = self.ip {
+            if ![session.internal_addr, session.mapped_addr, session.remote_addr].contains(&ip) {
+                return false;
+            }
+        }
+        if let Some(port) = self.port {
+            if ![session.internal_port, session.mapped_port, session.remote_port].contains(&port) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// 通过内网端点定位动态映射
+#[derive(Debug, Serialize, Deserialize, Clone, TS)]
+#[ts(export, export_to = "common/nat.d.ts")]
+pub struct NatSessionKey {
+    pub l4proto: u8,
+    pub internal_addr: Ipv4Addr,
+    pub internal_port: u16,
+}
+
+/// IPv6 地址转换模式
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default, TS)]
+#[ts(export, export_to = "common/nat.d.ts")]
+#[serde(tag = "t")]
+#[serde(rename_all = "snake_case")]
+pub enum Nat6Mode {
+    #[default]
+    Disabled,
+    /// RFC 6296 无状态前缀转换
+    /// LAN 使用 ULA 前缀, 出口时替换为 DHCPv6-PD 获得的前缀, 前缀长度与委托前缀相同
+    Nptv6 { ula_prefix: Ipv6Addr },
+    /// 上游只分配单个地址时, 将 LAN 的源地址转换为 WAN 网卡的全局地址
+    Masquerade,
+}
+
+/// NPTv6 转换参数, 前缀之外的一个 16 位字用于抵消前缀变化带来的校验和差异
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Nptv6Translation {
+    /// 已按前缀长度截断的内部 (ULA) 前缀
+    pub inner_prefix: Ipv6Addr,
+    /// 已按前缀长度截断的外部 (委托) 前缀
+    pub outer_prefix: Ipv6Addr,
+    pub prefix_len: u8,
+    /// 调整字在地址中的下标 (以 16 位为单位)
+    pub adj_word: u8,
+    /// 内部 -> 外部 时加到调整字上的值
+    pub egress_adj: u16,
+    /// 外部 -> 内部 时加到调整字上的值
+    pub ingress_adj: u16,
+}
+
+impl Nptv6Translation {
+    pub fn new(ula_prefix: Ipv6Addr, pd_prefix: Ipv6Addr, prefix_len: u8) -> Result<Self, String> {
+        if prefix_len == 0 || prefix_len > 64 {
+            return Err(format!("NPTv6 prefix length /{prefix_len} is not supported"));
+        }
+        let mask = Self::prefix_mask(prefix_len);
+        let inner_prefix = Ipv6Addr::from_bits(ula_prefix.to_bits() & mask);
+        let outer_prefix = Ipv6Addr::from_bits(pd_prefix.to_bits() & mask);
+
+        let inner_sum = ones_complement_sum(&inner_prefix.segments());
+        let outer_sum = ones_complement_sum(&outer_prefix.segments());
+        Ok(Nptv6Translation {
+            inner_prefix,
+            outer_prefix,
+            prefix_len,
+            // /48 及更短的前缀调整子网字, 否则调整接口标识的第一个字
+            adj_word: if prefix_len <= 48 { 3 } else { 4 },
+            egress_adj: ones_complement_sum(&[inner_sum, !outer_sum]),
+            ingress_adj: ones_complement_sum(&[outer_sum, !inner_sum]),
+        })
+    }
+
+    pub fn prefix_mask(prefix_len: u8) -> u128 {
+        u128::MAX.checked_shl(128 - prefix_len as u32).unwrap_or(0)
+    }
+
+    /// 与 eBPF 中相同的转换过程, 调整字为 0xFFFF 时无法转换
+    pub fn translate(&self, addr: Ipv6Addr, egress: bool) -> Option<Ipv6Addr> {
+        let (from, to, adj) = if egress {
+            (self.inner_prefix, self.outer_prefix, self.egress_adj)
+        } else {
+            (self.outer_prefix, self.inner_prefix, self.ingress_adj)
+        };
+        let mask = Self::prefix_mask(self.prefix_len);
+        if addr.to_bits() & mask != from.to_bits() {
+            return None;
+        }
+        let mut segments = addr.segments();
+        let word = segments[self.adj_word as usize];
+        if word == 0xFFFF {
+            return None;
+        }
+        let word = ones_complement_sum(&[word, adj]);
+        segments[self.adj_word as usize] = if word == 0xFFFF { 0 } else { word };
+
+        let bits = (Ipv6Addr::from(segments).to_bits() & !mask) | to.to_bits();
+        Some(Ipv6Addr::from_bits(bits))
+    }
+}
+
+fn ones_complement_sum(words: &[u16]) -> u16 {
+    let mut sum: u32 = words.iter().map(|word| *word as u32).sum();
+    while sum > 0xFFFF {
+        sum = (sum & 0xFFFF) + (sum >> 16);
+    }
+    sum as u16
+}
+
+/// 端口转发协议
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Default, TS)]
+#[ts(export, export_to = "common/nat.d.ts")]
+#[serde(rename_all = "lowercase")]
+pub enum PortForwardProtocol {
+    #[default]
+    Tcp,
+    Udp,
+    Both,
+}
+
+impl PortForwardProtocol {
+    /// IP 承载的协议号
+    pub fn l4_protocols(&self) -> &'static [u8] {
+        match self {
+            PortForwardProtocol::Tcp => &[6],
+            PortForwardProtocol::Udp => &[17],
+            PortForwardProtocol::Both => &[6, 17],
+        }
+    }
+
+    fn overlaps(&self, other: &PortForwardProtocol) -> bool {
+        self.l4_protocols().iter().any(|proto| other.l4_protocols().contains(proto))
+    }
+}
+
+/// 端口转发 (DNAT) 规则
+/// 将 WAN 网卡地址上的端口 (或端口范围) 映射到内网主机
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[ts(export, export_to = "common/nat.d.ts")]
+pub struct PortForwardRuleConfig {
+    pub id: Option<Uuid>,
+    pub enable: bool,
+    #[serde(default)]
+    pub remark: String,
+    /// 外部端口所在的 WAN 网卡
+    pub wan_iface_name: String,
+    #[serde(default)]
+    pub protocol: PortForwardProtocol,
+    /// 外部端口范围, 包含结束端口, 单个端口时两者相同
+    pub external_port_start: u16,
+    pub external_port_end: u16,
+    pub internal_ip: Ipv4Addr,
+    /// 内部起始端口, 端口范围按相同的偏移进行映射
+    pub internal_port: u16,
+    /// LAN 客户端通过 WAN 地址访问时回流到内部主机
+    #[serde(default)]
+    pub hairpin: bool,
+    #[serde(default = "get_f64_timestamp")]
+    pub update_at: f64,
+}
+
+impl LandscapeDBStore<Uuid> for PortForwardRuleConfig {
+    fn get_id(&self) -> Uuid {
+        self.id.unwrap_or(Uuid::new_v4())
+    }
+}
+
+/// static_nat_mappings 的容量, 与 eBPF 中的 STATIC_NAT_MAPPING_CACHE_SIZE 保持一致
+pub const STATIC_NAT_MAPPING_CAPACITY: usize = 1024 * 64;
+
+impl PortForwardRuleConfig {
+    fn external_ports(&self) -> RangeInclusive<u16> {
+        self.external_port_start..=self.external_port_end
+    }
+
+    fn internal_ports(&self) -> RangeInclusive<u16> {
+        let len = self.external_port_end.saturating_sub(self.external_port_start);
+        self.internal_port..=self.internal_port.saturating_add(len)
+    }
+
+    pub fn check(&self) -> Result<(), String> {
+        if self.wan_iface_name.is_empty() {
+            return Err("WAN iface name is empty".to_string());
+        }
+        if self.external_port_start == 0 || self.internal_port == 0 {
+            return Err("port 0 is not allowed".to_string());
+        }
+        if self.external_port_start > self.external_port_end {
+            return Err(format!(
+                "external port range {}-{} is invalid",
+                self.external_port_start, self.external_port_end
+            ));
+        }
+        let len = self.external_port_end - self.external_port_start;
+        if self.internal_port.checked_add(len).is_none() {
+            return Err(format!(
+                "internal port range starting at {} overflows",
+                self.internal_port
+            ));
+        }
+        if self.map_entries() > STATIC_NAT_MAPPING_CAPACITY {
+            return Err(format!(
+                "port range {}-{} needs {} static mappings, at most {} are supported",
+                self.external_port_start,
+                self.external_port_end,
+                self.map_entries(),
+                STATIC_NAT_MAPPING_CAPACITY
+            ));
+        }
+        if self.internal_ip.is_unspecified()
+            || self.internal_ip.is_loopback()
+            || self.internal_ip.is_broadcast()
+        {
+            return Err(format!("internal ip {} is invalid", self.internal_ip));
+        }
+        Ok(())
+    }
+
+    /// 写入 static_nat_mappings 的条目数, 每个端口每种协议需要入向与出向两条
+    pub fn map_entries(&self) -> usize {
+        let ports = self.external_port_end.saturating_sub(self.external_port_start) as usize + 1;
+        ports * self.protocol.l4_protocols().len() * 2
+    }
+
+    /// 两条规则写入的映射是否会互相覆盖
+    /// 外部端口按 WAN 网卡区分, 内部地址的回程映射与 WAN 网卡无关
+    pub fn is_conflict(&self, other: &PortForwardRuleConfig) -> bool {
+        if !self.protocol.overlaps(&other.protocol) {
+            return false;
         }
+        let range_overlaps = |a: RangeInclusive<u16>, b: RangeInclusive<u16>| {
+            a.start() <= b.end() && b.start() <= a.end()
+        };
+        let external = self.wan_iface_name == other.wan_iface_name
+            && range_overlaps(self.external_ports(), other.external_ports());
+        let internal = self.internal_ip == other.internal_ip
+            && range_overlaps(self.internal_ports(), other.internal_ports());
+        external || internal
+    }
+
+    /// 根据 WAN 网卡当前的地址展开为逐个端口的映射
+    pub fn mappings(&self, wan_addr: Ipv4Addr) -> Vec<StaticNatMappingItem> {
+        let mut result = vec![];
+        if !self.enable || self.check().is_err() {
+            return result;
+        }
+        for &l4_protocol in self.protocol.l4_protocols() {
+            for (wan_port, lan_port) in self.external_ports().zip(self.internal_ports()) {
+                result.push(StaticNatMappingItem {
+                    l4_protocol,
+                    wan_addr,
+                    wan_port,
+                    lan_addr: self.internal_ip,
+                    lan_port,
+                    hairpin: self.hairpin,
+                });
+            }
+        }
+        result
+    }
+}
+
+/// 存入 static_nat_mappings 的一条映射
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct StaticNatMappingItem {
+    pub l4_protocol: u8,
+    pub wan_addr: Ipv4Addr,
+    pub wan_port: u16,
+    pub lan_addr: Ipv4Addr,
+    pub lan_port: u16,
+    /// 同时写入回流映射
+    pub hairpin: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use std::net::Ipv6Addr;
+
+    use super::{
+        ones_complement_sum, Nat6Mode, NatConfig, NatEndpointPolicy, NatServiceConfig, NatSession,
+        NatSessionQuery, Nptv6Translation, PortForwardProtocol, PortForwardRuleConfig,
+        SnatPoolConfig, SnatSubnetOverride, STATIC_NAT_MAPPING_CAPACITY,
+    };
+
+    fn rule(
+        iface: &str,
+        external: (u16, u16),
+        internal_ip: [u8; 4],
+        internal_port: u16,
+    ) -> PortForwardRuleConfig {
+        PortForwardRuleConfig {
+            id: None,
+            enable: true,
+            remark: String::new(),
+            wan_iface_name: iface.to_string(),
+            protocol: PortForwardProtocol::Tcp,
+            external_port_start: external.0,
+            external_port_end: external.1,
+            internal_ip: Ipv4Addr::from(internal_ip),
+            internal_port,
+            hairpin: false,
+            update_at: 0.0,
+        }
+    }
+
+    #[test]
+    fn test_port_forward_mappings() {
+        let mut config = rule("eth0", (8000, 8002), [192, 168, 1, 10], 80);
+        config.protocol = PortForwardProtocol::Both;
+        let wan_addr = Ipv4Addr::new(1, 2, 3, 4);
+        let mappings = config.mappings(wan_addr);
+        assert_eq!(mappings.len(), 6);
+        assert_eq!(mappings[0].wan_port, 8000);
+        assert_eq!(mappings[0].lan_port, 80);
+        assert_eq!(mappings[2].wan_port, 8002);
+        assert_eq!(mappings[2].lan_port, 82);
+        assert_eq!(mappings[3].l4_protocol, 17);
+        assert!(!mappings[0].hairpin);
+
+        config.hairpin = true;
+        assert!(config.mappings(wan_addr).iter().all(|item| item.hairpin));
+
+        config.enable = false;
+        assert!(config.mappings(wan_addr).is_empty());
+
+        assert!(rule("eth0", (9000, 8000), [192, 168, 1, 10], 80).check().is_err());
+        assert!(rule("eth0", (8000, 8100), [192, 168, 1, 10], 65500).check().is_err());
+    }
+
+    #[test]
+    fn test_port_forward_capacity() {
+        // 32768 个端口的 TCP 规则刚好占满
+        let mut config = rule("eth0", (1, 32768), [192, 168, 1, 10], 1);
+        assert_eq!(config.map_entries(), STATIC_NAT_MAPPING_CAPACITY);
+        assert!(config.check().is_ok());
+
+        // 同时转发 TCP 与 UDP 时需要两倍的条目
+        config.protocol = PortForwardProtocol::Both;
+        assert!(config.check().is_err());
+        config.external_port_end = 16384;
+        assert!(config.check().is_ok());
+
+        assert!(rule("eth0", (1, 65535), [192, 168, 1, 10], 1).check().is_err());
+    }
+
+    #[test]
+    fn test_port_forward_conflict() {
+        let a = rule("eth0", (8000, 8010), [192, 168, 1, 10], 8000);
+        // 不同 WAN 网卡的同一外部端口
+        assert!(!a.is_conflict(&rule("eth1", (8005, 8005), [192, 168, 1, 11], 80)));
+        assert!(a.is_conflict(&rule("eth0", (8010, 8020), [192, 168, 1, 11], 80)));
+        // 同一个内部端口的回程映射会被覆盖
+        assert!(a.is_conflict(&rule("eth1", (9000, 9000), [192, 168, 1, 10], 8003)));
+
+        let mut udp = rule("eth0", (8000, 8010), [192, 168, 1, 10], 8000);
+        udp.protocol = PortForwardProtocol::Udp;
+        assert!(!a.is_conflict(&udp));
+    }
+
+    #[test]
+    fn test_nptv6_checksum_neutral() {
+        let ula: Ipv6Addr = "fd01:203:405::".parse().unwrap();
+        let pd: Ipv6Addr = "2001:db8:1234:5600::".parse().unwrap();
+        for prefix_len in [48, 56, 64] {
+            let translation = Nptv6Translation::new(ula, pd, prefix_len).unwrap();
+            let host: Ipv6Addr = "fd01:203:405:1:1234:5678:9abc:def0".parse().unwrap();
+            let host = Ipv6Addr::from_bits(
+                (host.to_bits() & !Nptv6Translation::prefix_mask(prefix_len))
+                    | translation.inner_prefix.to_bits(),
+            );
+
+            let outer = translation.translate(host, true).unwrap();
+            assert_eq!(
+                outer.to_bits() & Nptv6Translation::prefix_mask(prefix_len),
+                translation.outer_prefix.to_bits()
+            );
+            let fold = |addr: Ipv6Addr| match ones_complement_sum(&addr.segments()) {
+                0xFFFF => 0,
+                sum => sum,
+            };
+            assert_eq!(fold(host), fold(outer));
+            assert_eq!(translation.translate(outer, false), Some(host));
+            // 不属于内部前缀的地址不转换
+            assert_eq!(translation.translate(outer, true), None);
+        }
+        assert!(Nptv6Translation::new(ula, pd, 80).is_err());
+    }
+
+    #[test]
+    fn test_nat_config_check() {
+        let mut config = NatConfig::default();
+        assert!(config.check().is_ok());
+
+        config.udp_timeout = 0;
+        assert!(config.check().is_err());
+
+        config.udp_timeout = 10;
+        config.tcp_established_timeout = 59;
+        assert!(config.check().is_err());
+
+        config.tcp_established_timeout = 60;
+        config.icmp_timeout = 1;
+        assert!(config.check().is_ok());
+    }
+
+    #[test]
+    fn test_snat_pool_check() {
+        let mut pool = SnatPoolConfig {
+            addrs: (1..=8).map(|i| Ipv4Addr::new(203, 0, 113, i)).collect(),
+            ..Default::default()
+        };
+        assert!(pool.check().is_ok());
+
+        pool.subnet_overrides.push(SnatSubnetOverride {
+            subnet: Ipv4Addr::new(192, 168, 2, 0),
+            prefix_len: 33,
+            addr: Ipv4Addr::new(203, 0, 113, 2),
+        });
+        assert!(pool.check().is_err());
+
+        let pool = SnatPoolConfig {
+            addrs: (0..=16).map(|i| Ipv4Addr::new(203, 0, 113, i)).collect(),
+            ..Default::default()
+        };
+        assert!(pool.check().is_err());
+    }
+
+    #[test]
+    fn test_nat_session_query() {
+        let session = NatSession {
+            l4proto: 17,
+            internal_addr: Ipv4Addr::new(192, 168, 1, 10),
+            internal_port: 5000,
+            mapped_addr: Ipv4Addr::new(203, 0, 113, 1),
+            mapped_port: 40000,
+            remote_addr: Ipv4Addr::new(8, 8, 8, 8),
+            remote_port: 53,
+            flow_id: 0,
+            age: 10,
+            remaining_timeout: 290,
+        };
+        assert!(NatSessionQuery::default().matches(&session));
+
+        let query = NatSessionQuery {
+            ip: Some(Ipv4Addr::new(8, 8, 8, 8)),
+            port: Some(40000),
+            l4proto: Some(17),
+        };
+        assert!(query.matches(&session));
+
+        let query = NatSessionQuery { l4proto: Some(6), ..Default::default() };
+        assert!(!query.matches(&session));
+
+        let query = NatSessionQuery {
+            ip: Some(Ipv4Addr::new(1, 1, 1, 1)),
+            ..Default::default()
+        };
+        assert!(!query.matches(&session));
+    }
+
+    #[test]
+    fn test_nat_ct_only_change() {
+        let old = NatServiceConfig {
+            iface_name: "eth0".to_string(),
+            enable: true,
+            nat_config: NatConfig::default(),
+            nat6_mode: Nat6Mode::Disabled,
+            snat_pool: SnatPoolConfig::default(),
+            update_at: 0.0,
+        };
+
+        let mut new = old.clone();
+        new.nat_config.udp_timeout = 30;
+        new.nat_config.max_mappings_per_host = 1024;
+        new.nat_config.behavior.filtering = NatEndpointPolicy::AddressDependent;
+        assert!(new.is_ct_only_change(&old));
+
+        new.nat_config.udp_range = 40000..50000;
+        assert!(!new.is_ct_only_change(&old));
+
+        let mut new = old.clone();
+        new.nat6_mode = Nat6Mode::Masquerade;
+        assert!(!new.is_ct_only_change(&old));
+
+        let mut new = old.clone();
+        new.snat_pool.addrs.push(Ipv4Addr::new(203, 0, 113, 1));
+        assert!(!new.is_ct_only_change(&old));
     }
 }
@@ -8,6 +8,10 @@ pub mod network;
 
 pub const DOCKER_NETWORK_BRIDGE_NAME_OPTION_KEY: &str = "com.docker.network.bridge.name";
 
+/// 容器 label, 值为需要注册到 DNS 的域名, 多个域名使用 `,` 分隔
+/// e.g. `landscape.dns.name=grafana.lan`
+pub const DOCKER_DNS_NAME_LABEL_KEY: &str = "landscape.dns.name";
+
 #[derive(Serialize, Deserialize, Debug, Default)]
 pub struct DockerTargetEnroll {
     pub id: String,
@@ -1,6 +1,12 @@
 pub enum DnsEvent {
-    RuleUpdated { flow_id: Option<u32> },
+    RuleUpdated {
+        flow_id: Option<u32>,
+    },
     GeositeUpdated,
+    /// hosts / adblock 列表更新
+    DomainListUpdated,
+    /// 应答过滤中引用的 geoip 更新
+    GeoIpUpdated,
     FlowUpdated,
 }
 
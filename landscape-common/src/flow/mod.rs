@@ -4,6 +4,8 @@ use serde::{Deserialize, Serialize};
 use ts_rs::TS;
 use uuid::Uuid;
 
+use crate::config::dns::FlowDnsPolicy;
+use crate::config::nat::NatBehavior;
 use crate::database::repository::LandscapeDBStore;
 use crate::flow::mark::FlowDnsMark;
 use crate::store::storev2::LandscapeStore;
@@ -28,6 +30,12 @@ pub struct FlowConfig {
     pub flow_targets: Vec<FlowTarget>,
     /// 备注
     pub remark: String,
+    /// 该 flow 中 DNS 请求的限速以及查询类型策略
+    #[serde(default)]
+    pub dns_policy: FlowDnsPolicy,
+    /// 覆盖该 flow 经过 WAN NAT 时的映射与过滤行为
+    #[serde(default)]
+    pub nat_behavior: Option<NatBehavior>,
     #[serde(default = "get_f64_timestamp")]
     pub update_at: f64,
 }
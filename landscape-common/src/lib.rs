@@ -52,6 +52,8 @@ pub const LANDSCAPE_LOG_DIR_NAME: &str = "logs";
 pub const LANDSCAPE_WEBROOT_DIR_NAME: &str = "static";
 /// metric
 pub const LANDSCAPE_METRIC_DIR_NAME: &str = "metric";
+/// DNS 缓存快照
+pub const LANDSCAPE_DNS_CACHE_DIR_NAME: &str = "dns_cache";
 
 /// default sqlite path
 pub const LANDSCAPE_DB_SQLITE_NAME: &str = "landscape_db.sqlite";
@@ -69,6 +71,8 @@ pub const LANDSCAPE_DEFAULT_LAN_NAME: &str = "br_lan";
 pub const LANDSCAPE_DEFAULE_LAN_DHCP_SERVER_IP: Ipv4Addr = Ipv4Addr::new(192, 168, 5, 1);
 pub const LANDSCAPE_DEFAULT_LAN_DHCP_SERVER_NETMASK: u8 = 24_u8;
 pub const LANDSCAPE_DEFAULE_LAN_DHCP_RANGE_START: Ipv4Addr = Ipv4Addr::new(192, 168, 5, 100);
+/// DHCP 客户端主机名注册使用的默认本地域名
+pub const LANDSCAPE_DEFAULT_LOCAL_DOMAIN: &str = "lan";
 
 pub const LANDSCAPE_DEFAULE_DHCP_V6_CLIENT_PORT: u16 = 546;
 pub const LANDSCAPE_DEFAULE_DHCP_V6_SERVER_PORT: u16 = 547;
@@ -1,5 +1,4 @@
 use std::collections::HashSet;
-use std::path::PathBuf;
 use std::{net::IpAddr, sync::Arc};
 
 use serde::{Deserialize, Serialize};
@@ -121,13 +120,10 @@ pub struct ConnectMetricManager {
 
 #[allow(unused_variables)]
 impl ConnectMetricManager {
-    pub async fn new(base_path: PathBuf) -> Self {
+    pub async fn new(#[cfg(feature = "duckdb")] metric_store: DuckMetricStore) -> Self {
         let active_connects = Arc::new(RwLock::new(HashSet::new()));
         let active_connects_clone = active_connects.clone();
 
-        #[cfg(feature = "duckdb")]
-        let metric_store = DuckMetricStore::new(base_path).await;
-
         #[cfg(feature = "duckdb")]
         let metric_store_clone = metric_store.clone();
 
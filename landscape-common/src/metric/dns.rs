@@ -0,0 +1,269 @@
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, RwLock,
+    },
+};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use ts_rs::TS;
+
+#[cfg(feature = "duckdb")]
+use crate::metric::duckdb::DuckMetricStore;
+
+/// 查询日志保留时间
+#[cfg(feature = "duckdb")]
+const DNS_LOG_RETENTION_MS: u64 = 24 * 60 * 60 * 1000;
+/// 查询日志最多保留的条数
+#[cfg(feature = "duckdb")]
+const DNS_LOG_MAX_ROWS: u64 = 1_000_000;
+#[cfg(feature = "duckdb")]
+const DNS_LOG_CLEAR_INTERVAL: u64 = 60 * 10;
+
+/// 默认返回的条数
+const DEFAULT_QUERY_LIMIT: u32 = 100;
+/// 单次查询最多返回的条数
+const MAX_QUERY_LIMIT: u32 = 1000;
+
+/// 单个 DNS 问题的处理记录
+#[derive(Debug, Serialize, Deserialize, Clone, TS)]
+#[ts(export, export_to = "common/metric/dns.d.ts")]
+pub struct DnsQueryLog {
+    #[ts(type = "number")]
+    pub report_time: u64,
+    pub client_ip: IpAddr,
+    pub flow_id: u32,
+    pub domain: String,
+    /// 例如 `A`, `AAAA`
+    pub query_type: String,
+    /// 匹配到的规则名称, 本地区域应答时为空
+    pub rule_name: Option<String>,
+    /// 使用的上游, 未向上游查询时为空
+    pub upstream: Option<String>,
+    /// 例如 `No Error`, `Non-Existent Domain`
+    pub response_code: String,
+    pub latency_ms: u32,
+    pub cache_hit: bool,
+    /// 是否被规则拦截
+    pub blocked: bool,
+}
+
+/// 查询日志的搜索条件, 未设置的条件不进行过滤
+#[derive(Debug, Serialize, Deserialize, Clone, Default, TS)]
+#[ts(export, export_to = "common/metric/dns.d.ts")]
+pub struct DnsQueryLogFilter {
+    /// 起始时间 (毫秒)
+    #[ts(type = "number | null")]
+    pub start_time: Option<u64>,
+    /// 结束时间 (毫秒)
+    #[ts(type = "number | null")]
+    pub end_time: Option<u64>,
+    pub client_ip: Option<IpAddr>,
+    pub flow_id: Option<u32>,
+    /// 包含该字符串的域名
+    pub domain: Option<String>,
+    pub rule_name: Option<String>,
+    pub blocked: Option<bool>,
+    pub limit: Option<u32>,
+}
+
+impl DnsQueryLogFilter {
+    pub fn limit(&self) -> u32 {
+        self.limit.unwrap_or(DEFAULT_QUERY_LIMIT).min(MAX_QUERY_LIMIT)
+    }
+}
+
+/// 按域名统计的查询次数
+#[derive(Debug, Serialize, Deserialize, Clone, TS)]
+#[ts(export, export_to = "common/metric/dns.d.ts")]
+pub struct DnsDomainCount {
+    pub domain: String,
+    #[ts(type = "number")]
+    pub count: u64,
+}
+
+/// 按客户端统计的查询次数
+#[derive(Debug, Serialize, Deserialize, Clone, TS)]
+#[ts(export, export_to = "common/metric/dns.d.ts")]
+pub struct DnsClientCount {
+    pub client_ip: IpAddr,
+    #[ts(type = "number")]
+    pub count: u64,
+    #[ts(type = "number")]
+    pub blocked: u64,
+    #[ts(type = "number")]
+    pub cache_hits: u64,
+}
+
+/// 每个 flow 被限速以及按查询类型处理的请求数
+#[derive(Debug, Serialize, Deserialize, Clone, Default, TS)]
+#[ts(export, export_to = "common/metric/dns.d.ts")]
+pub struct DnsPolicyStats {
+    pub flow_id: u32,
+    /// 超过单个客户端限速的请求
+    #[ts(type = "number")]
+    pub client_limited: u64,
+    /// 超过 flow 限速的请求
+    #[ts(type = "number")]
+    pub flow_limited: u64,
+    #[ts(type = "number")]
+    pub qtype_refused: u64,
+    #[ts(type = "number")]
+    pub qtype_empty: u64,
+}
+
+#[derive(Debug, Default)]
+pub struct DnsPolicyCounter {
+    client_limited: AtomicU64,
+    flow_limited: AtomicU64,
+    qtype_refused: AtomicU64,
+    qtype_empty: AtomicU64,
+}
+
+impl DnsPolicyCounter {
+    pub fn client_limited(&self) {
+        self.client_limited.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn flow_limited(&self) {
+        self.flow_limited.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn qtype_refused(&self) {
+        self.qtype_refused.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn qtype_empty(&self) {
+        self.qtype_empty.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn stats(&self, flow_id: u32) -> DnsPolicyStats {
+        DnsPolicyStats {
+            flow_id,
+            client_limited: self.client_limited.load(Ordering::Relaxed),
+            flow_limited: self.flow_limited.load(Ordering::Relaxed),
+            qtype_refused: self.qtype_refused.load(Ordering::Relaxed),
+            qtype_empty: self.qtype_empty.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct DnsMetricManager {
+    msg_channel: mpsc::Sender<DnsQueryLog>,
+    /// flow_id <-> 限速以及查询类型策略的计数, 仅保存在内存中
+    policy_counters: Arc<RwLock<HashMap<u32, Arc<DnsPolicyCounter>>>>,
+    #[cfg(feature = "duckdb")]
+    metric_store: DuckMetricStore,
+}
+
+impl DnsMetricManager {
+    pub async fn new(#[cfg(feature = "duckdb")] metric_store: DuckMetricStore) -> Self {
+        #[cfg(feature = "duckdb")]
+        let metric_store_clone = metric_store.clone();
+
+        #[cfg(feature = "duckdb")]
+        tokio::spawn(async move {
+            // 定时清理, 按保留时间以及最大条数限制日志大小
+            let mut interval =
+                tokio::time::interval(tokio::time::Duration::from_secs(DNS_LOG_CLEAR_INTERVAL));
+            loop {
+                interval.tick().await;
+                let now = chrono::Utc::now().timestamp_millis() as u64;
+                let cutoff = now.saturating_sub(DNS_LOG_RETENTION_MS);
+                metric_store_clone.cleanup_dns_query_logs(cutoff, DNS_LOG_MAX_ROWS).await;
+            }
+        });
+
+        #[cfg(feature = "duckdb")]
+        let metric_store_clone = metric_store.clone();
+        let (msg_channel, mut message_rx) = mpsc::channel::<DnsQueryLog>(1024);
+        tokio::spawn(async move {
+            while let Some(log) = message_rx.recv().await {
+                #[cfg(feature = "duckdb")]
+                metric_store_clone.insert_dns_query_log(log).await;
+                #[cfg(not(feature = "duckdb"))]
+                let _ = log;
+            }
+
+            tracing::info!("dns metric exit");
+        });
+
+        DnsMetricManager {
+            msg_channel,
+            policy_counters: Arc::new(RwLock::new(HashMap::new())),
+            #[cfg(feature = "duckdb")]
+            metric_store,
+        }
+    }
+
+    /// 记录查询日志, 通道满时直接丢弃, 不阻塞 DNS 应答
+    pub fn send_query_log(&self, log: DnsQueryLog) {
+        if let Err(e) = self.msg_channel.try_send(log) {
+            tracing::debug!("send dns query log error: {e:?}");
+        }
+    }
+
+    /// flow 的策略更新后计数保持不变
+    pub fn policy_counter(&self, flow_id: u32) -> Arc<DnsPolicyCounter> {
+        if let Some(counter) = self.policy_counters.read().unwrap().get(&flow_id) {
+            return counter.clone();
+        }
+        self.policy_counters.write().unwrap().entry(flow_id).or_default().clone()
+    }
+
+    pub fn policy_stats(&self) -> Vec<DnsPolicyStats> {
+        let mut result: Vec<DnsPolicyStats> = self
+            .policy_counters
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(flow_id, counter)| counter.stats(*flow_id))
+            .collect();
+        result.sort_by_key(|stats| stats.flow_id);
+        result
+    }
+
+    pub async fn search(&self, filter: DnsQueryLogFilter) -> Vec<DnsQueryLog> {
+        #[cfg(feature = "duckdb")]
+        {
+            self.metric_store.search_dns_query_logs(filter).await
+        }
+
+        #[cfg(not(feature = "duckdb"))]
+        {
+            let _ = filter;
+            Vec::new()
+        }
+    }
+
+    /// 查询次数最多的域名, `blocked` 为 true 时只统计被拦截的查询
+    pub async fn top_domains(&self, filter: DnsQueryLogFilter) -> Vec<DnsDomainCount> {
+        #[cfg(feature = "duckdb")]
+        {
+            self.metric_store.top_dns_domains(filter).await
+        }
+
+        #[cfg(not(feature = "duckdb"))]
+        {
+            let _ = filter;
+            Vec::new()
+        }
+    }
+
+    pub async fn client_counts(&self, filter: DnsQueryLogFilter) -> Vec<DnsClientCount> {
+        #[cfg(feature = "duckdb")]
+        {
+            self.metric_store.dns_client_counts(filter).await
+        }
+
+        #[cfg(not(feature = "duckdb"))]
+        {
+            let _ = filter;
+            Vec::new()
+        }
+    }
+}
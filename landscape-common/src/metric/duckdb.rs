@@ -1,5 +1,6 @@
 use crate::metric::connect::{ConnectInfo, ConnectKey, ConnectMetric};
-use duckdb::{params, Connection};
+use crate::metric::dns::{DnsClientCount, DnsDomainCount, DnsQueryLog, DnsQueryLogFilter};
+use duckdb::{params, params_from_iter, Connection, ToSql};
 use std::path::PathBuf;
 use std::thread;
 use tokio::sync::{mpsc, oneshot};
@@ -14,9 +15,15 @@ pub enum DBMessage {
 
     CollectAndCleanupOldMetrics { cutoff: u64, resp: oneshot::Sender<Box<Vec<ConnectMetric>>> },
     CollectAndCleanupOldInfos { cutoff: u64, resp: oneshot::Sender<Box<Vec<ConnectInfo>>> },
+
+    InsertDnsQueryLog(DnsQueryLog),
+    SearchDnsQueryLogs { filter: DnsQueryLogFilter, resp: oneshot::Sender<Vec<DnsQueryLog>> },
+    TopDnsDomains { filter: DnsQueryLogFilter, resp: oneshot::Sender<Vec<DnsDomainCount>> },
+    DnsClientCounts { filter: DnsQueryLogFilter, resp: oneshot::Sender<Vec<DnsClientCount>> },
+    CleanupDnsQueryLogs { cutoff: u64, max_rows: u64 },
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub struct DuckMetricStore {
     tx: mpsc::Sender<DBMessage>,
 }
@@ -259,6 +266,199 @@ pub fn current_active_connect_keys(conn: &Connection) -> Vec<ConnectKey> {
     rows.filter_map(Result::ok).collect()
 }
 
+pub fn insert_dns_query_log(conn: &Connection, log: &DnsQueryLog) -> duckdb::Result<()> {
+    let stmt = "
+        INSERT INTO dns_query_log VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)
+    ";
+
+    conn.execute(
+        stmt,
+        params![
+            log.report_time as i64,
+            log.client_ip.to_string(),
+            log.flow_id as i64,
+            log.domain,
+            log.query_type,
+            log.rule_name,
+            log.upstream,
+            log.response_code,
+            log.latency_ms as i64,
+            log.cache_hit,
+            log.blocked,
+        ],
+    )?;
+    Ok(())
+}
+
+/// 解析数据库中保存的 IP 地址
+fn parse_ip_column(row: &duckdb::Row, index: usize) -> duckdb::Result<std::net::IpAddr> {
+    row.get::<_, String>(index)?.parse().map_err(|e| {
+        duckdb::Error::FromSqlConversionFailure(index, duckdb::types::Type::Text, Box::new(e))
+    })
+}
+
+/// 根据搜索条件生成 WHERE 子句以及对应的参数
+fn dns_query_log_conditions(filter: &DnsQueryLogFilter) -> (String, Vec<Box<dyn ToSql>>) {
+    let mut conditions: Vec<&str> = vec![];
+    let mut values: Vec<Box<dyn ToSql>> = vec![];
+
+    if let Some(start_time) = filter.start_time {
+        conditions.push("report_time >= ?");
+        values.push(Box::new(start_time as i64));
+    }
+    if let Some(end_time) = filter.end_time {
+        conditions.push("report_time <= ?");
+        values.push(Box::new(end_time as i64));
+    }
+    if let Some(client_ip) = filter.client_ip {
+        conditions.push("client_ip = ?");
+        values.push(Box::new(client_ip.to_string()));
+    }
+    if let Some(flow_id) = filter.flow_id {
+        conditions.push("flow_id = ?");
+        values.push(Box::new(flow_id as i64));
+    }
+    if let Some(domain) = filter.domain.as_ref().filter(|domain| !domain.is_empty()) {
+        conditions.push("contains(domain, ?)");
+        values.push(Box::new(domain.clone()));
+    }
+    if let Some(rule_name) = &filter.rule_name {
+        conditions.push("rule_name = ?");
+        values.push(Box::new(rule_name.clone()));
+    }
+    if let Some(blocked) = filter.blocked {
+        conditions.push("blocked = ?");
+        values.push(Box::new(blocked));
+    }
+
+    if conditions.is_empty() {
+        (String::new(), values)
+    } else {
+        (format!("WHERE {}", conditions.join(" AND ")), values)
+    }
+}
+
+pub fn search_dns_query_logs(
+    conn: &Connection,
+    filter: &DnsQueryLogFilter,
+) -> duckdb::Result<Vec<DnsQueryLog>> {
+    let (conditions, mut values) = dns_query_log_conditions(filter);
+    values.push(Box::new(filter.limit() as i64));
+    let stmt = format!(
+        "
+        SELECT report_time, client_ip, flow_id, domain, query_type, rule_name,
+               upstream, response_code, latency_ms, cache_hit, blocked
+        FROM dns_query_log
+        {conditions}
+        ORDER BY report_time DESC
+        LIMIT ?
+    "
+    );
+
+    let mut stmt = conn.prepare(&stmt)?;
+    let rows = stmt.query_map(params_from_iter(values.iter()), |row| {
+        Ok(DnsQueryLog {
+            report_time: row.get::<_, i64>(0)? as u64,
+            client_ip: parse_ip_column(row, 1)?,
+            flow_id: row.get::<_, i64>(2)? as u32,
+            domain: row.get(3)?,
+            query_type: row.get(4)?,
+            rule_name: row.get(5)?,
+            upstream: row.get(6)?,
+            response_code: row.get(7)?,
+            latency_ms: row.get::<_, i64>(8)? as u32,
+            cache_hit: row.get(9)?,
+            blocked: row.get(10)?,
+        })
+    })?;
+
+    rows.collect()
+}
+
+pub fn top_dns_domains(
+    conn: &Connection,
+    filter: &DnsQueryLogFilter,
+) -> duckdb::Result<Vec<DnsDomainCount>> {
+    let (conditions, mut values) = dns_query_log_conditions(filter);
+    values.push(Box::new(filter.limit() as i64));
+    let stmt = format!(
+        "
+        SELECT domain, COUNT(*) AS count
+        FROM dns_query_log
+        {conditions}
+        GROUP BY domain
+        ORDER BY count DESC
+        LIMIT ?
+    "
+    );
+
+    let mut stmt = conn.prepare(&stmt)?;
+    let rows = stmt.query_map(params_from_iter(values.iter()), |row| {
+        Ok(DnsDomainCount {
+            domain: row.get(0)?,
+            count: row.get::<_, i64>(1)? as u64,
+        })
+    })?;
+
+    rows.collect()
+}
+
+pub fn dns_client_counts(
+    conn: &Connection,
+    filter: &DnsQueryLogFilter,
+) -> duckdb::Result<Vec<DnsClientCount>> {
+    let (conditions, mut values) = dns_query_log_conditions(filter);
+    values.push(Box::new(filter.limit() as i64));
+    let stmt = format!(
+        "
+        SELECT client_ip,
+               COUNT(*) AS count,
+               COUNT(*) FILTER (WHERE blocked) AS blocked,
+               COUNT(*) FILTER (WHERE cache_hit) AS cache_hits
+        FROM dns_query_log
+        {conditions}
+        GROUP BY client_ip
+        ORDER BY count DESC
+        LIMIT ?
+    "
+    );
+
+    let mut stmt = conn.prepare(&stmt)?;
+    let rows = stmt.query_map(params_from_iter(values.iter()), |row| {
+        Ok(DnsClientCount {
+            client_ip: parse_ip_column(row, 0)?,
+            count: row.get::<_, i64>(1)? as u64,
+            blocked: row.get::<_, i64>(2)? as u64,
+            cache_hits: row.get::<_, i64>(3)? as u64,
+        })
+    })?;
+
+    rows.collect()
+}
+
+/// 删除过期的查询日志, 并且只保留最新的 `max_rows` 条
+pub fn cleanup_dns_query_logs(conn: &Connection, cutoff: u64, max_rows: u64) -> duckdb::Result<()> {
+    let mut deleted =
+        conn.execute("DELETE FROM dns_query_log WHERE report_time < ?1", params![cutoff as i64])?;
+
+    deleted += conn.execute(
+        "
+            DELETE FROM dns_query_log WHERE report_time < (
+                SELECT report_time FROM dns_query_log
+                ORDER BY report_time DESC
+                LIMIT 1 OFFSET ?1
+            )
+            ",
+        params![max_rows as i64],
+    )?;
+
+    let size = conn
+        .prepare("SELECT COUNT(*) FROM dns_query_log")?
+        .query_row([], |row| row.get::<_, usize>(0))?;
+    tracing::info!("Cleanup complete: deleted {} dns query logs, remaining: {}", deleted, size);
+    Ok(())
+}
+
 pub fn start_db_thread(mut rx: mpsc::Receiver<DBMessage>, base_path: PathBuf) {
     // Create a single-threaded DuckDB connection
     // let conn = Connection::open_in_memory().unwrap();
@@ -269,6 +469,7 @@ pub fn start_db_thread(mut rx: mpsc::Receiver<DBMessage>, base_path: PathBuf) {
 
     create_connect_table(&conn).unwrap();
     create_metrics_table(&conn).unwrap();
+    create_dns_query_log_table(&conn).unwrap();
 
     while let Some(msg) = rx.blocking_recv() {
         match msg {
@@ -294,6 +495,38 @@ pub fn start_db_thread(mut rx: mpsc::Receiver<DBMessage>, base_path: PathBuf) {
                 let result = collect_and_cleanup_old_infos(&conn, cutoff);
                 let _ = resp.send(result);
             }
+            // 查询日志出错时记录错误并返回空结果, 不能让数据库线程退出
+            DBMessage::InsertDnsQueryLog(log) => {
+                if let Err(e) = insert_dns_query_log(&conn, &log) {
+                    tracing::error!("insert dns query log error: {e:?}");
+                }
+            }
+            DBMessage::SearchDnsQueryLogs { filter, resp } => {
+                let result = search_dns_query_logs(&conn, &filter).unwrap_or_else(|e| {
+                    tracing::error!("search dns query logs error: {e:?}");
+                    vec![]
+                });
+                let _ = resp.send(result);
+            }
+            DBMessage::TopDnsDomains { filter, resp } => {
+                let result = top_dns_domains(&conn, &filter).unwrap_or_else(|e| {
+                    tracing::error!("query top dns domains error: {e:?}");
+                    vec![]
+                });
+                let _ = resp.send(result);
+            }
+            DBMessage::DnsClientCounts { filter, resp } => {
+                let result = dns_client_counts(&conn, &filter).unwrap_or_else(|e| {
+                    tracing::error!("query dns client counts error: {e:?}");
+                    vec![]
+                });
+                let _ = resp.send(result);
+            }
+            DBMessage::CleanupDnsQueryLogs { cutoff, max_rows } => {
+                if let Err(e) = cleanup_dns_query_logs(&conn, cutoff, max_rows) {
+                    tracing::error!("cleanup dns query logs error: {e:?}");
+                }
+            }
         }
     }
 }
@@ -342,6 +575,32 @@ impl DuckMetricStore {
 
         rx.await.unwrap()
     }
+
+    pub async fn insert_dns_query_log(&self, log: DnsQueryLog) {
+        let _ = self.tx.send(DBMessage::InsertDnsQueryLog(log)).await;
+    }
+
+    pub async fn search_dns_query_logs(&self, filter: DnsQueryLogFilter) -> Vec<DnsQueryLog> {
+        let (resp, rx) = oneshot::channel();
+        let _ = self.tx.send(DBMessage::SearchDnsQueryLogs { filter, resp }).await;
+        rx.await.unwrap_or_default()
+    }
+
+    pub async fn top_dns_domains(&self, filter: DnsQueryLogFilter) -> Vec<DnsDomainCount> {
+        let (resp, rx) = oneshot::channel();
+        let _ = self.tx.send(DBMessage::TopDnsDomains { filter, resp }).await;
+        rx.await.unwrap_or_default()
+    }
+
+    pub async fn dns_client_counts(&self, filter: DnsQueryLogFilter) -> Vec<DnsClientCount> {
+        let (resp, rx) = oneshot::channel();
+        let _ = self.tx.send(DBMessage::DnsClientCounts { filter, resp }).await;
+        rx.await.unwrap_or_default()
+    }
+
+    pub async fn cleanup_dns_query_logs(&self, cutoff: u64, max_rows: u64) {
+        let _ = self.tx.send(DBMessage::CleanupDnsQueryLogs { cutoff, max_rows }).await;
+    }
 }
 
 /// Create `connect` table
@@ -388,3 +647,24 @@ fn create_metrics_table(conn: &Connection) -> duckdb::Result<()> {
         ",
     )
 }
+
+/// Create `dns_query_log` table
+fn create_dns_query_log_table(conn: &Connection) -> duckdb::Result<()> {
+    conn.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS dns_query_log (
+            report_time BIGINT,
+            client_ip TEXT,
+            flow_id INTEGER,
+            domain TEXT,
+            query_type TEXT,
+            rule_name TEXT,
+            upstream TEXT,
+            response_code TEXT,
+            latency_ms INTEGER,
+            cache_hit BOOLEAN,
+            blocked BOOLEAN
+        );
+        ",
+    )
+}
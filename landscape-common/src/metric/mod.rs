@@ -1,8 +1,12 @@
 use std::path::PathBuf;
 
 use crate::metric::connect::ConnectMetricManager;
+use crate::metric::dns::DnsMetricManager;
+#[cfg(feature = "duckdb")]
+use crate::metric::duckdb::DuckMetricStore;
 
 pub mod connect;
+pub mod dns;
 #[cfg(feature = "duckdb")]
 pub mod duckdb;
 #[cfg(feature = "polars")]
@@ -11,12 +15,28 @@ pub mod polars;
 #[derive(Clone)]
 pub struct MetricData {
     pub connect_metric: ConnectMetricManager,
+    pub dns_metric: DnsMetricManager,
 }
 
 impl MetricData {
     pub async fn new(home_path: PathBuf) -> Self {
-        MetricData {
-            connect_metric: ConnectMetricManager::new(home_path).await,
+        // 连接指标与 DNS 查询日志共用同一个 DuckDB 数据库
+        #[cfg(feature = "duckdb")]
+        {
+            let metric_store = DuckMetricStore::new(home_path).await;
+            MetricData {
+                connect_metric: ConnectMetricManager::new(metric_store.clone()).await,
+                dns_metric: DnsMetricManager::new(metric_store).await,
+            }
+        }
+
+        #[cfg(not(feature = "duckdb"))]
+        {
+            let _ = home_path;
+            MetricData {
+                connect_metric: ConnectMetricManager::new().await,
+                dns_metric: DnsMetricManager::new().await,
+            }
         }
     }
 }
@@ -60,3 +60,50 @@ pub struct LanRouteInfo {
     pub mac: Option<MacAddr>,
     pub prefix: u8,
 }
+
+impl LanRouteInfo {
+    /// 地址是否属于该 LAN 网卡的网段
+    pub fn contains(&self, addr: IpAddr) -> bool {
+        match (self.iface_ip, addr.to_canonical()) {
+            (IpAddr::V4(net), IpAddr::V4(addr)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix.min(32) as u32).unwrap_or(0);
+                u32::from(net) & mask == u32::from(addr) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(addr)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix.min(128) as u32).unwrap_or(0);
+                u128::from(net) & mask == u128::from(addr) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::IpAddr;
+
+    use super::LanRouteInfo;
+
+    fn lan(iface_ip: &str, prefix: u8) -> LanRouteInfo {
+        LanRouteInfo {
+            ifindex: 1,
+            iface_name: "br0".to_string(),
+            iface_ip: iface_ip.parse().unwrap(),
+            mac: None,
+            prefix,
+        }
+    }
+
+    #[test]
+    fn test_lan_route_contains() {
+        let info = lan("192.168.1.1", 24);
+        assert!(info.contains("192.168.1.100".parse().unwrap()));
+        assert!(info.contains("::ffff:192.168.1.100".parse().unwrap()));
+        assert!(!info.contains("192.168.2.100".parse().unwrap()));
+        assert!(!info.contains("fd00::1".parse::<IpAddr>().unwrap()));
+
+        let info = lan("fd00::1", 64);
+        assert!(info.contains("fd00::abcd".parse().unwrap()));
+        assert!(!info.contains("fd01::1".parse().unwrap()));
+    }
+}
@@ -40,6 +40,18 @@ pub fn get_boot_time_ns() -> Result<u64, i32> {
     }
 }
 
+/// 与 eBPF 中 bpf_ktime_get_ns 使用同一个时钟
+pub fn get_monotonic_time_ns() -> Result<u64, i32> {
+    let mut ts: timespec = unsafe { mem::zeroed() };
+    let result = unsafe { clock_gettime(CLOCK_MONOTONIC, &mut ts) };
+
+    if result == 0 {
+        Ok((ts.tv_sec as u64) * 1_000_000_000 + (ts.tv_nsec as u64))
+    } else {
+        Err(unsafe { *libc::__errno_location() })
+    }
+}
+
 pub fn get_current_time_ns() -> Result<u64, i32> {
     let mut ts: timespec = unsafe { std::mem::zeroed() };
     let result = unsafe { clock_gettime(CLOCK_REALTIME, &mut ts) };
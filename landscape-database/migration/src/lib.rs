@@ -19,6 +19,16 @@ mod m20250525_030646_geo_site;
 mod m20250530_142817_geo_ip;
 mod m20250706_165958_route_lan;
 mod m20250706_170000_route_wan;
+mod m20250712_093000_dhcp_v4_local_domain;
+mod m20250715_120000_dns_upstream_group;
+mod m20250718_090000_dns_rule_answer_filter;
+mod m20250720_080000_flow_dns_policy;
+mod m20250722_090000_port_forward_rule;
+mod m20250723_090000_port_forward_hairpin;
+mod m20250724_090000_nat6_mode;
+mod m20250725_090000_nat_ct_timeout;
+mod m20250726_090000_nat_behavior;
+mod m20250727_090000_nat_snat_pool;
 mod tables;
 
 pub struct Migrator;
@@ -46,6 +56,16 @@ impl MigratorTrait for Migrator {
             Box::new(m20250530_142817_geo_ip::Migration),
             Box::new(m20250706_165958_route_lan::Migration),
             Box::new(m20250706_170000_route_wan::Migration),
+            Box::new(m20250712_093000_dhcp_v4_local_domain::Migration),
+            Box::new(m20250715_120000_dns_upstream_group::Migration),
+            Box::new(m20250718_090000_dns_rule_answer_filter::Migration),
+            Box::new(m20250720_080000_flow_dns_policy::Migration),
+            Box::new(m20250722_090000_port_forward_rule::Migration),
+            Box::new(m20250723_090000_port_forward_hairpin::Migration),
+            Box::new(m20250724_090000_nat6_mode::Migration),
+            Box::new(m20250725_090000_nat_ct_timeout::Migration),
+            Box::new(m20250726_090000_nat_behavior::Migration),
+            Box::new(m20250727_090000_nat_snat_pool::Migration),
         ]
     }
 }
@@ -0,0 +1,42 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+use crate::tables::dhcp_v4_server::DHCPv4ServerConfigs;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(DHCPv4ServerConfigs::Table)
+                    .add_column(string_null(DHCPv4ServerConfigs::LocalDomain))
+                    .to_owned(),
+            )
+            .await?;
+
+        // 已有的配置使用与新建配置相同的默认本地域名
+        manager
+            .exec_stmt(
+                Query::update()
+                    .table(DHCPv4ServerConfigs::Table)
+                    .value(DHCPv4ServerConfigs::LocalDomain, "lan")
+                    .and_where(Expr::col(DHCPv4ServerConfigs::LocalDomain).is_null())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(DHCPv4ServerConfigs::Table)
+                    .drop_column(DHCPv4ServerConfigs::LocalDomain)
+                    .to_owned(),
+            )
+            .await
+    }
+}
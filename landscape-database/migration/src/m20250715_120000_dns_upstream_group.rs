@@ -0,0 +1,29 @@
+use sea_orm_migration::prelude::*;
+
+use crate::tables::dns_upstream_group::DNSUpstreamGroups;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(DNSUpstreamGroups::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(DNSUpstreamGroups::Id).uuid().not_null().primary_key())
+                    .col(ColumnDef::new(DNSUpstreamGroups::Name).string().not_null())
+                    .col(ColumnDef::new(DNSUpstreamGroups::Strategy).json().not_null())
+                    .col(ColumnDef::new(DNSUpstreamGroups::Servers).json().not_null())
+                    .col(ColumnDef::new(DNSUpstreamGroups::UpdateAt).double().default(0).not_null())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.drop_table(Table::drop().table(DNSUpstreamGroups::Table).to_owned()).await
+    }
+}
@@ -0,0 +1,31 @@
+use sea_orm_migration::prelude::*;
+
+use crate::tables::dns_rule::DNSRuleConfigs;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(DNSRuleConfigs::Table)
+                    .add_column(ColumnDef::new(DNSRuleConfigs::AnswerFilter).json().null())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(DNSRuleConfigs::Table)
+                    .drop_column(DNSRuleConfigs::AnswerFilter)
+                    .to_owned(),
+            )
+            .await
+    }
+}
@@ -0,0 +1,47 @@
+use sea_orm_migration::prelude::*;
+
+use crate::tables::port_forward::PortForwardRuleConfigs;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(PortForwardRuleConfigs::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(PortForwardRuleConfigs::Id).uuid().not_null().primary_key())
+                    .col(ColumnDef::new(PortForwardRuleConfigs::Enable).boolean().not_null())
+                    .col(ColumnDef::new(PortForwardRuleConfigs::Remark).string().not_null())
+                    .col(ColumnDef::new(PortForwardRuleConfigs::WanIfaceName).string().not_null())
+                    .col(ColumnDef::new(PortForwardRuleConfigs::Protocol).json().not_null())
+                    .col(
+                        ColumnDef::new(PortForwardRuleConfigs::ExternalPortStart)
+                            .unsigned()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(PortForwardRuleConfigs::ExternalPortEnd)
+                            .unsigned()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(PortForwardRuleConfigs::InternalIp).string().not_null())
+                    .col(ColumnDef::new(PortForwardRuleConfigs::InternalPort).unsigned().not_null())
+                    .col(
+                        ColumnDef::new(PortForwardRuleConfigs::UpdateAt)
+                            .double()
+                            .default(0)
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.drop_table(Table::drop().table(PortForwardRuleConfigs::Table).to_owned()).await
+    }
+}
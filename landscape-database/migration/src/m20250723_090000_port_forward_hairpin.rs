@@ -0,0 +1,36 @@
+use sea_orm_migration::prelude::*;
+
+use crate::tables::port_forward::PortForwardRuleConfigs;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(PortForwardRuleConfigs::Table)
+                    .add_column(
+                        ColumnDef::new(PortForwardRuleConfigs::Hairpin)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(PortForwardRuleConfigs::Table)
+                    .drop_column(PortForwardRuleConfigs::Hairpin)
+                    .to_owned(),
+            )
+            .await
+    }
+}
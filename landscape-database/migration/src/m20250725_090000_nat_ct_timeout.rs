@@ -0,0 +1,45 @@
+use sea_orm_migration::prelude::*;
+
+use crate::tables::nat::NatServiceConfigs;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+/// 列与默认值, 默认值与 NatConfig::default 保持一致
+const CT_COLUMNS: [(NatServiceConfigs, u32); 6] = [
+    (NatServiceConfigs::TcpSynTimeout, 6),
+    (NatServiceConfigs::TcpEstablishedTimeout, 600),
+    (NatServiceConfigs::TcpClosingTimeout, 60),
+    (NatServiceConfigs::UdpTimeout, 300),
+    (NatServiceConfigs::IcmpTimeout, 300),
+    (NatServiceConfigs::MaxMappingsPerHost, 0),
+];
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // SQLite 每次只能添加一列
+        for (column, default) in CT_COLUMNS {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(NatServiceConfigs::Table)
+                        .add_column(ColumnDef::new(column).unsigned().not_null().default(default))
+                        .to_owned(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for (column, _) in CT_COLUMNS {
+            manager
+                .alter_table(
+                    Table::alter().table(NatServiceConfigs::Table).drop_column(column).to_owned(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+}
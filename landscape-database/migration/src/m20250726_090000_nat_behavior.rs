@@ -0,0 +1,49 @@
+use sea_orm_migration::prelude::*;
+
+use crate::tables::{flow_rule::FlowConfigs, nat::NatServiceConfigs};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(NatServiceConfigs::Table)
+                    .add_column(ColumnDef::new(NatServiceConfigs::NatBehavior).json().null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(FlowConfigs::Table)
+                    .add_column(ColumnDef::new(FlowConfigs::NatBehavior).json().null())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(FlowConfigs::Table)
+                    .drop_column(FlowConfigs::NatBehavior)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(NatServiceConfigs::Table)
+                    .drop_column(NatServiceConfigs::NatBehavior)
+                    .to_owned(),
+            )
+            .await
+    }
+}
@@ -0,0 +1,31 @@
+use sea_orm_migration::prelude::*;
+
+use crate::tables::nat::NatServiceConfigs;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(NatServiceConfigs::Table)
+                    .add_column(ColumnDef::new(NatServiceConfigs::SnatPool).json().null())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(NatServiceConfigs::Table)
+                    .drop_column(NatServiceConfigs::SnatPool)
+                    .to_owned(),
+            )
+            .await
+    }
+}
@@ -12,5 +12,6 @@ pub enum DHCPv4ServerConfigs {
     NetworkMask,
     AddressLeaseTime,
     MacBindingRecords,
+    LocalDomain,
     UpdateAt,
 }
@@ -13,4 +13,5 @@ pub enum DNSRuleConfigs {
     Source,
     FlowId,
     UpdateAt,
+    AnswerFilter,
 }
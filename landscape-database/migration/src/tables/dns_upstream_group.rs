@@ -0,0 +1,12 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveIden)]
+pub enum DNSUpstreamGroups {
+    #[sea_orm(iden = "dns_upstream_groups")]
+    Table,
+    Id,
+    Name,
+    Strategy,
+    Servers,
+    UpdateAt,
+}
@@ -10,4 +10,6 @@ pub enum FlowConfigs {
     PacketHandleIfaceName,
     Remark,
     UpdateAt,
+    DnsPolicy,
+    NatBehavior,
 }
@@ -11,9 +11,11 @@ pub mod ra;
 pub mod wifi;
 
 pub mod dns_rule;
+pub mod dns_upstream_group;
 pub mod dst_ip_rule;
 pub mod firewall_rule;
 pub mod flow_rule;
+pub mod port_forward;
 
 pub mod geo;
 
@@ -12,4 +12,13 @@ pub enum NatServiceConfigs {
     IcmpInRangeStart,
     IcmpInRangeEnd,
     UpdateAt,
+    Nat6Mode,
+    TcpSynTimeout,
+    TcpEstablishedTimeout,
+    TcpClosingTimeout,
+    UdpTimeout,
+    IcmpTimeout,
+    MaxMappingsPerHost,
+    NatBehavior,
+    SnatPool,
 }
@@ -0,0 +1,18 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveIden)]
+pub enum PortForwardRuleConfigs {
+    #[sea_orm(iden = "port_forward_rule_configs")]
+    Table,
+    Id,
+    Enable,
+    Remark,
+    WanIfaceName,
+    Protocol,
+    ExternalPortStart,
+    ExternalPortEnd,
+    InternalIp,
+    InternalPort,
+    Hairpin,
+    UpdateAt,
+}
@@ -28,6 +28,7 @@ pub struct Model {
     pub address_lease_time: Option<u32>,
 
     pub mac_binding_records: DBJson,
+    pub local_domain: Option<String>,
     pub update_at: DBTimestamp,
 }
 
@@ -46,6 +47,7 @@ impl From<Model> for DHCPv4ServiceConfig {
             network_mask: entity.network_mask,
             address_lease_time: entity.address_lease_time,
             mac_binding_records: serde_json::from_value(entity.mac_binding_records).unwrap(),
+            local_domain: entity.local_domain,
         };
         DHCPv4ServiceConfig {
             iface_name: entity.iface_name,
@@ -77,6 +79,7 @@ impl UpdateActiveModel<ActiveModel> for DHCPv4ServiceConfig {
         active.address_lease_time = Set(self.config.address_lease_time);
         active.mac_binding_records = Set(serde_json::to_value(&self.config.mac_binding_records)
             .unwrap_or(serde_json::Value::Array(vec![])));
+        active.local_domain = Set(self.config.local_domain);
         active.update_at = Set(self.update_at);
     }
 }
@@ -31,6 +31,7 @@ pub struct Model {
     pub source: String,
     pub flow_id: u32,
     pub update_at: DBTimestamp,
+    pub answer_filter: Option<DBJson>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -62,6 +63,10 @@ impl From<Model> for DNSRuleConfig {
             source: serde_json::from_str(&entity.source).unwrap(),
             flow_id: entity.flow_id,
             update_at: entity.update_at,
+            answer_filter: entity
+                .answer_filter
+                .and_then(|val| serde_json::from_value(val).ok())
+                .unwrap_or_default(),
         }
     }
 }
@@ -88,6 +93,7 @@ impl UpdateActiveModel<ActiveModel> for DNSRuleConfig {
         active.source = Set(serde_json::to_string(&self.source).unwrap());
         active.flow_id = Set(self.flow_id);
         active.update_at = Set(self.update_at);
+        active.answer_filter = Set(serde_json::to_value(&self.answer_filter).ok());
     }
 }
 
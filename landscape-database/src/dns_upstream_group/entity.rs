@@ -0,0 +1,72 @@
+use landscape_common::{
+    config::dns::DnsUpstreamGroupConfig, database::repository::UpdateActiveModel,
+};
+use sea_orm::{entity::prelude::*, ActiveValue::Set};
+use serde::{Deserialize, Serialize};
+
+use crate::{DBId, DBJson, DBTimestamp};
+
+pub type DNSUpstreamGroupModel = Model;
+pub type DNSUpstreamGroupEntity = Entity;
+pub type DNSUpstreamGroupActiveModel = ActiveModel;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "dns_upstream_groups")]
+#[cfg_attr(feature = "postgres", sea_orm(schema_name = "public"))]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    /// 主键 ID
+    pub id: DBId,
+    pub name: String,
+    pub strategy: DBJson,
+    pub servers: DBJson,
+    pub update_at: DBTimestamp,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+#[async_trait::async_trait]
+impl ActiveModelBehavior for ActiveModel {
+    async fn before_save<C>(mut self, _db: &C, insert: bool) -> Result<Self, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        if insert && self.id.is_not_set() {
+            self.id = Set(Uuid::new_v4());
+        }
+        Ok(self)
+    }
+}
+
+impl From<Model> for DnsUpstreamGroupConfig {
+    fn from(entity: Model) -> Self {
+        DnsUpstreamGroupConfig {
+            id: Some(entity.id),
+            name: entity.name,
+            strategy: serde_json::from_value(entity.strategy).unwrap(),
+            servers: serde_json::from_value(entity.servers).unwrap(),
+            update_at: entity.update_at,
+        }
+    }
+}
+
+impl Into<ActiveModel> for DnsUpstreamGroupConfig {
+    fn into(self) -> ActiveModel {
+        let mut active = ActiveModel {
+            id: Set(self.id.unwrap_or_else(Uuid::new_v4)),
+            ..Default::default()
+        };
+        self.update(&mut active);
+        active
+    }
+}
+
+impl UpdateActiveModel<ActiveModel> for DnsUpstreamGroupConfig {
+    fn update(self, active: &mut ActiveModel) {
+        active.name = Set(self.name);
+        active.strategy = Set(serde_json::to_value(self.strategy).unwrap().into());
+        active.servers = Set(serde_json::to_value(self.servers).unwrap().into());
+        active.update_at = Set(self.update_at);
+    }
+}
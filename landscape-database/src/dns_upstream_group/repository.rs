@@ -0,0 +1,36 @@
+use landscape_common::{
+    config::dns::DnsUpstreamGroupConfig,
+    database::{repository::Repository, LandscapeDBTrait},
+};
+use sea_orm::DatabaseConnection;
+
+use crate::DBId;
+
+use super::entity::{DNSUpstreamGroupActiveModel, DNSUpstreamGroupEntity, DNSUpstreamGroupModel};
+
+#[derive(Clone)]
+pub struct DNSUpstreamGroupRepository {
+    db: DatabaseConnection,
+}
+
+impl DNSUpstreamGroupRepository {
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait::async_trait]
+impl LandscapeDBTrait for DNSUpstreamGroupRepository {}
+
+#[async_trait::async_trait]
+impl Repository for DNSUpstreamGroupRepository {
+    type Model = DNSUpstreamGroupModel;
+    type Entity = DNSUpstreamGroupEntity;
+    type ActiveModel = DNSUpstreamGroupActiveModel;
+    type Data = DnsUpstreamGroupConfig;
+    type Id = DBId;
+
+    fn db(&self) -> &DatabaseConnection {
+        &self.db
+    }
+}
@@ -26,6 +26,8 @@ pub struct Model {
     pub packet_handle_iface_name: DBJson,
     pub remark: String,
     pub update_at: DBTimestamp,
+    pub dns_policy: Option<DBJson>,
+    pub nat_behavior: Option<DBJson>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -53,6 +55,11 @@ impl From<Model> for FlowConfig {
             flow_match_rules: serde_json::from_value(entity.flow_match_rules).unwrap(),
             flow_targets: serde_json::from_value(entity.packet_handle_iface_name).unwrap(),
             remark: entity.remark,
+            dns_policy: entity
+                .dns_policy
+                .and_then(|val| serde_json::from_value(val).ok())
+                .unwrap_or_default(),
+            nat_behavior: entity.nat_behavior.and_then(|val| serde_json::from_value(val).ok()),
             update_at: entity.update_at,
         }
     }
@@ -77,6 +84,8 @@ impl UpdateActiveModel<ActiveModel> for FlowConfig {
         active.packet_handle_iface_name =
             Set(serde_json::to_value(self.flow_targets).unwrap().into());
         active.remark = Set(self.remark);
+        active.dns_policy = Set(serde_json::to_value(&self.dns_policy).ok());
+        active.nat_behavior = Set(self.nat_behavior.and_then(|b| serde_json::to_value(b).ok()));
         active.update_at = Set(self.update_at);
     }
 }
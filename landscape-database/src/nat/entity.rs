@@ -3,7 +3,7 @@ use landscape_common::database::repository::UpdateActiveModel;
 use sea_orm::{entity::prelude::*, ActiveValue::Set};
 use serde::{Deserialize, Serialize};
 
-use crate::DBTimestamp;
+use crate::{DBJson, DBTimestamp};
 
 pub type NatServiceConfigModel = Model;
 pub type NatServiceConfigEntity = Entity;
@@ -26,6 +26,17 @@ pub struct Model {
     pub icmp_in_range_start: u16,
     pub icmp_in_range_end: u16,
 
+    pub nat6_mode: Option<DBJson>,
+    pub snat_pool: Option<DBJson>,
+
+    pub tcp_syn_timeout: u32,
+    pub tcp_established_timeout: u32,
+    pub tcp_closing_timeout: u32,
+    pub udp_timeout: u32,
+    pub icmp_timeout: u32,
+    pub max_mappings_per_host: u32,
+    pub nat_behavior: Option<DBJson>,
+
     pub update_at: DBTimestamp,
 }
 
@@ -44,7 +55,25 @@ impl From<Model> for NatServiceConfig {
                 tcp_range: model.tcp_range_start..model.tcp_range_end,
                 udp_range: model.udp_range_start..model.udp_range_end,
                 icmp_in_range: model.icmp_in_range_start..model.icmp_in_range_end,
+                tcp_syn_timeout: model.tcp_syn_timeout,
+                tcp_established_timeout: model.tcp_established_timeout,
+                tcp_closing_timeout: model.tcp_closing_timeout,
+                udp_timeout: model.udp_timeout,
+                icmp_timeout: model.icmp_timeout,
+                max_mappings_per_host: model.max_mappings_per_host,
+                behavior: model
+                    .nat_behavior
+                    .and_then(|val| serde_json::from_value(val).ok())
+                    .unwrap_or_default(),
             },
+            nat6_mode: model
+                .nat6_mode
+                .and_then(|val| serde_json::from_value(val).ok())
+                .unwrap_or_default(),
+            snat_pool: model
+                .snat_pool
+                .and_then(|val| serde_json::from_value(val).ok())
+                .unwrap_or_default(),
             update_at: model.update_at,
         }
     }
@@ -74,6 +103,17 @@ impl UpdateActiveModel<ActiveModel> for NatServiceConfig {
         active.icmp_in_range_start = Set(self.nat_config.icmp_in_range.start);
         active.icmp_in_range_end = Set(self.nat_config.icmp_in_range.end);
 
+        active.tcp_syn_timeout = Set(self.nat_config.tcp_syn_timeout);
+        active.tcp_established_timeout = Set(self.nat_config.tcp_established_timeout);
+        active.tcp_closing_timeout = Set(self.nat_config.tcp_closing_timeout);
+        active.udp_timeout = Set(self.nat_config.udp_timeout);
+        active.icmp_timeout = Set(self.nat_config.icmp_timeout);
+        active.max_mappings_per_host = Set(self.nat_config.max_mappings_per_host);
+        active.nat_behavior = Set(serde_json::to_value(&self.nat_config.behavior).ok());
+
+        active.nat6_mode = Set(serde_json::to_value(&self.nat6_mode).ok());
+        active.snat_pool = Set(serde_json::to_value(&self.snat_pool).ok());
+
         active.update_at = Set(self.update_at);
     }
 }
@@ -0,0 +1,91 @@
+use landscape_common::{
+    config::nat::PortForwardRuleConfig, database::repository::UpdateActiveModel,
+};
+use sea_orm::{entity::prelude::*, ActiveValue::Set};
+use serde::{Deserialize, Serialize};
+
+use crate::{DBId, DBJson, DBTimestamp};
+
+pub type PortForwardRuleConfigModel = Model;
+pub type PortForwardRuleConfigEntity = Entity;
+pub type PortForwardRuleConfigActiveModel = ActiveModel;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "port_forward_rule_configs")]
+#[cfg_attr(feature = "postgres", sea_orm(schema_name = "public"))]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = false)]
+    /// 主键 ID
+    pub id: DBId,
+    pub enable: bool,
+    pub remark: String,
+    pub wan_iface_name: String,
+    #[sea_orm(column_type = "Json")]
+    pub protocol: DBJson,
+    pub external_port_start: u16,
+    pub external_port_end: u16,
+    pub internal_ip: String,
+    pub internal_port: u16,
+    pub hairpin: bool,
+    pub update_at: DBTimestamp,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+#[async_trait::async_trait]
+impl ActiveModelBehavior for ActiveModel {
+    async fn before_save<C>(mut self, _db: &C, insert: bool) -> Result<Self, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        if insert && self.id.is_not_set() {
+            self.id = Set(Uuid::new_v4());
+        }
+        Ok(self)
+    }
+}
+
+impl From<Model> for PortForwardRuleConfig {
+    fn from(entity: Model) -> Self {
+        PortForwardRuleConfig {
+            id: Some(entity.id),
+            enable: entity.enable,
+            remark: entity.remark,
+            wan_iface_name: entity.wan_iface_name,
+            protocol: serde_json::from_value(entity.protocol).unwrap_or_default(),
+            external_port_start: entity.external_port_start,
+            external_port_end: entity.external_port_end,
+            internal_ip: entity.internal_ip.parse().expect("Invalid IP format"),
+            internal_port: entity.internal_port,
+            hairpin: entity.hairpin,
+            update_at: entity.update_at,
+        }
+    }
+}
+
+impl Into<ActiveModel> for PortForwardRuleConfig {
+    fn into(self) -> ActiveModel {
+        let mut active = ActiveModel {
+            id: Set(self.id.unwrap_or_else(Uuid::new_v4)),
+            ..Default::default()
+        };
+        self.update(&mut active);
+        active
+    }
+}
+
+impl UpdateActiveModel<ActiveModel> for PortForwardRuleConfig {
+    fn update(self, active: &mut ActiveModel) {
+        active.enable = Set(self.enable);
+        active.remark = Set(self.remark);
+        active.wan_iface_name = Set(self.wan_iface_name);
+        active.protocol = Set(serde_json::to_value(self.protocol).unwrap().into());
+        active.external_port_start = Set(self.external_port_start);
+        active.external_port_end = Set(self.external_port_end);
+        active.internal_ip = Set(self.internal_ip.to_string());
+        active.internal_port = Set(self.internal_port);
+        active.hairpin = Set(self.hairpin);
+        active.update_at = Set(self.update_at);
+    }
+}
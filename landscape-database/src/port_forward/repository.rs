@@ -0,0 +1,36 @@
+use landscape_common::{
+    config::nat::PortForwardRuleConfig,
+    database::{repository::Repository, LandscapeDBTrait},
+};
+use sea_orm::DatabaseConnection;
+
+use crate::{port_forward::entity::PortForwardRuleConfigEntity, DBId};
+
+use super::entity::{PortForwardRuleConfigActiveModel, PortForwardRuleConfigModel};
+
+#[derive(Clone)]
+pub struct PortForwardRuleRepository {
+    db: DatabaseConnection,
+}
+
+impl PortForwardRuleRepository {
+    pub fn new(db: DatabaseConnection) -> Self {
+        Self { db }
+    }
+}
+
+#[async_trait::async_trait]
+impl LandscapeDBTrait for PortForwardRuleRepository {}
+
+#[async_trait::async_trait]
+impl Repository for PortForwardRuleRepository {
+    type Model = PortForwardRuleConfigModel;
+    type Entity = PortForwardRuleConfigEntity;
+    type ActiveModel = PortForwardRuleConfigActiveModel;
+    type Data = PortForwardRuleConfig;
+    type Id = DBId;
+
+    fn db(&self) -> &DatabaseConnection {
+        &self.db
+    }
+}
@@ -11,14 +11,15 @@ use migration::{Migrator, MigratorTrait};
 use crate::{
     dhcp_v4_server::repository::DHCPv4ServerRepository,
     dhcp_v6_client::repository::DHCPv6ClientRepository, dns_rule::repository::DNSRuleRepository,
+    dns_upstream_group::repository::DNSUpstreamGroupRepository,
     dst_ip_rule::repository::DstIpRuleRepository, firewall::repository::FirewallServiceRepository,
     firewall_rule::repository::FirewallRuleRepository, flow_rule::repository::FlowConfigRepository,
     flow_wan::repository::FlowWanServiceRepository,
     geo_ip::repository::GeoIpSourceConfigRepository, geo_site::repository::GeoSiteConfigRepository,
     iface::repository::NetIfaceRepository, iface_ip::repository::IfaceIpServiceRepository,
     mss_clamp::repository::MssClampServiceRepository, nat::repository::NatServiceRepository,
-    pppd::repository::PPPDServiceRepository, ra::repository::IPV6RAServiceRepository,
-    route_lan::repository::RouteLanServiceRepository,
+    port_forward::repository::PortForwardRuleRepository, pppd::repository::PPPDServiceRepository,
+    ra::repository::IPV6RAServiceRepository, route_lan::repository::RouteLanServiceRepository,
     route_wan::repository::RouteWanServiceRepository, wifi::repository::WifiServiceRepository,
 };
 
@@ -55,10 +56,12 @@ impl LandscapeDBServiceProvider {
             ifaces,
             ipconfigs,
             nats,
+            port_forwards,
             marks,
             pppds,
             flow_rules,
             dns_rules,
+            dns_upstream_groups,
             dhcpv6pds,
             icmpras,
             firewalls,
@@ -115,6 +118,12 @@ impl LandscapeDBServiceProvider {
                 iface_nat_store.set_model(each_config).await.unwrap();
             }
 
+            let port_forward_store = self.port_forward_rule_store();
+            port_forward_store.truncate_table().await.unwrap();
+            for each_config in port_forwards {
+                port_forward_store.set_model(each_config).await.unwrap();
+            }
+
             let flow_store = self.flow_rule_store();
             flow_store.truncate_table().await.unwrap();
             for each_config in flow_rules {
@@ -145,6 +154,12 @@ impl LandscapeDBServiceProvider {
                 dns_store.set_model(each_config).await.unwrap();
             }
 
+            let dns_upstream_group_store = self.dns_upstream_group_store();
+            dns_upstream_group_store.truncate_table().await.unwrap();
+            for each_config in dns_upstream_groups {
+                dns_upstream_group_store.set_model(each_config).await.unwrap();
+            }
+
             let ipv6pd_store = self.dhcp_v6_client_store();
             ipv6pd_store.truncate_table().await.unwrap();
             for each_config in dhcpv6pds {
@@ -195,6 +210,10 @@ impl LandscapeDBServiceProvider {
         DNSRuleRepository::new(self.database.clone())
     }
 
+    pub fn dns_upstream_group_store(&self) -> DNSUpstreamGroupRepository {
+        DNSUpstreamGroupRepository::new(self.database.clone())
+    }
+
     pub fn firewall_rule_store(&self) -> FirewallRuleRepository {
         FirewallRuleRepository::new(self.database.clone())
     }
@@ -203,6 +222,10 @@ impl LandscapeDBServiceProvider {
         FlowConfigRepository::new(self.database.clone())
     }
 
+    pub fn port_forward_rule_store(&self) -> PortForwardRuleRepository {
+        PortForwardRuleRepository::new(self.database.clone())
+    }
+
     pub fn dst_ip_rule_store(&self) -> DstIpRuleRepository {
         DstIpRuleRepository::new(self.database.clone())
     }
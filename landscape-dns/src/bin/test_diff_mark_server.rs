@@ -4,8 +4,16 @@ use std::{
     sync::Arc,
 };
 
-use landscape_common::config::dns::DNSRuntimeRule;
-use landscape_dns::server::{request::LandscapeDnsRequestHandle, server::DiffFlowServer};
+use landscape_common::{
+    config::{dns::DNSRuntimeRule, DnsRuntimeConfig},
+    metric::dns::DnsMetricManager,
+};
+use landscape_dns::{
+    local_zone::LocalZone,
+    policy::DnsPolicyManager,
+    server::{request::LandscapeDnsRequestHandle, server::DiffFlowServer},
+    upstream::UpstreamHealthRegistry,
+};
 use tokio::sync::RwLock;
 
 /// cargo run --package landscape-dns --bin test_diff_mark_server
@@ -16,15 +24,28 @@ async fn main() -> std::io::Result<()> {
     let listen_port = 53;
 
     let default_rule = vec![DNSRuntimeRule::default()];
-    let handler = LandscapeDnsRequestHandle::new(default_rule, 100);
+    let dns_metric = DnsMetricManager::new().await;
+    let policy = DnsPolicyManager::new(dns_metric.clone());
+    let handler = LandscapeDnsRequestHandle::new(
+        default_rule,
+        100,
+        LocalZone::new(),
+        DnsRuntimeConfig::default(),
+        UpstreamHealthRegistry::new(),
+        dns_metric,
+        policy.clone(),
+    );
     let mut handlers_map = HashMap::new();
     handlers_map.insert(100, handler);
     let mut server = DiffFlowServer::new(
         Arc::new(RwLock::new(handlers_map)),
         Arc::new(RwLock::new(HashMap::new())),
+        policy,
     );
 
-    server.listen_on(SocketAddr::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), listen_port));
+    let listen_addr = SocketAddr::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), listen_port);
+    server.listen_on(listen_addr, None).unwrap();
+    server.listen_on_tcp(listen_addr, None).unwrap();
 
     server.block_until_done().await.unwrap();
     Ok(())
@@ -0,0 +1,133 @@
+use std::path::{Path, PathBuf};
+
+use hickory_proto::{
+    rr::{Record, RecordType},
+    serialize::binary::{BinDecodable, BinEncodable},
+};
+use landscape_common::utils::time::get_f64_timestamp;
+use serde::{Deserialize, Serialize};
+
+/// 缓存中的一个域名, 记录使用 DNS 报文格式保存
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CacheSnapshotEntry {
+    pub domain: String,
+    pub query_type: u16,
+    /// TTL 为保存时的剩余时间
+    pub records: Vec<Vec<u8>>,
+}
+
+/// 单个 flow 的缓存快照
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FlowCacheSnapshot {
+    pub flow_id: u32,
+    /// 保存时间 (毫秒)
+    pub save_time: f64,
+    pub entries: Vec<CacheSnapshotEntry>,
+}
+
+impl CacheSnapshotEntry {
+    pub fn new(domain: String, query_type: RecordType, records: &[Record]) -> Option<Self> {
+        let records: Vec<Vec<u8>> =
+            records.iter().filter_map(|record| record.to_bytes().ok()).collect();
+        if records.is_empty() {
+            return None;
+        }
+        Some(CacheSnapshotEntry { domain, query_type: query_type.into(), records })
+    }
+
+    /// 扣除保存之后经过的时间, 所有记录都已过期时返回 None
+    pub fn restore(&self, elapsed_secs: u64) -> Option<(String, RecordType, Vec<Record>)> {
+        let mut records = Vec::with_capacity(self.records.len());
+        for bytes in self.records.iter() {
+            let Ok(mut record) = Record::from_bytes(bytes) else {
+                return None;
+            };
+            if (record.ttl() as u64) <= elapsed_secs {
+                return None;
+            }
+            record.set_ttl(record.ttl() - elapsed_secs as u32);
+            records.push(record);
+        }
+        Some((self.domain.clone(), RecordType::from(self.query_type), records))
+    }
+}
+
+impl FlowCacheSnapshot {
+    pub fn new(flow_id: u32, entries: Vec<CacheSnapshotEntry>) -> Self {
+        FlowCacheSnapshot { flow_id, save_time: get_f64_timestamp(), entries }
+    }
+
+    /// 保存之后经过的秒数
+    pub fn elapsed_secs(&self) -> u64 {
+        ((get_f64_timestamp() - self.save_time).max(0.0) / 1000.0) as u64
+    }
+}
+
+fn snapshot_path(dir: &Path, flow_id: u32) -> PathBuf {
+    dir.join(format!("flow_{flow_id}.json"))
+}
+
+/// 先写入临时文件再重命名, 避免中途退出时损坏已有快照
+pub fn save_snapshot(dir: &Path, snapshot: &FlowCacheSnapshot) {
+    if let Err(e) = std::fs::create_dir_all(dir) {
+        tracing::error!("create dns cache dir {dir:?} error: {e:?}");
+        return;
+    }
+    let path = snapshot_path(dir, snapshot.flow_id);
+    let tmp_path = path.with_extension("json.tmp");
+    let result = serde_json::to_vec(snapshot)
+        .map_err(std::io::Error::from)
+        .and_then(|content| std::fs::write(&tmp_path, content))
+        .and_then(|_| std::fs::rename(&tmp_path, &path));
+    if let Err(e) = result {
+        tracing::error!("save dns cache snapshot {path:?} error: {e:?}");
+    }
+}
+
+pub fn load_snapshot(dir: &Path, flow_id: u32) -> Option<FlowCacheSnapshot> {
+    let path = snapshot_path(dir, flow_id);
+    let content = std::fs::read(&path).ok()?;
+    match serde_json::from_slice(&content) {
+        Ok(snapshot) => Some(snapshot),
+        Err(e) => {
+            tracing::error!("read dns cache snapshot {path:?} error: {e:?}");
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::Ipv4Addr, str::FromStr};
+
+    use hickory_proto::rr::{rdata::A, Name, RData, Record, RecordType};
+
+    use super::{load_snapshot, save_snapshot, CacheSnapshotEntry, FlowCacheSnapshot};
+
+    #[test]
+    fn test_snapshot_restore() {
+        let record = Record::from_rdata(
+            Name::from_str("example.com.").unwrap(),
+            300,
+            RData::A(A(Ipv4Addr::new(93, 184, 216, 34))),
+        );
+        let entry =
+            CacheSnapshotEntry::new("example.com.".to_string(), RecordType::A, &[record.clone()])
+                .unwrap();
+
+        let dir = std::env::temp_dir().join(format!("landscape_dns_cache_{}", std::process::id()));
+        save_snapshot(&dir, &FlowCacheSnapshot::new(3, vec![entry]));
+        let snapshot = load_snapshot(&dir, 3).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(snapshot.flow_id, 3);
+        let (domain, query_type, records) = snapshot.entries[0].restore(100).unwrap();
+        assert_eq!(domain, "example.com.");
+        assert_eq!(query_type, RecordType::A);
+        assert_eq!(records[0].data(), record.data());
+        assert_eq!(records[0].ttl(), 200);
+
+        // 已经过期的记录不再恢复
+        assert!(snapshot.entries[0].restore(300).is_none());
+    }
+}
@@ -2,12 +2,16 @@ use std::fmt;
 use std::net::SocketAddr;
 use std::os::fd::RawFd;
 use std::os::unix::io::AsRawFd;
+use std::sync::Arc;
 use std::{future::Future, io, pin::Pin};
 
 use hickory_resolver::{
     name_server::GenericConnector,
-    proto::runtime::{iocompat::AsyncIoTokioAsStd, RuntimeProvider, TokioHandle, TokioTime},
+    proto::runtime::{
+        iocompat::AsyncIoTokioAsStd, QuicSocketBinder, RuntimeProvider, TokioHandle, TokioTime,
+    },
 };
+use quinn::Runtime;
 
 use libc::{setsockopt, SOL_SOCKET, SO_MARK, SO_RCVMARK};
 use std::time::Duration;
@@ -107,9 +111,36 @@ impl RuntimeProvider for MarkRuntimeProvider {
             Ok(socket)
         })
     }
+
+    /// DoQ / DoH3 使用的 UDP socket 同样需要标记
+    fn quic_binder(&self) -> Option<&dyn QuicSocketBinder> {
+        Some(self)
+    }
+}
+
+impl QuicSocketBinder for MarkRuntimeProvider {
+    fn bind_quic(
+        &self,
+        local_addr: SocketAddr,
+        _server_addr: SocketAddr,
+    ) -> Result<Arc<dyn quinn::AsyncUdpSocket>, io::Error> {
+        let socket = bind_mark_udp(local_addr, self.mark_value)?;
+        quinn::TokioRuntime.wrap_udp_socket(socket)
+    }
+}
+
+/// 创建带有 SO_MARK 的 UDP socket, 交由 quinn 管理
+fn bind_mark_udp(local_addr: SocketAddr, mark_value: u32) -> io::Result<std::net::UdpSocket> {
+    let socket = std::net::UdpSocket::bind(local_addr)?;
+    set_socket_mark(socket.as_raw_fd(), mark_value)?;
+    Ok(socket)
 }
 
 pub fn set_socket_mark(fd: RawFd, mark_value: u32) -> io::Result<()> {
+    // socket 默认的 mark 即为 0, 无需设置 (同时也不需要 CAP_NET_ADMIN)
+    if mark_value == 0 {
+        return Ok(());
+    }
     // 设置 SO_MARK 选项
     let result = unsafe {
         setsockopt(
@@ -146,3 +177,36 @@ pub fn set_socket_income_mark(fd: RawFd, mark_value: u32) -> io::Result<()> {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+    use std::os::unix::io::AsRawFd;
+
+    use libc::{getsockopt, SOL_SOCKET, SO_MARK};
+
+    use super::bind_mark_udp;
+
+    /// 设置 SO_MARK 需要 CAP_NET_ADMIN
+    /// cargo test --package landscape-dns -- connection::tests --ignored
+    #[test]
+    #[ignore]
+    fn test_quic_socket_mark() {
+        let local_addr: SocketAddr = "127.0.0.1:0".parse().unwrap();
+        let socket = bind_mark_udp(local_addr, 100).unwrap();
+
+        let mut mark_value: u32 = 0;
+        let mut len = std::mem::size_of::<u32>() as libc::socklen_t;
+        let result = unsafe {
+            getsockopt(
+                socket.as_raw_fd(),
+                SOL_SOCKET,
+                SO_MARK,
+                &mut mark_value as *mut u32 as *mut libc::c_void,
+                &mut len,
+            )
+        };
+        assert_eq!(result, 0);
+        assert_eq!(mark_value, 100);
+    }
+}
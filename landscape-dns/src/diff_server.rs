@@ -1,18 +1,34 @@
 use hickory_proto::rr::{Record, RecordType};
-use landscape_common::config::dns::{DNSRuntimeRule, LandscapeDnsRecordType};
-use landscape_common::config::FlowId;
-use landscape_common::flow::{FlowConfig, PacketMatchMark};
+use hickory_proto::xfer::Protocol;
+use landscape_common::config::dns::{
+    DNSRuntimeRule, DomainConfig, LandscapeDnsRecordType, RuleSource,
+};
+use landscape_common::config::{DnsRuntimeConfig, FlowId};
+use landscape_common::flow::{mark::FlowDnsMark, FlowConfig, PacketMatchMark};
+use landscape_common::metric::dns::DnsMetricManager;
 use landscape_common::service::{DefaultWatchServiceStatus, ServiceStatus};
-use std::collections::HashMap;
-use std::net::{IpAddr, Ipv6Addr, SocketAddr};
+use rustls::ServerConfig;
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::time::Duration;
+use tokio::sync::{mpsc, RwLock};
+use tokio_rustls::TlsAcceptor;
+use uuid::Uuid;
 
 use serde::{Deserialize, Serialize};
 use ts_rs::TS;
 
+use crate::cache_snapshot::save_snapshot;
+use crate::local_zone::LocalZone;
+use crate::policy::DnsPolicyManager;
+use crate::resolv_conf;
 use crate::server::request::LandscapeDnsRequestHandle;
-use crate::server::server::DiffFlowServer;
+use crate::server::server::{
+    check_rate_limit, find_request_handler, handle_raw_request, DiffFlowServer,
+};
+use crate::socket::SendDnsMessage;
+use crate::upstream::{DnsUpstreamHealth, UpstreamHealthRegistry, UPSTREAM_PROBE_INTERVAL};
 
 #[derive(Serialize, Deserialize, Debug, Default, TS)]
 #[ts(export, export_to = "dns.d.ts")]
@@ -25,6 +41,23 @@ pub struct CheckDnsResult {
     pub cache_records: Option<Vec<Record>>,
 }
 
+/// 每个 flow 的 DNS 缓存统计
+#[derive(Serialize, Deserialize, Debug, Default, TS)]
+#[ts(export, export_to = "dns.d.ts")]
+pub struct DnsCacheStats {
+    pub flow_id: FlowId,
+    pub capacity: u32,
+    pub size: u32,
+    /// NXDOMAIN / NODATA 缓存数量
+    pub negative_size: u32,
+    #[ts(type = "number")]
+    pub hits: u64,
+    #[ts(type = "number")]
+    pub negative_hits: u64,
+    #[ts(type = "number")]
+    pub misses: u64,
+}
+
 #[derive(Serialize, Deserialize, Debug, TS)]
 #[ts(export, export_to = "dns.d.ts")]
 pub struct CheckDnsReq {
@@ -39,10 +72,99 @@ impl CheckDnsReq {
     }
 }
 
+/// 批量追踪域名的规则匹配过程
+#[derive(Serialize, Deserialize, Debug, TS)]
+#[ts(export, export_to = "dns.d.ts")]
+pub struct TraceDnsReq {
+    pub domains: Vec<String>,
+    /// 未指定时根据 `client_ip` 查找 flow, 都未指定时使用默认 flow
+    #[serde(default)]
+    pub flow_id: Option<FlowId>,
+    #[serde(default)]
+    pub client_ip: Option<IpAddr>,
+    /// 用于检查缓存状态, 默认为 A
+    #[serde(default)]
+    pub record_type: Option<LandscapeDnsRecordType>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, TS)]
+#[ts(export, export_to = "dns.d.ts")]
+pub struct TraceDnsResult {
+    /// 实际使用的 flow
+    pub flow_id: FlowId,
+    pub domains: Vec<DomainTrace>,
+}
+
+/// 单个域名的匹配过程
+#[derive(Serialize, Deserialize, Debug, Default, TS)]
+#[ts(export, export_to = "dns.d.ts")]
+pub struct DomainTrace {
+    pub domain: String,
+    /// 本地区域中存在记录, 查询时直接应答不经过规则
+    pub local_zone: bool,
+    /// 按照优先级依次检查的规则, 最后一条为最终处理的规则
+    pub rules: Vec<DnsRuleTrace>,
+    pub mark: Option<FlowDnsMark>,
+    /// 写入 eBPF map 中的标记值, 不需要写入时为空
+    pub mark_value: Option<u32>,
+    pub upstream: Option<String>,
+    pub cache: DnsCacheTrace,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq, TS)]
+#[ts(export, export_to = "dns.d.ts")]
+#[serde(rename_all = "snake_case")]
+pub enum DnsRuleTraceResult {
+    Matched,
+    /// 命中了规则的例外列表
+    Excluded,
+    #[default]
+    NotMatched,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, TS)]
+#[ts(export, export_to = "dns.d.ts")]
+pub struct DnsRuleTrace {
+    pub index: u32,
+    pub name: String,
+    pub result: DnsRuleTraceResult,
+    /// 命中的配置来源 (geo key / 内联配置), 规则未配置来源时匹配所有域名, 此时为空
+    pub source: Option<RuleSource>,
+    /// 命中的具体条目
+    pub domain_config: Option<DomainConfig>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq, TS)]
+#[ts(export, export_to = "dns.d.ts")]
+#[serde(rename_all = "snake_case")]
+pub enum DnsCacheState {
+    #[default]
+    Miss,
+    Fresh,
+    /// 已过期, 开启 serve stale 时仍会返回
+    Stale,
+    /// NXDOMAIN / NODATA 缓存
+    Negative,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, TS)]
+#[ts(export, export_to = "dns.d.ts")]
+pub struct DnsCacheTrace {
+    pub state: DnsCacheState,
+    /// 剩余的 TTL, 过期后为负数
+    #[ts(type = "number | null")]
+    pub remaining_ttl: Option<i64>,
+}
+
 fn convert_record_type(record_type: LandscapeDnsRecordType) -> RecordType {
     match record_type {
         LandscapeDnsRecordType::A => RecordType::A,
         LandscapeDnsRecordType::AAAA => RecordType::AAAA,
+        LandscapeDnsRecordType::CNAME => RecordType::CNAME,
+        LandscapeDnsRecordType::TXT => RecordType::TXT,
+        LandscapeDnsRecordType::MX => RecordType::MX,
+        LandscapeDnsRecordType::SRV => RecordType::SRV,
+        LandscapeDnsRecordType::PTR => RecordType::PTR,
     }
 }
 
@@ -53,25 +175,150 @@ pub struct LandscapeFiffFlowDnsService {
     handlers: Arc<RwLock<HashMap<u32, LandscapeDnsRequestHandle>>>,
     #[serde(skip)]
     dispatch_rules: Arc<RwLock<HashMap<PacketMatchMark, u32>>>,
+    #[serde(skip)]
+    local_zone: LocalZone,
+    #[serde(skip)]
+    config: DnsRuntimeConfig,
+    /// 上游组健康状态, 在规则刷新之间保留
+    #[serde(skip)]
+    upstream_health: UpstreamHealthRegistry,
+    /// 查询日志
+    #[serde(skip)]
+    dns_metric: DnsMetricManager,
+    /// 每个 flow 的限速以及查询类型策略
+    #[serde(skip)]
+    policy: DnsPolicyManager,
+    /// DoT 使用的证书
+    #[serde(skip)]
+    tls_config: Option<Arc<ServerConfig>>,
 }
 
 impl LandscapeFiffFlowDnsService {
-    pub async fn new() -> Self {
+    pub async fn new(
+        config: DnsRuntimeConfig,
+        tls_config: Option<ServerConfig>,
+        dns_metric: DnsMetricManager,
+    ) -> Self {
         let status = DefaultWatchServiceStatus::new();
         let handlers = Arc::new(RwLock::new(HashMap::new()));
         let dispatch_rules = Arc::new(RwLock::new(HashMap::new()));
-        LandscapeFiffFlowDnsService { status, handlers, dispatch_rules }
+        let tls_config = tls_config.map(|mut tls_config| {
+            // RFC 7858 ALPN
+            tls_config.alpn_protocols = vec![b"dot".to_vec()];
+            Arc::new(tls_config)
+        });
+        let policy = DnsPolicyManager::new(dns_metric.clone());
+        policy.update(&config.default_flow_policy, &[]);
+        let service = LandscapeFiffFlowDnsService {
+            status,
+            handlers,
+            dispatch_rules,
+            local_zone: LocalZone::new(),
+            config,
+            upstream_health: UpstreamHealthRegistry::new(),
+            dns_metric,
+            policy,
+            tls_config,
+        };
+
+        if service.config.cache_snapshot_dir.is_some() {
+            let service_clone = service.clone();
+            let interval = Duration::from_secs(service.config.cache_snapshot_interval.max(10));
+            tokio::spawn(async move {
+                let mut ticker = tokio::time::interval(interval);
+                // 跳过第一次立即触发, 避免在恢复之前覆盖快照
+                ticker.tick().await;
+                loop {
+                    ticker.tick().await;
+                    service_clone.save_cache_snapshot().await;
+                }
+            });
+        }
+
+        // 不可用的上游只在客户端查询时重试会一直处于不可用状态, 定时主动探测
+        let service_clone = service.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(UPSTREAM_PROBE_INTERVAL);
+            loop {
+                ticker.tick().await;
+                let handlers: Vec<LandscapeDnsRequestHandle> =
+                    service_clone.handlers.read().await.values().cloned().collect();
+                for handler in handlers {
+                    handler.probe_upstreams().await;
+                }
+            }
+        });
+        service
     }
 
-    pub async fn restart(&self, listen_port: u16) {
+    /// 将所有 flow 的 DNS 缓存保存到磁盘, 启动时用于恢复缓存以及 flow 的 DNS 标记
+    pub async fn save_cache_snapshot(&self) {
+        let Some(dir) = self.config.cache_snapshot_dir.clone() else {
+            return;
+        };
+        let handlers: Vec<LandscapeDnsRequestHandle> =
+            self.handlers.read().await.values().cloned().collect();
+        for handler in handlers {
+            let snapshot = handler.cache_snapshot().await;
+            tracing::debug!(
+                "save dns cache snapshot, flow: {}, entries: {}",
+                snapshot.flow_id,
+                snapshot.entries.len()
+            );
+            save_snapshot(&dir, &snapshot);
+        }
+    }
+
+    pub async fn restart(&self) {
         let service_status = self.status.clone();
         service_status.wait_stop().await;
 
         let handlers = self.handlers.clone();
         let dispatch_rules = self.dispatch_rules.clone();
-        let mut server = DiffFlowServer::new(handlers, dispatch_rules);
+        let mut server = DiffFlowServer::new(handlers, dispatch_rules, self.policy.clone());
+
+        // 未指定网卡时不绑定
+        let interfaces: Vec<Option<&str>> = if self.config.listen_interfaces.is_empty() {
+            vec![None]
+        } else {
+            self.config.listen_interfaces.iter().map(|iface| Some(iface.as_str())).collect()
+        };
+        let tls_acceptor = match (self.config.dot_enable, self.tls_config.clone()) {
+            (true, Some(tls_config)) => Some(TlsAcceptor::from(tls_config)),
+            (true, None) => {
+                tracing::error!("DNS over TLS is enabled, but no certificate is provided");
+                None
+            }
+            (false, _) => None,
+        };
+        for ip in self.config.listen_addresses.iter() {
+            for iface in interfaces.iter() {
+                // 单个地址或网卡监听失败时跳过, 不影响其他监听
+                let listen_addr = SocketAddr::new(*ip, self.config.listen_port);
+                if let Err(e) = server.listen_on(listen_addr, *iface) {
+                    tracing::error!("DNS listen on udp {listen_addr} ({iface:?}) error: {e:?}");
+                }
+                if let Err(e) = server.listen_on_tcp(listen_addr, *iface) {
+                    tracing::error!("DNS listen on tcp {listen_addr} ({iface:?}) error: {e:?}");
+                }
 
-        server.listen_on(SocketAddr::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), listen_port));
+                if let Some(tls_acceptor) = tls_acceptor.clone() {
+                    let dot_addr = SocketAddr::new(*ip, self.config.dot_port);
+                    if let Err(e) = server.listen_on_tls(dot_addr, *iface, tls_acceptor) {
+                        tracing::error!("DNS listen on tls {dot_addr} ({iface:?}) error: {e:?}");
+                    }
+                }
+            }
+        }
+        tracing::info!("DNS listen on: {}", self.config.listen_summary());
+
+        if self.config.takeover_resolv_conf {
+            resolv_conf::takeover(&self.config);
+        } else {
+            // 之前的版本或者配置变更前接管过
+            resolv_conf::restore();
+        }
+        let takeover_resolv_conf = self.config.takeover_resolv_conf;
 
         service_status.just_change_status(ServiceStatus::Staring);
 
@@ -99,14 +346,26 @@ impl LandscapeFiffFlowDnsService {
                 tracing::info!("DNS stopping trigger by ui");
                 if let Err(e) = server.shutdown_gracefully().await {
                     tracing::error!("{e:?}");
-                    service_status.just_change_status(ServiceStatus::Stop);
-                } else {
-                    service_status.just_change_status(ServiceStatus::Stop);
                 }
             }
+            // 服务停止后系统不能再指向本机
+            if takeover_resolv_conf {
+                resolv_conf::restore();
+            }
+            if trigger_by_ui {
+                service_status.just_change_status(ServiceStatus::Stop);
+            }
         });
     }
 
+    /// 进程退出前调用, 保存缓存快照并还原 /etc/resolv.conf
+    pub async fn shutdown(&self) {
+        self.save_cache_snapshot().await;
+        if self.config.takeover_resolv_conf {
+            resolv_conf::restore();
+        }
+    }
+
     pub async fn init_handle(&self, dns_rules: Vec<DNSRuntimeRule>) {
         let dns_rules: Vec<DNSRuntimeRule> =
             dns_rules.into_iter().filter(|rule| rule.enable).collect();
@@ -125,10 +384,19 @@ impl LandscapeFiffFlowDnsService {
                     entry.get_mut().renew_rules(rules);
                 }
                 std::collections::hash_map::Entry::Vacant(entry) => {
-                    entry.insert(LandscapeDnsRequestHandle::new(rules, flow_id));
+                    entry.insert(LandscapeDnsRequestHandle::new(
+                        rules,
+                        flow_id,
+                        self.local_zone.clone(),
+                        self.config.clone(),
+                        self.upstream_health.clone(),
+                        self.dns_metric.clone(),
+                        self.policy.clone(),
+                    ));
                 }
             }
         }
+        self.prune_upstream_health(&write);
     }
 
     pub async fn update_flow_map(&self, flow_config: &Vec<FlowConfig>) {
@@ -143,6 +411,17 @@ impl LandscapeFiffFlowDnsService {
             }
         }
 
+        // 路由器自身的查询来自回环地址, flow 中已经配置的优先
+        if self.config.local_flow_id != 0 {
+            for ip in [IpAddr::V4(Ipv4Addr::LOCALHOST), IpAddr::V6(Ipv6Addr::LOCALHOST)] {
+                new_map
+                    .entry(PacketMatchMark { ip, vlan_id: None, qos: None })
+                    .or_insert(self.config.local_flow_id);
+            }
+        }
+
+        self.policy.update(&self.config.default_flow_policy, flow_config);
+
         tracing::debug!("update dispatch_rules: {new_map:?}");
         let mut map = self.dispatch_rules.write().await;
         *map = new_map;
@@ -165,10 +444,55 @@ impl LandscapeFiffFlowDnsService {
                     entry.get_mut().renew_rules(dns_rules);
                 }
                 std::collections::hash_map::Entry::Vacant(entry) => {
-                    entry.insert(LandscapeDnsRequestHandle::new(dns_rules, flow_id));
+                    entry.insert(LandscapeDnsRequestHandle::new(
+                        dns_rules,
+                        flow_id,
+                        self.local_zone.clone(),
+                        self.config.clone(),
+                        self.upstream_health.clone(),
+                        self.dns_metric.clone(),
+                        self.policy.clone(),
+                    ));
                 }
             }
         }
+        self.prune_upstream_health(&write);
+    }
+
+    /// 规则变化后移除不再被任何 flow 引用的上游健康状态
+    fn prune_upstream_health(&self, handlers: &HashMap<u32, LandscapeDnsRequestHandle>) {
+        let keys: HashSet<(Uuid, String)> =
+            handlers.values().flat_map(|handler| handler.upstream_health_keys()).collect();
+        self.upstream_health.retain(&keys);
+    }
+
+    /// 处理 DNS over HTTPS 请求, 使用与 UDP 相同的 flow 分发逻辑
+    pub async fn handle_https_message(
+        &self,
+        message: Vec<u8>,
+        src_addr: SocketAddr,
+    ) -> Option<Vec<u8>> {
+        if !self.config.doh_enable {
+            return None;
+        }
+
+        // HTTPS 无法获得 TOS, 仅使用来源 IP 进行匹配
+        let (flow_id, request_handler) =
+            find_request_handler(&self.handlers, &self.dispatch_rules, src_addr, 0).await?;
+
+        let (send_msg_tx, mut send_msg_rc) = mpsc::channel::<SendDnsMessage>(1);
+        if !check_rate_limit(&self.policy, flow_id, src_addr, &message, &send_msg_tx) {
+            drop(send_msg_tx);
+            return send_msg_rc.recv().await.map(|SendDnsMessage { message, .. }| message);
+        }
+        handle_raw_request(message, src_addr, Protocol::Https, request_handler, send_msg_tx).await;
+
+        send_msg_rc.recv().await.map(|SendDnsMessage { message, .. }| message)
+    }
+
+    /// DHCP / Docker 等向本地区域写入记录使用
+    pub fn local_zone(&self) -> LocalZone {
+        self.local_zone.clone()
     }
 
     pub fn stop(&self) {
@@ -191,4 +515,45 @@ impl LandscapeFiffFlowDnsService {
             CheckDnsResult::default()
         }
     }
+
+    pub async fn trace_domains(&self, req: TraceDnsReq) -> TraceDnsResult {
+        let flow_id = match (req.flow_id, req.client_ip) {
+            (Some(flow_id), _) => flow_id,
+            (None, Some(ip)) => {
+                let find_key = PacketMatchMark { ip, vlan_id: None, qos: None };
+                self.dispatch_rules.read().await.get(&find_key).cloned().unwrap_or(0)
+            }
+            (None, None) => 0,
+        };
+        let query_type = convert_record_type(req.record_type.unwrap_or(LandscapeDnsRecordType::A));
+
+        let Some(handler) = self.handlers.read().await.get(&flow_id).cloned() else {
+            return TraceDnsResult { flow_id, domains: vec![] };
+        };
+
+        let mut domains = Vec::with_capacity(req.domains.len());
+        for domain in req.domains.iter() {
+            let domain = domain.trim().trim_end_matches('.').to_ascii_lowercase();
+            if domain.is_empty() {
+                continue;
+            }
+            domains.push(handler.trace_domain(&domain, query_type).await);
+        }
+        TraceDnsResult { flow_id, domains }
+    }
+
+    pub async fn cache_stats(&self) -> Vec<DnsCacheStats> {
+        let handlers: Vec<LandscapeDnsRequestHandle> =
+            self.handlers.read().await.values().cloned().collect();
+        let mut result = Vec::with_capacity(handlers.len());
+        for handler in handlers {
+            result.push(handler.cache_stats().await);
+        }
+        result.sort_by_key(|stats| stats.flow_id);
+        result
+    }
+
+    pub fn upstream_health(&self) -> Vec<DnsUpstreamHealth> {
+        self.upstream_health.snapshot()
+    }
 }
@@ -0,0 +1,140 @@
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use hickory_proto::rr::{rdata::AAAA, RData, Record, RecordType};
+
+/// RFC 6052 允许的前缀长度
+const VALID_PREFIX_LENS: [u8; 6] = [32, 40, 48, 56, 64, 96];
+
+/// DNS64 使用的 NAT64 前缀
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Dns64Prefix {
+    prefix: Ipv6Addr,
+    prefix_len: u8,
+}
+
+impl Default for Dns64Prefix {
+    /// Well-Known Prefix 64:ff9b::/96
+    fn default() -> Self {
+        Dns64Prefix {
+            prefix: Ipv6Addr::new(0x64, 0xff9b, 0, 0, 0, 0, 0, 0),
+            prefix_len: 96,
+        }
+    }
+}
+
+impl Dns64Prefix {
+    /// 前缀长度不合法时使用默认前缀
+    pub fn new(prefix: Ipv6Addr, prefix_len: u8) -> Self {
+        if !VALID_PREFIX_LENS.contains(&prefix_len) {
+            tracing::error!(
+                "invalid dns64 prefix length: {prefix_len}, use {}",
+                Dns64Prefix::default()
+            );
+            return Dns64Prefix::default();
+        }
+        Dns64Prefix { prefix, prefix_len }
+    }
+
+    /// 按照 RFC 6052 Section 2.2 将 IPv4 地址嵌入前缀中, 跳过 bit 64 ~ 71
+    pub fn embed(&self, ip: Ipv4Addr) -> Ipv6Addr {
+        let mut octets = self.prefix.octets();
+        let mut index = (self.prefix_len / 8) as usize;
+        for octet in ip.octets() {
+            if index == 8 {
+                octets[index] = 0;
+                index += 1;
+            }
+            octets[index] = octet;
+            index += 1;
+        }
+        for octet in octets.iter_mut().skip(index) {
+            *octet = 0;
+        }
+        Ipv6Addr::from(octets)
+    }
+
+    /// 使用 A 记录合成 AAAA 记录, CNAME 等其余记录保持不变
+    pub fn synthesize(&self, records: Vec<Record>) -> Vec<Record> {
+        records
+            .into_iter()
+            .filter_map(|record| match record.data() {
+                RData::A(a) => Some(Record::from_rdata(
+                    record.name().clone(),
+                    record.ttl(),
+                    RData::AAAA(AAAA(self.embed(a.0))),
+                )),
+                _ if record.record_type() == RecordType::RRSIG => None,
+                _ => Some(record),
+            })
+            .collect()
+    }
+}
+
+impl std::fmt::Display for Dns64Prefix {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.prefix, self.prefix_len)
+    }
+}
+
+/// 结果中是否包含 AAAA 记录
+pub fn has_aaaa(records: &[Record]) -> bool {
+    records.iter().any(|record| record.record_type() == RecordType::AAAA)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::{Ipv4Addr, Ipv6Addr},
+        str::FromStr,
+    };
+
+    use hickory_proto::rr::{
+        rdata::{A, CNAME},
+        Name, RData, Record, RecordType,
+    };
+
+    use super::Dns64Prefix;
+
+    /// RFC 6052 Section 2.4 中的示例
+    #[test]
+    fn test_embed() {
+        let ip = Ipv4Addr::new(192, 0, 2, 33);
+        let cases = [
+            ("2001:db8::", 32, "2001:db8:c000:221::"),
+            ("2001:db8:100::", 40, "2001:db8:1c0:2:21::"),
+            ("2001:db8:122::", 48, "2001:db8:122:c000:2:2100::"),
+            ("2001:db8:122:300::", 56, "2001:db8:122:3c0:0:221::"),
+            ("2001:db8:122:344::", 64, "2001:db8:122:344:c0:2:2100:0"),
+            ("2001:db8:122:344::", 96, "2001:db8:122:344::192.0.2.33"),
+            ("64:ff9b::", 96, "64:ff9b::192.0.2.33"),
+        ];
+        for (prefix, len, expected) in cases {
+            let prefix = Dns64Prefix::new(prefix.parse().unwrap(), len);
+            assert_eq!(prefix.embed(ip), expected.parse::<Ipv6Addr>().unwrap(), "/{len}");
+        }
+
+        assert_eq!(Dns64Prefix::new("2001:db8::".parse().unwrap(), 80), Dns64Prefix::default());
+    }
+
+    #[test]
+    fn test_synthesize() {
+        let name = Name::from_str("www.example.com.").unwrap();
+        let target = Name::from_str("cdn.example.net.").unwrap();
+        let records = vec![
+            Record::from_rdata(name.clone(), 60, RData::CNAME(CNAME(target.clone()))),
+            Record::from_rdata(target.clone(), 30, RData::A(A(Ipv4Addr::new(192, 0, 2, 1)))),
+        ];
+        let result = Dns64Prefix::default().synthesize(records);
+        assert_eq!(result.len(), 2);
+        assert_eq!(result[0].record_type(), RecordType::CNAME);
+        assert_eq!(result[1].record_type(), RecordType::AAAA);
+        assert_eq!(result[1].name(), &target);
+        assert_eq!(result[1].ttl(), 30);
+        match result[1].data() {
+            RData::AAAA(aaaa) => {
+                assert_eq!(aaaa.0, "64:ff9b::192.0.2.1".parse::<Ipv6Addr>().unwrap())
+            }
+            _ => panic!("expect AAAA"),
+        }
+    }
+}
@@ -1,16 +1,31 @@
-use hickory_proto::rr::{Record, RecordType};
+use hickory_proto::{
+    op::ResponseCode,
+    rr::{Record, RecordType},
+};
 use landscape_common::{
     config::dns::FilterResult,
     flow::{DnsRuntimeMarkInfo, FlowDnsMarkInfo},
 };
 use lru::LruCache;
-use std::{collections::HashSet, time::Instant};
+use std::{
+    collections::{hash_map::Entry, HashMap, HashSet},
+    net::IpAddr,
+    num::NonZeroUsize,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Instant,
+};
 
+pub mod cache_snapshot;
 pub mod connection;
 pub mod diff_server;
+pub mod dns64;
+pub mod local_zone;
+pub mod policy;
+pub mod resolv_conf;
 pub mod rule;
 pub mod server;
 pub mod socket;
+pub mod upstream;
 
 #[derive(Clone)]
 pub struct CacheDNSItem {
@@ -18,9 +33,34 @@ pub struct CacheDNSItem {
     insert_time: Instant,
     mark: DnsRuntimeMarkInfo,
     filter: FilterResult,
+    /// 命中次数, 用于判断是否需要预取
+    hits: u32,
+    /// 最近一次发起后台刷新的时间
+    refresh_time: Option<Instant>,
 }
 
 impl CacheDNSItem {
+    /// 记录中最小的 TTL
+    fn min_ttl(&self) -> u32 {
+        self.rdatas.iter().map(|record| record.ttl()).min().unwrap_or(0)
+    }
+
+    /// 剩余的存活时间, 小于等于 0 表示已过期
+    fn remaining_ttl(&self) -> i64 {
+        self.min_ttl() as i64 - self.insert_time.elapsed().as_secs() as i64
+    }
+
+    fn get_ips(&self) -> HashSet<std::net::IpAddr> {
+        self.rdatas
+            .iter()
+            .filter_map(|record| match record.data() {
+                hickory_proto::rr::RData::A(a) => Some(std::net::IpAddr::V4(a.0)),
+                hickory_proto::rr::RData::AAAA(a) => Some(std::net::IpAddr::V6(a.0)),
+                _ => None,
+            })
+            .collect()
+    }
+
     fn get_update_rules(&self) -> HashSet<FlowDnsMarkInfo> {
         self.get_update_rules_with_mark(&self.mark)
     }
@@ -54,4 +94,193 @@ impl CacheDNSItem {
     }
 }
 
-pub type DNSCache = LruCache<(String, RecordType), Vec<CacheDNSItem>>;
+/// 域名, 查询类型以及是否为 DO 请求
+/// DO 请求的应答携带 DNSSEC 记录, 与普通应答分开缓存
+pub(crate) type DNSCacheKey = (String, RecordType, bool);
+
+/// 正向应答缓存
+/// 同时记录写入 ebpf map 的 IP 被多少条缓存引用, 只有不再被引用的 IP 才能从 map 中删除
+pub struct DNSCache {
+    entries: LruCache<DNSCacheKey, Vec<CacheDNSItem>>,
+    marked_ips: HashMap<IpAddr, usize>,
+}
+
+impl DNSCache {
+    pub fn new(capacity: NonZeroUsize) -> Self {
+        DNSCache {
+            entries: LruCache::new(capacity),
+            marked_ips: HashMap::new(),
+        }
+    }
+
+    pub fn cap(&self) -> NonZeroUsize {
+        self.entries.cap()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn peek(&self, key: &DNSCacheKey) -> Option<&Vec<CacheDNSItem>> {
+        self.entries.peek(key)
+    }
+
+    /// 仅用于更新命中统计等信息, 不能修改记录内容
+    pub fn get_mut(&mut self, key: &DNSCacheKey) -> Option<&mut Vec<CacheDNSItem>> {
+        self.entries.get_mut(key)
+    }
+
+    pub fn iter(&self) -> lru::Iter<'_, DNSCacheKey, Vec<CacheDNSItem>> {
+        self.entries.iter()
+    }
+
+    /// 写入缓存, 返回被替换的旧缓存中不再被任何缓存引用的 IP
+    /// 因容量被淘汰的缓存只减少引用计数, 已写入 ebpf map 的 mark 保留
+    pub fn put(&mut self, key: DNSCacheKey, items: Vec<CacheDNSItem>) -> HashSet<IpAddr> {
+        self.retain_ips(&items);
+        let mut released = HashSet::new();
+        match self.entries.push(key.clone(), items) {
+            Some((old_key, old_items)) if old_key == key => {
+                self.release_ips(&old_items, &mut released);
+            }
+            Some((_, evicted_items)) => {
+                self.release_ips(&evicted_items, &mut HashSet::new());
+            }
+            None => {}
+        }
+        released
+    }
+
+    /// 删除缓存, 返回不再被任何缓存引用的 IP
+    pub fn pop(&mut self, key: &DNSCacheKey) -> HashSet<IpAddr> {
+        let mut released = HashSet::new();
+        if let Some(items) = self.entries.pop(key) {
+            self.release_ips(&items, &mut released);
+        }
+        released
+    }
+
+    fn retain_ips(&mut self, items: &[CacheDNSItem]) {
+        for item in items.iter().filter(|item| item.mark.mark.need_insert_in_ebpf_map()) {
+            for ip in item.get_ips() {
+                *self.marked_ips.entry(ip).or_default() += 1;
+            }
+        }
+    }
+
+    fn release_ips(&mut self, items: &[CacheDNSItem], released: &mut HashSet<IpAddr>) {
+        for item in items.iter().filter(|item| item.mark.mark.need_insert_in_ebpf_map()) {
+            for ip in item.get_ips() {
+                if let Entry::Occupied(mut entry) = self.marked_ips.entry(ip) {
+                    *entry.get_mut() -= 1;
+                    if *entry.get() == 0 {
+                        entry.remove();
+                        released.insert(ip);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// 否定应答缓存 (RFC 2308)
+#[derive(Clone)]
+pub struct NegativeCacheItem {
+    /// NXDomain 或者 NoError (NODATA)
+    code: ResponseCode,
+    /// 上游返回的 SOA, 应答时放入 Authority 中
+    soa: Record,
+    insert_time: Instant,
+    /// 已经按照 SOA minimum 以及配置限制后的 TTL
+    ttl: u32,
+}
+
+impl NegativeCacheItem {
+    /// 剩余的存活时间, 小于等于 0 表示已过期
+    fn remaining_ttl(&self) -> i64 {
+        self.ttl as i64 - self.insert_time.elapsed().as_secs() as i64
+    }
+}
+
+pub type NegativeDNSCache = LruCache<DNSCacheKey, NegativeCacheItem>;
+
+/// 每个 flow 缓存的命中统计
+#[derive(Debug, Default)]
+pub struct DNSCacheCounter {
+    hits: AtomicU64,
+    negative_hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl DNSCacheCounter {
+    fn hit(&self) {
+        self.hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn negative_hit(&self) {
+        self.negative_hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn miss(&self) {
+        self.misses.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashSet,
+        net::{IpAddr, Ipv4Addr},
+        num::NonZeroUsize,
+        str::FromStr,
+        time::Instant,
+    };
+
+    use hickory_proto::rr::{rdata::A, Name, RData, Record, RecordType};
+    use landscape_common::{
+        config::dns::FilterResult,
+        flow::{mark::FlowDnsMark, DnsRuntimeMarkInfo},
+    };
+
+    use super::{CacheDNSItem, DNSCache};
+
+    fn cache_item(ips: &[Ipv4Addr], mark: FlowDnsMark) -> Vec<CacheDNSItem> {
+        let name = Name::from_str("example.com.").unwrap();
+        vec![CacheDNSItem {
+            rdatas: ips
+                .iter()
+                .map(|ip| Record::from_rdata(name.clone(), 60, RData::A(A(*ip))))
+                .collect(),
+            insert_time: Instant::now(),
+            mark: DnsRuntimeMarkInfo { mark, priority: 1 },
+            filter: FilterResult::Unfilter,
+            hits: 0,
+            refresh_time: None,
+        }]
+    }
+
+    fn key(domain: &str) -> (String, RecordType, bool) {
+        (domain.to_string(), RecordType::A, false)
+    }
+
+    #[test]
+    fn test_cache_marked_ip_refcount() {
+        let shared = Ipv4Addr::new(1, 1, 1, 1);
+        let only_a = Ipv4Addr::new(2, 2, 2, 2);
+        let mut cache = DNSCache::new(NonZeroUsize::new(2).unwrap());
+
+        assert!(cache
+            .put(key("a.com."), cache_item(&[shared, only_a], FlowDnsMark::Direct))
+            .is_empty());
+        assert!(cache.put(key("b.com."), cache_item(&[shared], FlowDnsMark::Direct)).is_empty());
+
+        // 刷新后 only_a 不再被引用, shared 仍被 b.com 引用
+        let released = cache.put(key("a.com."), cache_item(&[], FlowDnsMark::Direct));
+        assert_eq!(released, HashSet::from([IpAddr::V4(only_a)]));
+
+        // 不需要写入 ebpf map 的缓存不增加引用
+        cache.put(key("a.com."), cache_item(&[shared], FlowDnsMark::KeepGoing));
+        assert_eq!(cache.pop(&key("b.com.")), HashSet::from([IpAddr::V4(shared)]));
+        assert!(cache.pop(&key("a.com.")).is_empty());
+    }
+}
@@ -0,0 +1,151 @@
+use std::{collections::HashMap, net::IpAddr, str::FromStr, sync::Arc};
+
+use hickory_proto::rr::{
+    rdata::{A, AAAA, PTR},
+    Name, RData, Record, RecordType,
+};
+use landscape_common::config::dns::{LocalZoneRecord, LocalZoneSource};
+use tokio::sync::RwLock;
+
+/// 本地区域记录的 TTL, 租约和容器变化较快, 不宜过长
+const LOCAL_ZONE_TTL: u32 = 60;
+
+fn normalize_name(name: &str) -> String {
+    name.trim().trim_end_matches('.').to_lowercase()
+}
+
+/// 所有 flow 共享的本地区域
+/// 在转发上游之前进行查询
+#[derive(Clone, Debug, Default)]
+pub struct LocalZone {
+    records: Arc<RwLock<HashMap<LocalZoneSource, Vec<LocalZoneRecord>>>>,
+}
+
+impl LocalZone {
+    pub fn new() -> Self {
+        LocalZone::default()
+    }
+
+    /// 替换某一来源的全部记录, 传入空列表等同于移除
+    pub async fn replace_source(&self, source: LocalZoneSource, records: Vec<LocalZoneRecord>) {
+        let mut write = self.records.write().await;
+        if records.is_empty() {
+            write.remove(&source);
+        } else {
+            let records = records
+                .into_iter()
+                .map(|mut record| {
+                    record.name = normalize_name(&record.name);
+                    record.source = source.clone();
+                    record
+                })
+                .collect();
+            write.insert(source, records);
+        }
+    }
+
+    pub async fn remove_source(&self, source: &LocalZoneSource) {
+        self.records.write().await.remove(source);
+    }
+
+    /// 仅保留满足条件的来源
+    pub async fn retain_sources<F>(&self, f: F)
+    where
+        F: Fn(&LocalZoneSource) -> bool,
+    {
+        self.records.write().await.retain(|source, _| f(source));
+    }
+
+    pub async fn list(&self) -> Vec<LocalZoneRecord> {
+        let read = self.records.read().await;
+        let mut result: Vec<LocalZoneRecord> = read.values().flatten().cloned().collect();
+        result.sort_by(|a, b| a.name.cmp(&b.name));
+        result
+    }
+
+    /// 返回 None 表示本地区域中不存在该域名, 需要继续交由规则处理
+    /// 返回空列表表示域名存在但没有对应类型的记录
+    pub async fn lookup(&self, domain: &str, query_type: RecordType) -> Option<Vec<Record>> {
+        let domain = normalize_name(domain);
+        let owner = Name::from_str(&format!("{domain}.")).ok()?;
+        let read = self.records.read().await;
+
+        if domain.ends_with(".in-addr.arpa") || domain.ends_with(".ip6.arpa") {
+            let mut result = vec![];
+            for record in read.values().flatten() {
+                if normalize_name(&Name::from(record.ip).to_string()) != domain {
+                    continue;
+                }
+                let Ok(target) = Name::from_str(&format!("{}.", record.name)) else {
+                    continue;
+                };
+                if matches!(query_type, RecordType::PTR | RecordType::ANY) {
+                    result.push(Record::from_rdata(
+                        owner.clone(),
+                        LOCAL_ZONE_TTL,
+                        RData::PTR(PTR(target)),
+                    ));
+                }
+            }
+            return if result.is_empty() { None } else { Some(result) };
+        }
+
+        let mut found = false;
+        let mut result = vec![];
+        for record in read.values().flatten().filter(|record| record.name == domain) {
+            found = true;
+            let rdata = match (record.ip, query_type) {
+                (IpAddr::V4(ip), RecordType::A | RecordType::ANY) => RData::A(A(ip)),
+                (IpAddr::V6(ip), RecordType::AAAA | RecordType::ANY) => RData::AAAA(AAAA(ip)),
+                _ => continue,
+            };
+            result.push(Record::from_rdata(owner.clone(), LOCAL_ZONE_TTL, rdata));
+        }
+
+        if found {
+            Some(result)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use hickory_proto::rr::RecordType;
+    use landscape_common::config::dns::{LocalZoneRecord, LocalZoneSource};
+
+    use super::LocalZone;
+
+    fn dhcp_source() -> LocalZoneSource {
+        LocalZoneSource::Dhcp { iface_name: "br_lan".into() }
+    }
+
+    #[tokio::test]
+    async fn test_local_zone_lookup() {
+        let zone = LocalZone::new();
+        zone.replace_source(
+            dhcp_source(),
+            vec![LocalZoneRecord {
+                name: "NAS.lan.".into(),
+                ip: IpAddr::V4(Ipv4Addr::new(192, 168, 5, 20)),
+                source: dhcp_source(),
+            }],
+        )
+        .await;
+
+        assert_eq!(zone.lookup("nas.lan.", RecordType::A).await.unwrap().len(), 1);
+        // 存在域名但无 AAAA 记录
+        assert_eq!(zone.lookup("nas.lan.", RecordType::AAAA).await.unwrap().len(), 0);
+        assert!(zone.lookup("other.lan.", RecordType::A).await.is_none());
+
+        let ptr = zone.lookup("20.5.168.192.in-addr.arpa.", RecordType::PTR).await.unwrap();
+        assert_eq!(ptr.len(), 1);
+        assert_eq!(ptr[0].data().to_string(), "nas.lan.");
+
+        zone.replace_source(dhcp_source(), vec![]).await;
+        assert!(zone.lookup("nas.lan.", RecordType::A).await.is_none());
+    }
+}
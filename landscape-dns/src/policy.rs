@@ -0,0 +1,289 @@
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    str::FromStr,
+    sync::{Arc, Mutex, RwLock},
+    time::{Duration, Instant},
+};
+
+use hickory_proto::{
+    op::{Message, MessageType, ResponseCode},
+    rr::RecordType,
+};
+use landscape_common::{
+    config::dns::{DnsQtypeAction, DnsRateLimitAction, FlowDnsPolicy},
+    flow::FlowConfig,
+    metric::dns::{DnsMetricManager, DnsPolicyCounter},
+};
+
+/// 每个 flow 记录的客户端数量超过该值时清理空闲的客户端
+const MAX_TRACKED_CLIENTS: usize = 4096;
+/// 超过该时间没有请求的客户端令牌已经补满, 可以直接移除
+const CLIENT_IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// 令牌桶, 每秒补充 `rate` 个令牌, 最多保存 `burst` 个
+#[derive(Debug)]
+struct TokenBucket {
+    tokens: f64,
+    last: Instant,
+}
+
+impl TokenBucket {
+    fn new(burst: f64, now: Instant) -> Self {
+        TokenBucket { tokens: burst, last: now }
+    }
+
+    fn take(&mut self, rate: f64, burst: f64, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.last).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate).min(burst);
+        self.last = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// (每秒令牌数, 桶容量), qps 为 0 时不限制
+fn bucket_limit(qps: u32, burst: u32) -> Option<(f64, f64)> {
+    if qps == 0 {
+        return None;
+    }
+    let burst = if burst == 0 { qps } else { burst.max(1) };
+    Some((qps as f64, burst as f64))
+}
+
+/// 限速检查的结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RateLimitResult {
+    Allow,
+    /// 丢弃请求, 不进行应答
+    Drop,
+    /// 直接发送该应答, 不再进行处理
+    Respond(Vec<u8>),
+}
+
+/// 只复制请求的 ID 以及问题, 无法解析的请求不进行应答
+fn limited_response(message: &[u8], code: ResponseCode) -> Option<Vec<u8>> {
+    let request = Message::from_vec(message).ok()?;
+    if request.message_type() == MessageType::Response {
+        return None;
+    }
+    let mut response = Message::new();
+    response
+        .set_id(request.id())
+        .set_message_type(MessageType::Response)
+        .set_op_code(request.op_code())
+        .set_recursion_desired(request.recursion_desired())
+        .set_recursion_available(true)
+        .set_response_code(code)
+        .add_queries(request.queries().to_vec());
+    response.to_vec().ok()
+}
+
+#[derive(Debug)]
+struct FlowPolicyState {
+    policy: FlowDnsPolicy,
+    client_limit: Option<(f64, f64)>,
+    flow_limit: Option<(f64, f64)>,
+    qtype_actions: HashMap<RecordType, DnsQtypeAction>,
+    client_buckets: Mutex<HashMap<IpAddr, TokenBucket>>,
+    flow_bucket: Mutex<Option<TokenBucket>>,
+    counter: Arc<DnsPolicyCounter>,
+}
+
+impl FlowPolicyState {
+    fn new(flow_id: u32, policy: FlowDnsPolicy, counter: Arc<DnsPolicyCounter>) -> Self {
+        let mut qtype_actions = HashMap::new();
+        for rule in policy.qtype_rules.iter() {
+            match RecordType::from_str(&rule.qtype.trim().to_ascii_uppercase()) {
+                Ok(qtype) => {
+                    qtype_actions.insert(qtype, rule.action);
+                }
+                Err(_) => {
+                    tracing::warn!("flow: {flow_id}, unknown dns query type: {}", rule.qtype)
+                }
+            }
+        }
+        FlowPolicyState {
+            client_limit: bucket_limit(policy.client_qps, policy.client_burst),
+            flow_limit: bucket_limit(policy.flow_qps, policy.flow_burst),
+            qtype_actions,
+            client_buckets: Mutex::new(HashMap::new()),
+            flow_bucket: Mutex::new(None),
+            counter,
+            policy,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.client_limit.is_none() && self.flow_limit.is_none() && self.qtype_actions.is_empty()
+    }
+
+    fn check_rate(&self, ip: IpAddr, now: Instant) -> bool {
+        if let Some((rate, burst)) = self.client_limit {
+            let mut buckets = self.client_buckets.lock().unwrap();
+            if buckets.len() >= MAX_TRACKED_CLIENTS && !buckets.contains_key(&ip) {
+                buckets.retain(|_, bucket| {
+                    now.saturating_duration_since(bucket.last) < CLIENT_IDLE_TIMEOUT
+                });
+            }
+            let bucket = buckets.entry(ip).or_insert_with(|| TokenBucket::new(burst, now));
+            if !bucket.take(rate, burst, now) {
+                self.counter.client_limited();
+                return false;
+            }
+        }
+
+        // 被单个客户端限速的请求不消耗 flow 的令牌
+        if let Some((rate, burst)) = self.flow_limit {
+            let mut bucket = self.flow_bucket.lock().unwrap();
+            let bucket = bucket.get_or_insert_with(|| TokenBucket::new(burst, now));
+            if !bucket.take(rate, burst, now) {
+                self.counter.flow_limited();
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// 每个 flow 的 DNS 请求限速以及查询类型策略
+#[derive(Clone, Debug)]
+pub struct DnsPolicyManager {
+    flows: Arc<RwLock<HashMap<u32, Arc<FlowPolicyState>>>>,
+    dns_metric: DnsMetricManager,
+}
+
+impl DnsPolicyManager {
+    pub fn new(dns_metric: DnsMetricManager) -> Self {
+        DnsPolicyManager {
+            flows: Arc::new(RwLock::new(HashMap::new())),
+            dns_metric,
+        }
+    }
+
+    /// 默认 flow 的策略来自配置文件, 其余来自 flow 配置
+    /// 策略没有变化的 flow 保留原有的令牌桶
+    pub fn update(&self, default_policy: &FlowDnsPolicy, flow_configs: &[FlowConfig]) {
+        let mut policies = vec![(0, default_policy.clone())];
+        policies.extend(
+            flow_configs
+                .iter()
+                .filter(|config| config.enable)
+                .map(|config| (config.flow_id, config.dns_policy.clone())),
+        );
+
+        let mut flows = self.flows.write().unwrap();
+        let mut new_flows = HashMap::new();
+        for (flow_id, policy) in policies {
+            let state = match flows.remove(&flow_id) {
+                Some(state) if state.policy == policy => state,
+                _ => Arc::new(FlowPolicyState::new(
+                    flow_id,
+                    policy,
+                    self.dns_metric.policy_counter(flow_id),
+                )),
+            };
+            if !state.is_empty() {
+                new_flows.insert(flow_id, state);
+            }
+        }
+        *flows = new_flows;
+    }
+
+    fn get(&self, flow_id: u32) -> Option<Arc<FlowPolicyState>> {
+        self.flows.read().unwrap().get(&flow_id).cloned()
+    }
+
+    /// 在创建处理任务之前调用, 被限速的请求不会进入处理流程
+    pub fn check_rate(&self, flow_id: u32, ip: IpAddr, message: &[u8]) -> RateLimitResult {
+        let Some(state) = self.get(flow_id) else {
+            return RateLimitResult::Allow;
+        };
+        if state.check_rate(ip, Instant::now()) {
+            return RateLimitResult::Allow;
+        }
+        let code = match state.policy.rate_limit_action {
+            DnsRateLimitAction::Drop => return RateLimitResult::Drop,
+            DnsRateLimitAction::Refused => ResponseCode::Refused,
+            DnsRateLimitAction::ServFail => ResponseCode::ServFail,
+        };
+        match limited_response(message, code) {
+            Some(response) => RateLimitResult::Respond(response),
+            None => RateLimitResult::Drop,
+        }
+    }
+
+    pub fn qtype_action(&self, flow_id: u32, query_type: RecordType) -> Option<DnsQtypeAction> {
+        let state = self.get(flow_id)?;
+        let action = state.qtype_actions.get(&query_type).copied()?;
+        match action {
+            DnsQtypeAction::Refused => state.counter.qtype_refused(),
+            DnsQtypeAction::Empty => state.counter.qtype_empty(),
+        }
+        Some(action)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        str::FromStr,
+        time::{Duration, Instant},
+    };
+
+    use hickory_proto::{
+        op::{Message, MessageType, Query, ResponseCode},
+        rr::{Name, RecordType},
+    };
+
+    use super::{bucket_limit, limited_response, TokenBucket};
+
+    #[test]
+    fn test_limited_response() {
+        let mut request = Message::new();
+        request
+            .set_id(1234)
+            .set_recursion_desired(true)
+            .add_query(Query::query(Name::from_str("example.com.").unwrap(), RecordType::A));
+        let response = limited_response(&request.to_vec().unwrap(), ResponseCode::Refused).unwrap();
+        let response = Message::from_vec(&response).unwrap();
+        assert_eq!(response.id(), 1234);
+        assert_eq!(response.message_type(), MessageType::Response);
+        assert_eq!(response.response_code(), ResponseCode::Refused);
+        assert_eq!(response.queries(), request.queries());
+        assert!(response.answers().is_empty());
+
+        // 不应答其他服务器的响应, 避免反射
+        assert!(limited_response(&response.to_vec().unwrap(), ResponseCode::Refused).is_none());
+    }
+
+    #[test]
+    fn test_token_bucket() {
+        let (rate, burst) = bucket_limit(10, 20).unwrap();
+        let now = Instant::now();
+        let mut bucket = TokenBucket::new(burst, now);
+        for _ in 0..20 {
+            assert!(bucket.take(rate, burst, now));
+        }
+        assert!(!bucket.take(rate, burst, now));
+
+        // 100ms 补充一个令牌
+        let now = now + Duration::from_millis(100);
+        assert!(bucket.take(rate, burst, now));
+        assert!(!bucket.take(rate, burst, now));
+
+        // 补充的令牌不超过桶容量
+        let now = now + Duration::from_secs(60);
+        for _ in 0..20 {
+            assert!(bucket.take(rate, burst, now));
+        }
+        assert!(!bucket.take(rate, burst, now));
+
+        assert!(bucket_limit(0, 20).is_none());
+        assert_eq!(bucket_limit(5, 0), Some((5.0, 5.0)));
+    }
+}
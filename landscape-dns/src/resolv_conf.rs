@@ -0,0 +1,121 @@
+use std::{
+    fs,
+    net::{IpAddr, Ipv4Addr},
+    path::Path,
+};
+
+use landscape_common::config::DnsRuntimeConfig;
+
+const RESOLVER_CONF: &str = "/etc/resolv.conf";
+const RESOLVER_CONF_LD_BACK: &str = "/etc/resolv.conf.ld_back";
+
+/// resolv.conf 中无法指定端口, 仅在监听 53 端口时接管
+pub fn takeover(config: &DnsRuntimeConfig) {
+    if config.listen_port != 53 {
+        tracing::warn!(
+            "DNS is listening on port {}, skip takeover {RESOLVER_CONF}",
+            config.listen_port
+        );
+        return;
+    }
+    let Some(nameserver) = local_nameserver(&config.listen_addresses) else {
+        return;
+    };
+    write_resolv_conf(Path::new(RESOLVER_CONF), Path::new(RESOLVER_CONF_LD_BACK), nameserver);
+}
+
+/// 将备份的原文件 (包括符号链接) 移动回去, 没有备份时不做任何操作
+pub fn restore() {
+    restore_resolv_conf(Path::new(RESOLVER_CONF), Path::new(RESOLVER_CONF_LD_BACK));
+}
+
+/// 未指定地址使用回环地址
+fn local_nameserver(listen_addresses: &[IpAddr]) -> Option<IpAddr> {
+    // [::] 同时接收 IPv4 请求
+    listen_addresses.first().map(|addr| {
+        if addr.is_unspecified() {
+            IpAddr::V4(Ipv4Addr::LOCALHOST)
+        } else {
+            *addr
+        }
+    })
+}
+
+/// 使用 symlink_metadata 判断, systemd-resolved 未运行时的符号链接同样需要处理
+fn path_exists(path: &Path) -> bool {
+    path.symlink_metadata().is_ok()
+}
+
+fn write_resolv_conf(file: &Path, backup: &Path, nameserver: IpAddr) {
+    // 已经存在备份时说明当前文件是之前写入的, 直接覆盖
+    let result = if path_exists(backup) {
+        if path_exists(file) {
+            fs::remove_file(file)
+        } else {
+            Ok(())
+        }
+    } else if path_exists(file) {
+        fs::rename(file, backup)
+    } else {
+        Ok(())
+    };
+    if let Err(e) = result {
+        tracing::error!("backup {file:?} error: {e:?}, skip takeover");
+        return;
+    }
+
+    if let Err(e) = fs::write(file, format!("nameserver {nameserver}\n")) {
+        tracing::error!("write {file:?} error: {e:?}");
+    } else {
+        tracing::info!("{file:?} now points to {nameserver}");
+    }
+}
+
+fn restore_resolv_conf(file: &Path, backup: &Path) {
+    if !path_exists(backup) {
+        return;
+    }
+    if path_exists(file) {
+        if let Err(e) = fs::remove_file(file) {
+            tracing::error!("remove {file:?} error: {e:?}");
+            return;
+        }
+    }
+    match fs::rename(backup, file) {
+        Ok(()) => tracing::info!("restore {file:?} from {backup:?}"),
+        Err(e) => tracing::error!("restore {file:?} error: {e:?}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, os::unix::fs::symlink};
+
+    use super::{restore_resolv_conf, write_resolv_conf};
+
+    #[test]
+    fn test_takeover_and_restore() {
+        let dir = std::env::temp_dir().join(format!("landscape_resolv_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("resolv.conf");
+        let backup = dir.join("resolv.conf.ld_back");
+        // 模拟 systemd-resolved 的 stub 符号链接
+        symlink("../run/systemd/resolve/stub-resolv.conf", &file).unwrap();
+
+        write_resolv_conf(&file, &backup, "127.0.0.1".parse().unwrap());
+        // 再次接管不会覆盖备份
+        write_resolv_conf(&file, &backup, "127.0.0.1".parse().unwrap());
+        assert_eq!(fs::read_to_string(&file).unwrap(), "nameserver 127.0.0.1\n");
+        assert!(backup.symlink_metadata().unwrap().file_type().is_symlink());
+
+        restore_resolv_conf(&file, &backup);
+        assert!(file.symlink_metadata().unwrap().file_type().is_symlink());
+        assert!(backup.symlink_metadata().is_err());
+
+        // 没有备份时不做任何操作
+        restore_resolv_conf(&file, &backup);
+        assert!(file.symlink_metadata().unwrap().file_type().is_symlink());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
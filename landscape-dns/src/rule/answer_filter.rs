@@ -0,0 +1,295 @@
+use std::net::{IpAddr, Ipv4Addr};
+
+use hickory_proto::rr::{RData, Record};
+use landscape_common::{
+    config::dns::{DNSRuntimeRule, DomainConfig, DomainMatchType},
+    ip_mark::IpConfig,
+};
+
+use super::matcher::DomainMatcher;
+
+/// 本地使用的域名后缀, 解析到私有地址是正常的
+const LOCAL_DOMAIN_SUFFIXES: [&str; 5] = ["lan", "local", "localhost", "internal", "home.arpa"];
+
+/// 上游应答过滤, 与规则是 1:1 创建的
+#[derive(Debug)]
+pub struct AnswerFilter {
+    rebind_protection: bool,
+    /// 允许解析到私有地址的域名
+    rebind_allow_matcher: Option<DomainMatcher>,
+    bogus_nets: IpRanges,
+}
+
+impl AnswerFilter {
+    /// 未开启任何过滤时返回 None
+    pub fn new(config: &DNSRuntimeRule) -> Option<Self> {
+        let filter = &config.answer_filter;
+        if !filter.rebind_protection && config.bogus_nets.is_empty() {
+            return None;
+        }
+
+        let allow_domains: Vec<DomainConfig> = filter
+            .rebind_allow_domains
+            .iter()
+            .map(|domain| domain.trim().trim_end_matches('.').to_ascii_lowercase())
+            .filter(|domain| !domain.is_empty())
+            .map(|value| DomainConfig { match_type: DomainMatchType::Domain, value })
+            .collect();
+        let rebind_allow_matcher =
+            if allow_domains.is_empty() { None } else { Some(DomainMatcher::new(allow_domains)) };
+
+        Some(AnswerFilter {
+            rebind_protection: filter.rebind_protection,
+            rebind_allow_matcher,
+            bogus_nets: IpRanges::new(&config.bogus_nets),
+        })
+    }
+
+    /// 丢弃不符合要求的 A / AAAA 记录, 其余记录原样返回
+    pub fn filter(&self, rule_name: &str, domain: &str, records: Vec<Record>) -> Vec<Record> {
+        let domain = domain.strip_suffix('.').unwrap_or(domain).to_ascii_lowercase();
+        let check_rebind = self.rebind_protection
+            && !is_local_domain(&domain)
+            && !self.rebind_allow_matcher.as_ref().is_some_and(|matcher| matcher.is_match(&domain));
+
+        records
+            .into_iter()
+            .filter(|record| {
+                let ip = match record.data() {
+                    RData::A(a) => IpAddr::V4(a.0),
+                    RData::AAAA(aaaa) => IpAddr::V6(aaaa.0),
+                    _ => return true,
+                };
+
+                if check_rebind && is_internal_ip(&ip) {
+                    tracing::warn!(
+                        "[rule: {rule_name}] drop answer {ip} for {domain}: rebinding protection"
+                    );
+                    return false;
+                }
+
+                if self.bogus_nets.contains(&ip) {
+                    tracing::warn!(
+                        "[rule: {rule_name}] drop answer {ip} for {domain}: in bogus nets"
+                    );
+                    return false;
+                }
+                true
+            })
+            .collect()
+    }
+}
+
+/// 单标签域名以及本地使用的后缀
+fn is_local_domain(domain: &str) -> bool {
+    !domain.contains('.')
+        || LOCAL_DOMAIN_SUFFIXES.iter().any(|suffix| {
+            domain == *suffix
+                || domain.strip_suffix(suffix).is_some_and(|prefix| prefix.ends_with('.'))
+        })
+}
+
+/// 私有, 回环, 链路本地以及未指定地址
+fn is_internal_ip(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_internal_ipv4(ip),
+        IpAddr::V6(ip) => {
+            if let Some(ipv4) = ip.to_ipv4_mapped() {
+                return is_internal_ipv4(&ipv4);
+            }
+            let first = ip.segments()[0];
+            ip.is_loopback()
+                || ip.is_unspecified()
+                // fc00::/7 ULA
+                || (first & 0xfe00) == 0xfc00
+                // fe80::/10 链路本地
+                || (first & 0xffc0) == 0xfe80
+        }
+    }
+}
+
+fn is_internal_ipv4(ip: &Ipv4Addr) -> bool {
+    let octets = ip.octets();
+    ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        // 100.64.0.0/10 CGNAT
+        || (octets[0] == 100 && (octets[1] & 0xc0) == 64)
+}
+
+/// 合并后按起始地址排序的地址区间, geoip 展开后的网段较多, 使用二分查找
+#[derive(Debug, Default)]
+struct IpRanges {
+    v4: Vec<(u128, u128)>,
+    v6: Vec<(u128, u128)>,
+}
+
+impl IpRanges {
+    fn new(nets: &[IpConfig]) -> Self {
+        let mut v4 = vec![];
+        let mut v6 = vec![];
+        for net in nets {
+            match net.ip {
+                IpAddr::V4(ip) => v4.push(net_range(u32::from(ip) as u128, net.prefix, 32)),
+                IpAddr::V6(ip) => v6.push(net_range(u128::from(ip), net.prefix, 128)),
+            }
+        }
+        IpRanges { v4: merge_ranges(v4), v6: merge_ranges(v6) }
+    }
+
+    fn contains(&self, ip: &IpAddr) -> bool {
+        let (ranges, value) = match ip {
+            IpAddr::V4(ip) => (&self.v4, u32::from(*ip) as u128),
+            IpAddr::V6(ip) => (&self.v6, u128::from(*ip)),
+        };
+        let index = ranges.partition_point(|(start, _)| *start <= value);
+        index > 0 && ranges[index - 1].1 >= value
+    }
+}
+
+/// 网段的起始与结束地址 (包含)
+fn net_range(ip: u128, prefix: u8, bits: u8) -> (u128, u128) {
+    let host_bits = (bits - prefix.min(bits)) as u32;
+    let host_mask = if host_bits == 128 { u128::MAX } else { (1u128 << host_bits) - 1 };
+    (ip & !host_mask, ip | host_mask)
+}
+
+fn merge_ranges(mut ranges: Vec<(u128, u128)>) -> Vec<(u128, u128)> {
+    ranges.sort_unstable();
+    let mut merged: Vec<(u128, u128)> = Vec::with_capacity(ranges.len());
+    for (start, end) in ranges {
+        match merged.last_mut() {
+            Some(last) if start <= last.1.saturating_add(1) => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::IpAddr, str::FromStr};
+
+    use hickory_proto::rr::{
+        rdata::{A, AAAA, CNAME},
+        Name, RData, Record,
+    };
+    use landscape_common::{
+        config::dns::{DNSRuntimeRule, DnsAnswerFilter},
+        ip_mark::IpConfig,
+    };
+
+    use super::{AnswerFilter, IpRanges};
+
+    fn record(domain: &str, ip: &str) -> Record {
+        let rdata = match ip.parse::<IpAddr>().unwrap() {
+            IpAddr::V4(ip) => RData::A(A(ip)),
+            IpAddr::V6(ip) => RData::AAAA(AAAA(ip)),
+        };
+        Record::from_rdata(Name::from_str(domain).unwrap(), 300, rdata)
+    }
+
+    fn ips(records: &[Record]) -> Vec<String> {
+        records
+            .iter()
+            .filter_map(|r| match r.data() {
+                RData::A(a) => Some(a.0.to_string()),
+                RData::AAAA(aaaa) => Some(aaaa.0.to_string()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_rebind_protection() {
+        let config = DNSRuntimeRule {
+            answer_filter: DnsAnswerFilter {
+                rebind_protection: true,
+                rebind_allow_domains: vec!["corp.example.com".to_string()],
+                bogus_sources: vec![],
+            },
+            ..Default::default()
+        };
+        let filter = AnswerFilter::new(&config).unwrap();
+
+        let answers = vec![
+            Record::from_rdata(
+                Name::from_str("evil.example.").unwrap(),
+                300,
+                RData::CNAME(CNAME(Name::from_str("target.example.").unwrap())),
+            ),
+            record("evil.example.", "192.168.1.1"),
+            record("evil.example.", "127.0.0.1"),
+            record("evil.example.", "fd00::1"),
+            record("evil.example.", "fe80::1"),
+            record("evil.example.", "::ffff:10.0.0.1"),
+            record("evil.example.", "93.184.216.34"),
+            record("evil.example.", "2606:2800:220:1::1"),
+        ];
+        let result = filter.filter("test", "evil.example.", answers);
+        assert_eq!(result.len(), 3);
+        assert_eq!(ips(&result), vec!["93.184.216.34", "2606:2800:220:1::1"]);
+
+        // 允许的域名以及本地域名不进行过滤
+        let result = filter.filter("test", "git.corp.example.com.", vec![record("a.", "10.0.0.1")]);
+        assert_eq!(result.len(), 1);
+        let result = filter.filter("test", "nas.lan.", vec![record("a.", "192.168.1.2")]);
+        assert_eq!(result.len(), 1);
+        let result = filter.filter("test", "nas.", vec![record("a.", "192.168.1.2")]);
+        assert_eq!(result.len(), 1);
+    }
+
+    #[test]
+    fn test_bogus_nets() {
+        let config = DNSRuntimeRule {
+            bogus_nets: vec![
+                IpConfig { ip: "198.18.0.0".parse().unwrap(), prefix: 15 },
+                IpConfig { ip: "2001:db8::".parse().unwrap(), prefix: 32 },
+            ],
+            ..Default::default()
+        };
+        let filter = AnswerFilter::new(&config).unwrap();
+        let answers = vec![
+            record("a.example.", "198.19.1.1"),
+            record("a.example.", "198.20.1.1"),
+            record("a.example.", "2001:db8::1"),
+            record("a.example.", "192.168.1.1"),
+        ];
+        let result = filter.filter("test", "a.example.", answers);
+        assert_eq!(ips(&result), vec!["198.20.1.1", "192.168.1.1"]);
+
+        assert!(AnswerFilter::new(&DNSRuntimeRule::default()).is_none());
+    }
+
+    #[test]
+    fn test_ip_ranges() {
+        let net = |ip: &str, prefix: u8| IpConfig { ip: ip.parse().unwrap(), prefix };
+        let ranges = IpRanges::new(&[
+            net("10.0.1.0", 24),
+            net("10.0.0.0", 24),
+            net("10.0.0.128", 25),
+            net("172.16.0.0", 12),
+            net("2001:db8::", 32),
+        ]);
+        // 相邻以及重叠的网段会被合并
+        assert_eq!(ranges.v4.len(), 2);
+        assert_eq!(ranges.v6.len(), 1);
+
+        let contains = |ip: &str| ranges.contains(&ip.parse().unwrap());
+        assert!(contains("10.0.0.0"));
+        assert!(contains("10.0.1.255"));
+        assert!(!contains("10.0.2.0"));
+        assert!(!contains("9.255.255.255"));
+        assert!(contains("172.31.255.255"));
+        assert!(!contains("172.32.0.0"));
+        assert!(contains("2001:db8:ffff::1"));
+        assert!(!contains("2001:db9::1"));
+        // IPv4 区间不会匹配 IPv6 地址
+        assert!(!contains("::a00:1"));
+
+        let all = IpRanges::new(&[net("0.0.0.0", 0), net("::", 0)]);
+        assert!(all.contains(&"255.255.255.255".parse().unwrap()));
+        assert!(all.contains(&"ffff::1".parse().unwrap()));
+    }
+}
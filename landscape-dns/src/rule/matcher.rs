@@ -111,6 +111,20 @@ impl DomainMatcher {
     }
 }
 
+/// 单条配置的匹配, 与 `DomainMatcher` 的结果一致, 仅用于追踪匹配来源
+pub fn is_config_match(config: &DomainConfig, domain: &str) -> bool {
+    match config.match_type {
+        DomainMatchType::Plain => domain.contains(&config.value),
+        DomainMatchType::Regex => {
+            Regex::new(&config.value).is_ok_and(|regex| regex.is_match(domain))
+        }
+        DomainMatchType::Domain => domain
+            .strip_suffix(config.value.as_str())
+            .is_some_and(|prefix| prefix.is_empty() || prefix.ends_with('.')),
+        DomainMatchType::Full => domain == config.value,
+    }
+}
+
 #[cfg(test)]
 #[global_allocator]
 static GLOBAL: jemallocator::Jemalloc = jemallocator::Jemalloc;
@@ -128,7 +142,7 @@ mod tests {
         LANDSCAPE_GEO_CACHE_TMP_DIR,
     };
 
-    use super::DomainMatcher;
+    use super::{is_config_match, DomainMatcher};
 
     #[test]
     fn domain_matcher() {
@@ -143,6 +157,18 @@ mod tests {
         assert!(matcher.is_match("abaidu.com"));
     }
 
+    #[test]
+    fn config_match() {
+        let domain = |match_type, value: &str| DomainConfig { match_type, value: value.into() };
+        assert!(is_config_match(&domain(DomainMatchType::Domain, "baidu.com"), "www.baidu.com"));
+        assert!(is_config_match(&domain(DomainMatchType::Domain, "baidu.com"), "baidu.com"));
+        assert!(!is_config_match(&domain(DomainMatchType::Domain, "baidu.com"), "abaidu.com"));
+        assert!(is_config_match(&domain(DomainMatchType::Full, "baidu.com"), "baidu.com"));
+        assert!(!is_config_match(&domain(DomainMatchType::Full, "baidu.com"), "www.baidu.com"));
+        assert!(is_config_match(&domain(DomainMatchType::Plain, "baidu"), "abaidu.com"));
+        assert!(is_config_match(&domain(DomainMatchType::Regex, r"^ad\d+\."), "ad1.example.com"));
+    }
+
     fn test_memory_usage() {
         epoch::advance().unwrap();
 
@@ -1,37 +1,117 @@
 use hickory_proto::{
-    op::ResponseCode,
+    op::{Edns, Message, MessageType, OpCode, Query, ResponseCode},
     rr::{
-        rdata::{A, AAAA},
-        RData, Record, RecordType,
+        rdata::{
+            opt::{ClientSubnet, EdnsOption},
+            A, AAAA,
+        },
+        Name, RData, Record, RecordType,
     },
+    xfer::{DnsHandle, FirstAnswer},
+    ProtoError, ProtoErrorKind,
 };
 use hickory_resolver::{
     config::{NameServerConfigGroup, ResolverConfig, ResolverOpts},
+    name_server::NameServerPool,
     Resolver,
 };
 use landscape_common::{
     config::dns::{
-        DNSResolveMode, DNSRuntimeRule, DnsUpstreamType, DomainConfig, DomainMatchType,
-        FilterResult,
+        CloudflareMode, DNSResolveMode, DNSRuntimeRule, DnsBlockResponse, DnsEcsMode,
+        DnsUpstreamServer, DnsUpstreamType, DomainConfig, DomainMatchType, FilterResult,
     },
     flow::{mark::FlowDnsMark, DnsRuntimeMarkInfo},
 };
-use matcher::DomainMatcher;
-use std::net::IpAddr;
+use matcher::{is_config_match, DomainMatcher};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 use std::str::FromStr;
+use uuid::Uuid;
 
 use crate::connection::{MarkConnectionProvider, MarkRuntimeProvider};
+use crate::diff_server::{DnsRuleTrace, DnsRuleTraceResult};
+use crate::upstream::UpstreamHealthRegistry;
 
+mod answer_filter;
 mod matcher;
+mod record_set;
+mod upstream_group;
+
+use answer_filter::AnswerFilter;
+use record_set::LocalRecordSet;
+use upstream_group::UpstreamGroupResolver;
+
+/// 查询失败或者否定应答
+#[derive(Debug, Clone)]
+pub struct LookupError {
+    pub code: ResponseCode,
+    /// 否定应答中上游返回的 SOA, 用于否定缓存 (RFC 2308)
+    pub soa: Option<Record>,
+}
+
+impl From<ResponseCode> for LookupError {
+    fn from(code: ResponseCode) -> Self {
+        LookupError { code, soa: None }
+    }
+}
+
+/// 转发至上游 DNS 的 EDNS 数据包大小 (DNS Flag Day 2020)
+const UPSTREAM_MAX_PAYLOAD: u16 = 1232;
+
+/// 客户端请求中需要传递给上游的 EDNS 信息
+#[derive(Debug, Clone, Default)]
+pub struct UpstreamQueryOptions {
+    /// DNSSEC OK
+    pub dnssec_ok: bool,
+    /// Checking Disabled
+    pub checking_disabled: bool,
+    /// 客户端请求中携带的 ECS
+    pub client_subnet: Option<ClientSubnet>,
+}
+
+impl UpstreamQueryOptions {
+    /// 结果与客户端相关的请求不使用共享的缓存结果
+    /// DO 的应答按照单独的键缓存, ECS 仅在规则将其转发给上游时影响结果
+    pub fn need_bypass_cache(&self, forward_ecs: bool) -> bool {
+        self.checking_disabled || (forward_ecs && self.client_subnet.is_some())
+    }
+}
+
+/// 将上游返回的 NoRecordsFound 转换为否定应答
+fn convert_negative_answer(error: &ProtoError) -> Option<LookupError> {
+    match error.kind() {
+        // ServFail / Refused 等同样以 NoRecordsFound 返回, 不能当作否定应答
+        ProtoErrorKind::NoRecordsFound {
+            response_code: code @ (ResponseCode::NXDomain | ResponseCode::NoError),
+            soa,
+            ..
+        } => Some(LookupError {
+            code: *code,
+            soa: soa.as_ref().map(|soa| soa.as_ref().clone().into_record_of_rdata()),
+        }),
+        _ => None,
+    }
+}
 
-#[derive(Debug)]
 pub struct CacheResolver {
     pub flow_id: u32,
     pub resolver: Resolver<MarkConnectionProvider>,
+    /// 需要携带 EDNS 标记时直接向上游发送请求
+    pool: NameServerPool<MarkConnectionProvider>,
+    ecs: DnsEcsMode,
+}
+
+impl std::fmt::Debug for CacheResolver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CacheResolver")
+            .field("flow_id", &self.flow_id)
+            .field("resolver", &self.resolver)
+            .field("ecs", &self.ecs)
+            .finish_non_exhaustive()
+    }
 }
 
 impl CacheResolver {
-    pub fn new(resolve: ResolverConfig, mark: &FlowDnsMark, flow_id: u32) -> Self {
+    pub fn new(resolve: ResolverConfig, mark: &FlowDnsMark, flow_id: u32, ecs: DnsEcsMode) -> Self {
         let mark_value = match mark.clone() {
             // 转发时候使用目标 flow 进行标记 DNS 请求
             FlowDnsMark::Redirect { flow_id } => flow_id as u32,
@@ -41,68 +121,178 @@ impl CacheResolver {
             _ => flow_id,
         };
 
-        let mut options = ResolverOpts::default();
-        options.cache_size = 0;
-        options.num_concurrent_reqs = 1;
-        options.preserve_intermediates = true;
-        // options.use_hosts_file = ResolveHosts::Never;
-        let resolver = Resolver::builder_with_config(
-            resolve,
-            MarkConnectionProvider::new(MarkRuntimeProvider::new(mark_value)),
-        )
-        .with_options(options)
-        .build();
-        CacheResolver { resolver, flow_id: mark_value }
+        CacheResolver::with_options(resolve, mark_value, ecs, upstream_options())
+    }
+
+    fn with_options(
+        resolve: ResolverConfig,
+        mark_value: u32,
+        ecs: DnsEcsMode,
+        options: ResolverOpts,
+    ) -> Self {
+        let provider = MarkConnectionProvider::new(MarkRuntimeProvider::new(mark_value));
+        let pool = NameServerPool::from_config(
+            NameServerConfigGroup::from(resolve.name_servers().to_vec()),
+            options.clone(),
+            provider.clone(),
+        );
+        let resolver =
+            Resolver::builder_with_config(resolve, provider).with_options(options).build();
+        CacheResolver { resolver, flow_id: mark_value, pool, ecs }
+    }
+
+    fn client_subnet(&self, options: &UpstreamQueryOptions) -> Option<ClientSubnet> {
+        match &self.ecs {
+            DnsEcsMode::Strip => None,
+            DnsEcsMode::Forward => options.client_subnet.clone(),
+            DnsEcsMode::Inject { ip, prefix } => Some(ClientSubnet::new(*ip, *prefix, 0)),
+        }
+    }
+
+    pub async fn lookup(
+        &self,
+        domain: &str,
+        query_type: RecordType,
+        options: &UpstreamQueryOptions,
+    ) -> Result<Vec<Record>, LookupError> {
+        let client_subnet = self.client_subnet(options);
+        if !options.dnssec_ok && !options.checking_disabled && client_subnet.is_none() {
+            return match self.resolver.lookup(domain, query_type).await {
+                Ok(lookup) => Ok(lookup.records().to_vec()),
+                Err(e) => Err(e.proto().and_then(convert_negative_answer).unwrap_or_else(|| {
+                    tracing::error!(
+                        "[flow_id: {:?}] DNS resolution failed for {}: {}",
+                        self.flow_id,
+                        domain,
+                        e
+                    );
+                    ResponseCode::ServFail.into()
+                })),
+            };
+        }
+
+        let name = Name::from_str(domain).map_err(|_| ResponseCode::FormErr)?;
+        let mut message = Message::new();
+        message
+            .add_query(Query::query(name, query_type))
+            .set_message_type(MessageType::Query)
+            .set_op_code(OpCode::Query)
+            .set_recursion_desired(true)
+            .set_checking_disabled(options.checking_disabled);
+
+        let mut edns = Edns::new();
+        edns.set_max_payload(UPSTREAM_MAX_PAYLOAD).set_version(0).set_dnssec_ok(options.dnssec_ok);
+        if let Some(client_subnet) = client_subnet {
+            edns.options_mut().insert(EdnsOption::Subnet(client_subnet));
+        }
+        message.set_edns(edns);
+
+        match self.pool.send(message).first_answer().await {
+            Ok(response) => Ok(response.answers().to_vec()),
+            Err(e) => Err(convert_negative_answer(&e).unwrap_or_else(|| {
+                tracing::error!(
+                    "[flow_id: {:?}] DNS resolution failed for {}: {}",
+                    self.flow_id,
+                    domain,
+                    e
+                );
+                ResponseCode::ServFail.into()
+            })),
+        }
+    }
+}
+
+fn upstream_options() -> ResolverOpts {
+    let mut options = ResolverOpts::default();
+    options.cache_size = 0;
+    options.num_concurrent_reqs = 1;
+    options.preserve_intermediates = true;
+    // options.use_hosts_file = ResolveHosts::Never;
+    options
+}
+
+fn upstream_name_servers(
+    upstream: &DnsUpstreamType,
+    ips: &[IpAddr],
+    port: Option<u16>,
+) -> NameServerConfigGroup {
+    let port = port.unwrap_or(upstream.default_port());
+    match upstream {
+        DnsUpstreamType::Plaintext => NameServerConfigGroup::from_ips_clear(ips, port, true),
+        DnsUpstreamType::Tls { domain } => {
+            NameServerConfigGroup::from_ips_tls(ips, port, domain.to_string(), true)
+        }
+        DnsUpstreamType::Https { domain } => {
+            NameServerConfigGroup::from_ips_https(ips, port, domain.to_string(), true)
+        }
+        DnsUpstreamType::Quic { domain } => {
+            NameServerConfigGroup::from_ips_quic(ips, port, domain.to_string(), true)
+        }
+        DnsUpstreamType::Http3 { domain } => {
+            NameServerConfigGroup::from_ips_h3(ips, port, domain.to_string(), true)
+        }
     }
 }
 
 #[derive(Debug)]
 pub enum ResolverType {
     RedirectResolver(Vec<IpAddr>),
+    BlockResolver(DnsBlockResponse),
+    RecordSetResolver(LocalRecordSet),
     CacheResolver(CacheResolver),
+    GroupResolver(UpstreamGroupResolver),
 }
 impl ResolverType {
-    pub fn new(config: &DNSRuntimeRule, flow_id: u32) -> Self {
+    pub fn new(config: &DNSRuntimeRule, flow_id: u32, health: &UpstreamHealthRegistry) -> Self {
         match &config.resolve_mode {
             DNSResolveMode::Redirect { ips } => ResolverType::RedirectResolver(ips.clone()),
-            DNSResolveMode::Upstream { upstream, ips, port } => {
-                let name_server = match upstream {
-                    DnsUpstreamType::Plaintext => {
-                        NameServerConfigGroup::from_ips_clear(ips, port.unwrap_or(53), true)
-                    }
-                    DnsUpstreamType::Tls { domain } => NameServerConfigGroup::from_ips_tls(
-                        ips,
-                        port.unwrap_or(843),
-                        domain.to_string(),
-                        true,
-                    ),
-                    DnsUpstreamType::Https { domain } => NameServerConfigGroup::from_ips_https(
-                        ips,
-                        port.unwrap_or(443),
-                        domain.to_string(),
-                        true,
-                    ),
-                };
-
+            DNSResolveMode::Block { response } => ResolverType::BlockResolver(response.clone()),
+            DNSResolveMode::RecordSet { records, ttl } => {
+                ResolverType::RecordSetResolver(LocalRecordSet::new(records, *ttl))
+            }
+            DNSResolveMode::Upstream { upstream, ips, port, ecs } => {
+                let name_server = upstream_name_servers(upstream, ips, *port);
                 let resolve = ResolverConfig::from_parts(None, vec![], name_server);
 
-                ResolverType::CacheResolver(CacheResolver::new(resolve, &config.mark, flow_id))
+                ResolverType::CacheResolver(CacheResolver::new(
+                    resolve,
+                    &config.mark,
+                    flow_id,
+                    ecs.clone(),
+                ))
             }
             DNSResolveMode::Cloudflare { mode } => {
                 let server = match mode {
-                    landscape_common::config::dns::CloudflareMode::Plaintext => {
-                        NameServerConfigGroup::cloudflare()
-                    }
-                    landscape_common::config::dns::CloudflareMode::Tls => {
-                        NameServerConfigGroup::cloudflare_tls()
-                    }
-                    landscape_common::config::dns::CloudflareMode::Https => {
-                        NameServerConfigGroup::cloudflare_https()
-                    }
+                    CloudflareMode::Plaintext => NameServerConfigGroup::cloudflare(),
+                    CloudflareMode::Tls => NameServerConfigGroup::cloudflare_tls(),
+                    CloudflareMode::Https => NameServerConfigGroup::cloudflare_https(),
                 };
                 let resolve = ResolverConfig::from_parts(None, vec![], server);
-                ResolverType::CacheResolver(CacheResolver::new(resolve, &config.mark, flow_id))
+                // Cloudflare 不支持 ECS
+                ResolverType::CacheResolver(CacheResolver::new(
+                    resolve,
+                    &config.mark,
+                    flow_id,
+                    DnsEcsMode::Strip,
+                ))
             }
+            DNSResolveMode::Group { group_id, ecs } => match &config.upstream_group {
+                Some(group) => ResolverType::GroupResolver(UpstreamGroupResolver::new(
+                    group,
+                    &config.mark,
+                    flow_id,
+                    ecs,
+                    health,
+                )),
+                None => {
+                    tracing::error!(
+                        "DNS rule {} references missing upstream group {}",
+                        config.name,
+                        group_id
+                    );
+                    ResolverType::GroupResolver(UpstreamGroupResolver::empty(*group_id))
+                }
+            },
         }
     }
 
@@ -110,78 +300,177 @@ impl ResolverType {
         &self,
         domain: &str,
         query_type: RecordType,
-    ) -> Result<Vec<Record>, ResponseCode> {
+        options: &UpstreamQueryOptions,
+    ) -> Result<Vec<Record>, LookupError> {
         match self {
             ResolverType::RedirectResolver(result_ip) => {
-                let mut result = vec![];
-                for ip in result_ip {
-                    let rdata_ip = match (ip, &query_type) {
-                        (IpAddr::V4(ip), RecordType::A) => Some(RData::A(A(*ip))),
-                        (IpAddr::V6(ip), RecordType::AAAA) => Some(RData::AAAA(AAAA(*ip))),
-                        _ => None,
-                    };
-
-                    if let Some(rdata) = rdata_ip {
-                        result.push(Record::from_rdata(
-                            hickory_resolver::Name::from_str(domain).unwrap(),
-                            300,
-                            rdata,
-                        ));
-                    }
-                }
-
-                Ok(result)
+                Ok(redirect_records(domain, query_type, result_ip))
             }
+            ResolverType::BlockResolver(response) => match response {
+                DnsBlockResponse::NxDomain => Err(ResponseCode::NXDomain.into()),
+                DnsBlockResponse::Refused => Err(ResponseCode::Refused.into()),
+                DnsBlockResponse::NoData => Ok(vec![]),
+                DnsBlockResponse::Unspecified => Ok(redirect_records(
+                    domain,
+                    query_type,
+                    &[IpAddr::V4(Ipv4Addr::UNSPECIFIED), IpAddr::V6(Ipv6Addr::UNSPECIFIED)],
+                )),
+            },
+            ResolverType::RecordSetResolver(record_set) => record_set.lookup(domain, query_type),
             ResolverType::CacheResolver(resolver) => {
-                match resolver.resolver.lookup(domain, query_type).await {
-                    Ok(lookup) => Ok(lookup.records().to_vec()),
-                    Err(e) => {
-                        let result = if e.is_no_records_found() {
-                            ResponseCode::NoError
-                        } else {
-                            tracing::error!(
-                                "[flow_id: {:?}] DNS resolution failed for {}: {}",
-                                resolver.flow_id,
-                                domain,
-                                e
-                            );
-                            ResponseCode::ServFail
-                        };
-                        Err(result)
-                    }
-                }
+                resolver.lookup(domain, query_type, options).await
+            }
+            ResolverType::GroupResolver(resolver) => {
+                resolver.lookup(domain, query_type, options).await
             }
         }
     }
 }
 
+/// 使用指定的 IP 生成 A / AAAA 记录, 其余类型返回空应答
+fn redirect_records(domain: &str, query_type: RecordType, ips: &[IpAddr]) -> Vec<Record> {
+    let mut result = vec![];
+    for ip in ips {
+        let rdata_ip = match (ip, &query_type) {
+            (IpAddr::V4(ip), RecordType::A) => Some(RData::A(A(*ip))),
+            (IpAddr::V6(ip), RecordType::AAAA) => Some(RData::AAAA(AAAA(*ip))),
+            _ => None,
+        };
+
+        if let Some(rdata) = rdata_ip {
+            result.push(Record::from_rdata(
+                hickory_resolver::Name::from_str(domain).unwrap(),
+                300,
+                rdata,
+            ));
+        }
+    }
+    result
+}
+
+/// 用于展示的上游描述, 本地应答的规则返回 None
+fn upstream_label(config: &DNSRuntimeRule) -> Option<String> {
+    match &config.resolve_mode {
+        DNSResolveMode::Redirect { .. }
+        | DNSResolveMode::Block { .. }
+        | DNSResolveMode::RecordSet { .. } => None,
+        DNSResolveMode::Upstream { upstream, ips, port, .. } => Some(
+            ips.iter()
+                .map(|ip| {
+                    DnsUpstreamServer { upstream: upstream.clone(), ip: *ip, port: *port }
+                        .to_string()
+                })
+                .collect::<Vec<_>>()
+                .join(", "),
+        ),
+        DNSResolveMode::Cloudflare { mode } => Some(
+            match mode {
+                CloudflareMode::Plaintext => "udp://1.1.1.1",
+                CloudflareMode::Tls => "tls://1.1.1.1",
+                CloudflareMode::Https => "https://cloudflare-dns.com",
+            }
+            .to_string(),
+        ),
+        DNSResolveMode::Group { group_id, .. } => Some(match &config.upstream_group {
+            Some(group) => format!("group: {}", group.name),
+            None => format!("group: {group_id}"),
+        }),
+    }
+}
+
 #[derive(Debug)]
 /// 与规则是 1:1 创建的
 pub struct ResolutionRule {
     // 启动之后配置的 matcher
     matcher: DomainMatcher,
+    /// 例外的域名, 为空时不进行匹配
+    exclude_matcher: Option<DomainMatcher>,
     //
     config: DNSRuntimeRule,
 
     mark: DnsRuntimeMarkInfo,
 
     resolver: ResolverType,
+    /// 查询日志中记录的上游
+    upstream: Option<String>,
+    /// 上游应答过滤, 本地应答的规则不进行过滤
+    answer_filter: Option<AnswerFilter>,
 }
 
 impl ResolutionRule {
-    pub fn new(config: DNSRuntimeRule, flow_id: u32) -> Self {
+    pub fn new(config: DNSRuntimeRule, flow_id: u32, health: &UpstreamHealthRegistry) -> Self {
         let span = tracing::info_span!("dns_rule", flow_id = flow_id);
         let _ = span.enter();
 
         let matcher = DomainMatcher::new(config.source.clone());
+        let exclude_matcher = if config.exclude.is_empty() {
+            None
+        } else {
+            Some(DomainMatcher::new(config.exclude.clone()))
+        };
 
-        let resolver = ResolverType::new(&config, flow_id);
+        let resolver = ResolverType::new(&config, flow_id, health);
+        let upstream = upstream_label(&config);
+        let answer_filter = if upstream.is_some() { AnswerFilter::new(&config) } else { None };
 
         let mark = DnsRuntimeMarkInfo {
             mark: config.mark.clone(),
             priority: config.index as u16,
         };
-        ResolutionRule { matcher, config, resolver, mark }
+        ResolutionRule {
+            matcher,
+            exclude_matcher,
+            config,
+            resolver,
+            mark,
+            upstream,
+            answer_filter,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.config.name
+    }
+
+    pub fn upstream(&self) -> Option<&str> {
+        self.upstream.as_deref()
+    }
+
+    /// 是否为拦截规则: 拦截模式, 或重定向至未指定地址 (0.0.0.0 / ::) 以及不返回任何地址
+    pub fn is_block(&self) -> bool {
+        match &self.config.resolve_mode {
+            DNSResolveMode::Block { .. } => true,
+            DNSResolveMode::Redirect { ips } => ips.iter().all(|ip| ip.is_unspecified()),
+            _ => false,
+        }
+    }
+
+    /// 规则引用的上游组中每个服务器在健康状态中的键
+    pub fn upstream_health_keys(&self) -> Vec<(Uuid, String)> {
+        match (&self.config.resolve_mode, &self.config.upstream_group) {
+            (DNSResolveMode::Group { .. }, Some(group)) => {
+                let group_id = group.id.unwrap_or_default();
+                group.servers.iter().map(|server| (group_id, server.to_string())).collect()
+            }
+            _ => vec![],
+        }
+    }
+
+    /// 主动探测上游组中不可用的服务器
+    pub async fn probe_upstreams(&self) {
+        if let ResolverType::GroupResolver(resolver) = &self.resolver {
+            resolver.probe_down_servers().await;
+        }
+    }
+
+    /// 是否将客户端请求中的 ECS 转发给上游, 此时应答与客户端相关
+    pub fn forwards_client_subnet(&self) -> bool {
+        match &self.config.resolve_mode {
+            DNSResolveMode::Upstream { ecs, .. } | DNSResolveMode::Group { ecs, .. } => {
+                matches!(ecs, DnsEcsMode::Forward)
+            }
+            _ => false,
+        }
     }
 
     pub fn mark(&self) -> &DnsRuntimeMarkInfo {
@@ -198,23 +487,58 @@ impl ResolutionRule {
 
     /// 确定是不是当前规则进行处理
     pub fn is_match(&self, domain: &str) -> bool {
-        let match_result = if self.config.source.is_empty() {
-            true
-        } else {
-            let domain =
-                if let Some(stripped) = domain.strip_suffix('.') { stripped } else { domain };
-            self.matcher.is_match(domain)
-        };
+        let domain = if let Some(stripped) = domain.strip_suffix('.') { stripped } else { domain };
+        if self.exclude_matcher.as_ref().is_some_and(|matcher| matcher.is_match(domain)) {
+            return false;
+        }
+        let match_result =
+            if self.config.source.is_empty() { true } else { self.matcher.is_match(domain) };
         match_result
     }
 
+    /// 与 `is_match` 的结果一致, 额外给出命中的配置来源
+    pub fn trace(&self, domain: &str) -> DnsRuleTrace {
+        let domain = if let Some(stripped) = domain.strip_suffix('.') { stripped } else { domain };
+        let mut trace = DnsRuleTrace {
+            index: self.config.index,
+            name: self.config.name.clone(),
+            result: DnsRuleTraceResult::NotMatched,
+            source: None,
+            domain_config: None,
+        };
+
+        if self.exclude_matcher.as_ref().is_some_and(|matcher| matcher.is_match(domain)) {
+            trace.result = DnsRuleTraceResult::Excluded;
+            trace.domain_config =
+                self.config.exclude.iter().find(|config| is_config_match(config, domain)).cloned();
+            return trace;
+        }
+
+        if self.config.source.is_empty() {
+            trace.result = DnsRuleTraceResult::Matched;
+        } else if self.matcher.is_match(domain) {
+            trace.result = DnsRuleTraceResult::Matched;
+            if let Some(index) =
+                self.config.source.iter().position(|config| is_config_match(config, domain))
+            {
+                trace.source = self.config.source_origin(index).cloned();
+                trace.domain_config = Some(self.config.source[index].clone());
+            }
+        }
+        trace
+    }
+
     pub async fn lookup(
         &self,
         domain: &str,
         query_type: RecordType,
-    ) -> Result<Vec<Record>, ResponseCode> {
-        // TODO: do fiter in here
-        self.resolver.lookup(domain, query_type).await
+        options: &UpstreamQueryOptions,
+    ) -> Result<Vec<Record>, LookupError> {
+        let records = self.resolver.lookup(domain, query_type, options).await?;
+        match &self.answer_filter {
+            Some(filter) => Ok(filter.filter(&self.config.name, domain, records)),
+            None => Ok(records),
+        }
     }
 }
 
@@ -238,3 +562,193 @@ impl ResolutionRule {
 //     }
 //     all_domain_rules
 // }
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+    use std::str::FromStr;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use hickory_proto::{
+        op::{Header, Query, ResponseCode},
+        rr::{
+            rdata::{opt::ClientSubnet, A, SOA},
+            Name, RData, Record, RecordType,
+        },
+        ProtoError,
+    };
+    use hickory_resolver::config::ResolverConfig;
+    use hickory_server::authority::MessageResponseBuilder;
+    use hickory_server::server::{Request, RequestHandler, ResponseHandler, ResponseInfo};
+    use hickory_server::ServerFuture;
+    use landscape_common::config::dns::{
+        DNSResolveMode, DNSRuntimeRule, DnsEcsMode, DnsUpstreamType,
+    };
+    use rustls::pki_types::PrivateKeyDer;
+    use rustls::server::{ClientHello, ResolvesServerCert};
+    use rustls::sign::CertifiedKey;
+    use rustls::{ClientConfig, RootCertStore};
+    use tokio::net::UdpSocket;
+
+    use super::{
+        convert_negative_answer, upstream_label, upstream_name_servers, upstream_options,
+        CacheResolver, UpstreamQueryOptions,
+    };
+
+    const TEST_TLS_DOMAIN: &str = "dns.lan";
+
+    /// 对每个查询返回 10.0.0.1
+    struct FixedAnswerHandler;
+
+    #[async_trait::async_trait]
+    impl RequestHandler for FixedAnswerHandler {
+        async fn handle_request<R: ResponseHandler>(
+            &self,
+            request: &Request,
+            mut response_handle: R,
+        ) -> ResponseInfo {
+            let name: Name = request.queries()[0].name().clone().into();
+            let records = [Record::from_rdata(name, 60, RData::A(A(Ipv4Addr::new(10, 0, 0, 1))))];
+            let header = Header::response_from_request(request.header());
+            let response = MessageResponseBuilder::from_message_request(request).build(
+                header,
+                records.iter(),
+                vec![].into_iter(),
+                vec![].into_iter(),
+                vec![].into_iter(),
+            );
+            response_handle.send_response(response).await.unwrap()
+        }
+    }
+
+    #[derive(Debug)]
+    struct TestCertResolver(Arc<CertifiedKey>);
+
+    impl ResolvesServerCert for TestCertResolver {
+        fn resolve(&self, _: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+            Some(self.0.clone())
+        }
+    }
+
+    /// 使用自签名证书在本地启动 DoQ / DoH3 服务, 并返回信任该证书的上游配置
+    async fn lookup_with(upstream: DnsUpstreamType) {
+        let rcgen::CertifiedKey { cert, key_pair } =
+            rcgen::generate_simple_self_signed(vec![TEST_TLS_DOMAIN.to_string()]).unwrap();
+        let key = PrivateKeyDer::Pkcs8(key_pair.serialize_der().into());
+        let signing_key = rustls::crypto::ring::sign::any_supported_type(&key).unwrap();
+        let cert_resolver = Arc::new(TestCertResolver(Arc::new(CertifiedKey::new(
+            vec![cert.der().clone()],
+            signing_key,
+        ))));
+
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = socket.local_addr().unwrap();
+        let mut server = ServerFuture::new(FixedAnswerHandler);
+        let timeout = Duration::from_secs(5);
+        let hostname = Some(TEST_TLS_DOMAIN.to_string());
+        match upstream {
+            DnsUpstreamType::Quic { .. } => {
+                server.register_quic_listener(socket, timeout, cert_resolver, hostname)
+            }
+            DnsUpstreamType::Http3 { .. } => {
+                server.register_h3_listener(socket, timeout, cert_resolver, hostname)
+            }
+            _ => unreachable!(),
+        }
+        .unwrap();
+
+        let mut roots = RootCertStore::empty();
+        roots.add(cert.der().clone()).unwrap();
+        let mut options = upstream_options();
+        options.tls_config =
+            ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+                .with_safe_default_protocol_versions()
+                .unwrap()
+                .with_root_certificates(roots)
+                .with_no_client_auth();
+
+        let name_server = upstream_name_servers(&upstream, &[addr.ip()], Some(addr.port()));
+        let resolve = ResolverConfig::from_parts(None, vec![], name_server);
+        // mark 为 0 时不会设置 SO_MARK, 无需 CAP_NET_ADMIN
+        let resolver = CacheResolver::with_options(resolve, 0, DnsEcsMode::Strip, options);
+        let result = resolver
+            .lookup("example.com.", RecordType::A, &UpstreamQueryOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].data().as_a(), Some(&A(Ipv4Addr::new(10, 0, 0, 1))));
+
+        server.shutdown_gracefully().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_doq_upstream() {
+        lookup_with(DnsUpstreamType::Quic { domain: TEST_TLS_DOMAIN.to_string() }).await;
+    }
+
+    #[tokio::test]
+    async fn test_doh3_upstream() {
+        lookup_with(DnsUpstreamType::Http3 { domain: TEST_TLS_DOMAIN.to_string() }).await;
+    }
+
+    fn no_records_error(response_code: ResponseCode) -> ProtoError {
+        let name = Name::from_str("example.com.").unwrap();
+        let soa = SOA::new(name.clone(), name.clone(), 1, 3600, 600, 86400, 60);
+        ProtoError::nx_error(
+            Box::new(Query::query(name.clone(), RecordType::A)),
+            Some(Box::new(Record::from_rdata(name, 60, soa))),
+            None,
+            Some(60),
+            response_code,
+            true,
+            None,
+        )
+    }
+
+    #[test]
+    fn test_convert_negative_answer() {
+        for code in [ResponseCode::NXDomain, ResponseCode::NoError] {
+            let error = convert_negative_answer(&no_records_error(code)).unwrap();
+            assert_eq!(error.code, code);
+            assert!(error.soa.is_some());
+        }
+        // ServFail / Refused 不能作为可缓存的否定应答
+        for code in [ResponseCode::ServFail, ResponseCode::Refused] {
+            assert!(convert_negative_answer(&no_records_error(code)).is_none());
+        }
+    }
+
+    #[test]
+    fn test_need_bypass_cache() {
+        let options = UpstreamQueryOptions {
+            dnssec_ok: true,
+            client_subnet: Some(ClientSubnet::new("192.168.1.0".parse().unwrap(), 24, 0)),
+            ..Default::default()
+        };
+        // DO 的应答单独缓存, 未转发给上游的 ECS 不影响结果
+        assert!(!options.need_bypass_cache(false));
+        assert!(options.need_bypass_cache(true));
+
+        let options = UpstreamQueryOptions { checking_disabled: true, ..Default::default() };
+        assert!(options.need_bypass_cache(false));
+    }
+
+    #[test]
+    fn test_upstream_label() {
+        let mut rule = DNSRuntimeRule::default();
+        rule.resolve_mode = DNSResolveMode::Upstream {
+            upstream: DnsUpstreamType::Tls { domain: "one.one.one.one".into() },
+            ips: vec!["1.1.1.1".parse().unwrap(), "1.0.0.1".parse().unwrap()],
+            port: None,
+            ecs: DnsEcsMode::Strip,
+        };
+        assert_eq!(
+            upstream_label(&rule).as_deref(),
+            Some("tls://one.one.one.one@1.1.1.1:853, tls://one.one.one.one@1.0.0.1:853")
+        );
+
+        rule.resolve_mode = DNSResolveMode::Redirect { ips: vec![] };
+        assert_eq!(upstream_label(&rule), None);
+    }
+}
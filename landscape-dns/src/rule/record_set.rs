@@ -0,0 +1,274 @@
+use std::str::FromStr;
+
+use hickory_proto::{
+    op::ResponseCode,
+    rr::{
+        rdata::{A, AAAA, CNAME, MX, PTR, SOA, SRV, TXT},
+        Name, RData, Record, RecordType,
+    },
+};
+use landscape_common::config::dns::{DnsRecordConfig, DnsRecordData};
+
+use super::LookupError;
+
+/// CNAME 链最大跟随深度, 防止配置出现环
+const MAX_CNAME_DEPTH: usize = 8;
+
+#[derive(Debug)]
+struct LocalRecord {
+    /// 规范化后的所属域名, 小写且不带末尾的 `.`
+    /// 为 None 时表示适用于匹配到规则的任意域名
+    name: Option<String>,
+    data: DnsRecordData,
+}
+
+/// 本地权威记录集合
+#[derive(Debug)]
+pub struct LocalRecordSet {
+    records: Vec<LocalRecord>,
+    ttl: u32,
+}
+
+fn normalize_name(name: &str) -> String {
+    name.trim().trim_end_matches('.').to_lowercase()
+}
+
+fn parse_name(name: &str) -> Option<Name> {
+    let name = normalize_name(name);
+    match Name::from_str(&format!("{name}.")) {
+        Ok(name) => Some(name),
+        Err(e) => {
+            tracing::error!("invalid name in dns record set: {name}, {e:?}");
+            None
+        }
+    }
+}
+
+fn record_type_of(data: &DnsRecordData) -> RecordType {
+    match data {
+        DnsRecordData::A { .. } => RecordType::A,
+        DnsRecordData::Aaaa { .. } => RecordType::AAAA,
+        DnsRecordData::Cname { .. } => RecordType::CNAME,
+        DnsRecordData::Txt { .. } => RecordType::TXT,
+        DnsRecordData::Mx { .. } => RecordType::MX,
+        DnsRecordData::Srv { .. } => RecordType::SRV,
+        DnsRecordData::Ptr { .. } => RecordType::PTR,
+    }
+}
+
+fn convert_rdata(data: &DnsRecordData) -> Option<RData> {
+    let rdata = match data {
+        DnsRecordData::A { ip } => RData::A(A(*ip)),
+        DnsRecordData::Aaaa { ip } => RData::AAAA(AAAA(*ip)),
+        DnsRecordData::Cname { target } => RData::CNAME(CNAME(parse_name(target)?)),
+        DnsRecordData::Txt { values } => RData::TXT(TXT::new(values.clone())),
+        DnsRecordData::Mx { preference, exchange } => {
+            RData::MX(MX::new(*preference, parse_name(exchange)?))
+        }
+        DnsRecordData::Srv { priority, weight, port, target } => {
+            RData::SRV(SRV::new(*priority, *weight, *port, parse_name(target)?))
+        }
+        DnsRecordData::Ptr { target } => RData::PTR(PTR(parse_name(target)?)),
+    };
+    Some(rdata)
+}
+
+impl LocalRecordSet {
+    pub fn new(records: &[DnsRecordConfig], ttl: u32) -> Self {
+        let records = records
+            .iter()
+            .map(|record| {
+                let name = normalize_name(&record.name);
+                let name = if name.is_empty() || name == "@" { None } else { Some(name) };
+                LocalRecord { name, data: record.data.clone() }
+            })
+            .collect();
+        LocalRecordSet { records, ttl }
+    }
+
+    fn records_of<'a>(&'a self, domain: &'a str) -> impl Iterator<Item = &'a LocalRecord> + 'a {
+        self.records.iter().filter(move |record| match &record.name {
+            Some(name) => name == domain,
+            None => true,
+        })
+    }
+
+    /// 是否存在以该域名为后缀的记录 (空非终端节点)
+    fn has_descendant(&self, domain: &str) -> bool {
+        let suffix = format!(".{domain}");
+        self.records
+            .iter()
+            .any(|record| record.name.as_ref().is_some_and(|name| name.ends_with(&suffix)))
+    }
+
+    fn push_records(
+        &self,
+        owner: &str,
+        query_type: RecordType,
+        result: &mut Vec<Record>,
+    ) -> Option<()> {
+        let owner_name = parse_name(owner)?;
+        for record in self.records_of(owner) {
+            let record_type = record_type_of(&record.data);
+            if query_type == RecordType::ANY || record_type == query_type {
+                if let Some(rdata) = convert_rdata(&record.data) {
+                    result.push(Record::from_rdata(owner_name.clone(), self.ttl, rdata));
+                }
+            }
+        }
+        Some(())
+    }
+
+    /// 否定应答需要在 Authority 中携带 SOA (RFC 2308 Section 3)
+    /// 本地记录没有所属的区域, 以查询的域名作为 SOA 的所有者
+    fn negative_answer(&self, domain: &str, code: ResponseCode) -> LookupError {
+        let soa = SOA::new(
+            Name::from_ascii("localhost.").unwrap(),
+            Name::from_ascii("hostmaster.localhost.").unwrap(),
+            1,
+            3600,
+            600,
+            86400,
+            self.ttl,
+        );
+        let soa =
+            parse_name(domain).map(|owner| Record::from_rdata(owner, self.ttl, RData::SOA(soa)));
+        LookupError { code, soa }
+    }
+
+    /// 查询记录
+    /// 域名不存在任何记录时返回 NXDOMAIN, 存在记录但类型不符时返回 NODATA (NoError)
+    pub fn lookup(&self, domain: &str, query_type: RecordType) -> Result<Vec<Record>, LookupError> {
+        let mut owner = normalize_name(domain);
+        if self.records_of(&owner).next().is_none() {
+            let code = if self.has_descendant(&owner) {
+                ResponseCode::NoError
+            } else {
+                ResponseCode::NXDomain
+            };
+            return Err(self.negative_answer(domain, code));
+        }
+
+        let mut result = vec![];
+        let mut visited = vec![];
+        for _ in 0..MAX_CNAME_DEPTH {
+            let before = result.len();
+            self.push_records(&owner, query_type, &mut result);
+            if result.len() > before || query_type == RecordType::CNAME {
+                break;
+            }
+
+            // 没有对应类型的记录时, 尝试跟随 CNAME
+            let Some(target) = self.records_of(&owner).find_map(|record| match &record.data {
+                DnsRecordData::Cname { target } => Some(normalize_name(target)),
+                _ => None,
+            }) else {
+                break;
+            };
+            self.push_records(&owner, RecordType::CNAME, &mut result);
+            // 目标不在本地记录中时, 交由客户端继续解析
+            visited.push(owner);
+            if visited.contains(&target) || self.records_of(&target).next().is_none() {
+                break;
+            }
+            owner = target;
+        }
+
+        if result.is_empty() {
+            return Err(self.negative_answer(domain, ResponseCode::NoError));
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use hickory_proto::{
+        op::ResponseCode,
+        rr::{Record, RecordType},
+    };
+    use landscape_common::config::dns::{DnsRecordConfig, DnsRecordData};
+
+    use super::LocalRecordSet;
+
+    fn record(name: &str, data: DnsRecordData) -> DnsRecordConfig {
+        DnsRecordConfig { name: name.to_string(), data }
+    }
+
+    fn test_set() -> LocalRecordSet {
+        LocalRecordSet::new(
+            &[
+                record("www.lan", DnsRecordData::Cname { target: "web.lan".into() }),
+                record("web.lan", DnsRecordData::A { ip: Ipv4Addr::new(192, 168, 1, 10) }),
+                record("web.lan", DnsRecordData::A { ip: Ipv4Addr::new(192, 168, 1, 11) }),
+                record("web.lan", DnsRecordData::Txt { values: vec!["v=test".into()] }),
+                record(
+                    "_http._tcp.srv.lan",
+                    DnsRecordData::Srv {
+                        priority: 0,
+                        weight: 5,
+                        port: 80,
+                        target: "web.lan".into(),
+                    },
+                ),
+            ],
+            60,
+        )
+    }
+
+    #[test]
+    fn test_multi_record() {
+        let set = test_set();
+        let result = set.lookup("web.lan.", RecordType::A).unwrap();
+        assert_eq!(result.len(), 2);
+        assert!(result.iter().all(|r| r.ttl() == 60));
+
+        let result = set.lookup("WEB.lan.", RecordType::TXT).unwrap();
+        assert_eq!(result.len(), 1);
+    }
+
+    #[test]
+    fn test_cname_chain() {
+        let set = test_set();
+        let result = set.lookup("www.lan.", RecordType::A).unwrap();
+        let types: Vec<RecordType> = result.iter().map(|r| r.record_type()).collect();
+        assert_eq!(types, vec![RecordType::CNAME, RecordType::A, RecordType::A]);
+
+        let result = set.lookup("www.lan.", RecordType::CNAME).unwrap();
+        assert_eq!(result.len(), 1);
+    }
+
+    fn soa_minimum(soa: &Option<Record>) -> Option<u32> {
+        soa.as_ref().and_then(|soa| soa.data().as_soa()).map(|soa| soa.minimum())
+    }
+
+    #[test]
+    fn test_nodata_and_nxdomain() {
+        let set = test_set();
+        let error = set.lookup("web.lan.", RecordType::AAAA).unwrap_err();
+        assert_eq!(error.code, ResponseCode::NoError);
+        assert_eq!(soa_minimum(&error.soa), Some(60));
+
+        // 空非终端节点
+        let error = set.lookup("srv.lan.", RecordType::A).unwrap_err();
+        assert_eq!(error.code, ResponseCode::NoError);
+        assert!(error.soa.is_some());
+
+        let error = set.lookup("none.lan.", RecordType::A).unwrap_err();
+        assert_eq!(error.code, ResponseCode::NXDomain);
+        assert_eq!(error.soa.unwrap().name().to_string(), "none.lan.");
+    }
+
+    #[test]
+    fn test_wildcard_owner() {
+        let set = LocalRecordSet::new(
+            &[record("@", DnsRecordData::A { ip: Ipv4Addr::new(10, 0, 0, 1) })],
+            300,
+        );
+        let result = set.lookup("any.example.com.", RecordType::A).unwrap();
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].name().to_string(), "any.example.com.");
+    }
+}
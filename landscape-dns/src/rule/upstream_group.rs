@@ -0,0 +1,187 @@
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
+
+use futures_util::{stream::FuturesUnordered, StreamExt};
+use hickory_proto::{
+    op::ResponseCode,
+    rr::{Record, RecordType},
+};
+use hickory_resolver::config::ResolverConfig;
+use landscape_common::{
+    config::dns::{DnsEcsMode, DnsUpstreamGroupConfig, DnsUpstreamStrategy},
+    flow::mark::FlowDnsMark,
+};
+use uuid::Uuid;
+
+use super::{upstream_name_servers, CacheResolver, LookupError, UpstreamQueryOptions};
+use crate::upstream::{UpstreamHealth, UpstreamHealthRegistry};
+
+/// 主动探测使用的查询, 根域的 NS 记录任何递归服务器都能应答
+const PROBE_DOMAIN: &str = ".";
+
+#[derive(Debug)]
+struct GroupServer {
+    label: String,
+    resolver: CacheResolver,
+    health: Arc<UpstreamHealth>,
+}
+
+impl GroupServer {
+    async fn lookup(
+        &self,
+        domain: &str,
+        query_type: RecordType,
+        options: &UpstreamQueryOptions,
+    ) -> Result<Vec<Record>, LookupError> {
+        let time = Instant::now();
+        let result = self.resolver.lookup(domain, query_type, options).await;
+        match &result {
+            // NXDOMAIN / NODATA 是上游给出的确定应答, 不算作失败
+            Err(error) if error.code == ResponseCode::ServFail => {
+                tracing::debug!("upstream {} failed for {}", self.label, domain);
+                self.health.record_failure();
+            }
+            _ => self.health.record_success(time.elapsed()),
+        }
+        result
+    }
+}
+
+/// 上游服务器组, 组内每个服务器使用独立的 resolver 以便分别统计健康状态
+#[derive(Debug)]
+pub struct UpstreamGroupResolver {
+    group_id: Uuid,
+    strategy: DnsUpstreamStrategy,
+    servers: Vec<GroupServer>,
+    /// RoundRobin 下一次开始的位置
+    next: AtomicUsize,
+}
+
+impl UpstreamGroupResolver {
+    pub fn new(
+        group: &DnsUpstreamGroupConfig,
+        mark: &FlowDnsMark,
+        flow_id: u32,
+        ecs: &DnsEcsMode,
+        health: &UpstreamHealthRegistry,
+    ) -> Self {
+        let group_id = group.id.unwrap_or_default();
+        let servers = group
+            .servers
+            .iter()
+            .map(|server| {
+                let name_server =
+                    upstream_name_servers(&server.upstream, &[server.ip], server.port);
+                let resolve = ResolverConfig::from_parts(None, vec![], name_server);
+                GroupServer {
+                    label: server.to_string(),
+                    resolver: CacheResolver::new(resolve, mark, flow_id, ecs.clone()),
+                    health: health.get_or_create(group_id, server),
+                }
+            })
+            .collect();
+        UpstreamGroupResolver {
+            group_id,
+            strategy: group.strategy.clone(),
+            servers,
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    /// 规则引用的上游组不存在时使用, 所有请求返回 ServFail
+    pub fn empty(group_id: Uuid) -> Self {
+        UpstreamGroupResolver {
+            group_id,
+            strategy: DnsUpstreamStrategy::default(),
+            servers: vec![],
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    /// 主动探测不可用的服务器, 成功后即恢复, 避免只能依赖客户端的查询恢复
+    pub async fn probe_down_servers(&self) {
+        for server in self.servers.iter().filter(|server| server.health.is_down()) {
+            let options = UpstreamQueryOptions::default();
+            let result = server.lookup(PROBE_DOMAIN, RecordType::NS, &options).await;
+            tracing::debug!("probe upstream {}: {:?}", server.label, result.err().map(|e| e.code));
+        }
+    }
+
+    /// 从 start 开始排列服务器, 健康的服务器排在前面
+    fn ordered_servers(&self, start: usize) -> Vec<&GroupServer> {
+        let len = self.servers.len();
+        let (mut healthy, unhealthy): (Vec<_>, Vec<_>) = (0..len)
+            .map(|index| &self.servers[(start + index) % len])
+            .partition(|server| server.health.is_healthy());
+        healthy.extend(unhealthy);
+        healthy
+    }
+
+    pub async fn lookup(
+        &self,
+        domain: &str,
+        query_type: RecordType,
+        options: &UpstreamQueryOptions,
+    ) -> Result<Vec<Record>, LookupError> {
+        if self.servers.is_empty() {
+            tracing::error!("DNS upstream group {} has no available server", self.group_id);
+            return Err(ResponseCode::ServFail.into());
+        }
+
+        match self.strategy {
+            DnsUpstreamStrategy::Failover => {
+                self.lookup_in_order(self.ordered_servers(0), domain, query_type, options).await
+            }
+            DnsUpstreamStrategy::RoundRobin => {
+                let start = self.next.fetch_add(1, Ordering::Relaxed) % self.servers.len();
+                self.lookup_in_order(self.ordered_servers(start), domain, query_type, options).await
+            }
+            DnsUpstreamStrategy::Race => self.race(domain, query_type, options).await,
+        }
+    }
+
+    async fn lookup_in_order(
+        &self,
+        servers: Vec<&GroupServer>,
+        domain: &str,
+        query_type: RecordType,
+        options: &UpstreamQueryOptions,
+    ) -> Result<Vec<Record>, LookupError> {
+        for server in servers {
+            match server.lookup(domain, query_type, options).await {
+                Err(error) if error.code == ResponseCode::ServFail => continue,
+                result => return result,
+            }
+        }
+        Err(ResponseCode::ServFail.into())
+    }
+
+    /// 同时查询所有健康的服务器, 返回第一个确定的应答
+    async fn race(
+        &self,
+        domain: &str,
+        query_type: RecordType,
+        options: &UpstreamQueryOptions,
+    ) -> Result<Vec<Record>, LookupError> {
+        let mut servers: Vec<&GroupServer> =
+            self.servers.iter().filter(|server| server.health.is_healthy()).collect();
+        if servers.is_empty() {
+            servers = self.servers.iter().collect();
+        }
+
+        let mut tasks: FuturesUnordered<_> =
+            servers.into_iter().map(|server| server.lookup(domain, query_type, options)).collect();
+        while let Some(result) = tasks.next().await {
+            match result {
+                Err(error) if error.code == ResponseCode::ServFail => continue,
+                result => return result,
+            }
+        }
+        Err(ResponseCode::ServFail.into())
+    }
+}
@@ -1,17 +1,18 @@
-use core::panic;
 use std::{
     collections::{BTreeMap, HashSet},
-    fs,
     num::NonZeroUsize,
-    path::PathBuf,
-    sync::Arc,
-    time::Instant,
+    path::Path,
+    sync::{atomic::Ordering, Arc},
+    time::{Duration, Instant},
     vec,
 };
 
 use hickory_proto::{
-    op::{Header, ResponseCode},
-    rr::{Record, RecordType},
+    op::{Edns, Header, ResponseCode},
+    rr::{
+        rdata::opt::{EdnsCode, EdnsOption},
+        Record, RecordType,
+    },
 };
 use hickory_server::{
     authority::MessageResponseBuilder,
@@ -19,42 +20,59 @@ use hickory_server::{
 };
 use lru::LruCache;
 use tokio::sync::Mutex;
-
-use crate::{diff_server::CheckDnsResult, rule::ResolutionRule, CacheDNSItem, DNSCache};
+use uuid::Uuid;
+
+use crate::{
+    cache_snapshot::{load_snapshot, CacheSnapshotEntry, FlowCacheSnapshot},
+    diff_server::{
+        CheckDnsResult, DnsCacheState, DnsCacheStats, DnsCacheTrace, DnsRuleTraceResult,
+        DomainTrace,
+    },
+    dns64::{has_aaaa, Dns64Prefix},
+    local_zone::LocalZone,
+    policy::DnsPolicyManager,
+    rule::{LookupError, ResolutionRule, UpstreamQueryOptions},
+    upstream::UpstreamHealthRegistry,
+    CacheDNSItem, DNSCache, DNSCacheCounter, NegativeCacheItem, NegativeDNSCache,
+};
 use landscape_common::{
-    config::dns::{DNSRuntimeRule, FilterResult},
+    config::{
+        dns::{DNSRuntimeRule, DnsQtypeAction, FilterResult},
+        DnsRuntimeConfig,
+    },
     flow::{DnsRuntimeMarkInfo, FlowDnsMarkInfo},
+    metric::dns::{DnsMetricManager, DnsQueryLog},
+    utils::time::get_f64_timestamp,
 };
 
-static RESOLVER_CONF: &'static str = "/etc/resolv.conf";
-static RESOLVER_CONF_LD_BACK: &'static str = "/etc/resolv.conf.ld_back";
-
-fn check_resolver_conf() {
-    let resolver_file = PathBuf::from(RESOLVER_CONF);
-    let resolver_file_back = PathBuf::from(RESOLVER_CONF_LD_BACK);
-    let new_content = "nameserver 127.0.0.1\n";
-
-    if resolver_file.is_symlink() {
-        // 如果是符号链接，直接删除
-        fs::remove_file(&resolver_file).unwrap();
-    } else if resolver_file.exists() {
-        if resolver_file.is_file() {
-            // 如果是普通文件，检查备份文件
-            if resolver_file_back.exists() {
-                fs::remove_file(&resolver_file).unwrap();
-            } else {
-                let Ok(()) = fs::rename(&resolver_file, &resolver_file_back) else {
-                    tracing::error!("move {resolver_file:?} error, Skip it");
-                    return;
-                };
-            }
-        } else {
-            panic!("other kind file");
-        }
-    }
+/// 返回过期缓存时使用的 TTL (RFC 8767 建议 30 秒)
+const STALE_ANSWER_TTL: u32 = 30;
+/// 命中次数达到该值的缓存才会被预取
+const PREFETCH_MIN_HITS: u32 = 3;
+/// 后台刷新超时时间, 超时后允许再次发起刷新
+const REFRESH_TIMEOUT: Duration = Duration::from_secs(5);
+/// 否定应答最长缓存时间 (RFC 2308 建议 1 ~ 3 小时)
+const NEGATIVE_CACHE_MAX_TTL: u32 = 60 * 60 * 3;
+/// RFC1035 中 UDP 消息的最大长度
+const MIN_UDP_PAYLOAD: u16 = 512;
+/// 响应中声明的最大 UDP 数据包大小 (DNS Flag Day 2020)
+const MAX_UDP_PAYLOAD: u16 = 1232;
+
+/// 缓存查询结果
+pub struct CacheLookupResult {
+    /// TTL 已经按照剩余时间递减
+    pub records: Vec<Record>,
+    pub filter: FilterResult,
+    /// 是否需要在后台刷新 (预取或过期)
+    pub need_refresh: bool,
+}
 
-    // 写入新内容到 /etc/resolv.conf
-    fs::write(&resolver_file, new_content).unwrap();
+/// 单个问题的处理过程, 用于记录查询日志
+#[derive(Default)]
+struct AnswerTrace {
+    /// 匹配到的规则, 本地区域应答时为空
+    rule: Option<Arc<ResolutionRule>>,
+    cache_hit: bool,
 }
 
 /// 整个 DNS 规则匹配树
@@ -64,74 +82,89 @@ pub struct LandscapeDnsRequestHandle {
     /// 遍历的顺序是小到大
     resolves: BTreeMap<u32, Arc<ResolutionRule>>,
     pub cache: Arc<Mutex<DNSCache>>,
+    /// NXDOMAIN / NODATA 缓存
+    negative_cache: Arc<Mutex<NegativeDNSCache>>,
+    counter: Arc<DNSCacheCounter>,
     pub flow_id: u32,
+    /// 所有 flow 共享的本地区域
+    local_zone: LocalZone,
+    config: DnsRuntimeConfig,
+    /// 上游组的健康状态
+    upstream_health: UpstreamHealthRegistry,
+    /// 查询日志
+    dns_metric: DnsMetricManager,
+    /// 按查询类型拒绝或者返回空应答
+    policy: DnsPolicyManager,
+    /// DNS64 合成 AAAA 记录使用的前缀
+    dns64_prefix: Dns64Prefix,
 }
 
 impl LandscapeDnsRequestHandle {
-    pub fn new(dns_rules: Vec<DNSRuntimeRule>, flow_id: u32) -> LandscapeDnsRequestHandle {
-        check_resolver_conf();
+    pub fn new(
+        dns_rules: Vec<DNSRuntimeRule>,
+        flow_id: u32,
+        local_zone: LocalZone,
+        config: DnsRuntimeConfig,
+        upstream_health: UpstreamHealthRegistry,
+        dns_metric: DnsMetricManager,
+        policy: DnsPolicyManager,
+    ) -> LandscapeDnsRequestHandle {
         let mut resolves = BTreeMap::new();
         for rule in dns_rules.into_iter() {
             // println!("dns_rules: {:?}", rule);
-            resolves.insert(rule.index, Arc::new(ResolutionRule::new(rule, flow_id)));
+            resolves
+                .insert(rule.index, Arc::new(ResolutionRule::new(rule, flow_id, &upstream_health)));
+        }
+        let capacity = cache_capacity(&config);
+        let mut cache = DNSCache::new(capacity);
+        if let Some(dir) = &config.cache_snapshot_dir {
+            restore_cache(&mut cache, &resolves, flow_id, dir);
         }
-        let cache = Arc::new(Mutex::new(LruCache::new(NonZeroUsize::new(2048).unwrap())));
+        let cache = Arc::new(Mutex::new(cache));
+        let negative_cache = Arc::new(Mutex::new(LruCache::new(capacity)));
+        let dns64_prefix = Dns64Prefix::new(config.dns64_prefix, config.dns64_prefix_len);
 
         // landscape_ebpf::map_setting::flow::create_flow_dns_inner_map(flow_id, vec![]);
-        LandscapeDnsRequestHandle { resolves, cache, flow_id }
+        LandscapeDnsRequestHandle {
+            resolves,
+            cache,
+            negative_cache,
+            counter: Arc::new(DNSCacheCounter::default()),
+            flow_id,
+            local_zone,
+            config,
+            upstream_health,
+            dns_metric,
+            policy,
+            dns64_prefix,
+        }
     }
 
     pub fn renew_rules(&mut self, dns_rules: Vec<DNSRuntimeRule>) {
-        check_resolver_conf();
-
         let mut resolves = BTreeMap::new();
         for rule in dns_rules.into_iter() {
-            // println!("dns_rules: {:?}", rule);
-            resolves.insert(rule.index, Arc::new(ResolutionRule::new(rule, self.flow_id)));
+            resolves.insert(
+                rule.index,
+                Arc::new(ResolutionRule::new(rule, self.flow_id, &self.upstream_health)),
+            );
         }
 
-        let mut cache = LruCache::new(NonZeroUsize::new(2048).unwrap());
+        let mut cache = DNSCache::new(cache_capacity(&self.config));
 
         if let Ok(old_cache) = self.cache.try_lock() {
+            // 新的 ebpf map 只包含这些标记, 旧标记随旧 map 一起移除, 不需要单独删除
             let mut update_dns_mark_list: HashSet<FlowDnsMarkInfo> = HashSet::new();
-            let mut del_dns_mark_list: HashSet<FlowDnsMarkInfo> = HashSet::new();
 
-            for ((domain, req_type), value) in old_cache.iter() {
+            for ((domain, req_type, dnssec_ok), value) in old_cache.iter() {
                 'resolver: for (_index, resolver) in resolves.iter() {
                     if resolver.is_match(&domain) {
                         let new_mark = resolver.mark().clone();
-                        // println!("old domain match resolver: {domain:?}");
                         let mut cache_items = vec![];
-                        // println!(
-                        //     "resolves: {:?}: match: {domain:?}, new_mark: {new_mark:?}",
-                        //     resolver.config
-                        // );
                         for cache_item in value.iter() {
                             // 新配置是 NoMark 的排除
-                            match (
-                                cache_item.mark.mark.need_insert_in_ebpf_map(),
-                                new_mark.mark.need_insert_in_ebpf_map(),
-                            ) {
-                                (true, true) => {
-                                    // 规则更新前后都需要写入 ebpf map
-                                    // 因为现在是创建新的 map 所以即使一样也需要更新
-                                    update_dns_mark_list
-                                        .extend(cache_item.get_update_rules_with_mark(&new_mark));
-                                }
-                                (true, false) => {
-                                    // 原先已经写入了
-                                    // 现在不需要了
-                                    del_dns_mark_list.extend(cache_item.get_update_rules());
-                                }
-                                (false, true) => {
-                                    // 原先没写入
-                                    // 目前需要缓存了
-                                    update_dns_mark_list
-                                        .extend(cache_item.get_update_rules_with_mark(&new_mark));
-                                }
-                                (false, false) => {
-                                    // 原先没缓存，现在也不需要存储的
-                                }
+                            if new_mark.mark.need_insert_in_ebpf_map() {
+                                update_dns_mark_list
+                                    .extend(cache_item.get_update_rules_with_mark(&new_mark));
                             }
 
                             let mut new_record = cache_item.clone();
@@ -140,7 +173,7 @@ impl LandscapeDnsRequestHandle {
                             cache_items.push(new_record);
                         }
                         if !cache_items.is_empty() {
-                            cache.push((domain.clone(), req_type.clone()), cache_items);
+                            cache.put((domain.clone(), *req_type, *dnssec_ok), cache_items);
                         }
                         break 'resolver;
                     }
@@ -152,17 +185,14 @@ impl LandscapeDnsRequestHandle {
                 self.flow_id,
                 update_dns_mark_list.into_iter().collect(),
             );
-            // landscape_ebpf::map_setting::flow_dns::del_flow_dns_mark_rules(
-            //     self.flow_id,
-            //     del_dns_mark_list.into_iter().collect(),
-            // );
         }
 
-        // println!("cache: {:?}", cache);
         let cache = Arc::new(Mutex::new(cache));
 
         self.resolves = resolves;
         self.cache = cache;
+        // 规则变化后否定应答可能不再成立, 直接丢弃
+        self.negative_cache = Arc::new(Mutex::new(LruCache::new(cache_capacity(&self.config))));
     }
 
     pub async fn check_domain(&self, domain: &str, query_type: RecordType) -> CheckDnsResult {
@@ -173,7 +203,7 @@ impl LandscapeDnsRequestHandle {
                 result.config = Some(resolver.get_runtime_config());
                 match tokio::time::timeout(
                     tokio::time::Duration::from_secs(5),
-                    resolver.lookup(&domain, query_type),
+                    resolver.lookup(&domain, query_type, &UpstreamQueryOptions::default()),
                 )
                 .await
                 {
@@ -191,71 +221,194 @@ impl LandscapeDnsRequestHandle {
             }
         }
 
-        if let Some((records, _)) = self.lookup_cache(domain, query_type).await {
+        if let Some(CacheLookupResult { records, .. }) =
+            self.lookup_cache(domain, query_type, false).await
+        {
             result.cache_records = Some(records);
         }
 
         result
     }
 
+    /// 追踪域名的处理过程, 不发起上游查询, 也不改变缓存状态
+    pub async fn trace_domain(&self, domain: &str, query_type: RecordType) -> DomainTrace {
+        let fqdn = format!("{domain}.");
+        let mut result = DomainTrace {
+            domain: domain.to_string(),
+            local_zone: self.local_zone.lookup(&fqdn, query_type).await.is_some(),
+            ..Default::default()
+        };
+
+        for (_index, resolver) in self.resolves.iter() {
+            let trace = resolver.trace(domain);
+            let matched = trace.result == DnsRuleTraceResult::Matched;
+            result.rules.push(trace);
+            if matched {
+                let mark = resolver.mark().mark.clone();
+                if mark.need_insert_in_ebpf_map() {
+                    result.mark_value = Some(mark.clone().into());
+                }
+                result.mark = Some(mark);
+                result.upstream = resolver.upstream().map(str::to_string);
+                break;
+            }
+        }
+
+        result.cache = self.trace_cache(&fqdn, query_type).await;
+        result
+    }
+
+    /// 使用 peek 读取缓存, 不影响 LRU 顺序以及命中统计
+    async fn trace_cache(&self, domain: &str, query_type: RecordType) -> DnsCacheTrace {
+        let key = (domain.to_string(), query_type, false);
+        if let Some(items) = self.cache.lock().await.peek(&key) {
+            if let Some(remaining_ttl) = items.iter().map(|item| item.remaining_ttl()).min() {
+                let state =
+                    if remaining_ttl > 0 { DnsCacheState::Fresh } else { DnsCacheState::Stale };
+                return DnsCacheTrace { state, remaining_ttl: Some(remaining_ttl) };
+            }
+        }
+        if let Some(item) = self.negative_cache.lock().await.peek(&key) {
+            let remaining_ttl = item.remaining_ttl();
+            if remaining_ttl > 0 {
+                return DnsCacheTrace {
+                    state: DnsCacheState::Negative,
+                    remaining_ttl: Some(remaining_ttl),
+                };
+            }
+        }
+        DnsCacheTrace::default()
+    }
+
+    /// 导出仍在 TTL 内的缓存, 按照最近使用的顺序从旧到新排列
+    pub async fn cache_snapshot(&self) -> FlowCacheSnapshot {
+        let cache = self.cache.lock().await;
+        let mut entries = Vec::with_capacity(cache.len());
+        for ((domain, query_type, dnssec_ok), items) in cache.iter().rev() {
+            // DO 请求的缓存不写入快照, 重启后重新查询
+            if *dnssec_ok {
+                continue;
+            }
+            let mut records = vec![];
+            for item in items.iter().filter(|item| item.remaining_ttl() > 0) {
+                let elapsed = item.insert_time.elapsed().as_secs() as u32;
+                for record in item.rdatas.iter() {
+                    let mut record = record.clone();
+                    record.set_ttl(record.ttl().saturating_sub(elapsed));
+                    records.push(record);
+                }
+            }
+            entries.extend(CacheSnapshotEntry::new(domain.clone(), *query_type, &records));
+        }
+        FlowCacheSnapshot::new(self.flow_id, entries)
+    }
+
     // 检查缓存并根据 TTL 判断是否过期
-    // 不同的记录可能的过期时间不同
+    // 不同的记录可能的过期时间不同, 以最小的为准
     pub async fn lookup_cache(
         &self,
         domain: &str,
         query_type: RecordType,
-    ) -> Option<(Vec<Record>, FilterResult)> {
+        dnssec_ok: bool,
+    ) -> Option<CacheLookupResult> {
         let mut cache = self.cache.lock().await;
-        if let Some(records) = cache.get(&(domain.to_string(), query_type)) {
-            let mut is_expire = false;
-            let mut valid_records: Vec<Record> = vec![];
-            let mut ret_fiter = FilterResult::Unfilter;
-            'a_record: for CacheDNSItem { rdatas, insert_time, filter, .. } in records.iter() {
-                ret_fiter = filter.clone();
-                for rdata in rdatas.iter() {
-                    if insert_time.elapsed().as_secs() > rdata.ttl() as u64 {
-                        is_expire = true;
-                        break 'a_record;
-                    }
-
-                    // tracing::debug!(
-                    //     "query {domain} , filter: {filter:?}, result: {valid_records:?}"
-                    // );
+        let items = cache.get_mut(&(domain.to_string(), query_type, dnssec_ok))?;
+
+        let mut valid_records: Vec<Record> = vec![];
+        let mut ret_fiter = FilterResult::Unfilter;
+        let mut need_refresh = false;
+        for item in items.iter_mut() {
+            ret_fiter = item.filter.clone();
+            item.hits = item.hits.saturating_add(1);
+
+            let elapsed = item.insert_time.elapsed().as_secs();
+            let remaining_ttl = item.remaining_ttl();
+            let is_refreshing =
+                item.refresh_time.is_some_and(|time| time.elapsed() < REFRESH_TIMEOUT);
+
+            if remaining_ttl <= 0 {
+                // 过期数据仅在开启 serve stale 时返回
+                if !self.config.cache_serve_stale
+                    || -remaining_ttl > self.config.cache_stale_max_ttl as i64
+                {
+                    return None;
+                }
+                if !is_refreshing {
+                    item.refresh_time = Some(Instant::now());
+                    need_refresh = true;
                 }
-                valid_records.extend_from_slice(rdatas);
+                for rdata in item.rdatas.iter() {
+                    let mut rdata = rdata.clone();
+                    rdata.set_ttl(STALE_ANSWER_TTL);
+                    valid_records.push(rdata);
+                }
+                continue;
             }
 
-            if is_expire {
-                return None;
+            // 热门记录在 TTL 即将到期时提前刷新
+            let prefetch_at = item.min_ttl() as f32 * self.config.cache_prefetch_ratio;
+            if self.config.cache_prefetch_ratio < 1.0
+                && item.hits >= PREFETCH_MIN_HITS
+                && elapsed as f32 >= prefetch_at
+                && !is_refreshing
+            {
+                item.refresh_time = Some(Instant::now());
+                need_refresh = true;
             }
 
-            // 如果有有效的记录，返回它们
-            if !valid_records.is_empty() {
-                return Some((valid_records, ret_fiter));
+            for rdata in item.rdatas.iter() {
+                let mut rdata = rdata.clone();
+                rdata.set_ttl((rdata.ttl() as u64).saturating_sub(elapsed).max(1) as u32);
+                valid_records.push(rdata);
             }
         }
-        None
+
+        // 如果有有效的记录，返回它们
+        if valid_records.is_empty() {
+            return None;
+        }
+        Some(CacheLookupResult {
+            records: valid_records,
+            filter: ret_fiter,
+            need_refresh,
+        })
     }
 
     pub async fn insert(
         &self,
         domain: &str,
         query_type: RecordType,
-        rdata_ttl_vec: Vec<Record>,
+        dnssec_ok: bool,
+        mut rdata_ttl_vec: Vec<Record>,
         mark: &DnsRuntimeMarkInfo,
         filter: FilterResult,
     ) {
+        for rdata in rdata_ttl_vec.iter_mut() {
+            let ttl = self.clamp_ttl(rdata.ttl());
+            rdata.set_ttl(ttl);
+        }
         let cache_item = CacheDNSItem {
             rdatas: rdata_ttl_vec,
             insert_time: Instant::now(),
             mark: mark.clone(),
             filter,
+            hits: 0,
+            refresh_time: None,
         };
         let update_dns_mark_list = cache_item.get_update_rules();
 
-        let mut cache = self.cache.lock().await;
-        cache.put((domain.to_string(), query_type), vec![cache_item]);
-        drop(cache);
+        let key = (domain.to_string(), query_type, dnssec_ok);
+        // 刷新后不再使用的 IP, 且没有被其他缓存引用时从 ebpf map 中删除
+        let removed_ips = self.cache.lock().await.put(key.clone(), vec![cache_item]);
+        self.negative_cache.lock().await.pop(&key);
+
+        if !removed_ips.is_empty() {
+            tracing::info!("remove ips: {:?} from flow: {}", removed_ips, self.flow_id);
+            landscape_ebpf::map_setting::flow_dns::del_flow_dns_rule(
+                self.flow_id,
+                removed_ips.into_iter().collect(),
+            );
+        }
         // 将 mark 写入 mark ebpf map
         if mark.mark.need_insert_in_ebpf_map() {
             tracing::info!("setting ips: {:?}, Mark: {:?}", update_dns_mark_list, mark);
@@ -266,6 +419,269 @@ impl LandscapeDnsRequestHandle {
             );
         }
     }
+
+    /// 检查否定应答缓存, 返回的 SOA TTL 为剩余时间
+    async fn lookup_negative_cache(
+        &self,
+        domain: &str,
+        query_type: RecordType,
+        dnssec_ok: bool,
+    ) -> Option<(ResponseCode, Record)> {
+        let mut cache = self.negative_cache.lock().await;
+        let key = (domain.to_string(), query_type, dnssec_ok);
+        let item = cache.get(&key)?;
+        let remaining_ttl = item.remaining_ttl();
+        if remaining_ttl <= 0 {
+            cache.pop(&key);
+            return None;
+        }
+        let mut soa = item.soa.clone();
+        soa.set_ttl(remaining_ttl as u32);
+        Some((item.code, soa))
+    }
+
+    /// 缓存 NXDOMAIN / NODATA 应答
+    /// 没有 SOA 的否定应答不进行缓存 (RFC 2308 Section 5)
+    async fn insert_negative(
+        &self,
+        domain: &str,
+        query_type: RecordType,
+        dnssec_ok: bool,
+        error: &LookupError,
+    ) {
+        if !matches!(error.code, ResponseCode::NXDomain | ResponseCode::NoError) {
+            return;
+        }
+        let Some(soa) = &error.soa else {
+            return;
+        };
+        let Some(minimum) = soa.data().as_soa().map(|data| data.minimum()) else {
+            return;
+        };
+        let ttl = self.clamp_ttl(soa.ttl().min(minimum)).min(NEGATIVE_CACHE_MAX_TTL);
+        if ttl == 0 {
+            return;
+        }
+        let mut soa = soa.clone();
+        soa.set_ttl(ttl);
+
+        let key = (domain.to_string(), query_type, dnssec_ok);
+        // 域名已经不存在时, 旧的缓存不能再返回, 其写入 ebpf map 的 mark 一并撤回
+        let removed_ips = self.cache.lock().await.pop(&key);
+        if !removed_ips.is_empty() {
+            tracing::info!("remove ips: {:?} from flow: {}", removed_ips, self.flow_id);
+            landscape_ebpf::map_setting::flow_dns::del_flow_dns_rule(
+                self.flow_id,
+                removed_ips.into_iter().collect(),
+            );
+        }
+        self.negative_cache.lock().await.put(
+            key,
+            NegativeCacheItem {
+                code: error.code,
+                soa,
+                insert_time: Instant::now(),
+                ttl,
+            },
+        );
+    }
+
+    fn clamp_ttl(&self, ttl: u32) -> u32 {
+        ttl.max(self.config.cache_min_ttl).min(self.config.cache_max_ttl)
+    }
+
+    /// 当前规则引用的上游服务器
+    pub fn upstream_health_keys(&self) -> impl Iterator<Item = (Uuid, String)> + '_ {
+        self.resolves.values().flat_map(|rule| rule.upstream_health_keys())
+    }
+
+    pub async fn probe_upstreams(&self) {
+        for rule in self.resolves.values() {
+            rule.probe_upstreams().await;
+        }
+    }
+
+    pub async fn cache_stats(&self) -> DnsCacheStats {
+        let (capacity, size) = {
+            let cache = self.cache.lock().await;
+            (cache.cap().get() as u32, cache.len() as u32)
+        };
+        let negative_size = self.negative_cache.lock().await.len() as u32;
+        DnsCacheStats {
+            flow_id: self.flow_id,
+            capacity,
+            size,
+            negative_size,
+            hits: self.counter.hits.load(Ordering::Relaxed),
+            negative_hits: self.counter.negative_hits.load(Ordering::Relaxed),
+            misses: self.counter.misses.load(Ordering::Relaxed),
+        }
+    }
+
+    /// 使用匹配的规则进行解析并写入缓存
+    /// 返回过滤后的结果
+    async fn resolve(
+        &self,
+        domain: &str,
+        query_type: RecordType,
+        options: &UpstreamQueryOptions,
+    ) -> Result<Vec<Record>, LookupError> {
+        let Some(resolver) = self.match_rule(domain) else {
+            return Ok(vec![]);
+        };
+        let result = resolver.lookup(domain, query_type, options).await;
+        let result = if query_type == RecordType::AAAA
+            && matches!(resolver.filter_mode(), FilterResult::Dns64)
+        {
+            self.synthesize_aaaa(resolver, domain, options, result).await
+        } else {
+            result
+        };
+        let rdata_vec = match result {
+            Ok(rdata_vec) => rdata_vec,
+            Err(error) => {
+                self.insert_negative(domain, query_type, options.dnssec_ok, &error).await;
+                return Err(error);
+            }
+        };
+        // DNSSEC 签名只保存在 DO 请求的缓存中
+        let cache_records: Vec<Record> = rdata_vec
+            .iter()
+            .filter(|record| options.dnssec_ok || record.record_type() != RecordType::RRSIG)
+            .cloned()
+            .collect();
+        if cache_records.len() > 0 {
+            self.insert(
+                domain,
+                query_type,
+                options.dnssec_ok,
+                cache_records,
+                resolver.mark(),
+                resolver.filter_mode(),
+            )
+            .await;
+        }
+        Ok(fiter_result(rdata_vec, &resolver.filter_mode()))
+    }
+
+    /// 上游没有返回 AAAA 记录时, 查询 A 记录并合成 AAAA 记录 (RFC 6147 Section 5.1)
+    /// NXDOMAIN 以及查询失败时返回原结果, CD 标记的请求不进行合成 (RFC 6147 Section 5.5)
+    async fn synthesize_aaaa(
+        &self,
+        resolver: &ResolutionRule,
+        domain: &str,
+        options: &UpstreamQueryOptions,
+        result: Result<Vec<Record>, LookupError>,
+    ) -> Result<Vec<Record>, LookupError> {
+        let need_synthesize = match &result {
+            Ok(records) => !has_aaaa(records),
+            Err(error) => error.code == ResponseCode::NoError,
+        };
+        if !need_synthesize || options.checking_disabled {
+            return result;
+        }
+
+        match resolver.lookup(domain, RecordType::A, options).await {
+            Ok(a_records) if a_records.iter().any(|r| r.record_type() == RecordType::A) => {
+                tracing::debug!("dns64 synthesize {domain} with prefix {}", self.dns64_prefix);
+                Ok(self.dns64_prefix.synthesize(a_records))
+            }
+            _ => result,
+        }
+    }
+
+    /// 按照优先级找到第一个匹配的规则
+    fn match_rule(&self, domain: &str) -> Option<&Arc<ResolutionRule>> {
+        self.resolves.values().find(|resolver| resolver.is_match(domain))
+    }
+
+    /// 后台刷新缓存, 不阻塞当前请求
+    fn spawn_refresh(&self, domain: String, query_type: RecordType, dnssec_ok: bool) {
+        let handle = self.clone();
+        tokio::spawn(async move {
+            tracing::debug!("refresh cache: {domain} {query_type}");
+            let options = UpstreamQueryOptions { dnssec_ok, ..Default::default() };
+            if let Err(error) = handle.resolve(&domain, query_type, &options).await {
+                tracing::debug!("refresh cache: {domain} {query_type} error: {:?}", error.code);
+            }
+        });
+    }
+
+    /// 解析单个问题: 本地区域 -> 缓存 -> 规则
+    async fn answer_query(
+        &self,
+        domain: &str,
+        query_type: RecordType,
+        options: &UpstreamQueryOptions,
+        trace: &mut AnswerTrace,
+    ) -> Result<Vec<Record>, LookupError> {
+        match self.policy.qtype_action(self.flow_id, query_type) {
+            Some(DnsQtypeAction::Refused) => return Err(ResponseCode::Refused.into()),
+            Some(DnsQtypeAction::Empty) => return Ok(vec![]),
+            None => {}
+        }
+        if let Some(local_records) = self.local_zone.lookup(domain, query_type).await {
+            return Ok(local_records);
+        }
+        trace.rule = self.match_rule(domain).cloned();
+
+        // 携带 CD 或者需要转发 ECS 的请求结果与客户端相关, 不使用缓存中的结果
+        let forward_ecs = trace.rule.as_ref().is_some_and(|rule| rule.forwards_client_subnet());
+        let dnssec_ok = options.dnssec_ok;
+        if !options.need_bypass_cache(forward_ecs) {
+            if let Some(cache_result) = self.lookup_cache(domain, query_type, dnssec_ok).await {
+                self.counter.hit();
+                trace.cache_hit = true;
+                if cache_result.need_refresh {
+                    self.spawn_refresh(domain.to_string(), query_type, dnssec_ok);
+                }
+                return Ok(fiter_result(cache_result.records, &cache_result.filter));
+            }
+            if let Some((code, soa)) =
+                self.lookup_negative_cache(domain, query_type, dnssec_ok).await
+            {
+                self.counter.negative_hit();
+                trace.cache_hit = true;
+                return Err(LookupError { code, soa: Some(soa) });
+            }
+        }
+
+        self.counter.miss();
+        self.resolve(domain, query_type, options).await
+    }
+
+    fn record_query_log(
+        &self,
+        request: &Request,
+        domain: &str,
+        query_type: RecordType,
+        result: &Result<Vec<Record>, LookupError>,
+        trace: &AnswerTrace,
+        time: Instant,
+    ) {
+        let response_code = match result {
+            Ok(_) => ResponseCode::NoError,
+            Err(error) => error.code,
+        };
+        let rule = trace.rule.as_ref();
+        self.dns_metric.send_query_log(DnsQueryLog {
+            report_time: get_f64_timestamp() as u64,
+            client_ip: request.src().ip(),
+            flow_id: self.flow_id,
+            domain: domain.trim_end_matches('.').to_string(),
+            query_type: query_type.to_string(),
+            rule_name: rule.map(|rule| rule.name().to_string()),
+            // 缓存命中时没有向上游发送请求
+            upstream: rule
+                .filter(|_| !trace.cache_hit)
+                .and_then(|rule| rule.upstream())
+                .map(str::to_string),
+            response_code: response_code.to_string(),
+            latency_ms: time.elapsed().as_millis() as u32,
+            cache_hit: trace.cache_hit,
+            blocked: rule.is_some_and(|rule| rule.is_block()),
+        });
+    }
 }
 
 #[async_trait::async_trait]
@@ -279,8 +695,7 @@ impl RequestHandler for LandscapeDnsRequestHandle {
         if queries.is_empty() {
             let mut header = Header::response_from_request(request.header());
             header.set_response_code(ResponseCode::FormErr);
-            let response =
-                MessageResponseBuilder::from_message_request(request).build_no_records(header);
+            let response = new_response_builder(request).build_no_records(header);
             let result = response_handle.send_response(response).await;
             return match result {
                 Err(e) => {
@@ -291,78 +706,60 @@ impl RequestHandler for LandscapeDnsRequestHandle {
             };
         }
 
-        // 先只处理第一个查询
-        let req = &queries[0];
-        let domain = req.name().to_string();
-        let query_type = req.query_type();
-
-        let response_builder = MessageResponseBuilder::from_message_request(request);
+        let response_builder = new_response_builder(request);
         let mut header = Header::response_from_request(request.header());
         header.set_response_code(ResponseCode::NoError);
         header.set_authoritative(true);
         header.set_recursion_available(true);
 
-        let mut records = vec![];
-
-        // TODO: 修改逻辑
-        if let Some((result, filter)) = self.lookup_cache(&domain, query_type).await {
-            records = fiter_result(result, &filter);
-        } else {
-            for (_index, resolver) in self.resolves.iter() {
-                if resolver.is_match(&domain) {
-                    records = match resolver.lookup(&domain, query_type).await {
-                        Ok(rdata_vec) => {
-                            if rdata_vec.len() > 0 {
-                                self.insert(
-                                    &domain,
-                                    query_type,
-                                    rdata_vec.clone(),
-                                    resolver.mark(),
-                                    resolver.filter_mode(),
-                                )
-                                .await;
-                            }
-                            fiter_result(rdata_vec, &resolver.filter_mode())
-                        }
-                        Err(error_code) => {
-                            // 构建并返回错误响应
-                            header.set_response_code(error_code);
-                            let response = MessageResponseBuilder::from_message_request(request)
-                                .build_no_records(header);
-                            let result = response_handle.send_response(response).await;
-                            return match result {
-                                Err(e) => {
-                                    tracing::error!("Request failed: {}", e);
-                                    serve_failed()
-                                }
-                                Ok(info) => info,
-                            };
-                        }
-                    };
-                    break;
-                }
-            }
-        }
-
-        // 如果没有找到记录，返回 NXDomain 响应
-        if records.is_empty() {
-            // header.set_response_code(ResponseCode::NXDomain);
+        // 仅支持 EDNS 版本 0 (RFC 6891 Section 6.1.3)
+        if request.edns().is_some_and(|edns| edns.version() > 0) {
+            header.set_response_code(ResponseCode::BADVERS);
             let response = response_builder.build_no_records(header);
             let result = response_handle.send_response(response).await;
             return match result {
                 Err(e) => {
-                    tracing::error!("Record Empty and response error: {}", e);
+                    tracing::error!("Request failed: {}", e);
                     serve_failed()
                 }
                 Ok(info) => info,
             };
         }
 
+        let options = upstream_query_options(request);
+
+        // 多个问题时按顺序逐个解析并合并应答
+        // 响应码以及 Authority 中的 SOA 以第一个问题的结果为准
+        let mut records = vec![];
+        let mut soa_records = vec![];
+        for (index, query) in queries.iter().enumerate() {
+            let domain = query.name().to_string();
+            let query_type = query.query_type();
+            let time = Instant::now();
+            let mut trace = AnswerTrace::default();
+            let result = self.answer_query(&domain, query_type, &options, &mut trace).await;
+            self.record_query_log(request, &domain, query_type, &result, &trace, time);
+            match result {
+                Ok(answers) => records.extend(answers),
+                Err(error) => {
+                    if index == 0 {
+                        header.set_response_code(error.code);
+                        soa_records.extend(error.soa);
+                    } else {
+                        tracing::debug!(
+                            "query: {domain} {query_type} failed: {:?}, ignore",
+                            error.code
+                        );
+                    }
+                }
+            }
+        }
+
         let response = response_builder.build(
             header,
             records.iter(),
             vec![].into_iter(),
-            vec![].into_iter(),
+            soa_records.iter(),
             vec![].into_iter(),
         );
 
@@ -377,6 +774,82 @@ impl RequestHandler for LandscapeDnsRequestHandle {
     }
 }
 
+/// 创建响应, 若请求中携带了 EDNS 则在响应中回显
+/// 以便按照客户端声明的 UDP 大小进行截断
+fn new_response_builder(request: &Request) -> MessageResponseBuilder<'_> {
+    let mut response_builder = MessageResponseBuilder::from_message_request(request);
+    if let Some(req_edns) = request.edns() {
+        let mut resp_edns = Edns::new();
+        resp_edns.set_max_payload(req_edns.max_payload().clamp(MIN_UDP_PAYLOAD, MAX_UDP_PAYLOAD));
+        resp_edns.set_version(0);
+        // 回显 DO 位 (RFC 3225)
+        resp_edns.set_dnssec_ok(req_edns.flags().dnssec_ok);
+        response_builder.edns(resp_edns);
+    }
+    response_builder
+}
+
+/// 从客户端请求中提取需要传递给上游的 DO / CD / ECS
+fn upstream_query_options(request: &Request) -> UpstreamQueryOptions {
+    let mut options = UpstreamQueryOptions {
+        checking_disabled: request.header().checking_disabled(),
+        ..Default::default()
+    };
+    if let Some(req_edns) = request.edns() {
+        options.dnssec_ok = req_edns.flags().dnssec_ok;
+        if let Some(EdnsOption::Subnet(subnet)) = req_edns.options().get(EdnsCode::Subnet) {
+            options.client_subnet = Some(subnet.clone());
+        }
+    }
+    options
+}
+
+/// 从快照中恢复仍在 TTL 内的缓存, 使用当前的规则重新标记, 并重建 flow 的 DNS 标记 map
+fn restore_cache(
+    cache: &mut DNSCache,
+    resolves: &BTreeMap<u32, Arc<ResolutionRule>>,
+    flow_id: u32,
+    dir: &Path,
+) {
+    let Some(snapshot) = load_snapshot(dir, flow_id) else {
+        return;
+    };
+    let elapsed = snapshot.elapsed_secs();
+    let mut update_dns_mark_list: HashSet<FlowDnsMarkInfo> = HashSet::new();
+    for entry in snapshot.entries.iter() {
+        let Some((domain, query_type, records)) = entry.restore(elapsed) else {
+            continue;
+        };
+        let Some(resolver) = resolves.values().find(|resolver| resolver.is_match(&domain)) else {
+            continue;
+        };
+        let cache_item = CacheDNSItem {
+            rdatas: records,
+            insert_time: Instant::now(),
+            mark: resolver.mark().clone(),
+            filter: resolver.filter_mode(),
+            hits: 0,
+            refresh_time: None,
+        };
+        update_dns_mark_list.extend(cache_item.get_update_rules());
+        cache.put((domain, query_type, false), vec![cache_item]);
+    }
+
+    tracing::info!(
+        "flow: {flow_id} restore {} dns cache entries, {} marks",
+        cache.len(),
+        update_dns_mark_list.len()
+    );
+    landscape_ebpf::map_setting::flow_dns::create_flow_dns_inner_map(
+        flow_id,
+        update_dns_mark_list.into_iter().collect(),
+    );
+}
+
+fn cache_capacity(config: &DnsRuntimeConfig) -> NonZeroUsize {
+    NonZeroUsize::new(config.cache_capacity.max(1) as usize).unwrap()
+}
+
 fn serve_failed() -> ResponseInfo {
     let mut header = Header::new();
     header.set_response_code(ResponseCode::ServFail);
@@ -386,7 +859,7 @@ fn serve_failed() -> ResponseInfo {
 fn fiter_result(un_filter_records: Vec<Record>, filter: &FilterResult) -> Vec<Record> {
     let mut valid_records = Vec::with_capacity(un_filter_records.len());
     for rdata in un_filter_records.into_iter() {
-        if matches!(filter, FilterResult::Unfilter) {
+        if matches!(filter, FilterResult::Unfilter | FilterResult::Dns64) {
             valid_records.push(rdata.clone());
         } else {
             match (rdata.record_type(), filter) {
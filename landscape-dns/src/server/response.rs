@@ -4,6 +4,7 @@ use hickory_proto::{
     op::{Header, LowerQuery},
     rr::Record,
     serialize::binary::BinEncoder,
+    xfer::Protocol,
 };
 use hickory_server::{
     authority::MessageResponse,
@@ -13,15 +14,19 @@ use tokio::sync::mpsc;
 
 use crate::socket::SendDnsMessage;
 
+/// RFC1035 中 UDP 消息的最大长度
+const MIN_UDP_PAYLOAD: u16 = 512;
+
 #[derive(Clone)]
 pub struct LandscapeResponse {
     dst: SocketAddr,
+    protocol: Protocol,
     sender: mpsc::Sender<SendDnsMessage>,
 }
 
 impl LandscapeResponse {
-    pub fn new(dst: SocketAddr, sender: mpsc::Sender<SendDnsMessage>) -> Self {
-        Self { dst, sender }
+    pub fn new(dst: SocketAddr, protocol: Protocol, sender: mpsc::Sender<SendDnsMessage>) -> Self {
+        Self { dst, protocol, sender }
     }
 
     fn max_size_for_response<'a>(
@@ -35,12 +40,18 @@ impl LandscapeResponse {
             impl Iterator<Item = &'a Record> + Send + 'a,
         >,
     ) -> u16 {
-        // Use EDNS, if available.
-        if let Some(edns) = response.get_edns() {
-            edns.max_payload()
-        } else {
-            // No EDNS, use the recommended max from RFC6891.
-            hickory_proto::udp::MAX_RECEIVE_BUFFER_SIZE as u16
+        match self.protocol {
+            Protocol::Udp => {
+                // Use EDNS, if available.
+                if let Some(edns) = response.get_edns() {
+                    edns.max_payload().max(MIN_UDP_PAYLOAD)
+                } else {
+                    // No EDNS, the client can only receive 512 bytes (RFC1035)
+                    // emit 超出大小时会截断并设置 TC 位, 客户端会使用 TCP 重试
+                    MIN_UDP_PAYLOAD
+                }
+            }
+            _ => u16::MAX,
         }
     }
 }
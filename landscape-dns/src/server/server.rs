@@ -1,7 +1,9 @@
 use std::net::IpAddr;
 use std::os::fd::AsRawFd;
+use std::time::Duration;
 use std::{
     collections::HashMap,
+    io,
     mem::MaybeUninit,
     net::{Ipv6Addr, SocketAddr, SocketAddrV6},
     sync::Arc,
@@ -11,6 +13,7 @@ use super::response::LandscapeResponse;
 use hickory_proto::{
     op::{Header, LowerQuery, MessageType, Query, ResponseCode},
     serialize::binary::{BinDecodable, BinDecoder},
+    xfer::Protocol,
     ProtoError,
 };
 use hickory_server::{
@@ -19,17 +22,28 @@ use hickory_server::{
 };
 use landscape_common::flow::PacketMatchMark;
 use socket2::{Domain, MsgHdrMut, Type};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpListener;
 use tokio::sync::RwLock;
 use tokio::{io::unix::AsyncFd, sync::mpsc, task::JoinSet};
+use tokio_rustls::TlsAcceptor;
 use tokio_util::sync::CancellationToken;
 
+use crate::policy::{DnsPolicyManager, RateLimitResult};
 use crate::server::response::ReportingResponseHandler;
 use crate::socket::{RecvDnsMessage, SendDnsMessage};
 
+/// TCP 连接空闲超时时间
+const TCP_IDLE_TIMEOUT: Duration = Duration::from_secs(10);
+/// accept 出错 (例如 EMFILE) 后的等待时间
+const TCP_ACCEPT_ERROR_BACKOFF: Duration = Duration::from_millis(100);
+
 pub struct DiffFlowServer<T: RequestHandler + Clone> {
     /// flow_id <-> Handler
     handlers: Arc<RwLock<HashMap<u32, T>>>,
     dispatch_rules: Arc<RwLock<HashMap<PacketMatchMark, u32>>>,
+    /// 在创建处理任务之前进行限速
+    policy: DnsPolicyManager,
     join_set: JoinSet<Result<(), ProtoError>>,
     shutdown_token: CancellationToken,
 }
@@ -39,31 +53,41 @@ impl<T: RequestHandler + Clone> DiffFlowServer<T> {
     pub fn new(
         handlers: Arc<RwLock<HashMap<u32, T>>>,
         dispatch_rules: Arc<RwLock<HashMap<PacketMatchMark, u32>>>,
+        policy: DnsPolicyManager,
     ) -> Self {
         Self {
             handlers,
             dispatch_rules,
+            policy,
             join_set: JoinSet::new(),
             shutdown_token: CancellationToken::new(),
         }
     }
 
-    pub fn listen_on(&mut self, socket_addr: SocketAddr) {
-        tracing::debug!("registering udp: {:?}", socket_addr);
+    /// `iface_name` 不为空时仅接收该网卡上的请求
+    pub fn listen_on(
+        &mut self,
+        socket_addr: SocketAddr,
+        iface_name: Option<&str>,
+    ) -> io::Result<()> {
+        tracing::debug!("registering udp: {:?}, iface: {:?}", socket_addr, iface_name);
 
         let socket2 = if socket_addr.is_ipv4() {
-            socket2::Socket::new(Domain::IPV4, Type::DGRAM, Some(socket2::Protocol::UDP)).unwrap()
+            socket2::Socket::new(Domain::IPV4, Type::DGRAM, Some(socket2::Protocol::UDP))?
         } else {
-            socket2::Socket::new(Domain::IPV6, Type::DGRAM, Some(socket2::Protocol::UDP)).unwrap()
+            socket2::Socket::new(Domain::IPV6, Type::DGRAM, Some(socket2::Protocol::UDP))?
         };
 
         let fd = socket2.as_raw_fd();
-        let _ = crate::socket::set_socket_rcvmark(fd).unwrap();
-        socket2.set_recv_tos(true).unwrap();
-        socket2.set_nonblocking(true).unwrap();
-        socket2.bind(&socket_addr.into()).unwrap();
+        crate::socket::set_socket_rcvmark(fd)?;
+        if let Some(iface_name) = iface_name {
+            bind_device(&socket2, iface_name)?;
+        }
+        socket2.set_recv_tos(true)?;
+        socket2.set_nonblocking(true)?;
+        socket2.bind(&socket_addr.into())?;
 
-        let async_socket = Arc::new(AsyncFd::new(socket2).unwrap());
+        let async_socket = Arc::new(AsyncFd::new(socket2)?);
 
         let (recv_msg_tx, mut recv_msg_rc) = tokio::sync::mpsc::channel::<RecvDnsMessage>(1024);
         let (send_msg_tx, mut send_msg_rc) = tokio::sync::mpsc::channel::<SendDnsMessage>(1024);
@@ -111,6 +135,7 @@ impl<T: RequestHandler + Clone> DiffFlowServer<T> {
         });
 
         let shutdown = self.shutdown_token.clone();
+        let policy = self.policy.clone();
         self.join_set.spawn({
             async move {
                 let mut inner_join_set = JoinSet::new();
@@ -129,35 +154,23 @@ impl<T: RequestHandler + Clone> DiffFlowServer<T> {
                     };
 
                     // tracing::info!("tos: {tos:?}, addr: {addr:?}, is_ipv4: {}", addr.is_ipv4());
-                    let qos = if tos == 0 { None } else { Some(tos) };
-
-                    let ip = match landscape_common::utils::ip::extract_real_ip(addr) {
-                        std::net::IpAddr::V4(ipv4_addr) => IpAddr::V4(ipv4_addr),
-                        std::net::IpAddr::V6(ipv6_addr) => IpAddr::V6(ipv6_addr),
-                    };
-                    let find_key = PacketMatchMark { ip, vlan_id: None, qos };
-                    let mark = if let Some(mark) = dispatch_rules.read().await.get(&find_key) {
-                        let mark = mark.clone();
-                        // tracing::info!("get mark: {mark:?}, using: {find_key:?}");
-                        mark
-                    } else {
-                        // tracing::info!("can not get mark, using: {find_key:?}");
-                        0
-                    };
-
-                    if let Some(request_handler) =
-                        handlers.read().await.get(&mark).map(Clone::clone)
+                    if let Some((flow_id, request_handler)) =
+                        find_request_handler(&handlers, &dispatch_rules, addr, tos).await
                     {
+                        if !check_rate_limit(&policy, flow_id, addr, &message, &send_msg_tx) {
+                            continue;
+                        }
                         let send_msg_tx_clone = send_msg_tx.clone();
                         inner_join_set.spawn(async move {
-                            handle_raw_request(message, addr, request_handler, send_msg_tx_clone)
-                                .await;
+                            handle_raw_request(
+                                message,
+                                addr,
+                                Protocol::Udp,
+                                request_handler,
+                                send_msg_tx_clone,
+                            )
+                            .await;
                         });
-                    } else {
-                        tracing::error!(
-                            "mark: {mark:?}, can not found handler, addr: {addr:?}, \
-                        Or maybe you just forgot to add the DNS rules in this flow config"
-                        );
                     }
 
                     reap_tasks(&mut inner_join_set);
@@ -305,6 +318,125 @@ impl<T: RequestHandler + Clone> DiffFlowServer<T> {
                 }
             }
         });
+        Ok(())
+    }
+
+    pub fn listen_on_tcp(
+        &mut self,
+        socket_addr: SocketAddr,
+        iface_name: Option<&str>,
+    ) -> io::Result<()> {
+        tracing::debug!("registering tcp: {:?}, iface: {:?}", socket_addr, iface_name);
+        let listener = bind_tcp_listener(socket_addr, iface_name)?;
+        self.spawn_stream_listener(listener, None);
+        Ok(())
+    }
+
+    /// DNS over TLS
+    pub fn listen_on_tls(
+        &mut self,
+        socket_addr: SocketAddr,
+        iface_name: Option<&str>,
+        tls_acceptor: TlsAcceptor,
+    ) -> io::Result<()> {
+        tracing::debug!("registering tls: {:?}, iface: {:?}", socket_addr, iface_name);
+        let listener = bind_tcp_listener(socket_addr, iface_name)?;
+        self.spawn_stream_listener(listener, Some(tls_acceptor));
+        Ok(())
+    }
+
+    fn spawn_stream_listener(&mut self, listener: TcpListener, tls_acceptor: Option<TlsAcceptor>) {
+        let shutdown = self.shutdown_token.clone();
+        let handlers = self.handlers.clone();
+        let dispatch_rules = self.dispatch_rules.clone();
+        let policy = self.policy.clone();
+
+        let is_ipv6 = listener.local_addr().map(|addr| addr.is_ipv6()).unwrap_or(false);
+        self.join_set.spawn(async move {
+            let mut inner_join_set = JoinSet::new();
+            loop {
+                let (tcp_stream, src_addr) = tokio::select! {
+                    result = listener.accept() => match result {
+                        Ok(c) => c,
+                        Err(e) => {
+                            // EMFILE 等错误会立即重复出现, 稍作等待避免空转
+                            tracing::debug!("error receiving TCP tcp_stream error: {e}");
+                            tokio::time::sleep(TCP_ACCEPT_ERROR_BACKOFF).await;
+                            continue;
+                        }
+                    },
+                    _ = shutdown.cancelled() => {
+                        break;
+                    },
+                };
+
+                let tos = match crate::socket::get_tcp_recv_tos(tcp_stream.as_raw_fd(), is_ipv6) {
+                    Ok(tos) => tos,
+                    Err(e) => {
+                        tracing::debug!("read tcp tos error: {e:?}");
+                        0
+                    }
+                };
+
+                let handlers = handlers.clone();
+                let dispatch_rules = dispatch_rules.clone();
+                let policy = policy.clone();
+                let shutdown = shutdown.clone();
+                let tls_acceptor = tls_acceptor.clone();
+                inner_join_set.spawn(async move {
+                    let Some(tls_acceptor) = tls_acceptor else {
+                        handle_stream(
+                            tcp_stream,
+                            src_addr,
+                            tos,
+                            Protocol::Tcp,
+                            handlers,
+                            dispatch_rules,
+                            policy,
+                            shutdown,
+                        )
+                        .await;
+                        return;
+                    };
+
+                    let tls_stream = match tokio::time::timeout(
+                        TCP_IDLE_TIMEOUT,
+                        tls_acceptor.accept(tcp_stream),
+                    )
+                    .await
+                    {
+                        Ok(Ok(tls_stream)) => tls_stream,
+                        Ok(Err(e)) => {
+                            tracing::debug!("tls handshake error: {e:?}, src: {src_addr:?}");
+                            return;
+                        }
+                        Err(_) => {
+                            tracing::debug!("tls handshake timeout, src: {src_addr:?}");
+                            return;
+                        }
+                    };
+                    handle_stream(
+                        tls_stream,
+                        src_addr,
+                        tos,
+                        Protocol::Tls,
+                        handlers,
+                        dispatch_rules,
+                        policy,
+                        shutdown,
+                    )
+                    .await;
+                });
+
+                reap_tasks(&mut inner_join_set);
+            }
+
+            if shutdown.is_cancelled() {
+                Ok(())
+            } else {
+                Err(ProtoError::from("unexpected close of TCP listener"))
+            }
+        });
     }
 
     pub async fn block_until_done(&mut self) -> Result<(), ProtoError> {
@@ -345,6 +477,180 @@ async fn block_until_done(
     out
 }
 
+/// 多个网卡监听同一个端口时需要 SO_REUSEADDR
+/// 未能绑定到网卡时不能退化为监听所有网卡, 直接返回错误
+fn bind_device(socket2: &socket2::Socket, iface_name: &str) -> io::Result<()> {
+    socket2.set_reuse_address(true)?;
+    crate::socket::set_socket_bind_device(socket2.as_raw_fd(), iface_name)
+}
+
+fn bind_tcp_listener(socket_addr: SocketAddr, iface_name: Option<&str>) -> io::Result<TcpListener> {
+    let socket2 = if socket_addr.is_ipv4() {
+        socket2::Socket::new(Domain::IPV4, Type::STREAM, Some(socket2::Protocol::TCP))?
+    } else {
+        socket2::Socket::new(Domain::IPV6, Type::STREAM, Some(socket2::Protocol::TCP))?
+    };
+    if let Some(iface_name) = iface_name {
+        bind_device(&socket2, iface_name)?;
+    }
+
+    // 接收的连接会继承 IP_RECVTOS / IPV6_RECVTCLASS, 用于读取客户端的 TOS
+    if socket_addr.is_ipv4() {
+        socket2.set_recv_tos(true)?;
+    } else {
+        crate::socket::set_socket_recv_tclass(socket2.as_raw_fd())?;
+    }
+    socket2.set_reuse_address(true)?;
+    socket2.set_nonblocking(true)?;
+    socket2.bind(&socket_addr.into())?;
+    socket2.listen(1024)?;
+
+    TcpListener::from_std(socket2.into())
+}
+
+/// 依据来源 IP 与 TOS 找到对应的 flow 以及处理器
+pub(crate) async fn find_request_handler<T: Clone>(
+    handlers: &Arc<RwLock<HashMap<u32, T>>>,
+    dispatch_rules: &Arc<RwLock<HashMap<PacketMatchMark, u32>>>,
+    addr: SocketAddr,
+    tos: u8,
+) -> Option<(u32, T)> {
+    let qos = if tos == 0 { None } else { Some(tos) };
+
+    let ip = match landscape_common::utils::ip::extract_real_ip(addr) {
+        std::net::IpAddr::V4(ipv4_addr) => IpAddr::V4(ipv4_addr),
+        std::net::IpAddr::V6(ipv6_addr) => IpAddr::V6(ipv6_addr),
+    };
+    let find_key = PacketMatchMark { ip, vlan_id: None, qos };
+    let mark = if let Some(mark) = dispatch_rules.read().await.get(&find_key) {
+        let mark = mark.clone();
+        // tracing::info!("get mark: {mark:?}, using: {find_key:?}");
+        mark
+    } else {
+        // tracing::info!("can not get mark, using: {find_key:?}");
+        0
+    };
+
+    let request_handler = handlers.read().await.get(&mark).map(Clone::clone);
+    if request_handler.is_none() {
+        tracing::error!(
+            "mark: {mark:?}, can not found handler, addr: {addr:?}, \
+        Or maybe you just forgot to add the DNS rules in this flow config"
+        );
+    }
+    request_handler.map(|handler| (mark, handler))
+}
+
+/// 被限速的请求返回 false, 需要应答时直接发送应答
+pub(crate) fn check_rate_limit(
+    policy: &DnsPolicyManager,
+    flow_id: u32,
+    addr: SocketAddr,
+    message: &[u8],
+    send_msg_tx: &mpsc::Sender<SendDnsMessage>,
+) -> bool {
+    let ip = landscape_common::utils::ip::extract_real_ip(addr);
+    match policy.check_rate(flow_id, ip, message) {
+        RateLimitResult::Allow => true,
+        RateLimitResult::Drop => false,
+        RateLimitResult::Respond(message) => {
+            if let Err(e) = send_msg_tx.try_send(SendDnsMessage { message, addr }) {
+                tracing::debug!("send rate limited response error: {e:?}");
+            }
+            false
+        }
+    }
+}
+
+/// 处理基于流的 DNS 连接 (TCP), 每个消息前有 2 字节的长度
+pub(crate) async fn handle_stream<S, T>(
+    stream: S,
+    src_addr: SocketAddr,
+    tos: u8,
+    protocol: Protocol,
+    handlers: Arc<RwLock<HashMap<u32, T>>>,
+    dispatch_rules: Arc<RwLock<HashMap<PacketMatchMark, u32>>>,
+    policy: DnsPolicyManager,
+    shutdown: CancellationToken,
+) where
+    S: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+    T: RequestHandler + Clone,
+{
+    let Some((flow_id, request_handler)) =
+        find_request_handler(&handlers, &dispatch_rules, src_addr, tos).await
+    else {
+        return;
+    };
+
+    let (mut reader, mut writer) = tokio::io::split(stream);
+    let (send_msg_tx, mut send_msg_rc) = mpsc::channel::<SendDnsMessage>(32);
+
+    let write_task = tokio::spawn(async move {
+        while let Some(SendDnsMessage { message, .. }) = send_msg_rc.recv().await {
+            let len = (message.len() as u16).to_be_bytes();
+            if let Err(e) = writer.write_all(&len).await {
+                tracing::debug!("tcp write error: {e:?}");
+                break;
+            }
+            if let Err(e) = writer.write_all(&message).await {
+                tracing::debug!("tcp write error: {e:?}");
+                break;
+            }
+            if let Err(e) = writer.flush().await {
+                tracing::debug!("tcp flush error: {e:?}");
+                break;
+            }
+        }
+        let _ = writer.shutdown().await;
+    });
+
+    let mut inner_join_set = JoinSet::new();
+    loop {
+        let mut len_buf = [0_u8; 2];
+        let read_len = tokio::select! {
+            result = tokio::time::timeout(TCP_IDLE_TIMEOUT, reader.read_exact(&mut len_buf)) => result,
+            _ = shutdown.cancelled() => {
+                break;
+            },
+        };
+        if !matches!(read_len, Ok(Ok(_))) {
+            // 超时或者对端关闭
+            break;
+        }
+
+        let len = u16::from_be_bytes(len_buf) as usize;
+        let mut message = vec![0_u8; len];
+        match tokio::time::timeout(TCP_IDLE_TIMEOUT, reader.read_exact(&mut message)).await {
+            Ok(Ok(_)) => {}
+            Ok(Err(e)) => {
+                tracing::debug!("tcp read message error: {e:?}, src: {src_addr:?}");
+                break;
+            }
+            Err(_) => {
+                tracing::debug!("tcp read message timeout, src: {src_addr:?}");
+                break;
+            }
+        }
+
+        if !check_rate_limit(&policy, flow_id, src_addr, &message, &send_msg_tx) {
+            continue;
+        }
+        let request_handler = request_handler.clone();
+        let send_msg_tx_clone = send_msg_tx.clone();
+        inner_join_set.spawn(async move {
+            handle_raw_request(message, src_addr, protocol, request_handler, send_msg_tx_clone)
+                .await;
+        });
+
+        reap_tasks(&mut inner_join_set);
+    }
+
+    // 等待所有已经接收的请求响应完成后再关闭连接
+    while inner_join_set.join_next().await.is_some() {}
+    drop(send_msg_tx);
+    let _ = write_task.await;
+}
+
 fn reap_tasks(join_set: &mut JoinSet<()>) {
     use futures_util::FutureExt;
     while FutureExt::now_or_never(join_set.join_next()).flatten().is_some() {}
@@ -353,24 +659,24 @@ fn reap_tasks(join_set: &mut JoinSet<()>) {
 pub(crate) async fn handle_raw_request<T: RequestHandler>(
     message: Vec<u8>,
     dst_addr: SocketAddr,
+    protocol: Protocol,
     request_handler: T,
     response_stream: mpsc::Sender<SendDnsMessage>,
 ) {
-    let response_handler = LandscapeResponse::new(dst_addr, response_stream);
+    let response_handler = LandscapeResponse::new(dst_addr, protocol, response_stream);
 
-    handle_request(&message, dst_addr, request_handler, response_handler).await;
+    handle_request(&message, dst_addr, protocol, request_handler, response_handler).await;
 }
 
 pub(crate) async fn handle_request<R: ResponseHandler, T: RequestHandler>(
     // TODO: allow Message here...
     message_bytes: &[u8],
     src_addr: SocketAddr,
+    protocol: Protocol,
     request_handler: T,
     response_handler: R,
 ) {
     let mut decoder = BinDecoder::new(message_bytes);
-
-    let protocol = hickory_proto::xfer::Protocol::Udp;
     // method to handle the request
     let inner_handle_request = |message: MessageRequest, response_handler: R| async move {
         if message.message_type() == MessageType::Response {
@@ -420,7 +726,7 @@ pub(crate) async fn handle_request<R: ResponseHandler, T: RequestHandler>(
     };
 
     // method to return an error to the client
-    let error_response_handler = |protocol: hickory_proto::xfer::Protocol,
+    let error_response_handler = |protocol: Protocol,
                                   src_addr: SocketAddr,
                                   header: Header,
                                   _query: LowerQuery,
@@ -490,3 +796,137 @@ pub(crate) async fn handle_request<R: ResponseHandler, T: RequestHandler>(
         ),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::net::{Ipv4Addr, SocketAddr};
+    use std::str::FromStr;
+    use std::sync::Arc;
+
+    use hickory_proto::op::{Header, Message, Query, ResponseCode};
+    use hickory_proto::rr::{rdata::A, Name, RData, Record, RecordType};
+    use hickory_proto::xfer::Protocol;
+    use hickory_server::authority::MessageResponseBuilder;
+    use hickory_server::server::{Request, RequestHandler, ResponseHandler, ResponseInfo};
+    use landscape_common::metric::dns::DnsMetricManager;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::sync::{mpsc, RwLock};
+    use tokio_util::sync::CancellationToken;
+
+    use super::{handle_raw_request, handle_stream};
+    use crate::policy::DnsPolicyManager;
+    use crate::socket::SendDnsMessage;
+
+    /// 对每个查询返回固定数量的 A 记录
+    #[derive(Clone)]
+    struct FixedAnswerHandler {
+        count: u8,
+    }
+
+    #[async_trait::async_trait]
+    impl RequestHandler for FixedAnswerHandler {
+        async fn handle_request<R: ResponseHandler>(
+            &self,
+            request: &Request,
+            mut response_handle: R,
+        ) -> ResponseInfo {
+            let name: Name = request.queries()[0].name().clone().into();
+            let records: Vec<Record> = (0..self.count)
+                .map(|i| {
+                    Record::from_rdata(name.clone(), 60, RData::A(A(Ipv4Addr::new(10, 0, 0, i))))
+                })
+                .collect();
+            let mut header = Header::response_from_request(request.header());
+            header.set_response_code(ResponseCode::NoError);
+            let response = MessageResponseBuilder::from_message_request(request).build(
+                header,
+                records.iter(),
+                vec![].into_iter(),
+                vec![].into_iter(),
+                vec![].into_iter(),
+            );
+            response_handle.send_response(response).await.unwrap()
+        }
+    }
+
+    fn query_message(id: u16) -> Vec<u8> {
+        let mut message = Message::new();
+        message
+            .set_id(id)
+            .set_recursion_desired(true)
+            .add_query(Query::query(Name::from_str("example.com.").unwrap(), RecordType::A));
+        message.to_vec().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_tcp_length_prefix_framing() {
+        let handlers = Arc::new(RwLock::new(HashMap::from([(0, FixedAnswerHandler { count: 1 })])));
+        let dispatch_rules = Arc::new(RwLock::new(HashMap::new()));
+        let policy = DnsPolicyManager::new(DnsMetricManager::new().await);
+        let src_addr: SocketAddr = "192.168.1.10:50000".parse().unwrap();
+
+        let (mut client, server) = tokio::io::duplex(4096);
+        let server_task = tokio::spawn(handle_stream(
+            server,
+            src_addr,
+            0,
+            Protocol::Tcp,
+            handlers,
+            dispatch_rules,
+            policy,
+            CancellationToken::new(),
+        ));
+
+        // 两个请求放在同一次写入中, 第二个请求的长度字段被拆开发送
+        let first = query_message(1);
+        let second = query_message(2);
+        let mut data = (first.len() as u16).to_be_bytes().to_vec();
+        data.extend_from_slice(&first);
+        data.push((second.len() >> 8) as u8);
+        client.write_all(&data).await.unwrap();
+        client.flush().await.unwrap();
+        client.write_all(&[(second.len() & 0xff) as u8]).await.unwrap();
+        client.write_all(&second).await.unwrap();
+
+        let mut ids = vec![];
+        for _ in 0..2 {
+            let mut len_buf = [0_u8; 2];
+            client.read_exact(&mut len_buf).await.unwrap();
+            let mut message = vec![0_u8; u16::from_be_bytes(len_buf) as usize];
+            client.read_exact(&mut message).await.unwrap();
+            let response = Message::from_vec(&message).unwrap();
+            assert_eq!(response.answers().len(), 1);
+            ids.push(response.id());
+        }
+        ids.sort();
+        assert_eq!(ids, vec![1, 2]);
+
+        // 客户端关闭后服务端结束该连接
+        drop(client);
+        server_task.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_udp_response_truncated() {
+        let src_addr: SocketAddr = "192.168.1.10:50000".parse().unwrap();
+        // 40 条 A 记录超过无 EDNS 时 UDP 的 512 字节限制
+        let handler = FixedAnswerHandler { count: 40 };
+
+        let (tx, mut rx) = mpsc::channel::<SendDnsMessage>(4);
+        handle_raw_request(query_message(1), src_addr, Protocol::Udp, handler.clone(), tx).await;
+        let SendDnsMessage { message, .. } = rx.recv().await.unwrap();
+        assert!(message.len() <= 512);
+        let response = Message::from_vec(&message).unwrap();
+        assert!(response.truncated());
+        assert!(response.answers().len() < 40);
+
+        // TCP 不受 UDP 长度限制
+        let (tx, mut rx) = mpsc::channel::<SendDnsMessage>(4);
+        handle_raw_request(query_message(2), src_addr, Protocol::Tcp, handler, tx).await;
+        let SendDnsMessage { message, .. } = rx.recv().await.unwrap();
+        let response = Message::from_vec(&message).unwrap();
+        assert!(!response.truncated());
+        assert_eq!(response.answers().len(), 40);
+    }
+}
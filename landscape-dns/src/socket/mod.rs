@@ -31,6 +31,25 @@ pub fn set_socket_rcvmark(fd: i32) -> std::io::Result<()> {
     }
 }
 
+/// 仅接收指定网卡上的数据包 (SO_BINDTODEVICE)
+pub fn set_socket_bind_device(fd: i32, iface_name: &str) -> std::io::Result<()> {
+    let name = iface_name.as_bytes();
+    unsafe {
+        let res = libc::setsockopt(
+            fd,
+            libc::SOL_SOCKET,
+            libc::SO_BINDTODEVICE,
+            name.as_ptr() as *const libc::c_void,
+            name.len() as libc::socklen_t,
+        );
+        if res == -1 {
+            return Err(std::io::Error::last_os_error());
+        } else {
+            Ok(())
+        }
+    }
+}
+
 pub fn get_socket_rcvmark(fd: i32) -> std::io::Result<bool> {
     let mut mark: u32 = 1;
     let mut mark_size = std::mem::size_of::<u32>() as libc::socklen_t;
@@ -49,3 +68,68 @@ pub fn get_socket_rcvmark(fd: i32) -> std::io::Result<bool> {
         Err(std::io::Error::last_os_error())
     }
 }
+
+/// IPv6 的 TCP 连接通过 IPV6_2292PKTOPTIONS 读取缓存的辅助数据, libc 中未导出该常量
+const IPV6_2292PKTOPTIONS: libc::c_int = 6;
+
+/// 接收 IPv6 数据包的 Traffic Class (IPV6_RECVTCLASS)
+pub fn set_socket_recv_tclass(fd: i32) -> std::io::Result<()> {
+    let enable: libc::c_int = 1;
+    unsafe {
+        let res = libc::setsockopt(
+            fd,
+            libc::SOL_IPV6,
+            libc::IPV6_RECVTCLASS,
+            &enable as *const _ as *const libc::c_void,
+            std::mem::size_of_val(&enable) as libc::socklen_t,
+        );
+        if res == -1 {
+            return Err(std::io::Error::last_os_error());
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// 获取 TCP 连接收到的 TOS
+/// 需要监听的 socket 设置了 IP_RECVTOS / IPV6_RECVTCLASS, 接受的连接会继承该设置
+pub fn get_tcp_recv_tos(fd: i32, is_ipv6: bool) -> std::io::Result<u8> {
+    let (level, optname) = if is_ipv6 {
+        (libc::SOL_IPV6, IPV6_2292PKTOPTIONS)
+    } else {
+        (libc::SOL_IP, libc::IP_PKTOPTIONS)
+    };
+    let mut control_buf = [0_u8; 64];
+    let mut control_len = control_buf.len() as libc::socklen_t;
+    let ret = unsafe {
+        libc::getsockopt(
+            fd,
+            level,
+            optname,
+            control_buf.as_mut_ptr() as *mut libc::c_void,
+            &mut control_len,
+        )
+    };
+    if ret < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    let mut msg: libc::msghdr = unsafe { std::mem::zeroed() };
+    msg.msg_control = control_buf.as_mut_ptr() as *mut libc::c_void;
+    msg.msg_controllen = control_len as _;
+
+    let mut tos = 0;
+    let mut cmsg = unsafe { libc::CMSG_FIRSTHDR(&msg) };
+    while !cmsg.is_null() {
+        let cmsg_ref = unsafe { &*cmsg };
+        let is_tos = (cmsg_ref.cmsg_level == libc::SOL_IP && cmsg_ref.cmsg_type == libc::IP_TOS)
+            || (cmsg_ref.cmsg_level == libc::SOL_IPV6 && cmsg_ref.cmsg_type == libc::IPV6_TCLASS);
+        if is_tos {
+            // PKTOPTIONS 中的 TOS / Traffic Class 是以 int 类型写入的
+            let tos_ptr = unsafe { libc::CMSG_DATA(cmsg) } as *const libc::c_int;
+            tos = unsafe { std::ptr::read_unaligned(tos_ptr) } as u8;
+        }
+        cmsg = unsafe { libc::CMSG_NXTHDR(&msg, cmsg) };
+    }
+    Ok(tos)
+}
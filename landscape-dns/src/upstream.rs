@@ -0,0 +1,172 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        atomic::{AtomicU32, AtomicU64, Ordering},
+        Arc, Mutex, RwLock,
+    },
+    time::{Duration, Instant},
+};
+
+use landscape_common::config::dns::DnsUpstreamServer;
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+use uuid::Uuid;
+
+/// 连续失败多少次之后认为上游不可用
+const UNHEALTHY_FAILURES: u32 = 3;
+/// 不可用的上游在这段时间之后重新参与查询
+const UNHEALTHY_COOLDOWN: Duration = Duration::from_secs(30);
+/// 主动探测不可用上游的间隔
+pub const UPSTREAM_PROBE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// 上游服务器的健康状态, 根据实际的查询结果更新
+#[derive(Debug, Default)]
+pub struct UpstreamHealth {
+    queries: AtomicU64,
+    errors: AtomicU64,
+    consecutive_failures: AtomicU32,
+    /// 平均延迟 (EWMA), 单位微秒
+    latency_us: AtomicU64,
+    last_failure: Mutex<Option<Instant>>,
+}
+
+impl UpstreamHealth {
+    pub fn record_success(&self, latency: Duration) {
+        self.queries.fetch_add(1, Ordering::Relaxed);
+        self.consecutive_failures.store(0, Ordering::Relaxed);
+
+        let sample = latency.as_micros() as u64;
+        let old = self.latency_us.load(Ordering::Relaxed);
+        let new = if old == 0 { sample } else { (old * 7 + sample) / 8 };
+        self.latency_us.store(new, Ordering::Relaxed);
+    }
+
+    pub fn record_failure(&self) {
+        self.queries.fetch_add(1, Ordering::Relaxed);
+        self.errors.fetch_add(1, Ordering::Relaxed);
+        self.consecutive_failures.fetch_add(1, Ordering::Relaxed);
+        *self.last_failure.lock().unwrap() = Some(Instant::now());
+    }
+
+    /// 连续失败达到阈值, 需要主动探测以便恢复
+    pub fn is_down(&self) -> bool {
+        self.consecutive_failures.load(Ordering::Relaxed) >= UNHEALTHY_FAILURES
+    }
+
+    pub fn is_healthy(&self) -> bool {
+        if self.consecutive_failures.load(Ordering::Relaxed) < UNHEALTHY_FAILURES {
+            return true;
+        }
+        // 冷却结束后重新尝试, 成功一次即恢复
+        match *self.last_failure.lock().unwrap() {
+            Some(time) => time.elapsed() > UNHEALTHY_COOLDOWN,
+            None => true,
+        }
+    }
+}
+
+/// 上游服务器的健康统计
+#[derive(Serialize, Deserialize, Debug, Clone, TS)]
+#[ts(export, export_to = "dns.d.ts")]
+pub struct DnsUpstreamHealth {
+    pub group_id: Uuid,
+    /// 例如 `tls://one.one.one.one@1.1.1.1:853`
+    pub server: String,
+    #[ts(type = "number")]
+    pub queries: u64,
+    #[ts(type = "number")]
+    pub errors: u64,
+    pub avg_latency_ms: f64,
+    pub healthy: bool,
+}
+
+/// 所有上游组共享的健康状态, 规则刷新后保留
+#[derive(Debug, Clone, Default)]
+pub struct UpstreamHealthRegistry {
+    inner: Arc<RwLock<HashMap<(Uuid, String), Arc<UpstreamHealth>>>>,
+}
+
+impl UpstreamHealthRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get_or_create(&self, group_id: Uuid, server: &DnsUpstreamServer) -> Arc<UpstreamHealth> {
+        let key = (group_id, server.to_string());
+        if let Some(health) = self.inner.read().unwrap().get(&key) {
+            return health.clone();
+        }
+        self.inner.write().unwrap().entry(key).or_default().clone()
+    }
+
+    /// 规则刷新后移除不再被引用的上游组以及服务器
+    pub fn retain(&self, keys: &HashSet<(Uuid, String)>) {
+        self.inner.write().unwrap().retain(|key, _| keys.contains(key));
+    }
+
+    pub fn snapshot(&self) -> Vec<DnsUpstreamHealth> {
+        let mut result: Vec<DnsUpstreamHealth> = self
+            .inner
+            .read()
+            .unwrap()
+            .iter()
+            .map(|((group_id, server), health)| DnsUpstreamHealth {
+                group_id: *group_id,
+                server: server.clone(),
+                queries: health.queries.load(Ordering::Relaxed),
+                errors: health.errors.load(Ordering::Relaxed),
+                avg_latency_ms: health.latency_us.load(Ordering::Relaxed) as f64 / 1000.0,
+                healthy: health.is_healthy(),
+            })
+            .collect();
+        result.sort_by(|a, b| a.group_id.cmp(&b.group_id).then_with(|| a.server.cmp(&b.server)));
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, time::Duration};
+
+    use landscape_common::config::dns::{DnsUpstreamServer, DnsUpstreamType};
+    use uuid::Uuid;
+
+    use super::{UpstreamHealth, UpstreamHealthRegistry};
+
+    #[test]
+    fn test_unhealthy_after_consecutive_failures() {
+        let health = UpstreamHealth::default();
+        health.record_failure();
+        health.record_failure();
+        assert!(health.is_healthy());
+        health.record_failure();
+        assert!(!health.is_healthy());
+        assert!(health.is_down());
+        health.record_success(Duration::from_millis(20));
+        assert!(health.is_healthy());
+        assert!(!health.is_down());
+        assert_eq!(health.latency_us.load(std::sync::atomic::Ordering::Relaxed), 20_000);
+    }
+
+    #[test]
+    fn test_retain_upstream_health() {
+        let registry = UpstreamHealthRegistry::new();
+        let group_id = Uuid::new_v4();
+        let server = |ip: &str| DnsUpstreamServer {
+            upstream: DnsUpstreamType::Plaintext,
+            ip: ip.parse().unwrap(),
+            port: None,
+        };
+        let kept = server("1.1.1.1");
+        let removed = server("8.8.8.8");
+        registry.get_or_create(group_id, &kept);
+        registry.get_or_create(group_id, &removed);
+        assert_eq!(registry.snapshot().len(), 2);
+
+        // 组内被删除的服务器同样移除
+        registry.retain(&HashSet::from([(group_id, kept.to_string())]));
+        let snapshot = registry.snapshot();
+        assert_eq!(snapshot.len(), 1);
+        assert_eq!(snapshot[0].server, kept.to_string());
+    }
+}
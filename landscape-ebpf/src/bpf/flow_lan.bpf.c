@@ -7,6 +7,7 @@
 
 #include "landscape.h"
 #include "flow_lan_share.h"
+#include "nat_hairpin.h"
 
 char LICENSE[] SEC("license") = "Dual BSD/GPL";
 
@@ -472,6 +473,246 @@ static __always_inline int pick_wan_and_send_by_flow_id(struct __sk_buff *skb,
 #undef BPF_LOG_TOPIC
 }
 
+/// NAT Hairpin Start
+static __always_inline int hairpin_rewrite(struct __sk_buff *skb, int l3_off, int l4_off,
+                                           u8 l4proto, bool is_modify_source, __be32 from_addr,
+                                           __be16 from_port, __be32 to_addr, __be16 to_port) {
+    int ret;
+    int ip_offset =
+        is_modify_source ? offsetof(struct iphdr, saddr) : offsetof(struct iphdr, daddr);
+    ret = bpf_skb_store_bytes(skb, l3_off + ip_offset, &to_addr, sizeof(to_addr), 0);
+    if (ret) {
+        return ret;
+    }
+    ret = bpf_l3_csum_replace(skb, l3_off + offsetof(struct iphdr, check), from_addr, to_addr, 4);
+    if (ret) {
+        return ret;
+    }
+
+    // TCP 与 UDP 的端口位置相同
+    int port_off =
+        is_modify_source ? offsetof(struct tcphdr, source) : offsetof(struct tcphdr, dest);
+    int check_off = l4proto == IPPROTO_TCP ? offsetof(struct tcphdr, check)
+                                           : offsetof(struct udphdr, check);
+    ret = bpf_write_port(skb, l4_off + port_off, to_port);
+    if (ret) {
+        return ret;
+    }
+    ipv4_update_csum(skb, l4_off + check_off, from_addr, from_port, to_addr, to_port, true,
+                     l4proto == IPPROTO_UDP);
+    return 0;
+}
+
+static __always_inline int hairpin_redirect(__be32 daddr) {
+#define BPF_LOG_TOPIC "hairpin_redirect"
+    struct lan_route_key lan_search_key = {0};
+    lan_search_key.prefixlen = 160;
+    lan_search_key.l3_protocol = LANDSCAPE_IPV4_TYPE;
+    lan_search_key.addr.in6_u.u6_addr32[0] = daddr;
+
+    struct lan_route_info *lan_info = bpf_map_lookup_elem(&rt_lan_map, &lan_search_key);
+    if (lan_info == NULL) {
+        return TC_ACT_SHOT;
+    }
+
+    // 源地址已经是本机地址, 不能交给内核转发, 即使是同一个网卡也需要直接发送
+    struct bpf_redir_neigh param = {0};
+    param.nh_family = AF_INET;
+    param.ipv4_nh = daddr;
+    int ret = bpf_redirect_neigh(lan_info->ifindex, &param, sizeof(param), 0);
+    if (ret != 7) {
+        bpf_log_info("bpf_redirect_neigh error: %d", ret);
+    }
+    return ret;
+#undef BPF_LOG_TOPIC
+}
+
+/// 重新选择端口时使用的范围
+#define HAIRPIN_PORT_START 1024
+#define HAIRPIN_PORT_END 65535
+
+struct hairpin_search_port_ctx {
+    struct nat_hairpin_ct_key ct_key;
+    struct nat_hairpin_ct_value ct_value;
+    u16 curr_port;
+    u16 remaining_size;
+    bool found;
+};
+
+static int hairpin_search_port_callback(u32 index, struct hairpin_search_port_ctx *ctx) {
+    ctx->ct_key.router_port = bpf_htons(ctx->curr_port);
+    // BPF_NOEXIST 保证同时建立的连接不会选到同一个端口
+    if (!bpf_map_update_elem(&nat_hairpin_ct, &ctx->ct_key, &ctx->ct_value, BPF_NOEXIST)) {
+        ctx->found = true;
+        return BPF_LOOP_RET_BREAK;
+    }
+
+    if (ctx->curr_port != HAIRPIN_PORT_END) {
+        ctx->curr_port++;
+    } else {
+        ctx->curr_port = HAIRPIN_PORT_START;
+    }
+    if (--ctx->remaining_size == 0) {
+        return BPF_LOOP_RET_BREAK;
+    }
+    return BPF_LOOP_RET_CONTINUE;
+}
+
+/// 查找或者分配客户端访问内部主机时使用的路由器端口
+static __always_inline int hairpin_lookup_or_new_port(struct nat_hairpin_client_key *client_key,
+                                                      struct nat_hairpin_ct_key *ct_key,
+                                                      struct nat_hairpin_ct_value *ct_value,
+                                                      __be16 *router_port) {
+#define BPF_LOG_TOPIC "hairpin_lookup_or_new_port"
+    struct nat_hairpin_client_value *client_value =
+        bpf_map_lookup_elem(&nat_hairpin_client, client_key);
+    // 优先使用已有的端口, 其次是客户端的源端口
+    ct_key->router_port = client_value ? client_value->router_port : client_key->client_port;
+
+    struct nat_hairpin_ct_value *exist = bpf_map_lookup_elem(&nat_hairpin_ct, ct_key);
+    if (exist && exist->client_addr == ct_value->client_addr &&
+        exist->client_port == ct_value->client_port) {
+        exist->wan_addr = ct_value->wan_addr;
+        exist->wan_port = ct_value->wan_port;
+        *router_port = ct_key->router_port;
+        return TC_ACT_OK;
+    }
+
+    struct hairpin_search_port_ctx ctx = {
+        .ct_key = *ct_key,
+        .ct_value = *ct_value,
+        .curr_port = bpf_ntohs(client_key->client_port),
+        .remaining_size = HAIRPIN_PORT_END - HAIRPIN_PORT_START,
+        .found = false,
+    };
+    if (ctx.curr_port < HAIRPIN_PORT_START) {
+        ctx.curr_port = HAIRPIN_PORT_START + ctx.curr_port;
+    }
+    if (bpf_loop(65536, hairpin_search_port_callback, &ctx, 0) < 0 || !ctx.found) {
+        bpf_log_info("no free hairpin port, client: %pI4", &ct_value->client_addr);
+        return TC_ACT_SHOT;
+    }
+
+    struct nat_hairpin_client_value new_client_value = {0};
+    new_client_value.router_port = ctx.ct_key.router_port;
+    if (bpf_map_update_elem(&nat_hairpin_client, client_key, &new_client_value, BPF_ANY)) {
+        bpf_map_delete_elem(&nat_hairpin_ct, &ctx.ct_key);
+        return TC_ACT_SHOT;
+    }
+    *router_port = ctx.ct_key.router_port;
+    return TC_ACT_OK;
+#undef BPF_LOG_TOPIC
+}
+
+/// LAN 客户端访问 WAN 地址上开启了回流的转发端口
+/// 同时修改源地址与目标地址, 使内部主机的回复经过路由器
+static __always_inline int nat_hairpin(struct __sk_buff *skb, int current_eth_net_offset,
+                                       struct route_context *context) {
+#define BPF_LOG_TOPIC "nat_hairpin"
+    if (current_eth_net_offset == 0 || context->l3_protocol != LANDSCAPE_IPV4_TYPE) {
+        return TC_ACT_OK;
+    }
+    if (context->l4_protocol != IPPROTO_TCP && context->l4_protocol != IPPROTO_UDP) {
+        return TC_ACT_OK;
+    }
+
+    struct iphdr iph;
+    if (bpf_skb_load_bytes(skb, current_eth_net_offset, &iph, sizeof(iph))) {
+        return TC_ACT_OK;
+    }
+    // 分片的数据包不处理
+    if (iph.frag_off & (IP_MF | IP_OFFSET)) {
+        return TC_ACT_OK;
+    }
+    int l4_off = current_eth_net_offset + (iph.ihl * 4);
+    __be16 ports[2];
+    if (bpf_skb_load_bytes(skb, l4_off, ports, sizeof(ports))) {
+        return TC_ACT_OK;
+    }
+    __be16 sport = ports[0];
+    __be16 dport = ports[1];
+
+    // 内部主机的回复
+    struct nat_hairpin_ct_key ct_key = {0};
+    ct_key.l4proto = iph.protocol;
+    ct_key.host_port = sport;
+    ct_key.router_port = dport;
+    ct_key.host_addr = iph.saddr;
+    ct_key.router_addr = iph.daddr;
+    struct nat_hairpin_ct_value *ct_value = bpf_map_lookup_elem(&nat_hairpin_ct, &ct_key);
+    if (ct_value != NULL) {
+        __be32 client_addr = ct_value->client_addr;
+        __be16 client_port = ct_value->client_port;
+        if (hairpin_rewrite(skb, current_eth_net_offset, l4_off, iph.protocol, true, iph.saddr,
+                            sport, ct_value->wan_addr, ct_value->wan_port)) {
+            return TC_ACT_SHOT;
+        }
+        if (hairpin_rewrite(skb, current_eth_net_offset, l4_off, iph.protocol, false, iph.daddr,
+                            dport, client_addr, client_port)) {
+            return TC_ACT_SHOT;
+        }
+        return hairpin_redirect(client_addr);
+    }
+
+    // 客户端访问转发端口
+    struct nat_hairpin_key key = {0};
+    key.l4proto = iph.protocol;
+    key.port = dport;
+    key.addr = iph.daddr;
+    struct nat_hairpin_value *value = bpf_map_lookup_elem(&nat_hairpin_mappings, &key);
+    if (value == NULL) {
+        return TC_ACT_OK;
+    }
+    __be32 host_addr = value->addr;
+    __be16 host_port = value->port;
+
+    struct lan_route_key lan_search_key = {0};
+    lan_search_key.prefixlen = 160;
+    lan_search_key.l3_protocol = LANDSCAPE_IPV4_TYPE;
+    lan_search_key.addr.in6_u.u6_addr32[0] = host_addr;
+    struct lan_route_info *lan_info = bpf_map_lookup_elem(&rt_lan_map, &lan_search_key);
+    if (lan_info == NULL) {
+        // 内部主机不在 LAN 中, 按照原有流程处理
+        return TC_ACT_OK;
+    }
+    __be32 router_addr = lan_info->addr.in6_u.u6_addr32[0];
+
+    ct_key.host_port = host_port;
+    ct_key.host_addr = host_addr;
+    ct_key.router_addr = router_addr;
+
+    struct nat_hairpin_ct_value new_ct_value = {0};
+    new_ct_value.client_addr = iph.saddr;
+    new_ct_value.client_port = sport;
+    new_ct_value.wan_addr = iph.daddr;
+    new_ct_value.wan_port = dport;
+
+    struct nat_hairpin_client_key client_key = {0};
+    client_key.l4proto = iph.protocol;
+    client_key.client_port = sport;
+    client_key.host_port = host_port;
+    client_key.client_addr = iph.saddr;
+    client_key.host_addr = host_addr;
+
+    // 不同客户端使用相同的源端口访问同一个内部主机时, 为后来的客户端另外选择端口
+    __be16 router_port;
+    if (hairpin_lookup_or_new_port(&client_key, &ct_key, &new_ct_value, &router_port)) {
+        return TC_ACT_SHOT;
+    }
+
+    if (hairpin_rewrite(skb, current_eth_net_offset, l4_off, iph.protocol, false, iph.daddr, dport,
+                        host_addr, host_port)) {
+        return TC_ACT_SHOT;
+    }
+    if (hairpin_rewrite(skb, current_eth_net_offset, l4_off, iph.protocol, true, iph.saddr, sport,
+                        router_addr, router_port)) {
+        return TC_ACT_SHOT;
+    }
+    return hairpin_redirect(host_addr);
+#undef BPF_LOG_TOPIC
+}
+/// NAT Hairpin End
+
 // ================================
 // LAN Route Egress
 // ================================
@@ -535,6 +776,11 @@ int lan_route_ingress(struct __sk_buff *skb) {
         bpf_log_info("cache ip: %pI6 mac error", saddr.ip);
     }
 
+    ret = nat_hairpin(skb, current_eth_net_offset, &context);
+    if (ret != TC_ACT_OK) {
+        return ret;
+    }
+
     ret = lan_redirect_check(skb, current_eth_net_offset, &context);
     if (ret != TC_ACT_OK) {
         return ret;
@@ -8,6 +8,9 @@
 #include "landscape.h"
 #include "share_ifindex_ip.h"
 #include "nat.h"
+#include "nat6.h"
+#include "nat_ct_config.h"
+#include "snat_pool.h"
 
 char LICENSE[] SEC("license") = "Dual BSD/GPL";
 const volatile u8 LOG_LEVEL = BPF_LOG_LEVEL_DEBUG;
@@ -21,11 +24,47 @@ const volatile u8 LOG_LEVEL = BPF_LOG_LEVEL_DEBUG;
 
 const volatile int current_eth_net_offset = 14;
 
-const volatile u64 TCP_SYN_TIMEOUT = 1E9 * 6;
 const volatile u64 TCP_TCP_TRANS = 1E9 * 60 * 4;
-const volatile u64 TCP_TIMEOUT = 1E9 * 60 * 10;
 
-const volatile u64 UDP_TIMEOUT = 1E9 * 60 * 5;
+// nat_ct_config_map 中没有当前网卡的配置时使用
+struct nat_ct_config default_ct_config = {
+    .tcp_syn_timeout = 1E9 * 6,
+    .tcp_established_timeout = 1E9 * 60 * 10,
+    .tcp_closing_timeout = 1E9 * 60,
+    .udp_timeout = 1E9 * 60 * 5,
+    .icmp_timeout = 1E9 * 60 * 5,
+    .max_mappings_per_host = 0,
+    .mapping_policy = NAT_EP_ADDR_PORT_DEPENDENT,
+    .filtering_policy = NAT_EP_INDEPENDENT,
+};
+
+static __always_inline const struct nat_ct_config *get_ct_config(struct __sk_buff *skb) {
+    u32 ifindex = skb->ifindex;
+    struct nat_ct_config *config = bpf_map_lookup_elem(&nat_ct_config_map, &ifindex);
+    if (config == NULL) {
+        return &default_ct_config;
+    }
+    return config;
+}
+
+/// 获取当前数据包的映射与过滤行为, Flow 配置优先
+/// 标记模块允许端口复用的数据包总是使用 Endpoint-Independent 映射
+static __always_inline void get_nat_behavior(struct __sk_buff *skb,
+                                             const struct nat_ct_config *ct_config,
+                                             u8 *mapping_policy, u8 *filtering_policy) {
+    u32 flow_id = get_flow_id(skb->mark);
+    struct nat_behavior *behavior = bpf_map_lookup_elem(&nat_flow_behavior_map, &flow_id);
+    if (behavior) {
+        *mapping_policy = behavior->mapping_policy;
+        *filtering_policy = behavior->filtering_policy;
+    } else {
+        *mapping_policy = ct_config->mapping_policy;
+        *filtering_policy = ct_config->filtering_policy;
+    }
+    if (get_flow_action(skb->mark) == FLOW_ALLOW_REUSE) {
+        *mapping_policy = NAT_EP_INDEPENDENT;
+    }
+}
 
 static __always_inline int icmp_msg_type(struct icmphdr *icmph);
 static __always_inline bool is_icmp_error_pkt(const struct ip_packet_info *pkt) {
@@ -53,6 +92,189 @@ struct {
     __uint(map_flags, BPF_F_NO_PREALLOC);
 } map_mapping_timer SEC(".maps");
 
+/// 每个内网主机当前的动态映射数量
+struct {
+    __uint(type, BPF_MAP_TYPE_HASH);
+    __type(key, __be32);
+    __type(value, u32);
+    __uint(max_entries, 1024 * 64);
+} nat_host_mapping_count SEC(".maps");
+
+static __always_inline void host_mapping_count_inc(__be32 addr) {
+    u32 *count = bpf_map_lookup_elem(&nat_host_mapping_count, &addr);
+    if (count) {
+        __sync_fetch_and_add(count, 1);
+        return;
+    }
+    u32 init_count = 1;
+    if (bpf_map_update_elem(&nat_host_mapping_count, &addr, &init_count, BPF_NOEXIST)) {
+        // 被其他 CPU 抢先创建
+        count = bpf_map_lookup_elem(&nat_host_mapping_count, &addr);
+        if (count) {
+            __sync_fetch_and_add(count, 1);
+        }
+    }
+}
+
+static __always_inline void host_mapping_count_dec(__be32 addr) {
+    u32 *count = bpf_map_lookup_elem(&nat_host_mapping_count, &addr);
+    if (count && __sync_fetch_and_sub(count, 1) <= 1) {
+        bpf_map_delete_elem(&nat_host_mapping_count, &addr);
+    }
+}
+
+/// 过滤行为不是 Endpoint-Independent 时, 记录内网主机通过映射访问过的外部端点
+struct nat_filter_key {
+    u8 l4proto;
+    u8 _pad;
+    // 映射后的端口
+    __be16 wan_port;
+    // Address-Dependent 时为 0
+    __be16 remote_port;
+    u8 _pad2[2];
+    union u_inet_addr wan_addr;
+    union u_inet_addr remote_addr;
+};
+
+struct {
+    __uint(type, BPF_MAP_TYPE_LRU_HASH);
+    __type(key, struct nat_filter_key);
+    // 最后一次发出数据包的时间
+    __type(value, u64);
+    __uint(max_entries, NAT_MAPPING_CACHE_SIZE);
+} nat_filter_permits SEC(".maps");
+
+static __always_inline u64 ct_proto_timeout(u8 l4proto, const struct nat_ct_config *ct_config) {
+    if (l4proto == IPPROTO_TCP) {
+        return ct_config->tcp_established_timeout;
+    } else if (l4proto == IPPROTO_ICMP) {
+        return ct_config->icmp_timeout;
+    }
+    return ct_config->udp_timeout;
+}
+
+static __always_inline void nat_filter_key_init(struct nat_filter_key *key, u8 l4proto,
+                                                u8 filtering, const union u_inet_addr *wan_addr,
+                                                __be16 wan_port,
+                                                const union u_inet_addr *remote_addr,
+                                                __be16 remote_port) {
+    key->l4proto = l4proto;
+    key->wan_port = wan_port;
+    // ICMP 的端口是查询 ID, 只按地址过滤
+    if (filtering == NAT_EP_ADDR_PORT_DEPENDENT && l4proto != IPPROTO_ICMP) {
+        key->remote_port = remote_port;
+    }
+    COPY_ADDR_FROM(key->wan_addr.all, wan_addr->all);
+    COPY_ADDR_FROM(key->remote_addr.all, remote_addr->all);
+}
+
+/// 发出数据包时刷新外部端点的访问许可
+static __always_inline void nat_filter_permit(u8 l4proto,
+                                              const struct nat_mapping_value *nat_egress_value,
+                                              const struct inet_pair *pkt_ip_pair) {
+    if (nat_egress_value->filtering == NAT_EP_INDEPENDENT) {
+        return;
+    }
+    struct nat_filter_key key = {0};
+    nat_filter_key_init(&key, l4proto, nat_egress_value->filtering, &nat_egress_value->addr,
+                        nat_egress_value->port, &pkt_ip_pair->dst_addr, pkt_ip_pair->dst_port);
+    u64 now = bpf_ktime_get_ns();
+    bpf_map_update_elem(&nat_filter_permits, &key, &now, BPF_ANY);
+}
+
+/// 接收数据包时检查外部端点是否被允许访问该映射
+static __always_inline bool nat_filter_allow(u8 l4proto,
+                                             const struct nat_mapping_value *nat_egress_value,
+                                             const struct inet_pair *pkt_ip_pair,
+                                             const struct nat_ct_config *ct_config) {
+    if (nat_egress_value->filtering == NAT_EP_INDEPENDENT) {
+        return true;
+    }
+    struct nat_filter_key key = {0};
+    nat_filter_key_init(&key, l4proto, nat_egress_value->filtering, &pkt_ip_pair->dst_addr,
+                        pkt_ip_pair->dst_port, &pkt_ip_pair->src_addr, pkt_ip_pair->src_port);
+    u64 *active_time = bpf_map_lookup_elem(&nat_filter_permits, &key);
+    if (!active_time) {
+        return false;
+    }
+    return bpf_ktime_get_ns() - *active_time <= ct_proto_timeout(l4proto, ct_config);
+}
+
+/// 按映射行为在出方向映射的 key 中填入目的端点
+/// 不同的目的端点因此会使用不同的映射以及不同的外部端口
+static __always_inline void nat_mapping_key_set_remote(struct nat_mapping_key *key,
+                                                       u8 mapping_policy,
+                                                       const union u_inet_addr *remote_addr,
+                                                       __be16 remote_port) {
+    if (mapping_policy == NAT_EP_INDEPENDENT) {
+        return;
+    }
+    COPY_ADDR_FROM(key->remote_addr.all, remote_addr->all);
+    if (mapping_policy == NAT_EP_ADDR_PORT_DEPENDENT) {
+        key->remote_port = remote_port;
+    }
+}
+
+/// 为新的动态映射选择 SNAT 地址
+/// 优先级: flow 指定 > 网段指定 > 地址池 > WAN 网卡地址
+static __always_inline int select_snat_addr(struct __sk_buff *skb, __be32 src_addr,
+                                            __be32 *snat_addr) {
+    u32 ifindex = skb->ifindex;
+    __be32 *addr;
+
+    // 路由器自身发出的数据包使用 WAN 网卡地址
+    if (skb->ingress_ifindex != 0) {
+        struct snat_flow_key flow_key = {
+            .ifindex = ifindex,
+            .flow_id = get_flow_id(skb->mark),
+        };
+        addr = bpf_map_lookup_elem(&snat_flow_addr_map, &flow_key);
+        if (addr) {
+            *snat_addr = *addr;
+            return 0;
+        }
+
+        struct snat_subnet_key subnet_key = {
+            .prefixlen = 64,
+            .ifindex = ifindex,
+            .addr = src_addr,
+        };
+        addr = bpf_map_lookup_elem(&snat_subnet_addr_map, &subnet_key);
+        if (addr) {
+            *snat_addr = *addr;
+            return 0;
+        }
+
+        struct snat_pool *pool = bpf_map_lookup_elem(&snat_pool_map, &ifindex);
+        if (pool && pool->count > 0) {
+            u32 count = pool->count;
+            if (count > SNAT_POOL_MAX_ADDRS) {
+                count = SNAT_POOL_MAX_ADDRS;
+            }
+            u32 index;
+            if (pool->policy == SNAT_POOL_ROUND_ROBIN) {
+                index = __sync_fetch_and_add(&pool->next, 1);
+            } else {
+                // Knuth 乘法哈希
+                index = bpf_ntohl(src_addr) * 2654435761U;
+                index ^= index >> 16;
+            }
+            index %= count;
+            if (index < SNAT_POOL_MAX_ADDRS) {
+                *snat_addr = pool->addrs[index];
+                return 0;
+            }
+        }
+    }
+
+    addr = bpf_map_lookup_elem(&wan_ipv4_binding, &ifindex);
+    if (!addr) {
+        return -1;
+    }
+    *snat_addr = *addr;
+    return 0;
+}
+
 #define FRAG_CACHE_SIZE 1024 * 32
 struct {
     __uint(type, BPF_MAP_TYPE_LRU_HASH);
@@ -75,17 +297,6 @@ static __always_inline int icmpx_err_l3_offset(int l4_off) {
     return l4_off + sizeof(struct icmphdr);
 }
 
-static __always_inline void ipv4_update_csum(struct __sk_buff *skb, u32 l4_csum_off,
-                                             __be32 from_addr, __be16 from_port, __be32 to_addr,
-                                             __be16 to_port, bool l4_pseudo, bool l4_mangled_0) {
-    bpf_l4_csum_replace(skb, l4_csum_off, from_port, to_port,
-                        2 | (l4_mangled_0 ? BPF_F_MARK_MANGLED_0 : 0));
-    if (l4_pseudo) {
-        bpf_l4_csum_replace(skb, l4_csum_off, from_addr, to_addr,
-                            4 | BPF_F_PSEUDO_HDR | (l4_mangled_0 ? BPF_F_MARK_MANGLED_0 : 0));
-    }
-}
-
 static __always_inline void ipv4_update_csum_inner(struct __sk_buff *skb, u32 l4_csum_off,
                                                    __be32 from_addr, __be16 from_port,
                                                    __be32 to_addr, __be16 to_port, bool l4_pseudo,
@@ -235,13 +446,15 @@ static __always_inline bool ct_change_state(struct nat_timer_value *timer_track_
 
 static __always_inline int ct_reset_timer(struct nat_timer_value *timer_track_value, u64 timeout) {
 #define BPF_LOG_TOPIC "ct_reset_timer"
+    timer_track_value->expire_time = bpf_ktime_get_ns() + timeout;
     // bpf_log_info("ct_reset_timer : %llu", timeout);
     return bpf_timer_start(&timer_track_value->timer, timeout, 0);
 #undef BPF_LOG_TOPIC
 }
 
 static __always_inline int ct_state_transition(u8 l4proto, u8 pkt_type, u8 gress,
-                                               struct nat_timer_value *ct_timer_value) {
+                                               struct nat_timer_value *ct_timer_value,
+                                               const struct nat_ct_config *ct_config) {
 #define BPF_LOG_TOPIC "ct_state_transition"
     u64 curr_state = ct_timer_value->status;
 
@@ -253,27 +466,44 @@ static __always_inline int ct_state_transition(u8 l4proto, u8 pkt_type, u8 gress
 
     if (pkt_type == PKT_CONNLESS) {
         NEW_STATE(OTHER_EST);
-        RESET_TIMER(UDP_TIMEOUT);
+        if (l4proto == IPPROTO_ICMP) {
+            RESET_TIMER(ct_config->icmp_timeout);
+        } else {
+            RESET_TIMER(ct_config->udp_timeout);
+        }
         return TC_ACT_OK;
     }
 
     if (pkt_type == PKT_TCP_RST) {
         NEW_STATE(TIMER_INIT);
-        RESET_TIMER(TCP_SYN_TIMEOUT);
+        RESET_TIMER(ct_config->tcp_syn_timeout);
         return TC_ACT_OK;
     }
 
     if (pkt_type == PKT_TCP_SYN) {
         NEW_STATE(TIMER_INIT);
         if (gress == ct_timer_value->gress) {
-            RESET_TIMER(TCP_SYN_TIMEOUT);
+            RESET_TIMER(ct_config->tcp_syn_timeout);
         } else {
             RESET_TIMER(TCP_TCP_TRANS);
         }
         return TC_ACT_OK;
     }
 
-    RESET_TIMER(TCP_TIMEOUT);
+    if (pkt_type == PKT_TCP_FIN) {
+        if (curr_state != TCP_CLOSING) {
+            NEW_STATE(TCP_CLOSING);
+        }
+        RESET_TIMER(ct_config->tcp_closing_timeout);
+        return TC_ACT_OK;
+    }
+
+    // 关闭中的连接不再延长为已建立的超时
+    if (curr_state == TCP_CLOSING) {
+        RESET_TIMER(ct_config->tcp_closing_timeout);
+    } else {
+        RESET_TIMER(ct_config->tcp_established_timeout);
+    }
 
     return TC_ACT_OK;
 #undef BPF_LOG_TOPIC
@@ -290,6 +520,8 @@ static int timer_clean_callback(void *map_mapping_timer_, struct nat_timer_key *
         .from_addr = key->pair_ip.src_addr,
         .from_port = key->pair_ip.src_port,
     };
+    nat_mapping_key_set_remote(&egress_mapping_key, value->mapping, &value->trigger_saddr,
+                               value->trigger_port);
 
     struct nat_mapping_key ingress_mapping_key = {
         .l4proto = key->l4proto,
@@ -301,6 +533,7 @@ static int timer_clean_callback(void *map_mapping_timer_, struct nat_timer_key *
     bpf_map_delete_elem(&nat_mappings, &egress_mapping_key);
     bpf_map_delete_elem(&nat_mappings, &ingress_mapping_key);
 
+    host_mapping_count_dec(key->pair_ip.src_addr.ip);
     bpf_map_delete_elem(&map_mapping_timer, key);
     return 0;
 #undef BPF_LOG_TOPIC
@@ -308,7 +541,7 @@ static int timer_clean_callback(void *map_mapping_timer_, struct nat_timer_key *
 
 static __always_inline struct nat_timer_value *
 insert_new_nat_timer(u8 l4proto, const struct nat_timer_key *key,
-                     const struct nat_timer_value *val) {
+                     const struct nat_timer_value *val, const struct nat_ct_config *ct_config) {
 #define BPF_LOG_TOPIC "insert_new_nat_timer"
     // bpf_log_info("protocol: %u, src_port: %u -> dst_port: %u", l4proto,
     // bpf_ntohs(key->pair_ip.src_port), bpf_ntohs(key->pair_ip.dst_port)); bpf_log_info("src_ip:
@@ -331,11 +564,19 @@ insert_new_nat_timer(u8 l4proto, const struct nat_timer_key *key,
     if (ret) {
         goto delete_timer;
     }
-    ret = bpf_timer_start(&value->timer, l4proto == IPPROTO_TCP ? TCP_TIMEOUT : UDP_TIMEOUT, 0);
+    u64 timeout = ct_config->udp_timeout;
+    if (l4proto == IPPROTO_TCP) {
+        timeout = ct_config->tcp_established_timeout;
+    } else if (l4proto == IPPROTO_ICMP) {
+        timeout = ct_config->icmp_timeout;
+    }
+    ret = bpf_timer_start(&value->timer, timeout, 0);
     if (ret) {
         goto delete_timer;
     }
+    value->expire_time = value->create_time + timeout;
 
+    host_mapping_count_inc(key->pair_ip.src_addr.ip);
     return value;
 delete_timer:
     bpf_log_error("setup timer err:%d", ret);
@@ -348,7 +589,8 @@ static __always_inline int lookup_or_new_ct(u8 l4proto, bool do_new,
                                             const struct inet_pair *pkt_ip_pair,
                                             struct nat_mapping_value *nat_egress_value,
                                             struct nat_mapping_value *nat_ingress_value,
-                                            struct nat_timer_value **timer_value_) {
+                                            struct nat_timer_value **timer_value_,
+                                            const struct nat_ct_config *ct_config) {
 #define BPF_LOG_TOPIC "lookup_or_new_ct"
 
     struct nat_timer_key timer_key = {
@@ -382,8 +624,10 @@ static __always_inline int lookup_or_new_ct(u8 l4proto, bool do_new,
     timer_value_new.trigger_port = nat_ingress_value->trigger_port;
     timer_value_new.status = TIMER_INIT;
     timer_value_new.gress = NAT_MAPPING_EGRESS;
+    timer_value_new.mapping = nat_egress_value->mapping;
+    timer_value_new.create_time = bpf_ktime_get_ns();
     COPY_ADDR_FROM(timer_value_new.trigger_saddr.all, nat_egress_value->trigger_addr.all);
-    timer_value = insert_new_nat_timer(l4proto, &timer_key, &timer_value_new);
+    timer_value = insert_new_nat_timer(l4proto, &timer_key, &timer_value_new, ct_config);
     if (timer_value == NULL) {
         return TIMER_ERROR;
     }
@@ -399,7 +643,7 @@ static __always_inline int lookup_or_new_ct(u8 l4proto, bool do_new,
         event->dst_port = nat_egress_value->trigger_port;
         event->l4_proto = l4proto;
         event->l3_proto = LANDSCAPE_IPV4_TYPE;
-        event->flow_id = 0;
+        event->flow_id = nat_egress_value->flow_id;
         event->trace_id = 0;
         event->time = bpf_ktime_get_ns();
         event->event_type = NAT_CREATE_CONN;
@@ -429,7 +673,9 @@ insert_mappings(const struct nat_mapping_key *key, const struct nat_mapping_valu
         .trigger_addr = val->trigger_addr,
         .trigger_port = val->trigger_port,
         .active_time = val->active_time,
-        ._pad = {0, 0, 0},
+        .filtering = val->filtering,
+        .flow_id = val->flow_id,
+        .mapping = val->mapping,
     };
 
     ret = bpf_map_update_elem(&nat_mappings, key, val, BPF_ANY);
@@ -515,6 +761,9 @@ ingress_lookup_or_new_mapping(struct __sk_buff *skb, u8 ip_protocol, bool allow_
             .from_port = nat_ingress_value->port,  // 数据包中的 内网端口
             .from_addr = nat_ingress_value->addr,  // 内网原始地址
         };
+        nat_mapping_key_set_remote(&egress_key, nat_ingress_value->mapping,
+                                   &nat_ingress_value->trigger_addr,
+                                   nat_ingress_value->trigger_port);
         nat_egress_value = bpf_map_lookup_elem(&nat_mappings, &egress_key);
 
         if (!nat_egress_value) {
@@ -534,7 +783,9 @@ static __always_inline int
 egress_lookup_or_new_mapping(struct __sk_buff *skb, u8 ip_protocol, bool allow_create_mapping,
                              const struct inet_pair *pkt_ip_pair,
                              struct nat_mapping_value **nat_egress_value_,
-                             struct nat_mapping_value **nat_ingress_value_) {
+                             struct nat_mapping_value **nat_ingress_value_,
+                             const struct nat_ct_config *ct_config, u8 mapping_policy,
+                             u8 filtering_policy) {
 #define BPF_LOG_TOPIC "egress_lookup_or_new_mapping"
     //
     struct nat_mapping_key egress_key = {
@@ -543,6 +794,8 @@ egress_lookup_or_new_mapping(struct __sk_buff *skb, u8 ip_protocol, bool allow_c
         .from_port = pkt_ip_pair->src_port,  // 数据包中的 内网端口
         .from_addr = pkt_ip_pair->src_addr,  // 内网原始地址
     };
+    nat_mapping_key_set_remote(&egress_key, mapping_policy, &pkt_ip_pair->dst_addr,
+                               pkt_ip_pair->dst_port);
 
     // 倒置的值
     struct nat_mapping_value *nat_ingress_value = NULL;
@@ -551,19 +804,29 @@ egress_lookup_or_new_mapping(struct __sk_buff *skb, u8 ip_protocol, bool allow_c
         if (!allow_create_mapping) {
             return TC_ACT_SHOT;
         }
-        u32 wan_ip_key = skb->ifindex;
-        __be32 *wan_ip = bpf_map_lookup_elem(&wan_ipv4_binding, &wan_ip_key);
-        if (!wan_ip) {
+        if (ct_config->max_mappings_per_host != 0) {
+            u32 *count = bpf_map_lookup_elem(&nat_host_mapping_count, &pkt_ip_pair->src_addr.ip);
+            if (count && *count >= ct_config->max_mappings_per_host) {
+                bpf_log_info("host %pI4 reach mapping limit: %u", &pkt_ip_pair->src_addr.ip,
+                             *count);
+                return TC_ACT_SHOT;
+            }
+        }
+        __be32 snat_addr;
+        if (select_snat_addr(skb, pkt_ip_pair->src_addr.ip, &snat_addr)) {
             bpf_log_info("can't find the wan ip, using ifindex: %d", skb->ifindex);
             return TC_ACT_SHOT;
         }
         struct nat_mapping_value new_nat_egress_value = {0};
 
-        new_nat_egress_value.addr.ip = *wan_ip;
+        new_nat_egress_value.addr.ip = snat_addr;
         new_nat_egress_value.port = egress_key.from_port;  // 尽量先试试使用客户端发起时候的端口
         new_nat_egress_value.trigger_addr = pkt_ip_pair->dst_addr;
         new_nat_egress_value.trigger_port = pkt_ip_pair->dst_port;
         new_nat_egress_value.is_static = 0;
+        new_nat_egress_value.filtering = filtering_policy;
+        new_nat_egress_value.mapping = mapping_policy;
+        new_nat_egress_value.flow_id = get_flow_id(skb->mark);
         new_nat_egress_value.active_time = bpf_ktime_get_ns();
 
         int ret;
@@ -588,12 +851,12 @@ egress_lookup_or_new_mapping(struct __sk_buff *skb, u8 ip_protocol, bool allow_c
             ctx.range.start = udp_range_start;
             ctx.range.end = udp_range_end;
             ctx.remaining_size = udp_range_end - udp_range_start;
-            ctx.timeout_interval = UDP_TIMEOUT;
+            ctx.timeout_interval = ct_config->udp_timeout;
         } else if (ip_protocol == IPPROTO_ICMP) {
             ctx.range.start = icmp_range_start;
             ctx.range.end = icmp_range_end;
             ctx.remaining_size = icmp_range_end - icmp_range_start;
-            ctx.timeout_interval = UDP_TIMEOUT;
+            ctx.timeout_interval = ct_config->icmp_timeout;
         }
 
         if (ctx.remaining_size == 0) {
@@ -972,6 +1235,7 @@ static __always_inline int extract_packet_info(struct __sk_buff *skb, struct ip_
 #undef BPF_LOG_TOPIC
 }
 
+/// 返回 LANDSCAPE_IPV4_TYPE / LANDSCAPE_IPV6_TYPE, 其他类型返回 TC_ACT_UNSPEC
 static __always_inline int current_pkg_type(struct __sk_buff *skb) {
     if (current_eth_net_offset != 0) {
         struct ethhdr *eth;
@@ -979,8 +1243,10 @@ static __always_inline int current_pkg_type(struct __sk_buff *skb) {
             return TC_ACT_UNSPEC;
         }
 
-        if (eth->h_proto != ETH_IPV4) {
-            return TC_ACT_UNSPEC;
+        if (eth->h_proto == ETH_IPV4) {
+            return LANDSCAPE_IPV4_TYPE;
+        } else if (eth->h_proto == ETH_IPV6) {
+            return LANDSCAPE_IPV6_TYPE;
         }
     } else {
         u8 *p_version;
@@ -988,11 +1254,13 @@ static __always_inline int current_pkg_type(struct __sk_buff *skb) {
             return TC_ACT_UNSPEC;
         }
         u8 ip_version = (*p_version) >> 4;
-        if (ip_version != 4) {
-            return TC_ACT_UNSPEC;
+        if (ip_version == 4) {
+            return LANDSCAPE_IPV4_TYPE;
+        } else if (ip_version == 6) {
+            return LANDSCAPE_IPV6_TYPE;
         }
     }
-    return TC_ACT_OK;
+    return TC_ACT_UNSPEC;
 }
 
 static __always_inline bool is_out_nat_range(const struct ip_packet_info *pkt, const u16 port) {
@@ -1008,11 +1276,370 @@ static __always_inline bool is_out_nat_range(const struct ip_packet_info *pkt, c
         return false;
     }
 }
+/// IPv6 Masquerade 连接, 与 nat_mappings 一样分为两个方向
+/// 出方向的 local 为内部主机的地址端口, 入方向的 local 为 WAN 地址与映射后的端口
+/// 优先保持内部主机的源端口, 端口已被其他主机占用时另外选择一个端口
+struct nat6_masq_key {
+    u8 gress;
+    u8 l4proto;
+    __be16 local_port;
+    // ICMPv6 echo 的 id 会被改写, 所以为 0
+    __be16 remote_port;
+    u8 _pad[2];
+    union u_inet_addr local_addr;
+    union u_inet_addr remote_addr;
+};
+
+/// 出方向为映射后的端口, 入方向为内部主机的地址端口
+struct nat6_masq_value {
+    union u_inet_addr addr;
+    __be16 port;
+    u8 _pad[6];
+    u64 active_time;
+};
+
+struct {
+    __uint(type, BPF_MAP_TYPE_LRU_HASH);
+    __type(key, struct nat6_masq_key);
+    __type(value, struct nat6_masq_value);
+    __uint(max_entries, NAT_MAPPING_CACHE_SIZE);
+} nat6_masq_ct SEC(".maps");
+
+#define NAT6_MASQ_PORT_START 1024
+#define NAT6_MASQ_PORT_END 65535
+
+struct nat6_masq_search_ctx {
+    struct nat6_masq_key ingress_key;
+    struct nat6_masq_value ingress_value;
+    u64 timeout;
+    u16 curr_port;
+    u16 remaining_size;
+    bool found;
+};
+
+/// 没有连接状态, TCP 使用已建立连接的超时
+static __always_inline u64 nat6_masq_timeout(u8 l4proto, const struct nat_ct_config *ct_config) {
+    if (l4proto == IPPROTO_TCP) {
+        return ct_config->tcp_established_timeout;
+    } else if (l4proto == IPPROTO_ICMPV6) {
+        return ct_config->icmp_timeout;
+    }
+    return ct_config->udp_timeout;
+}
+
+static __always_inline bool nat6_masq_expired(const struct nat6_masq_value *value, u64 now,
+                                              u64 timeout) {
+    return now - value->active_time > timeout;
+}
+
+static int nat6_masq_search_port_callback(u32 index, struct nat6_masq_search_ctx *ctx) {
+    ctx->ingress_key.local_port = bpf_htons(ctx->curr_port);
+    struct nat6_masq_value *value = bpf_map_lookup_elem(&nat6_masq_ct, &ctx->ingress_key);
+    if (value == NULL) {
+        // BPF_NOEXIST 保证同时建立的连接不会选到同一个端口
+        if (!bpf_map_update_elem(&nat6_masq_ct, &ctx->ingress_key, &ctx->ingress_value,
+                                 BPF_NOEXIST)) {
+            ctx->found = true;
+            return BPF_LOOP_RET_BREAK;
+        }
+    } else if (nat6_masq_expired(value, ctx->ingress_value.active_time, ctx->timeout)) {
+        // 已经超时的连接视为空闲
+        if (!bpf_map_update_elem(&nat6_masq_ct, &ctx->ingress_key, &ctx->ingress_value,
+                                 BPF_EXIST)) {
+            ctx->found = true;
+            return BPF_LOOP_RET_BREAK;
+        }
+    }
+
+    if (ctx->curr_port != NAT6_MASQ_PORT_END) {
+        ctx->curr_port++;
+    } else {
+        ctx->curr_port = NAT6_MASQ_PORT_START;
+    }
+    if (--ctx->remaining_size == 0) {
+        return BPF_LOOP_RET_BREAK;
+    }
+    return BPF_LOOP_RET_CONTINUE;
+}
+
+static __always_inline bool ipv6_is_link_local(const union u_inet_addr *addr) {
+    return addr->bits[0] == 0xfe && (addr->bits[1] & 0xc0) == 0x80;
+}
+
+/// RFC 6296 无状态前缀转换, 通过调整字保持校验和不变, 无需修改 L4 校验和
+static __always_inline int nptv6_rewrite(struct __sk_buff *skb, u32 addr_off,
+                                         const struct nat6_config *config, bool is_egress) {
+#define BPF_LOG_TOPIC "nptv6_rewrite"
+    union u_inet_addr addr;
+    if (bpf_skb_load_bytes(skb, addr_off, &addr, sizeof(addr))) {
+        return TC_ACT_SHOT;
+    }
+
+    const union u_inet_addr *from = is_egress ? &config->inner_prefix : &config->outer_prefix;
+    const union u_inet_addr *to = is_egress ? &config->outer_prefix : &config->inner_prefix;
+
+    for (int i = 0; i < 4; i++) {
+        if ((addr.all[i] & config->prefix_mask.all[i]) != from->all[i]) {
+            return TC_ACT_UNSPEC;
+        }
+    }
+
+    u8 index = config->adj_word & 7;
+    __be16 *words = (__be16 *)addr.bits;
+    u32 word = bpf_ntohs(words[index]);
+    if (word == 0xffff) {
+        bpf_log_debug("adjustment word is 0xffff, can not translate");
+        return TC_ACT_SHOT;
+    }
+    word += is_egress ? config->egress_adj : config->ingress_adj;
+    word = (word & 0xffff) + (word >> 16);
+    if (word == 0xffff) {
+        word = 0;
+    }
+    words[index] = bpf_htons(word);
+
+    for (int i = 0; i < 4; i++) {
+        addr.all[i] = (addr.all[i] & ~config->prefix_mask.all[i]) | to->all[i];
+    }
+
+    if (bpf_skb_store_bytes(skb, addr_off, &addr, sizeof(addr), 0)) {
+        return TC_ACT_SHOT;
+    }
+    return TC_ACT_OK;
+#undef BPF_LOG_TOPIC
+}
+
+static __always_inline int nat6_rewrite_addr(struct __sk_buff *skb, u32 addr_off, u32 csum_off,
+                                             union u_inet_addr *from, union u_inet_addr *to) {
+    s64 diff = bpf_csum_diff(from->all, sizeof(from->all), to->all, sizeof(to->all), 0);
+    if (diff < 0) {
+        return TC_ACT_SHOT;
+    }
+    if (bpf_l4_csum_replace(skb, csum_off, 0, diff, BPF_F_PSEUDO_HDR)) {
+        return TC_ACT_SHOT;
+    }
+    if (bpf_skb_store_bytes(skb, addr_off, to->all, sizeof(to->all), 0)) {
+        return TC_ACT_SHOT;
+    }
+    return TC_ACT_OK;
+}
+
+static __always_inline int nat6_rewrite_port(struct __sk_buff *skb, u32 port_off, u32 csum_off,
+                                             __be16 from, __be16 to) {
+    if (from == to) {
+        return TC_ACT_OK;
+    }
+    if (bpf_l4_csum_replace(skb, csum_off, from, to, sizeof(to))) {
+        return TC_ACT_SHOT;
+    }
+    if (bpf_skb_store_bytes(skb, port_off, &to, sizeof(to), 0)) {
+        return TC_ACT_SHOT;
+    }
+    return TC_ACT_OK;
+}
+
+/// 有状态的 NAT66, 只处理没有扩展头的 TCP / UDP / ICMPv6 echo
+static __always_inline int nat6_masquerade(struct __sk_buff *skb, u32 l3_off,
+                                           struct nat6_config *config, bool is_egress) {
+#define BPF_LOG_TOPIC "nat6_masquerade"
+    struct ipv6hdr *ip6h;
+    if (VALIDATE_READ_DATA(skb, &ip6h, l3_off, sizeof(*ip6h))) {
+        return TC_ACT_UNSPEC;
+    }
+
+    u8 l4proto = ip6h->nexthdr;
+    union u_inet_addr saddr, daddr;
+    COPY_ADDR_FROM(saddr.all, ip6h->saddr.in6_u.u6_addr32);
+    COPY_ADDR_FROM(daddr.all, ip6h->daddr.in6_u.u6_addr32);
+
+    u32 l4_off = l3_off + sizeof(struct ipv6hdr);
+    u32 csum_off, src_port_off, dst_port_off;
+    __be16 src_port, dst_port;
+    if (l4proto == IPPROTO_TCP) {
+        struct tcphdr *tcph;
+        if (VALIDATE_READ_DATA(skb, &tcph, l4_off, sizeof(*tcph))) {
+            return TC_ACT_UNSPEC;
+        }
+        src_port = tcph->source;
+        dst_port = tcph->dest;
+        csum_off = l4_off + offsetof(struct tcphdr, check);
+        src_port_off = l4_off + offsetof(struct tcphdr, source);
+        dst_port_off = l4_off + offsetof(struct tcphdr, dest);
+    } else if (l4proto == IPPROTO_UDP) {
+        struct udphdr *udph;
+        if (VALIDATE_READ_DATA(skb, &udph, l4_off, sizeof(*udph))) {
+            return TC_ACT_UNSPEC;
+        }
+        src_port = udph->source;
+        dst_port = udph->dest;
+        csum_off = l4_off + offsetof(struct udphdr, check);
+        src_port_off = l4_off + offsetof(struct udphdr, source);
+        dst_port_off = l4_off + offsetof(struct udphdr, dest);
+    } else if (l4proto == IPPROTO_ICMPV6) {
+        struct icmp6hdr *icmp6h;
+        if (VALIDATE_READ_DATA(skb, &icmp6h, l4_off, sizeof(*icmp6h))) {
+            return TC_ACT_UNSPEC;
+        }
+        if (icmp6h->icmp6_type != (is_egress ? ICMPV6_ECHO_REQUEST : ICMPV6_ECHO_REPLY)) {
+            return TC_ACT_UNSPEC;
+        }
+        // 使用 echo id 作为两端的端口
+        src_port = icmp6h->icmp6_dataun.u_echo.identifier;
+        dst_port = icmp6h->icmp6_dataun.u_echo.identifier;
+        csum_off = l4_off + offsetof(struct icmp6hdr, icmp6_cksum);
+        src_port_off = l4_off + offsetof(struct icmp6hdr, icmp6_dataun.u_echo.identifier);
+        dst_port_off = src_port_off;
+    } else {
+        return TC_ACT_UNSPEC;
+    }
+
+    u64 now = bpf_ktime_get_ns();
+    u64 timeout = nat6_masq_timeout(l4proto, get_ct_config(skb));
+    __be16 remote_port;
+    struct nat6_masq_key egress_key = {0};
+    struct nat6_masq_key ingress_key = {0};
+    struct nat6_masq_value *egress_value, *ingress_value;
+    egress_key.gress = NAT_MAPPING_EGRESS;
+    egress_key.l4proto = l4proto;
+    ingress_key.gress = NAT_MAPPING_INGRESS;
+    ingress_key.l4proto = l4proto;
+
+    if (is_egress) {
+        if (ipv6_is_link_local(&saddr) || ip_addr_equal(&saddr, &config->wan_addr)) {
+            return TC_ACT_UNSPEC;
+        }
+        remote_port = l4proto == IPPROTO_ICMPV6 ? 0 : dst_port;
+        egress_key.local_port = src_port;
+        egress_key.remote_port = remote_port;
+        COPY_ADDR_FROM(egress_key.local_addr.all, saddr.all);
+        COPY_ADDR_FROM(egress_key.remote_addr.all, daddr.all);
+        ingress_key.remote_port = remote_port;
+        COPY_ADDR_FROM(ingress_key.local_addr.all, config->wan_addr.all);
+        COPY_ADDR_FROM(ingress_key.remote_addr.all, daddr.all);
+
+        __be16 wan_port = 0;
+        bool found = false;
+        egress_value = bpf_map_lookup_elem(&nat6_masq_ct, &egress_key);
+        if (egress_value != NULL && !nat6_masq_expired(egress_value, now, timeout)) {
+            ingress_key.local_port = egress_value->port;
+            ingress_value = bpf_map_lookup_elem(&nat6_masq_ct, &ingress_key);
+            // 入方向的记录可能已经超时并被其他主机占用
+            if (ingress_value != NULL && ip_addr_equal(&ingress_value->addr, &saddr) &&
+                ingress_value->port == src_port) {
+                wan_port = egress_value->port;
+                egress_value->active_time = now;
+                ingress_value->active_time = now;
+                found = true;
+            }
+        }
+
+        if (!found) {
+            struct nat6_masq_search_ctx ctx = {
+                .ingress_key = ingress_key,
+                .ingress_value =
+                    {
+                        .port = src_port,
+                        .active_time = now,
+                    },
+                .timeout = timeout,
+                .curr_port = bpf_ntohs(src_port),
+                .remaining_size = NAT6_MASQ_PORT_END - NAT6_MASQ_PORT_START,
+                .found = false,
+            };
+            COPY_ADDR_FROM(ctx.ingress_value.addr.all, saddr.all);
+            if (ctx.curr_port < NAT6_MASQ_PORT_START) {
+                ctx.curr_port = NAT6_MASQ_PORT_START + ctx.curr_port;
+            }
+            if (bpf_loop(65536, nat6_masq_search_port_callback, &ctx, 0) < 0 || !ctx.found) {
+                bpf_log_info("no free port for %pI6", &saddr);
+                return TC_ACT_SHOT;
+            }
+            wan_port = ctx.ingress_key.local_port;
+
+            struct nat6_masq_value new_egress_value = {0};
+            COPY_ADDR_FROM(new_egress_value.addr.all, config->wan_addr.all);
+            new_egress_value.port = wan_port;
+            new_egress_value.active_time = now;
+            if (bpf_map_update_elem(&nat6_masq_ct, &egress_key, &new_egress_value, BPF_ANY)) {
+                bpf_map_delete_elem(&nat6_masq_ct, &ctx.ingress_key);
+                return TC_ACT_SHOT;
+            }
+        }
+
+        union u_inet_addr wan_addr;
+        COPY_ADDR_FROM(wan_addr.all, config->wan_addr.all);
+        if (nat6_rewrite_addr(skb, l3_off + offsetof(struct ipv6hdr, saddr), csum_off, &saddr,
+                              &wan_addr)) {
+            return TC_ACT_SHOT;
+        }
+        return nat6_rewrite_port(skb, src_port_off, csum_off, src_port, wan_port);
+    }
+
+    if (!ip_addr_equal(&daddr, &config->wan_addr)) {
+        return TC_ACT_UNSPEC;
+    }
+    remote_port = l4proto == IPPROTO_ICMPV6 ? 0 : src_port;
+    ingress_key.local_port = dst_port;
+    ingress_key.remote_port = remote_port;
+    COPY_ADDR_FROM(ingress_key.local_addr.all, daddr.all);
+    COPY_ADDR_FROM(ingress_key.remote_addr.all, saddr.all);
+
+    ingress_value = bpf_map_lookup_elem(&nat6_masq_ct, &ingress_key);
+    if (ingress_value == NULL || nat6_masq_expired(ingress_value, now, timeout)) {
+        // 非内部主机发起的连接, 交给路由器自身处理
+        return TC_ACT_UNSPEC;
+    }
+    ingress_value->active_time = now;
+
+    union u_inet_addr client_addr;
+    COPY_ADDR_FROM(client_addr.all, ingress_value->addr.all);
+    __be16 client_port = ingress_value->port;
+
+    egress_key.local_port = client_port;
+    egress_key.remote_port = remote_port;
+    COPY_ADDR_FROM(egress_key.local_addr.all, client_addr.all);
+    COPY_ADDR_FROM(egress_key.remote_addr.all, saddr.all);
+    egress_value = bpf_map_lookup_elem(&nat6_masq_ct, &egress_key);
+    if (egress_value != NULL) {
+        egress_value->active_time = now;
+    }
+
+    if (nat6_rewrite_addr(skb, l3_off + offsetof(struct ipv6hdr, daddr), csum_off, &daddr,
+                          &client_addr)) {
+        return TC_ACT_SHOT;
+    }
+    return nat6_rewrite_port(skb, dst_port_off, csum_off, dst_port, client_port);
+#undef BPF_LOG_TOPIC
+}
+
+static __always_inline int nat6_handle(struct __sk_buff *skb, bool is_egress) {
+    u32 ifindex = skb->ifindex;
+    struct nat6_config *config = bpf_map_lookup_elem(&nat6_config_map, &ifindex);
+    if (config == NULL) {
+        return TC_ACT_UNSPEC;
+    }
+
+    int ret = TC_ACT_UNSPEC;
+    u32 l3_off = current_eth_net_offset;
+    if (config->mode == NAT6_MODE_NPTV6) {
+        u32 addr_off = l3_off + (is_egress ? offsetof(struct ipv6hdr, saddr)
+                                           : offsetof(struct ipv6hdr, daddr));
+        ret = nptv6_rewrite(skb, addr_off, config, is_egress);
+    } else if (config->mode == NAT6_MODE_MASQUERADE) {
+        ret = nat6_masquerade(skb, l3_off, config, is_egress);
+    }
+
+    return ret == TC_ACT_SHOT ? TC_ACT_SHOT : TC_ACT_UNSPEC;
+}
+
 SEC("tc/ingress")
 int ingress_nat(struct __sk_buff *skb) {
 #define BPF_LOG_TOPIC ">>> ingress_nat >>>"
 
-    if (current_pkg_type(skb) != TC_ACT_OK) {
+    int l3_type = current_pkg_type(skb);
+    if (l3_type == LANDSCAPE_IPV6_TYPE) {
+        return nat6_handle(skb, false);
+    } else if (l3_type != LANDSCAPE_IPV4_TYPE) {
         return TC_ACT_UNSPEC;
     }
 
@@ -1035,6 +1662,7 @@ int ingress_nat(struct __sk_buff *skb) {
     }
 
     bool is_icmpx_error = is_icmp_error_pkt(&packet_info);
+    const struct nat_ct_config *ct_config = get_ct_config(skb);
     bool allow_create_mapping = packet_info.ip_protocol == IPPROTO_ICMP;
 
     // egress  存储的是 Ac:Pc -> An:Pn 的值
@@ -1071,16 +1699,20 @@ int ingress_nat(struct __sk_buff *skb) {
         //              bpf_ntohs(nat_egress_value->port));
 
         if (!nat_egress_value->is_static) {
+            if (!is_icmpx_error && !nat_filter_allow(packet_info.ip_protocol, nat_egress_value,
+                                                     &packet_info.pair_ip, ct_config)) {
+                return TC_ACT_SHOT;
+            }
             struct nat_timer_value *ct_timer_value;
             ret = lookup_or_new_ct(packet_info.ip_protocol, allow_create_mapping,
                                    &packet_info.pair_ip, nat_egress_value, nat_ingress_value,
-                                   &ct_timer_value);
+                                   &ct_timer_value, ct_config);
             if (ret == TIMER_NOT_FOUND || ret == TIMER_ERROR) {
                 return TC_ACT_SHOT;
             }
             if (!is_icmpx_error || ct_timer_value != NULL) {
                 ct_state_transition(packet_info.ip_protocol, packet_info.pkt_type,
-                                    NAT_MAPPING_EGRESS, ct_timer_value);
+                                    NAT_MAPPING_EGRESS, ct_timer_value, ct_config);
             }
         }
         // } else {
@@ -1112,7 +1744,10 @@ SEC("tc/egress")
 int egress_nat(struct __sk_buff *skb) {
 #define BPF_LOG_TOPIC "<<< egress_nat <<<"
 
-    if (current_pkg_type(skb) != TC_ACT_OK) {
+    int l3_type = current_pkg_type(skb);
+    if (l3_type == LANDSCAPE_IPV6_TYPE) {
+        return nat6_handle(skb, true);
+    } else if (l3_type != LANDSCAPE_IPV4_TYPE) {
         return TC_ACT_UNSPEC;
     }
 
@@ -1138,6 +1773,7 @@ int egress_nat(struct __sk_buff *skb) {
     // bpf_log_info("icmp_error_payload_offset: %d", packet_info.icmp_error_payload_offset);
 
     bool is_icmpx_error = is_icmp_error_pkt(&packet_info);
+    const struct nat_ct_config *ct_config = get_ct_config(skb);
     bool allow_create_mapping = !is_icmpx_error && pkt_allow_initiating_ct(packet_info.pkt_type);
 
     // egress  存储的是 Ac:Pc -> An:Pn 的值
@@ -1156,50 +1792,37 @@ int egress_nat(struct __sk_buff *skb) {
             return TC_ACT_UNSPEC;
         }
 
+        u8 mapping_policy, filtering_policy;
+        get_nat_behavior(skb, ct_config, &mapping_policy, &filtering_policy);
+
         ret = egress_lookup_or_new_mapping(skb, packet_info.ip_protocol, allow_create_mapping,
                                            &packet_info.pair_ip, &nat_egress_value,
-                                           &nat_ingress_value);
+                                           &nat_ingress_value, ct_config, mapping_policy,
+                                           filtering_policy);
 
         if (ret != TC_ACT_OK) {
             return TC_ACT_SHOT;
         }
 
-        
-        u8 flow_action = get_flow_action(skb->mark);
-        if (flow_action != FLOW_ALLOW_REUSE) {
-            // PORT REUSE check
-            if (!ip_addr_equal(&packet_info.pair_ip.dst_addr, &nat_egress_value->trigger_addr) ||
-                packet_info.pair_ip.dst_port != nat_egress_value->trigger_port) {
-                bpf_log_info("FLOW_ALLOW_REUSE MARK not set, DROP PACKET");
-                bpf_log_info("dst IP: %pI4,", &packet_info.pair_ip.dst_addr);
-                bpf_log_info("trigger_addr IP: %pI4,", &nat_egress_value->trigger_addr);
-                bpf_log_info(
-                    "compare ip result: %d",
-                    ip_addr_equal(&packet_info.pair_ip.dst_addr, &nat_egress_value->trigger_addr));
-                bpf_log_info("trigger_port: %u,", bpf_ntohs(nat_egress_value->trigger_port));
-                bpf_log_info("dst_port: %u,", bpf_ntohs(packet_info.pair_ip.dst_port));
-                bpf_log_info("compare port result: %d",
-                             packet_info.pair_ip.dst_port == nat_egress_value->trigger_port);
-                return TC_ACT_SHOT;
-            }
-        }
-
         // bpf_log_info("ingress value, %pI4 : %u", &nat_ingress_value->addr,
         //              bpf_ntohs(nat_ingress_value->port));
         // bpf_log_info("egress  value, %pI4 : %u", &nat_egress_value->addr.ip,
         //              bpf_ntohs(nat_egress_value->port));
 
         if (!nat_egress_value->is_static) {
+            if (!is_icmpx_error) {
+                nat_filter_permit(packet_info.ip_protocol, nat_egress_value, &packet_info.pair_ip);
+            }
             struct nat_timer_value *ct_timer_value;
             ret = lookup_or_new_ct(packet_info.ip_protocol, allow_create_mapping,
                                    &packet_info.pair_ip, nat_egress_value, nat_ingress_value,
-                                   &ct_timer_value);
+                                   &ct_timer_value, ct_config);
             if (ret == TIMER_NOT_FOUND || ret == TIMER_ERROR) {
                 return TC_ACT_SHOT;
             }
             if (!is_icmpx_error || ct_timer_value != NULL) {
                 ct_state_transition(packet_info.ip_protocol, packet_info.pkt_type,
-                                    NAT_MAPPING_EGRESS, ct_timer_value);
+                                    NAT_MAPPING_EGRESS, ct_timer_value, ct_config);
             }
         }
     }
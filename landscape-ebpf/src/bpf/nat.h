@@ -23,6 +23,17 @@ static __always_inline int bpf_write_inet_addr(struct __sk_buff *skb, bool is_ip
                                is_ipv4 ? sizeof(to_addr->ip) : sizeof(to_addr->all), 0);
 }
 
+static __always_inline void ipv4_update_csum(struct __sk_buff *skb, u32 l4_csum_off,
+                                             __be32 from_addr, __be16 from_port, __be32 to_addr,
+                                             __be16 to_port, bool l4_pseudo, bool l4_mangled_0) {
+    bpf_l4_csum_replace(skb, l4_csum_off, from_port, to_port,
+                        2 | (l4_mangled_0 ? BPF_F_MARK_MANGLED_0 : 0));
+    if (l4_pseudo) {
+        bpf_l4_csum_replace(skb, l4_csum_off, from_addr, to_addr,
+                            4 | BPF_F_PSEUDO_HDR | (l4_mangled_0 ? BPF_F_MARK_MANGLED_0 : 0));
+    }
+}
+
 /// @brief  解析的 ip 数据包载体
 struct ip_packet_info {
     u8 _pad;
@@ -62,6 +73,11 @@ struct nat_mapping_key {
     u8 l4proto;
     __be16 from_port;
     union u_inet_addr from_addr;
+    // 出方向映射按映射行为 (NAT_EP_*) 区分的目的端点
+    // Endpoint-Independent 以及入方向时为 0
+    __be16 remote_port;
+    u8 _pad[2];
+    union u_inet_addr remote_addr;
 };
 
 struct nat_mapping_value {
@@ -72,7 +88,12 @@ struct nat_mapping_value {
     __be16 port;
     __be16 trigger_port;
     u8 is_static;
-    u8 _pad[3];
+    // 动态映射创建时确定的过滤行为 (NAT_EP_*)
+    u8 filtering;
+    // 创建映射时数据包所属的 flow
+    u8 flow_id;
+    // 动态映射创建时确定的映射行为 (NAT_EP_*)
+    u8 mapping;
     // 增加一个最后活跃时间
     u64 active_time;
     //
@@ -107,7 +128,12 @@ struct nat_timer_value {
     // Ps
     u16 trigger_port;
     u8 gress;
-    u8 _pad;
+    // 映射的映射行为, 用于还原出方向映射的 key
+    u8 mapping;
+    // 用于会话查询
+    u64 create_time;
+    // 最近一次设置的超时时刻
+    u64 expire_time;
 };
 
 // 用于搜寻可用的端口
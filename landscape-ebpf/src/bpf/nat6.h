@@ -0,0 +1,37 @@
+#ifndef __LD_NAT6_H__
+#define __LD_NAT6_H__
+#include "vmlinux.h"
+#include <bpf/bpf_helpers.h>
+#include "landscape.h"
+#include "packet_def.h"
+
+#define NAT6_MODE_NPTV6 1
+#define NAT6_MODE_MASQUERADE 2
+
+/// WAN 网卡的 IPv6 转换配置, 由用户态在前缀或地址变化时更新
+struct nat6_config {
+    u8 mode;
+    // NPTv6 调整字的下标 (以 16 位为单位)
+    u8 adj_word;
+    // NPTv6 调整值, 主机字节序
+    u16 egress_adj;
+    u16 ingress_adj;
+    u8 _pad[2];
+    // NPTv6 内部 (ULA) 前缀
+    union u_inet_addr inner_prefix;
+    // NPTv6 外部 (委托) 前缀
+    union u_inet_addr outer_prefix;
+    union u_inet_addr prefix_mask;
+    // Masquerade 使用的 WAN 地址
+    union u_inet_addr wan_addr;
+};
+
+struct {
+    __uint(type, BPF_MAP_TYPE_HASH);
+    __type(key, u32);
+    __type(value, struct nat6_config);
+    __uint(max_entries, 64);
+    __uint(pinning, LIBBPF_PIN_BY_NAME);
+} nat6_config_map SEC(".maps");
+
+#endif /* __LD_NAT6_H__ */
@@ -0,0 +1,50 @@
+#ifndef __LD_NAT_CT_CONFIG_H__
+#define __LD_NAT_CT_CONFIG_H__
+#include "vmlinux.h"
+#include <bpf/bpf_helpers.h>
+#include "landscape.h"
+
+// RFC 4787 中的端点范围, 用于映射与过滤行为
+#define NAT_EP_INDEPENDENT 0
+#define NAT_EP_ADDR_DEPENDENT 1
+#define NAT_EP_ADDR_PORT_DEPENDENT 2
+
+/// WAN 网卡的 NAT 连接跟踪参数, 可由用户态在运行时更新
+/// 超时单位为 ns
+struct nat_ct_config {
+    u64 tcp_syn_timeout;
+    u64 tcp_established_timeout;
+    u64 tcp_closing_timeout;
+    u64 udp_timeout;
+    u64 icmp_timeout;
+    // 单个内网主机的动态映射数量上限, 0 表示不限制
+    u32 max_mappings_per_host;
+    u8 mapping_policy;
+    u8 filtering_policy;
+    u8 _pad[2];
+};
+
+struct {
+    __uint(type, BPF_MAP_TYPE_HASH);
+    __type(key, u32);
+    __type(value, struct nat_ct_config);
+    __uint(max_entries, 64);
+    __uint(pinning, LIBBPF_PIN_BY_NAME);
+} nat_ct_config_map SEC(".maps");
+
+/// Flow 对 NAT 行为的覆盖
+struct nat_behavior {
+    u8 mapping_policy;
+    u8 filtering_policy;
+    u8 _pad[2];
+};
+
+struct {
+    __uint(type, BPF_MAP_TYPE_HASH);
+    __type(key, u32);
+    __type(value, struct nat_behavior);
+    __uint(max_entries, 256);
+    __uint(pinning, LIBBPF_PIN_BY_NAME);
+} nat_flow_behavior_map SEC(".maps");
+
+#endif /* __LD_NAT_CT_CONFIG_H__ */
@@ -0,0 +1,80 @@
+#ifndef __LD_NAT_HAIRPIN_H__
+#define __LD_NAT_HAIRPIN_H__
+#include "vmlinux.h"
+#include <bpf/bpf_helpers.h>
+#include "landscape.h"
+#include "nat.h"
+
+/// 开启回流的端口转发, 使用 WAN 地址端口查询内部主机
+struct nat_hairpin_key {
+    u8 l4proto;
+    u8 _pad;
+    __be16 port;
+    __be32 addr;
+};
+
+struct nat_hairpin_value {
+    __be32 addr;
+    __be16 port;
+    u8 _pad[2];
+};
+
+struct {
+    __uint(type, BPF_MAP_TYPE_HASH);
+    __type(key, struct nat_hairpin_key);
+    __type(value, struct nat_hairpin_value);
+    __uint(max_entries, 1024 * 64);
+    __uint(pinning, LIBBPF_PIN_BY_NAME);
+} nat_hairpin_mappings SEC(".maps");
+
+/// 回流连接, 以内部主机回复的方向作为 key
+/// 客户端地址被替换为路由器的 LAN 地址, 优先保留客户端端口,
+/// 端口已被其他客户端占用时另外选择一个端口
+struct nat_hairpin_ct_key {
+    u8 l4proto;
+    u8 _pad[3];
+    __be16 host_port;
+    __be16 router_port;
+    __be32 host_addr;
+    __be32 router_addr;
+};
+
+struct nat_hairpin_ct_value {
+    __be32 client_addr;
+    __be32 wan_addr;
+    __be16 client_port;
+    __be16 wan_port;
+};
+
+struct {
+    __uint(type, BPF_MAP_TYPE_LRU_HASH);
+    __type(key, struct nat_hairpin_ct_key);
+    __type(value, struct nat_hairpin_ct_value);
+    __uint(max_entries, 1024 * 64);
+    __uint(pinning, LIBBPF_PIN_BY_NAME);
+} nat_hairpin_ct SEC(".maps");
+
+/// 客户端方向使用的路由器端口
+struct nat_hairpin_client_key {
+    u8 l4proto;
+    u8 _pad[3];
+    __be16 client_port;
+    __be16 host_port;
+    __be32 client_addr;
+    __be32 host_addr;
+};
+
+struct nat_hairpin_client_value {
+    __be16 router_port;
+    u8 _pad[2];
+};
+
+struct {
+    __uint(type, BPF_MAP_TYPE_LRU_HASH);
+    __type(key, struct nat_hairpin_client_key);
+    __type(value, struct nat_hairpin_client_value);
+    __uint(max_entries, 1024 * 64);
+    __uint(pinning, LIBBPF_PIN_BY_NAME);
+} nat_hairpin_client SEC(".maps");
+
+#endif /* __LD_NAT_HAIRPIN_H__ */
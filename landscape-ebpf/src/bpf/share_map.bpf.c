@@ -1,5 +1,9 @@
 #include "landscape.h"
 #include "share_ifindex_ip.h"
+#include "nat_hairpin.h"
+#include "nat6.h"
+#include "nat_ct_config.h"
+#include "snat_pool.h"
 #include "firewall_share.h"
 #include "flow_lan_share.h"
 #include "flow_verdict_share.h"
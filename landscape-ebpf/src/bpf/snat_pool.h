@@ -0,0 +1,61 @@
+#ifndef __LD_SNAT_POOL_H__
+#define __LD_SNAT_POOL_H__
+#include "vmlinux.h"
+#include <bpf/bpf_helpers.h>
+#include "landscape.h"
+
+#define SNAT_POOL_MAX_ADDRS 16
+
+#define SNAT_POOL_SOURCE_HASH 0
+#define SNAT_POOL_ROUND_ROBIN 1
+
+/// WAN 网卡的 SNAT 地址池
+struct snat_pool {
+    u32 count;
+    // 轮询使用的计数
+    u32 next;
+    u8 policy;
+    u8 _pad[3];
+    __be32 addrs[SNAT_POOL_MAX_ADDRS];
+};
+
+struct {
+    __uint(type, BPF_MAP_TYPE_HASH);
+    __type(key, u32);
+    __type(value, struct snat_pool);
+    __uint(max_entries, 64);
+    __uint(pinning, LIBBPF_PIN_BY_NAME);
+} snat_pool_map SEC(".maps");
+
+struct snat_flow_key {
+    u32 ifindex;
+    u32 flow_id;
+};
+
+/// 指定 flow 使用的 SNAT 地址
+struct {
+    __uint(type, BPF_MAP_TYPE_HASH);
+    __type(key, struct snat_flow_key);
+    __type(value, __be32);
+    __uint(max_entries, 1024);
+    __uint(pinning, LIBBPF_PIN_BY_NAME);
+} snat_flow_addr_map SEC(".maps");
+
+struct snat_subnet_key {
+    // 32 位 ifindex 加上网段前缀长度
+    __u32 prefixlen;
+    u32 ifindex;
+    __be32 addr;
+};
+
+/// 指定内网网段使用的 SNAT 地址
+struct {
+    __uint(type, BPF_MAP_TYPE_LPM_TRIE);
+    __type(key, struct snat_subnet_key);
+    __type(value, __be32);
+    __uint(max_entries, 1024);
+    __uint(map_flags, BPF_F_NO_PREALLOC);
+    __uint(pinning, LIBBPF_PIN_BY_NAME);
+} snat_subnet_addr_map SEC(".maps");
+
+#endif /* __LD_SNAT_POOL_H__ */
@@ -42,6 +42,13 @@ pub fn attach_match_flow(
     open_skel.maps.ip_mac_tab.set_pin_path(&MAP_PATHS.ip_mac_tab)?;
     open_skel.maps.ip_mac_tab.reuse_pinned_map(&MAP_PATHS.ip_mac_tab)?;
 
+    open_skel.maps.nat_hairpin_mappings.set_pin_path(&MAP_PATHS.nat_hairpin_mappings)?;
+    open_skel.maps.nat_hairpin_mappings.reuse_pinned_map(&MAP_PATHS.nat_hairpin_mappings)?;
+    open_skel.maps.nat_hairpin_ct.set_pin_path(&MAP_PATHS.nat_hairpin_ct)?;
+    open_skel.maps.nat_hairpin_ct.reuse_pinned_map(&MAP_PATHS.nat_hairpin_ct)?;
+    open_skel.maps.nat_hairpin_client.set_pin_path(&MAP_PATHS.nat_hairpin_client)?;
+    open_skel.maps.nat_hairpin_client.reuse_pinned_map(&MAP_PATHS.nat_hairpin_client)?;
+
     open_skel.maps.flow_v_dns_map.set_pin_path(&MAP_PATHS.flow_verdict_dns_map)?;
     open_skel.maps.flow_v_dns_map.reuse_pinned_map(&MAP_PATHS.flow_verdict_dns_map)?;
 
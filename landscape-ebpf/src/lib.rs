@@ -28,6 +28,15 @@ static MAP_PATHS: Lazy<LandscapeMapPath> = Lazy::new(|| {
     let paths = LandscapeMapPath {
         wan_ip: PathBuf::from(format!("{}/wan_ipv4_binding", ebpf_map_path)),
         static_nat_mappings: PathBuf::from(format!("{}/nat_static_mapping", ebpf_map_path)),
+        nat_hairpin_mappings: PathBuf::from(format!("{}/nat_hairpin_mappings", ebpf_map_path)),
+        nat_hairpin_ct: PathBuf::from(format!("{}/nat_hairpin_ct", ebpf_map_path)),
+        nat_hairpin_client: PathBuf::from(format!("{}/nat_hairpin_client", ebpf_map_path)),
+        nat6_config: PathBuf::from(format!("{}/nat6_config_map", ebpf_map_path)),
+        nat_ct_config: PathBuf::from(format!("{}/nat_ct_config_map", ebpf_map_path)),
+        nat_flow_behavior: PathBuf::from(format!("{}/nat_flow_behavior_map", ebpf_map_path)),
+        snat_pool: PathBuf::from(format!("{}/snat_pool_map", ebpf_map_path)),
+        snat_flow_addr: PathBuf::from(format!("{}/snat_flow_addr_map", ebpf_map_path)),
+        snat_subnet_addr: PathBuf::from(format!("{}/snat_subnet_addr_map", ebpf_map_path)),
 
         firewall_ipv4_block: PathBuf::from(format!("{}/firewall_block_ip4_map", ebpf_map_path)),
         firewall_ipv6_block: PathBuf::from(format!("{}/firewall_block_ip6_map", ebpf_map_path)),
@@ -64,6 +73,18 @@ static MAP_PATHS: Lazy<LandscapeMapPath> = Lazy::new(|| {
 pub(crate) struct LandscapeMapPath {
     pub wan_ip: PathBuf,
     pub static_nat_mappings: PathBuf,
+    /// NAT 回流
+    pub nat_hairpin_mappings: PathBuf,
+    pub nat_hairpin_ct: PathBuf,
+    pub nat_hairpin_client: PathBuf,
+    /// NPTv6 / NAT66
+    pub nat6_config: PathBuf,
+    /// NAT 连接跟踪超时与映射数量限制
+    pub nat_ct_config: PathBuf,
+    pub nat_flow_behavior: PathBuf,
+    pub snat_pool: PathBuf,
+    pub snat_flow_addr: PathBuf,
+    pub snat_subnet_addr: PathBuf,
 
     // 防火墙黑名单
     pub firewall_ipv4_block: PathBuf,
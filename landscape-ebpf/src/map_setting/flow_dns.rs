@@ -1,3 +1,4 @@
+use std::net::IpAddr;
 use std::os::fd::{AsFd, AsRawFd};
 
 use landscape_common::flow::FlowDnsMarkInfo;
@@ -70,6 +71,44 @@ pub fn update_flow_dns_rule(flow_id: u32, data: Vec<FlowDnsMarkInfo>) {
     }
 }
 
+/// 删除 DNS 缓存刷新后不再使用的 IP
+pub fn del_flow_dns_rule(flow_id: u32, ips: Vec<IpAddr>) {
+    if ips.is_empty() {
+        return;
+    }
+    let flow_dns_match_map =
+        libbpf_rs::MapHandle::from_pinned_path(&MAP_PATHS.flow_verdict_dns_map).unwrap();
+
+    let key_value = unsafe { plain::as_bytes(&flow_id) };
+    if let Ok(Some(fd_id_arr)) = flow_dns_match_map.lookup(key_value, MapFlags::ANY) {
+        if let Ok(fd) = plain::from_bytes::<i32>(&fd_id_arr) {
+            let Ok(map) = libbpf_rs::MapHandle::from_map_id(*fd as u32) else {
+                return;
+            };
+            for ip in ips {
+                let key = convert_flow_dns_match_key(ip);
+                // 可能已经被 LRU 淘汰
+                let _ = map.delete(unsafe { plain::as_bytes(&key) });
+            }
+        }
+    }
+}
+
+fn convert_flow_dns_match_key(ip: IpAddr) -> flow_dns_match_key {
+    let mut key = flow_dns_match_key::default();
+    match ip {
+        IpAddr::V4(ipv4_addr) => {
+            key.addr.ip = ipv4_addr.to_bits().to_be();
+            key.l3_protocol = LANDSCAPE_IPV4_TYPE;
+        }
+        IpAddr::V6(ipv6_addr) => {
+            key.addr = u_inet_addr { bits: ipv6_addr.to_bits().to_be_bytes() };
+            key.l3_protocol = LANDSCAPE_IPV6_TYPE;
+        }
+    };
+    key
+}
+
 fn update_flow_dns_rules<'obj, T>(map: &T, ips: Vec<FlowDnsMarkInfo>) -> libbpf_rs::Result<()>
 where
     T: MapCore,
@@ -83,20 +122,10 @@ where
     let count = ips.len() as u32;
 
     for FlowDnsMarkInfo { ip, mark, priority } in ips.into_iter() {
-        let mut key = flow_dns_match_key::default();
+        let key = convert_flow_dns_match_key(ip);
         let mut value = flow_dns_match_value::default();
         value.mark = mark;
         value.priority = priority;
-        match ip {
-            std::net::IpAddr::V4(ipv4_addr) => {
-                key.addr.ip = ipv4_addr.to_bits().to_be();
-                key.l3_protocol = LANDSCAPE_IPV4_TYPE;
-            }
-            std::net::IpAddr::V6(ipv6_addr) => {
-                key.addr = u_inet_addr { bits: ipv6_addr.to_bits().to_be_bytes() };
-                key.l3_protocol = LANDSCAPE_IPV6_TYPE;
-            }
-        };
 
         keys.extend_from_slice(unsafe { plain::as_bytes(&key) });
         values.extend_from_slice(unsafe { plain::as_bytes(&value) });
@@ -19,6 +19,7 @@ pub mod flow_dns;
 pub mod flow_target;
 pub mod flow_wanip;
 pub mod metric;
+pub mod nat;
 pub mod route;
 
 pub(crate) fn init_path(paths: LandscapeMapPath) {
@@ -29,6 +30,15 @@ pub(crate) fn init_path(paths: LandscapeMapPath) {
 
     landscape_open.maps.wan_ipv4_binding.set_pin_path(&paths.wan_ip).unwrap();
     landscape_open.maps.static_nat_mappings.set_pin_path(&paths.static_nat_mappings).unwrap();
+    landscape_open.maps.nat_hairpin_mappings.set_pin_path(&paths.nat_hairpin_mappings).unwrap();
+    landscape_open.maps.nat_hairpin_ct.set_pin_path(&paths.nat_hairpin_ct).unwrap();
+    landscape_open.maps.nat_hairpin_client.set_pin_path(&paths.nat_hairpin_client).unwrap();
+    landscape_open.maps.nat6_config_map.set_pin_path(&paths.nat6_config).unwrap();
+    landscape_open.maps.nat_ct_config_map.set_pin_path(&paths.nat_ct_config).unwrap();
+    landscape_open.maps.nat_flow_behavior_map.set_pin_path(&paths.nat_flow_behavior).unwrap();
+    landscape_open.maps.snat_pool_map.set_pin_path(&paths.snat_pool).unwrap();
+    landscape_open.maps.snat_flow_addr_map.set_pin_path(&paths.snat_flow_addr).unwrap();
+    landscape_open.maps.snat_subnet_addr_map.set_pin_path(&paths.snat_subnet_addr).unwrap();
 
     // firewall
     landscape_open.maps.firewall_block_ip4_map.set_pin_path(&paths.firewall_ipv4_block).unwrap();
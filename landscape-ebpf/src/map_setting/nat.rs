@@ -0,0 +1,309 @@
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use landscape_common::config::nat::{
+    NatBehavior, NatConfig, Nptv6Translation, SnatPoolConfig, StaticNatMappingItem,
+    SNAT_POOL_MAX_ADDRS,
+};
+use libbpf_rs::{MapCore, MapFlags};
+
+use super::share_map::types::{
+    nat6_config, nat_behavior, nat_ct_config, nat_hairpin_key, nat_hairpin_value, nat_mapping_key,
+    nat_mapping_value, snat_flow_key, snat_pool, snat_subnet_key,
+};
+use crate::MAP_PATHS;
+
+const NAT_MAPPING_INGRESS: u8 = 0;
+const NAT_MAPPING_EGRESS: u8 = 1;
+
+/// 每条映射写入两个方向的记录
+fn convert_static_mapping(
+    item: &StaticNatMappingItem,
+) -> [(nat_mapping_key, nat_mapping_value); 2] {
+    let wan_addr = item.wan_addr.to_bits().to_be();
+    let wan_port = item.wan_port.to_be();
+    let lan_addr = item.lan_addr.to_bits().to_be();
+    let lan_port = item.lan_port.to_be();
+
+    // 外部访问 WAN 地址端口 -> 内部主机
+    let mut ingress_key = nat_mapping_key::default();
+    ingress_key.gress = NAT_MAPPING_INGRESS;
+    ingress_key.l4proto = item.l4_protocol;
+    ingress_key.from_port = wan_port;
+    ingress_key.from_addr.ip = wan_addr;
+
+    let mut ingress_value = nat_mapping_value::default();
+    ingress_value.addr.ip = lan_addr;
+    ingress_value.port = lan_port;
+    ingress_value.is_static = 1;
+
+    // 内部主机回程 -> WAN 地址端口
+    let mut egress_key = nat_mapping_key::default();
+    egress_key.gress = NAT_MAPPING_EGRESS;
+    egress_key.l4proto = item.l4_protocol;
+    egress_key.from_port = lan_port;
+    egress_key.from_addr.ip = lan_addr;
+
+    let mut egress_value = nat_mapping_value::default();
+    egress_value.addr.ip = wan_addr;
+    egress_value.port = wan_port;
+    egress_value.is_static = 1;
+
+    [(ingress_key, ingress_value), (egress_key, egress_value)]
+}
+
+/// 回流映射只需要 WAN 地址端口到内部主机的方向
+fn convert_hairpin_mapping(item: &StaticNatMappingItem) -> (nat_hairpin_key, nat_hairpin_value) {
+    let mut key = nat_hairpin_key::default();
+    key.l4proto = item.l4_protocol;
+    key.port = item.wan_port.to_be();
+    key.addr = item.wan_addr.to_bits().to_be();
+
+    let mut value = nat_hairpin_value::default();
+    value.addr = item.lan_addr.to_bits().to_be();
+    value.port = item.lan_port.to_be();
+    (key, value)
+}
+
+/// 写入失败时可能只写入了一部分, 由调用者删除后重试
+pub fn add_static_nat_mappings(items: &[StaticNatMappingItem]) -> libbpf_rs::Result<()> {
+    if items.is_empty() {
+        return Ok(());
+    }
+    let static_nat_mappings =
+        libbpf_rs::MapHandle::from_pinned_path(&MAP_PATHS.static_nat_mappings).unwrap();
+
+    let mut keys = vec![];
+    let mut values = vec![];
+    let count = items.len() as u32 * 2;
+    for item in items.iter() {
+        for (key, value) in convert_static_mapping(item) {
+            keys.extend_from_slice(unsafe { plain::as_bytes(&key) });
+            values.extend_from_slice(unsafe { plain::as_bytes(&value) });
+        }
+    }
+
+    static_nat_mappings.update_batch(&keys, &values, count, MapFlags::ANY, MapFlags::ANY)?;
+
+    let hairpin_items: Vec<_> = items.iter().filter(|item| item.hairpin).collect();
+    if hairpin_items.is_empty() {
+        return Ok(());
+    }
+    let nat_hairpin_mappings =
+        libbpf_rs::MapHandle::from_pinned_path(&MAP_PATHS.nat_hairpin_mappings).unwrap();
+    let mut keys = vec![];
+    let mut values = vec![];
+    let count = hairpin_items.len() as u32;
+    for item in hairpin_items {
+        let (key, value) = convert_hairpin_mapping(item);
+        keys.extend_from_slice(unsafe { plain::as_bytes(&key) });
+        values.extend_from_slice(unsafe { plain::as_bytes(&value) });
+    }
+    nat_hairpin_mappings.update_batch(&keys, &values, count, MapFlags::ANY, MapFlags::ANY)
+}
+
+pub fn del_static_nat_mappings(items: &[StaticNatMappingItem]) {
+    if items.is_empty() {
+        return;
+    }
+    let static_nat_mappings =
+        libbpf_rs::MapHandle::from_pinned_path(&MAP_PATHS.static_nat_mappings).unwrap();
+
+    for item in items.iter() {
+        for (key, _) in convert_static_mapping(item) {
+            // 记录可能已经不存在
+            let _ = static_nat_mappings.delete(unsafe { plain::as_bytes(&key) });
+        }
+    }
+
+    if !items.iter().any(|item| item.hairpin) {
+        return;
+    }
+    let nat_hairpin_mappings =
+        libbpf_rs::MapHandle::from_pinned_path(&MAP_PATHS.nat_hairpin_mappings).unwrap();
+    for item in items.iter().filter(|item| item.hairpin) {
+        let (key, _) = convert_hairpin_mapping(item);
+        let _ = nat_hairpin_mappings.delete(unsafe { plain::as_bytes(&key) });
+    }
+}
+
+const NAT6_MODE_NPTV6: u8 = 1;
+const NAT6_MODE_MASQUERADE: u8 = 2;
+
+fn update_nat6_config(ifindex: u32, config: nat6_config) {
+    let nat6_config_map = libbpf_rs::MapHandle::from_pinned_path(&MAP_PATHS.nat6_config).unwrap();
+    let key = ifindex;
+    if let Err(e) = nat6_config_map.update(
+        unsafe { plain::as_bytes(&key) },
+        unsafe { plain::as_bytes(&config) },
+        MapFlags::ANY,
+    ) {
+        tracing::error!("update nat6 config error: {e:?}");
+    }
+}
+
+pub fn set_nptv6_config(ifindex: u32, translation: &Nptv6Translation) {
+    let mut config = nat6_config::default();
+    config.mode = NAT6_MODE_NPTV6;
+    config.adj_word = translation.adj_word;
+    config.egress_adj = translation.egress_adj;
+    config.ingress_adj = translation.ingress_adj;
+    config.inner_prefix.bits = translation.inner_prefix.octets();
+    config.outer_prefix.bits = translation.outer_prefix.octets();
+    config.prefix_mask.bits = Nptv6Translation::prefix_mask(translation.prefix_len).to_be_bytes();
+    update_nat6_config(ifindex, config);
+}
+
+pub fn set_nat66_masquerade_config(ifindex: u32, wan_addr: Ipv6Addr) {
+    let mut config = nat6_config::default();
+    config.mode = NAT6_MODE_MASQUERADE;
+    config.wan_addr.bits = wan_addr.octets();
+    update_nat6_config(ifindex, config);
+}
+
+pub fn del_nat6_config(ifindex: u32) {
+    let nat6_config_map = libbpf_rs::MapHandle::from_pinned_path(&MAP_PATHS.nat6_config).unwrap();
+    let key = ifindex;
+    // 记录可能已经不存在
+    let _ = nat6_config_map.delete(unsafe { plain::as_bytes(&key) });
+}
+
+const NS_PER_SEC: u64 = 1_000_000_000;
+
+/// 运行中的 NAT 程序每个数据包都会读取, 更新后立即生效
+pub fn set_nat_ct_config(ifindex: u32, config: &NatConfig) {
+    let mut value = nat_ct_config::default();
+    value.tcp_syn_timeout = config.tcp_syn_timeout as u64 * NS_PER_SEC;
+    value.tcp_established_timeout = config.tcp_established_timeout as u64 * NS_PER_SEC;
+    value.tcp_closing_timeout = config.tcp_closing_timeout as u64 * NS_PER_SEC;
+    value.udp_timeout = config.udp_timeout as u64 * NS_PER_SEC;
+    value.icmp_timeout = config.icmp_timeout as u64 * NS_PER_SEC;
+    value.max_mappings_per_host = config.max_mappings_per_host;
+    value.mapping_policy = config.behavior.mapping.as_u8();
+    value.filtering_policy = config.behavior.filtering.as_u8();
+
+    let nat_ct_config_map =
+        libbpf_rs::MapHandle::from_pinned_path(&MAP_PATHS.nat_ct_config).unwrap();
+    let key = ifindex;
+    if let Err(e) = nat_ct_config_map.update(
+        unsafe { plain::as_bytes(&key) },
+        unsafe { plain::as_bytes(&value) },
+        MapFlags::ANY,
+    ) {
+        tracing::error!("update nat ct config error: {e:?}");
+    }
+}
+
+pub fn del_nat_ct_config(ifindex: u32) {
+    let nat_ct_config_map =
+        libbpf_rs::MapHandle::from_pinned_path(&MAP_PATHS.nat_ct_config).unwrap();
+    let key = ifindex;
+    let _ = nat_ct_config_map.delete(unsafe { plain::as_bytes(&key) });
+}
+
+/// 设置 flow 覆盖的 NAT 映射与过滤行为
+pub fn set_flow_nat_behavior(flow_id: u32, behavior: &NatBehavior) {
+    let mut value = nat_behavior::default();
+    value.mapping_policy = behavior.mapping.as_u8();
+    value.filtering_policy = behavior.filtering.as_u8();
+
+    let nat_flow_behavior_map =
+        libbpf_rs::MapHandle::from_pinned_path(&MAP_PATHS.nat_flow_behavior).unwrap();
+    let key = flow_id;
+    if let Err(e) = nat_flow_behavior_map.update(
+        unsafe { plain::as_bytes(&key) },
+        unsafe { plain::as_bytes(&value) },
+        MapFlags::ANY,
+    ) {
+        tracing::error!("update flow nat behavior error: {e:?}");
+    }
+}
+
+pub fn del_flow_nat_behavior(flow_id: u32) {
+    let nat_flow_behavior_map =
+        libbpf_rs::MapHandle::from_pinned_path(&MAP_PATHS.nat_flow_behavior).unwrap();
+    let key = flow_id;
+    let _ = nat_flow_behavior_map.delete(unsafe { plain::as_bytes(&key) });
+}
+
+fn snat_subnet_key(ifindex: u32, subnet: Ipv4Addr, prefix_len: u8) -> snat_subnet_key {
+    let mut key = snat_subnet_key::default();
+    key.prefixlen = 32 + prefix_len as u32;
+    key.ifindex = ifindex;
+    let mask = u32::MAX.checked_shl(32 - prefix_len.min(32) as u32).unwrap_or(0);
+    key.addr = (u32::from(subnet) & mask).to_be();
+    key
+}
+
+/// 写入 WAN 网卡的 SNAT 地址池与 flow / 网段指定的地址
+pub fn set_snat_pool(ifindex: u32, config: &SnatPoolConfig) {
+    if !config.addrs.is_empty() {
+        let mut value = snat_pool::default();
+        let addrs = &config.addrs[..config.addrs.len().min(SNAT_POOL_MAX_ADDRS)];
+        if addrs.len() < config.addrs.len() {
+            tracing::warn!("snat pool only use the first {SNAT_POOL_MAX_ADDRS} addresses");
+        }
+        value.count = addrs.len() as u32;
+        value.policy = config.policy.as_u8();
+        for (index, addr) in addrs.iter().enumerate() {
+            value.addrs[index] = u32::from(*addr).to_be();
+        }
+
+        let snat_pool_map = libbpf_rs::MapHandle::from_pinned_path(&MAP_PATHS.snat_pool).unwrap();
+        let key = ifindex;
+        if let Err(e) = snat_pool_map.update(
+            unsafe { plain::as_bytes(&key) },
+            unsafe { plain::as_bytes(&value) },
+            MapFlags::ANY,
+        ) {
+            tracing::error!("update snat pool error: {e:?}");
+        }
+    }
+
+    let snat_flow_addr_map =
+        libbpf_rs::MapHandle::from_pinned_path(&MAP_PATHS.snat_flow_addr).unwrap();
+    for item in config.flow_overrides.iter() {
+        let key = snat_flow_key { ifindex, flow_id: item.flow_id };
+        let value = u32::from(item.addr).to_be();
+        if let Err(e) = snat_flow_addr_map.update(
+            unsafe { plain::as_bytes(&key) },
+            &value.to_ne_bytes(),
+            MapFlags::ANY,
+        ) {
+            tracing::error!("update snat flow addr error: {e:?}");
+        }
+    }
+
+    let snat_subnet_addr_map =
+        libbpf_rs::MapHandle::from_pinned_path(&MAP_PATHS.snat_subnet_addr).unwrap();
+    for item in config.subnet_overrides.iter() {
+        let key = snat_subnet_key(ifindex, item.subnet, item.prefix_len);
+        let value = u32::from(item.addr).to_be();
+        if let Err(e) = snat_subnet_addr_map.update(
+            unsafe { plain::as_bytes(&key) },
+            &value.to_ne_bytes(),
+            MapFlags::ANY,
+        ) {
+            tracing::error!("update snat subnet addr error: {e:?}");
+        }
+    }
+}
+
+pub fn del_snat_pool(ifindex: u32, config: &SnatPoolConfig) {
+    let snat_pool_map = libbpf_rs::MapHandle::from_pinned_path(&MAP_PATHS.snat_pool).unwrap();
+    let key = ifindex;
+    let _ = snat_pool_map.delete(unsafe { plain::as_bytes(&key) });
+
+    let snat_flow_addr_map =
+        libbpf_rs::MapHandle::from_pinned_path(&MAP_PATHS.snat_flow_addr).unwrap();
+    for item in config.flow_overrides.iter() {
+        let key = snat_flow_key { ifindex, flow_id: item.flow_id };
+        let _ = snat_flow_addr_map.delete(unsafe { plain::as_bytes(&key) });
+    }
+
+    let snat_subnet_addr_map =
+        libbpf_rs::MapHandle::from_pinned_path(&MAP_PATHS.snat_subnet_addr).unwrap();
+    for item in config.subnet_overrides.iter() {
+        let key = snat_subnet_key(ifindex, item.subnet, item.prefix_len);
+        let _ = snat_subnet_addr_map.delete(unsafe { plain::as_bytes(&key) });
+    }
+}
@@ -1,20 +1,27 @@
 use std::{
+    collections::HashMap,
     mem::MaybeUninit,
     net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    sync::Mutex,
 };
 
 use land_nat::{
-    types::{nat_conn_event, nat_mapping_key, nat_mapping_value, u_inet_addr},
+    types::{
+        nat_conn_event, nat_mapping_key, nat_mapping_value, nat_timer_key, nat_timer_value,
+        u_inet_addr,
+    },
     *,
 };
 use landscape_common::{
-    config::nat::NatConfig,
+    config::nat::{NatConfig, NatSession, NatSessionKey, NatSessionQuery},
     event::nat::{NatEvent, NatEventType},
+    utils::time::get_monotonic_time_ns,
 };
 use libbpf_rs::{
     skel::{OpenSkel, SkelBuilder},
-    MapCore, MapFlags, TC_EGRESS, TC_INGRESS,
+    MapCore, MapFlags, MapHandle, TC_EGRESS, TC_INGRESS,
 };
+use once_cell::sync::Lazy;
 use tokio::sync::oneshot;
 
 use crate::{
@@ -36,6 +43,24 @@ pub(crate) mod land_nat {
 
 unsafe impl plain::Plain for nat_conn_event {}
 unsafe impl plain::Plain for u_inet_addr {}
+unsafe impl plain::Plain for nat_mapping_key {}
+unsafe impl plain::Plain for nat_mapping_value {}
+unsafe impl plain::Plain for nat_timer_key {}
+unsafe impl plain::Plain for nat_timer_value {}
+
+const NAT_MAPPING_INGRESS: u8 = 0;
+const NAT_MAPPING_EGRESS: u8 = 1;
+const NS_PER_SEC: u64 = 1_000_000_000;
+
+/// 运行中的 NAT 服务的会话相关 map, 以 WAN 网卡 ifindex 为 key
+struct NatSessionMaps {
+    mappings: MapHandle,
+    timers: MapHandle,
+    host_mapping_count: MapHandle,
+}
+
+static NAT_SESSION_MAPS: Lazy<Mutex<HashMap<u32, NatSessionMaps>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
 
 impl From<&nat_conn_event> for NatEvent {
     fn from(ev: &nat_conn_event) -> Self {
@@ -84,6 +109,12 @@ pub fn init_nat(
     landscape_open.maps.wan_ipv4_binding.set_pin_path(&MAP_PATHS.wan_ip).unwrap();
     landscape_open.maps.static_nat_mappings.set_pin_path(&MAP_PATHS.static_nat_mappings).unwrap();
     landscape_open.maps.nat_conn_events.set_pin_path(&MAP_PATHS.nat_conn_events).unwrap();
+    landscape_open.maps.nat6_config_map.set_pin_path(&MAP_PATHS.nat6_config).unwrap();
+    landscape_open.maps.nat_ct_config_map.set_pin_path(&MAP_PATHS.nat_ct_config).unwrap();
+    landscape_open.maps.nat_flow_behavior_map.set_pin_path(&MAP_PATHS.nat_flow_behavior).unwrap();
+    landscape_open.maps.snat_pool_map.set_pin_path(&MAP_PATHS.snat_pool).unwrap();
+    landscape_open.maps.snat_flow_addr_map.set_pin_path(&MAP_PATHS.snat_flow_addr).unwrap();
+    landscape_open.maps.snat_subnet_addr_map.set_pin_path(&MAP_PATHS.snat_subnet_addr).unwrap();
     if let Err(e) = landscape_open.maps.wan_ipv4_binding.reuse_pinned_map(&MAP_PATHS.wan_ip) {
         tracing::error!("error: {e:?}");
     }
@@ -97,6 +128,37 @@ pub fn init_nat(
     {
         tracing::error!("error: {e:?}");
     }
+
+    if let Err(e) = landscape_open.maps.nat6_config_map.reuse_pinned_map(&MAP_PATHS.nat6_config) {
+        tracing::error!("error: {e:?}");
+    }
+
+    if let Err(e) = landscape_open.maps.nat_ct_config_map.reuse_pinned_map(&MAP_PATHS.nat_ct_config)
+    {
+        tracing::error!("error: {e:?}");
+    }
+
+    if let Err(e) =
+        landscape_open.maps.nat_flow_behavior_map.reuse_pinned_map(&MAP_PATHS.nat_flow_behavior)
+    {
+        tracing::error!("error: {e:?}");
+    }
+
+    if let Err(e) = landscape_open.maps.snat_pool_map.reuse_pinned_map(&MAP_PATHS.snat_pool) {
+        tracing::error!("error: {e:?}");
+    }
+
+    if let Err(e) =
+        landscape_open.maps.snat_flow_addr_map.reuse_pinned_map(&MAP_PATHS.snat_flow_addr)
+    {
+        tracing::error!("error: {e:?}");
+    }
+
+    if let Err(e) =
+        landscape_open.maps.snat_subnet_addr_map.reuse_pinned_map(&MAP_PATHS.snat_subnet_addr)
+    {
+        tracing::error!("error: {e:?}");
+    }
     landscape_open.maps.rodata_data.tcp_range_start = config.tcp_range.start;
     landscape_open.maps.rodata_data.tcp_range_end = config.tcp_range.end;
     landscape_open.maps.rodata_data.udp_range_start = config.udp_range.start;
@@ -110,6 +172,21 @@ pub fn init_nat(
     }
 
     let landscape_skel = landscape_open.load().unwrap();
+    crate::map_setting::nat::set_nat_ct_config(ifindex as u32, &config);
+
+    match (
+        MapHandle::try_from(&landscape_skel.maps.nat_mappings),
+        MapHandle::try_from(&landscape_skel.maps.map_mapping_timer),
+        MapHandle::try_from(&landscape_skel.maps.nat_host_mapping_count),
+    ) {
+        (Ok(mappings), Ok(timers), Ok(host_mapping_count)) => {
+            NAT_SESSION_MAPS
+                .lock()
+                .unwrap()
+                .insert(ifindex as u32, NatSessionMaps { mappings, timers, host_mapping_count });
+        }
+        _ => tracing::error!("failed to get nat session maps of ifindex: {ifindex}"),
+    }
 
     // let (nat_conn_events_tx, mut nat_conn_events_rx) =
     //     tokio::sync::mpsc::unbounded_channel::<Box<NatEvent>>();
@@ -151,6 +228,165 @@ pub fn init_nat(
     let _ = service_status.blocking_recv();
     drop(nat_egress_hook);
     drop(nat_ingress_hook);
+    NAT_SESSION_MAPS.lock().unwrap().remove(&(ifindex as u32));
+    crate::map_setting::nat::del_nat_ct_config(ifindex as u32);
+}
+
+fn read_map_value<T: plain::Plain + Default>(bytes: &[u8]) -> Option<T> {
+    let mut value = T::default();
+    plain::copy_from_bytes(&mut value, bytes).ok()?;
+    Some(value)
+}
+
+fn inet_addr_to_ipv4(addr: &u_inet_addr) -> Ipv4Addr {
+    Ipv4Addr::from(u32::from_be(unsafe { addr.ip }))
+}
+
+/// 列出 NAT 服务中的动态映射, 服务未运行时返回 None
+pub fn list_nat_sessions(ifindex: u32, query: &NatSessionQuery) -> Option<Vec<NatSession>> {
+    let maps = NAT_SESSION_MAPS.lock().unwrap();
+    let maps = maps.get(&ifindex)?;
+    let now = get_monotonic_time_ns().unwrap_or_default();
+
+    let mut result = vec![];
+    for key_bytes in maps.mappings.keys() {
+        let Some(key) = read_map_value::<nat_mapping_key>(&key_bytes) else {
+            continue;
+        };
+        if key.gress != NAT_MAPPING_EGRESS {
+            continue;
+        }
+        let Ok(Some(value_bytes)) = maps.mappings.lookup(&key_bytes, MapFlags::ANY) else {
+            continue;
+        };
+        let Some(value) = read_map_value::<nat_mapping_value>(&value_bytes) else {
+            continue;
+        };
+        if value.is_static != 0 {
+            continue;
+        }
+
+        let mut timer_key = nat_timer_key::default();
+        timer_key.l4proto = key.l4proto;
+        timer_key.pair_ip.src_addr = key.from_addr;
+        timer_key.pair_ip.src_port = key.from_port;
+        timer_key.pair_ip.dst_addr = value.addr;
+        timer_key.pair_ip.dst_port = value.port;
+        let timer = maps
+            .timers
+            .lookup(unsafe { plain::as_bytes(&timer_key) }, MapFlags::ANY)
+            .ok()
+            .flatten()
+            .and_then(|bytes| read_map_value::<nat_timer_value>(&bytes));
+        let (age, remaining_timeout) = match timer {
+            Some(timer) => (
+                now.saturating_sub(timer.create_time) / NS_PER_SEC,
+                timer.expire_time.saturating_sub(now) / NS_PER_SEC,
+            ),
+            None => (0, 0),
+        };
+
+        let session = NatSession {
+            l4proto: key.l4proto,
+            internal_addr: inet_addr_to_ipv4(&key.from_addr),
+            internal_port: u16::from_be(key.from_port),
+            mapped_addr: inet_addr_to_ipv4(&value.addr),
+            mapped_port: u16::from_be(value.port),
+            remote_addr: inet_addr_to_ipv4(&value.trigger_addr),
+            remote_port: u16::from_be(value.trigger_port),
+            flow_id: value.flow_id,
+            age,
+            remaining_timeout,
+        };
+        if query.matches(&session) {
+            result.push(session);
+        }
+    }
+    Some(result)
+}
+
+/// 删除内网端点的动态映射以及反向映射, 之后的数据包会重新建立映射
+/// 映射行为不是 Endpoint-Independent 时一个内网端点会有多条映射, 会一并删除
+/// 返回是否找到了映射
+pub fn evict_nat_session(ifindex: u32, session: &NatSessionKey) -> bool {
+    let maps = NAT_SESSION_MAPS.lock().unwrap();
+    let Some(maps) = maps.get(&ifindex) else {
+        return false;
+    };
+
+    let internal_addr = u32::from(session.internal_addr).to_be();
+    let internal_port = session.internal_port.to_be();
+    // 先收集再删除, 避免删除影响 map 的遍历
+    let egress_keys: Vec<Vec<u8>> = maps
+        .mappings
+        .keys()
+        .filter(|key_bytes| {
+            read_map_value::<nat_mapping_key>(key_bytes).is_some_and(|key| {
+                key.gress == NAT_MAPPING_EGRESS
+                    && key.l4proto == session.l4proto
+                    && key.from_port == internal_port
+                    && unsafe { key.from_addr.ip } == internal_addr
+            })
+        })
+        .collect();
+
+    let mut found = false;
+    let mut timer_deleted = false;
+    for egress_key_bytes in egress_keys {
+        let Ok(Some(value_bytes)) = maps.mappings.lookup(&egress_key_bytes, MapFlags::ANY) else {
+            continue;
+        };
+        let Some(value) = read_map_value::<nat_mapping_value>(&value_bytes) else {
+            continue;
+        };
+        // 静态映射由端口转发规则管理
+        if value.is_static != 0 {
+            continue;
+        }
+
+        let mut ingress_key = nat_mapping_key::default();
+        ingress_key.gress = NAT_MAPPING_INGRESS;
+        ingress_key.l4proto = session.l4proto;
+        ingress_key.from_port = value.port;
+        ingress_key.from_addr = value.addr;
+
+        let mut timer_key = nat_timer_key::default();
+        timer_key.l4proto = session.l4proto;
+        timer_key.pair_ip.src_addr.ip = internal_addr;
+        timer_key.pair_ip.src_port = internal_port;
+        timer_key.pair_ip.dst_addr = value.addr;
+        timer_key.pair_ip.dst_port = value.port;
+
+        timer_deleted |= maps.timers.delete(unsafe { plain::as_bytes(&timer_key) }).is_ok();
+        let _ = maps.mappings.delete(&egress_key_bytes);
+        let _ = maps.mappings.delete(unsafe { plain::as_bytes(&ingress_key) });
+        found = true;
+    }
+    // 删除 timer 时不会触发回调, 由回调维护的计数需要重新统计
+    if timer_deleted {
+        recount_host_mappings(maps, internal_addr);
+    }
+    found
+}
+
+/// 按照 timer 重新统计内网主机的动态映射数量
+/// 不在用户态读取后扣减, 与 eBPF 中的增减交错时偏差不会累积
+fn recount_host_mappings(maps: &NatSessionMaps, host_addr: u32) {
+    let count = maps
+        .timers
+        .keys()
+        .filter_map(|key_bytes| read_map_value::<nat_timer_key>(&key_bytes))
+        .filter(|key| unsafe { key.pair_ip.src_addr.ip } == host_addr)
+        .count() as u32;
+
+    let host_key = host_addr.to_ne_bytes();
+    if count == 0 {
+        let _ = maps.host_mapping_count.delete(&host_key);
+    } else if let Err(e) =
+        maps.host_mapping_count.update(&host_key, &count.to_ne_bytes(), MapFlags::ANY)
+    {
+        tracing::error!("update host mapping count error: {e:?}");
+    }
 }
 
 #[allow(dead_code)]
@@ -168,6 +404,7 @@ where
         l4proto: 6,
         from_port: pn,
         from_addr: u_inet_addr { ip: an },
+        ..Default::default()
     };
     let kn = unsafe { plain::as_bytes(&kn) };
 
@@ -177,7 +414,9 @@ where
         port: pc,
         trigger_port: 0,
         is_static: 1,
-        _pad: [0; 3],
+        filtering: 0,
+        flow_id: 0,
+        mapping: 0,
         active_time: 0,
     };
     let vn = unsafe { plain::as_bytes(&vn) };
@@ -187,6 +426,7 @@ where
         l4proto: 6,
         from_port: pc,
         from_addr: u_inet_addr { ip: ac },
+        ..Default::default()
     };
     let kc = unsafe { plain::as_bytes(&kc) };
 
@@ -196,7 +436,9 @@ where
         port: pn,
         trigger_port: 0,
         is_static: 1,
-        _pad: [0; 3],
+        filtering: 0,
+        flow_id: 0,
+        mapping: 0,
         active_time: 0,
     };
     let vc = unsafe { plain::as_bytes(&vc) };
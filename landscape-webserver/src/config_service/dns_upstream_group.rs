@@ -0,0 +1,63 @@
+use axum::{
+    extract::{Path, State},
+    routing::{get, post},
+    Json, Router,
+};
+use landscape_common::config::{dns::DnsUpstreamGroupConfig, ConfigId};
+use landscape_common::service::controller_service::ConfigController;
+
+use crate::{api::LandscapeApiResp, error::LandscapeApiResult};
+use crate::{error::LandscapeApiError, LandscapeApp};
+
+pub async fn get_dns_upstream_group_config_paths() -> Router<LandscapeApp> {
+    Router::new()
+        .route("/dns_upstream_groups", get(get_dns_upstream_groups).post(add_dns_upstream_group))
+        .route("/dns_upstream_groups/set_many", post(add_many_dns_upstream_groups))
+        .route(
+            "/dns_upstream_groups/{id}",
+            get(get_dns_upstream_group).delete(del_dns_upstream_group),
+        )
+}
+
+async fn get_dns_upstream_groups(
+    State(state): State<LandscapeApp>,
+) -> LandscapeApiResult<Vec<DnsUpstreamGroupConfig>> {
+    let result = state.dns_upstream_group_service.list().await;
+    LandscapeApiResp::success(result)
+}
+
+async fn get_dns_upstream_group(
+    State(state): State<LandscapeApp>,
+    Path(id): Path<ConfigId>,
+) -> LandscapeApiResult<DnsUpstreamGroupConfig> {
+    let result = state.dns_upstream_group_service.find_by_id(id).await;
+    if let Some(config) = result {
+        LandscapeApiResp::success(config)
+    } else {
+        Err(LandscapeApiError::NotFound(format!("Dns Upstream Group id: {:?}", id)))
+    }
+}
+
+async fn add_many_dns_upstream_groups(
+    State(state): State<LandscapeApp>,
+    Json(groups): Json<Vec<DnsUpstreamGroupConfig>>,
+) -> LandscapeApiResult<()> {
+    state.dns_upstream_group_service.set_list(groups).await;
+    LandscapeApiResp::success(())
+}
+
+async fn add_dns_upstream_group(
+    State(state): State<LandscapeApp>,
+    Json(group): Json<DnsUpstreamGroupConfig>,
+) -> LandscapeApiResult<DnsUpstreamGroupConfig> {
+    let result = state.dns_upstream_group_service.set(group).await;
+    LandscapeApiResp::success(result)
+}
+
+async fn del_dns_upstream_group(
+    State(state): State<LandscapeApp>,
+    Path(id): Path<ConfigId>,
+) -> LandscapeApiResult<()> {
+    state.dns_upstream_group_service.delete(id).await;
+    LandscapeApiResp::success(())
+}
@@ -1,7 +1,9 @@
 pub mod dns_rule;
+pub mod dns_upstream_group;
 pub mod dst_ip_rule;
 pub mod firewall_rule;
 pub mod flow_rule;
+pub mod port_forward;
 
 pub mod geo_ip;
 pub mod geo_site;
@@ -0,0 +1,62 @@
+use axum::{
+    extract::{Path, State},
+    routing::get,
+    Json, Router,
+};
+use landscape_common::service::controller_service::ConfigController;
+use landscape_common::{config::nat::PortForwardRuleConfig, config::ConfigId};
+
+use crate::{error::LandscapeApiError, LandscapeApp};
+
+use crate::{api::LandscapeApiResp, error::LandscapeApiResult};
+
+pub async fn get_port_forward_config_paths() -> Router<LandscapeApp> {
+    Router::new()
+        .route("/port_forwards", get(get_port_forwards).post(add_port_forward))
+        .route("/port_forwards/{id}", get(get_port_forward).delete(del_port_forward))
+}
+
+async fn get_port_forwards(
+    State(state): State<LandscapeApp>,
+) -> LandscapeApiResult<Vec<PortForwardRuleConfig>> {
+    let mut result = state.port_forward_service.list().await;
+    result.sort_by(|a, b| {
+        a.wan_iface_name
+            .cmp(&b.wan_iface_name)
+            .then(a.external_port_start.cmp(&b.external_port_start))
+    });
+    LandscapeApiResp::success(result)
+}
+
+async fn get_port_forward(
+    State(state): State<LandscapeApp>,
+    Path(id): Path<ConfigId>,
+) -> LandscapeApiResult<PortForwardRuleConfig> {
+    let result = state.port_forward_service.find_by_id(id).await;
+    if let Some(config) = result {
+        LandscapeApiResp::success(config)
+    } else {
+        Err(LandscapeApiError::NotFound(format!("Port Forward Rule id: {:?}", id)))
+    }
+}
+
+async fn add_port_forward(
+    State(state): State<LandscapeApp>,
+    Json(port_forward): Json<PortForwardRuleConfig>,
+) -> LandscapeApiResult<PortForwardRuleConfig> {
+    state
+        .port_forward_service
+        .check_conflict(&port_forward)
+        .await
+        .map_err(LandscapeApiError::BadRequest)?;
+    let result = state.port_forward_service.set(port_forward).await;
+    LandscapeApiResp::success(result)
+}
+
+async fn del_port_forward(
+    State(state): State<LandscapeApp>,
+    Path(id): Path<ConfigId>,
+) -> LandscapeApiResult<()> {
+    state.port_forward_service.delete(id).await;
+    LandscapeApiResp::success(())
+}
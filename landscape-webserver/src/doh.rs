@@ -0,0 +1,72 @@
+use std::net::SocketAddr;
+
+use axum::{
+    body::Bytes,
+    extract::{ConnectInfo, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use serde::Deserialize;
+
+use crate::LandscapeApp;
+
+const DNS_MESSAGE_CONTENT_TYPE: &str = "application/dns-message";
+
+/// DNS over HTTPS (RFC 8484)
+pub async fn get_doh_paths() -> Router<LandscapeApp> {
+    Router::new().route("/dns-query", get(doh_get_query).post(doh_post_query))
+}
+
+#[derive(Deserialize)]
+struct DohGetReq {
+    dns: String,
+}
+
+async fn doh_get_query(
+    State(state): State<LandscapeApp>,
+    ConnectInfo(src_addr): ConnectInfo<SocketAddr>,
+    Query(req): Query<DohGetReq>,
+) -> Response {
+    // 部分客户端会携带填充
+    let Ok(message) = URL_SAFE_NO_PAD.decode(req.dns.trim_end_matches('=')) else {
+        return (StatusCode::BAD_REQUEST, "invalid dns param").into_response();
+    };
+    handle_doh_message(state, src_addr, message).await
+}
+
+async fn doh_post_query(
+    State(state): State<LandscapeApp>,
+    ConnectInfo(src_addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let is_dns_message = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.eq_ignore_ascii_case(DNS_MESSAGE_CONTENT_TYPE));
+    if !is_dns_message {
+        return (StatusCode::UNSUPPORTED_MEDIA_TYPE, "unsupported content type").into_response();
+    }
+    handle_doh_message(state, src_addr, body.to_vec()).await
+}
+
+async fn handle_doh_message(
+    state: LandscapeApp,
+    src_addr: SocketAddr,
+    message: Vec<u8>,
+) -> Response {
+    // Web 服务监听所有地址, 只为 LAN 网段以及本机提供解析, 避免成为开放解析器
+    let src_ip = src_addr.ip().to_canonical();
+    if !src_ip.is_loopback() && !state.route_service.is_lan_addr(src_ip).await {
+        return (StatusCode::FORBIDDEN, "not a lan client").into_response();
+    }
+    match state.dns_service.handle_https_message(message, src_addr).await {
+        Some(response) => {
+            ([(header::CONTENT_TYPE, DNS_MESSAGE_CONTENT_TYPE)], response).into_response()
+        }
+        None => (StatusCode::SERVICE_UNAVAILABLE, "dns service unavailable").into_response(),
+    }
+}
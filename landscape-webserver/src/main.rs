@@ -1,21 +1,23 @@
-use std::{net::SocketAddr, path::PathBuf, sync::Arc};
+use std::{net::SocketAddr, path::PathBuf, sync::Arc, time::Duration};
 
 use axum::{handler::HandlerWithoutStateExt, http::StatusCode, routing::get, Router};
 
 use axum_server::tls_rustls::RustlsConfig;
 use colored::Colorize;
 use config_service::{
-    dns_rule::get_dns_rule_config_paths, dst_ip_rule::get_dst_ip_rule_config_paths,
-    firewall_rule::get_firewall_rule_config_paths, flow_rule::get_flow_rule_config_paths,
-    geo_ip::get_geo_ip_config_paths, geo_site::get_geo_site_config_paths,
+    dns_rule::get_dns_rule_config_paths, dns_upstream_group::get_dns_upstream_group_config_paths,
+    dst_ip_rule::get_dst_ip_rule_config_paths, firewall_rule::get_firewall_rule_config_paths,
+    flow_rule::get_flow_rule_config_paths, geo_ip::get_geo_ip_config_paths,
+    geo_site::get_geo_site_config_paths, port_forward::get_port_forward_config_paths,
 };
 use landscape::{
     boot::{boot_check, log::init_logger},
     cert::load_or_generate_cert,
     config_service::{
-        dns_rule::DNSRuleService, dst_ip_rule::DstIpRuleService,
-        firewall_rule::FirewallRuleService, flow_rule::FlowRuleService,
-        geo_ip_service::GeoIpService, geo_site_service::GeoSiteService,
+        dns_rule::DNSRuleService, dns_upstream_group::DNSUpstreamGroupService,
+        dst_ip_rule::DstIpRuleService, firewall_rule::FirewallRuleService,
+        flow_rule::FlowRuleService, geo_ip_service::GeoIpService, geo_site_service::GeoSiteService,
+        port_forward::PortForwardService,
     },
     docker::LandscapeDockerService,
     metric::MetricService,
@@ -42,6 +44,7 @@ mod api;
 mod auth;
 mod config_service;
 mod docker;
+mod doh;
 mod dump;
 mod error;
 mod iface;
@@ -76,11 +79,13 @@ pub struct LandscapeApp {
     pub home_path: PathBuf,
     pub dns_service: LandscapeDnsService,
     pub dns_rule_service: DNSRuleService,
+    pub dns_upstream_group_service: DNSUpstreamGroupService,
     pub flow_rule_service: FlowRuleService,
     pub geo_site_service: GeoSiteService,
     pub fire_wall_rule_service: FirewallRuleService,
     pub dst_ip_rule_service: DstIpRuleService,
     pub geo_ip_service: GeoIpService,
+    pub port_forward_service: PortForwardService,
     pub config_service: LandscapeConfigService,
 
     pub dhcp_v4_server_service: DHCPv4ServerManagerService,
@@ -137,6 +142,8 @@ async fn main() -> LdResult<()> {
         GeoSiteService::new(db_store_provider.clone(), dns_service_tx.clone()).await;
     let dns_rule_service =
         DNSRuleService::new(db_store_provider.clone(), dns_service_tx.clone()).await;
+    let dns_upstream_group_service =
+        DNSUpstreamGroupService::new(db_store_provider.clone(), dns_service_tx.clone()).await;
     let flow_rule_service = FlowRuleService::new(
         db_store_provider.clone(),
         dns_service_tx.clone(),
@@ -144,17 +151,31 @@ async fn main() -> LdResult<()> {
     )
     .await;
 
+    let tls_config = load_or_generate_cert(home_path.clone()).await;
+
+    let metric_service = MetricService::new(home_path.clone()).await;
+
+    let geo_ip_service = GeoIpService::new(
+        db_store_provider.clone(),
+        dst_ip_service_tx.clone(),
+        dns_service_tx.clone(),
+    )
+    .await;
+
     let dns_service = LandscapeDnsService::new(
         dns_service_rx,
         dns_rule_service.clone(),
         flow_rule_service.clone(),
         geo_site_service.clone(),
+        dns_upstream_group_service.clone(),
+        geo_ip_service.clone(),
+        config.dns.clone(),
+        tls_config.clone(),
+        metric_service.data.dns_metric.clone(),
     )
     .await;
     let fire_wall_rule_service = FirewallRuleService::new(db_store_provider.clone()).await;
 
-    let geo_ip_service =
-        GeoIpService::new(db_store_provider.clone(), dst_ip_service_tx.clone()).await;
     let dst_ip_rule_service =
         DstIpRuleService::new(db_store_provider.clone(), geo_ip_service.clone(), dst_ip_service_rx)
             .await;
@@ -162,13 +183,14 @@ async fn main() -> LdResult<()> {
     let config_service =
         LandscapeConfigService::new(config.clone(), db_store_provider.clone()).await;
 
-    let metric_service = MetricService::new(home_path.clone()).await;
-
     let route_service = IpRouteService::new(route_service_rx, db_store_provider.flow_rule_store());
+    let port_forward_service =
+        PortForwardService::new(db_store_provider.clone(), route_service.clone()).await;
     let dhcp_v4_server_service = DHCPv4ServerManagerService::new(
         route_service.clone(),
         db_store_provider.clone(),
         dev_obs.resubscribe(),
+        dns_service.local_zone(),
     )
     .await;
 
@@ -184,7 +206,11 @@ async fn main() -> LdResult<()> {
     let route_wan_service =
         RouteWanServiceManagerService::new(db_store_provider.clone(), dev_obs.resubscribe()).await;
 
-    let docker_service = LandscapeDockerService::new(home_path.clone(), route_service.clone());
+    let docker_service = LandscapeDockerService::new(
+        home_path.clone(),
+        route_service.clone(),
+        dns_service.local_zone(),
+    );
 
     let pppd_service =
         PPPDServiceConfigManagerService::new(db_store_provider.clone(), route_service.clone())
@@ -210,11 +236,13 @@ async fn main() -> LdResult<()> {
         home_path: home_path.clone(),
         dns_service,
         dns_rule_service,
+        dns_upstream_group_service,
         flow_rule_service,
         geo_site_service,
         fire_wall_rule_service,
         dst_ip_rule_service,
         geo_ip_service,
+        port_forward_service,
         config_service,
         metric_service,
         route_service,
@@ -234,7 +262,6 @@ async fn main() -> LdResult<()> {
     };
     // 初始化结束
 
-    let tls_config = load_or_generate_cert(home_path.clone()).await;
     // let tls_config = Arc::new(tls_config);
     // let acceptor = TlsAcceptor::from(tls_config);
 
@@ -292,11 +319,13 @@ async fn main() -> LdResult<()> {
             "/config",
             Router::new()
                 .merge(get_dns_rule_config_paths().await)
+                .merge(get_dns_upstream_group_config_paths().await)
                 .merge(get_firewall_rule_config_paths().await)
                 .merge(get_flow_rule_config_paths().await)
                 .merge(get_geo_site_config_paths().await)
                 .merge(get_geo_ip_config_paths().await)
                 .merge(get_dst_ip_rule_config_paths().await)
+                .merge(get_port_forward_config_paths().await)
                 .with_state(landscape_app_status.clone()),
         )
         .nest(
@@ -328,22 +357,47 @@ async fn main() -> LdResult<()> {
         .nest("/src", source_route)
         // 认证路由
         .nest("/auth", auth::get_auth_route(auth_share));
+    let doh_route = if config.dns.doh_enable {
+        doh::get_doh_paths().await.with_state(landscape_app_status.clone())
+    } else {
+        Router::new()
+    };
     let app = Router::new()
         .nest("/api", api_route)
+        .merge(doh_route)
         .nest("/sock", dump::get_tump_router())
         .route("/foo", get(|| async { "Hi from /foo" }))
         .fallback_service(serve_dir)
         .layer(TraceLayer::new_for_http());
 
+    let server_handle = axum_server::Handle::new();
+    tokio::spawn(shutdown_signal(server_handle.clone()));
+
     axum_server::bind_rustls(addr, RustlsConfig::from_config(tls_config.into()))
-        .serve(app.into_make_service())
+        .handle(server_handle)
+        .serve(app.into_make_service_with_connect_info::<SocketAddr>())
         .await
         .unwrap();
 
+    // 退出前保存 DNS 缓存, 下次启动时恢复 flow 的 DNS 标记, 同时还原 /etc/resolv.conf
+    landscape_app_status.dns_service.shutdown().await;
+
     // axum::serve(listener, app.layer(TraceLayer::new_for_http())).await.unwrap();
     Ok(())
 }
 
+/// 收到 Ctrl+C 或 SIGTERM 后停止 Web 服务
+async fn shutdown_signal(handle: axum_server::Handle) {
+    let mut terminate = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+        .expect("failed to install SIGTERM handler");
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {}
+        _ = terminate.recv() => {}
+    }
+    info!("shutdown signal received");
+    handle.graceful_shutdown(Some(Duration::from_secs(5)));
+}
+
 /// NOT Found
 async fn handle_404() -> (StatusCode, &'static str) {
     (StatusCode::NOT_FOUND, "Not found")
@@ -3,7 +3,10 @@ use axum::{
     routing::{get, post},
     Json, Router,
 };
-use landscape_common::metric::connect::{ConnectKey, ConnectMetric};
+use landscape_common::metric::{
+    connect::{ConnectKey, ConnectMetric},
+    dns::{DnsClientCount, DnsDomainCount, DnsPolicyStats, DnsQueryLog, DnsQueryLogFilter},
+};
 use serde_json::Value;
 
 use crate::{api::LandscapeApiResp, error::LandscapeApiResult};
@@ -15,6 +18,11 @@ pub async fn get_metric_service_paths() -> Router<LandscapeApp> {
         .route("/status", get(get_metric_status))
         .route("/connects", get(get_connects_info))
         .route("/connects/chart", post(get_connect_metric_info))
+        .route("/dns/logs", post(search_dns_query_logs))
+        .route("/dns/top_domains", post(get_dns_top_domains))
+        .route("/dns/top_blocked", post(get_dns_top_blocked_domains))
+        .route("/dns/clients", post(get_dns_client_counts))
+        .route("/dns/policy_stats", get(get_dns_policy_stats))
 }
 
 pub async fn get_metric_status(State(state): State<LandscapeApp>) -> LandscapeApiResult<Value> {
@@ -35,3 +43,43 @@ pub async fn get_connect_metric_info(
     let data = state.metric_service.data.connect_metric.query_metric_by_key(key).await;
     LandscapeApiResp::success(data)
 }
+
+pub async fn search_dns_query_logs(
+    State(state): State<LandscapeApp>,
+    Json(filter): Json<DnsQueryLogFilter>,
+) -> LandscapeApiResult<Vec<DnsQueryLog>> {
+    let data = state.metric_service.data.dns_metric.search(filter).await;
+    LandscapeApiResp::success(data)
+}
+
+pub async fn get_dns_top_domains(
+    State(state): State<LandscapeApp>,
+    Json(filter): Json<DnsQueryLogFilter>,
+) -> LandscapeApiResult<Vec<DnsDomainCount>> {
+    let data = state.metric_service.data.dns_metric.top_domains(filter).await;
+    LandscapeApiResp::success(data)
+}
+
+pub async fn get_dns_top_blocked_domains(
+    State(state): State<LandscapeApp>,
+    Json(mut filter): Json<DnsQueryLogFilter>,
+) -> LandscapeApiResult<Vec<DnsDomainCount>> {
+    filter.blocked = Some(true);
+    let data = state.metric_service.data.dns_metric.top_domains(filter).await;
+    LandscapeApiResp::success(data)
+}
+
+pub async fn get_dns_client_counts(
+    State(state): State<LandscapeApp>,
+    Json(filter): Json<DnsQueryLogFilter>,
+) -> LandscapeApiResult<Vec<DnsClientCount>> {
+    let data = state.metric_service.data.dns_metric.client_counts(filter).await;
+    LandscapeApiResp::success(data)
+}
+
+pub async fn get_dns_policy_stats(
+    State(state): State<LandscapeApp>,
+) -> LandscapeApiResult<Vec<DnsPolicyStats>> {
+    let data = state.metric_service.data.dns_metric.policy_stats();
+    LandscapeApiResp::success(data)
+}
@@ -1,14 +1,18 @@
 use std::collections::HashMap;
 
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
     routing::{get, post},
     Json, Router,
 };
 use landscape::service::nat_service::NatServiceManagerService;
 use landscape_common::service::controller_service::ControllerService;
 use landscape_common::{
-    config::nat::NatServiceConfig, observer::IfaceObserverAction,
+    config::nat::{
+        NatBehaviorTestRequest, NatBehaviorTestResult, NatServiceConfig, NatSession, NatSessionKey,
+        NatSessionQuery,
+    },
+    observer::IfaceObserverAction,
     service::DefaultWatchServiceStatus,
 };
 use landscape_database::provider::LandscapeDBServiceProvider;
@@ -27,6 +31,8 @@ pub async fn get_iface_nat_paths(
         .route("/nats/status", get(get_all_nat_status))
         .route("/nats", post(handle_iface_nat_status))
         .route("/nats/{iface_name}", get(get_iface_nat_conifg).delete(delete_and_stop_iface_nat))
+        .route("/nats/{iface_name}/behavior_test", post(nat_behavior_test))
+        .route("/nats/{iface_name}/sessions", get(list_nat_sessions).delete(evict_nat_session))
         // .route("/nats/{iface_name}/restart", post(restart_nat_service_status))
         .with_state(share_state)
 }
@@ -52,6 +58,12 @@ async fn handle_iface_nat_status(
     State(state): State<NatServiceManagerService>,
     Json(config): Json<NatServiceConfig>,
 ) -> LandscapeApiResult<()> {
+    if let Err(e) = config.nat_config.check() {
+        return Err(LandscapeApiError::BadRequest(e));
+    }
+    if let Err(e) = config.snat_pool.check() {
+        return Err(LandscapeApiError::BadRequest(e));
+    }
     state.handle_service_config(config).await;
     LandscapeApiResp::success(())
 }
@@ -62,3 +74,38 @@ async fn delete_and_stop_iface_nat(
 ) -> LandscapeApiResult<Option<DefaultWatchServiceStatus>> {
     LandscapeApiResp::success(state.delete_and_stop_iface_service(iface_name).await)
 }
+
+async fn nat_behavior_test(
+    State(state): State<NatServiceManagerService>,
+    Path(iface_name): Path<String>,
+    Json(request): Json<NatBehaviorTestRequest>,
+) -> LandscapeApiResult<NatBehaviorTestResult> {
+    match state.behavior_test(iface_name, request.server).await {
+        Ok(result) => LandscapeApiResp::success(result),
+        Err(e) => Err(LandscapeApiError::BadRequest(e)),
+    }
+}
+
+async fn list_nat_sessions(
+    State(state): State<NatServiceManagerService>,
+    Path(iface_name): Path<String>,
+    Query(query): Query<NatSessionQuery>,
+) -> LandscapeApiResult<Vec<NatSession>> {
+    if let Some(sessions) = state.list_sessions(iface_name, query).await {
+        LandscapeApiResp::success(sessions)
+    } else {
+        Err(LandscapeApiError::NotFound("Running Nat Service".into()))
+    }
+}
+
+async fn evict_nat_session(
+    State(state): State<NatServiceManagerService>,
+    Path(iface_name): Path<String>,
+    Query(key): Query<NatSessionKey>,
+) -> LandscapeApiResult<()> {
+    if state.evict_session(iface_name, key).await {
+        LandscapeApiResp::success(())
+    } else {
+        Err(LandscapeApiError::NotFound("Nat Session".into()))
+    }
+}
@@ -1,10 +1,13 @@
 use axum::{
     extract::{Query, State},
-    routing::get,
-    Router,
+    routing::{get, post},
+    Json, Router,
+};
+use landscape_common::{config::dns::LocalZoneRecord, service::DefaultWatchServiceStatus};
+use landscape_dns::{
+    diff_server::{CheckDnsReq, CheckDnsResult, DnsCacheStats, TraceDnsReq, TraceDnsResult},
+    upstream::DnsUpstreamHealth,
 };
-use landscape_common::service::DefaultWatchServiceStatus;
-use landscape_dns::diff_server::{CheckDnsReq, CheckDnsResult};
 
 use crate::LandscapeApp;
 
@@ -14,6 +17,10 @@ pub async fn get_dns_paths() -> Router<LandscapeApp> {
     Router::new()
         .route("/dns", get(get_dns_service_status).post(start_dns_service).delete(stop_dns_service))
         .route("/dns/check", get(check_domain))
+        .route("/dns/trace", post(trace_domains))
+        .route("/dns/local_zone", get(list_local_zone_records))
+        .route("/dns/cache_stats", get(get_cache_stats))
+        .route("/dns/upstream_health", get(get_upstream_health))
 }
 
 async fn get_dns_service_status(
@@ -36,3 +43,28 @@ async fn check_domain(
 ) -> LandscapeApiResult<CheckDnsResult> {
     LandscapeApiResp::success(state.dns_service.check_domain(req).await)
 }
+
+async fn trace_domains(
+    State(state): State<LandscapeApp>,
+    Json(req): Json<TraceDnsReq>,
+) -> LandscapeApiResult<TraceDnsResult> {
+    LandscapeApiResp::success(state.dns_service.trace_domains(req).await)
+}
+
+async fn list_local_zone_records(
+    State(state): State<LandscapeApp>,
+) -> LandscapeApiResult<Vec<LocalZoneRecord>> {
+    LandscapeApiResp::success(state.dns_service.list_local_zone_records().await)
+}
+
+async fn get_cache_stats(
+    State(state): State<LandscapeApp>,
+) -> LandscapeApiResult<Vec<DnsCacheStats>> {
+    LandscapeApiResp::success(state.dns_service.cache_stats().await)
+}
+
+async fn get_upstream_health(
+    State(state): State<LandscapeApp>,
+) -> LandscapeApiResult<Vec<DnsUpstreamHealth>> {
+    LandscapeApiResp::success(state.dns_service.upstream_health())
+}
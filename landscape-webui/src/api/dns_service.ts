@@ -1,4 +1,12 @@
-import { CheckDnsReq, CheckDnsResult } from "@/rust_bindings/dns";
+import {
+  CheckDnsReq,
+  CheckDnsResult,
+  DnsCacheStats,
+  DnsUpstreamHealth,
+  TraceDnsReq,
+  TraceDnsResult,
+} from "@/rust_bindings/dns";
+import { LocalZoneRecord } from "@/rust_bindings/common/dns";
 import axiosService from ".";
 import { ServiceStatus } from "@/lib/services";
 
@@ -30,3 +38,25 @@ export async function check_domain(req: CheckDnsReq): Promise<CheckDnsResult> {
   });
   return data.data;
 }
+
+export async function trace_domains(
+  req: TraceDnsReq
+): Promise<TraceDnsResult> {
+  let data = await axiosService.post("sys_service/dns/trace", req);
+  return data.data;
+}
+
+export async function get_local_zone_records(): Promise<LocalZoneRecord[]> {
+  let data = await axiosService.get("sys_service/dns/local_zone");
+  return data.data;
+}
+
+export async function get_dns_cache_stats(): Promise<DnsCacheStats[]> {
+  let data = await axiosService.get("sys_service/dns/cache_stats");
+  return data.data;
+}
+
+export async function get_dns_upstream_health(): Promise<DnsUpstreamHealth[]> {
+  let data = await axiosService.get("sys_service/dns/upstream_health");
+  return data.data;
+}
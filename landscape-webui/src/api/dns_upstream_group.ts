@@ -0,0 +1,19 @@
+import axiosService from "@/api";
+import { DnsUpstreamGroupConfig } from "@/rust_bindings/common/dns";
+
+export async function get_dns_upstream_groups(): Promise<
+  DnsUpstreamGroupConfig[]
+> {
+  let data = await axiosService.get(`config/dns_upstream_groups`);
+  return data.data;
+}
+
+export async function push_dns_upstream_group(
+  group: DnsUpstreamGroupConfig
+): Promise<void> {
+  await axiosService.post(`config/dns_upstream_groups`, group);
+}
+
+export async function delete_dns_upstream_group(id: string): Promise<void> {
+  await axiosService.delete(`config/dns_upstream_groups/${id}`);
+}
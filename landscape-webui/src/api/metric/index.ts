@@ -4,6 +4,13 @@ import {
   ConnectKey,
   ConnectMetric,
 } from "@/rust_bindings/common/metric/connect";
+import {
+  DnsClientCount,
+  DnsDomainCount,
+  DnsPolicyStats,
+  DnsQueryLog,
+  DnsQueryLogFilter,
+} from "@/rust_bindings/common/metric/dns";
 
 export async function get_metric_status(): Promise<ServiceStatus> {
   let data = await axiosService.get("metric/status");
@@ -25,3 +32,36 @@ export async function get_connect_metric_info(
   // console.log(data.data);
   return data.data;
 }
+
+export async function search_dns_query_logs(
+  filter: DnsQueryLogFilter
+): Promise<DnsQueryLog[]> {
+  let data = await axiosService.post("metric/dns/logs", filter);
+  return data.data;
+}
+
+export async function get_dns_top_domains(
+  filter: DnsQueryLogFilter
+): Promise<DnsDomainCount[]> {
+  let data = await axiosService.post("metric/dns/top_domains", filter);
+  return data.data;
+}
+
+export async function get_dns_top_blocked_domains(
+  filter: DnsQueryLogFilter
+): Promise<DnsDomainCount[]> {
+  let data = await axiosService.post("metric/dns/top_blocked", filter);
+  return data.data;
+}
+
+export async function get_dns_client_counts(
+  filter: DnsQueryLogFilter
+): Promise<DnsClientCount[]> {
+  let data = await axiosService.post("metric/dns/clients", filter);
+  return data.data;
+}
+
+export async function get_dns_policy_stats(): Promise<DnsPolicyStats[]> {
+  let data = await axiosService.get("metric/dns/policy_stats");
+  return data.data;
+}
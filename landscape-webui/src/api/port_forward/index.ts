@@ -0,0 +1,24 @@
+import axiosService from "@/api";
+import { PortForwardRuleConfig } from "@/rust_bindings/common/nat";
+
+export async function get_port_forwards(): Promise<PortForwardRuleConfig[]> {
+  let data = await axiosService.get(`config/port_forwards`);
+  return data.data;
+}
+
+export async function get_port_forward(
+  id: string
+): Promise<PortForwardRuleConfig> {
+  let data = await axiosService.get(`config/port_forwards/${id}`);
+  return data.data;
+}
+
+export async function push_port_forward(
+  rule: PortForwardRuleConfig
+): Promise<void> {
+  await axiosService.post(`config/port_forwards`, rule);
+}
+
+export async function delete_port_forward(id: string): Promise<void> {
+  await axiosService.delete(`config/port_forwards/${id}`);
+}
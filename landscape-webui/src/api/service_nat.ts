@@ -1,4 +1,10 @@
 import { NatServiceConfig } from "@/lib/nat";
+import {
+  NatBehaviorTestResult,
+  NatSession,
+  NatSessionKey,
+  NatSessionQuery,
+} from "@/rust_bindings/common/nat";
 import { ServiceStatus } from "@/lib/services";
 import axiosService from ".";
 
@@ -34,3 +40,33 @@ export async function update_iface_nat_config(
 export async function stop_and_del_iface_nat(name: string): Promise<void> {
   return axiosService.delete(`services/nats/${name}`);
 }
+
+export async function nat_behavior_test(
+  iface_name: string,
+  server: string
+): Promise<NatBehaviorTestResult> {
+  let data = await axiosService.post(
+    `services/nats/${iface_name}/behavior_test`,
+    { server }
+  );
+  return data.data;
+}
+
+export async function get_nat_sessions(
+  iface_name: string,
+  query: Partial<NatSessionQuery>
+): Promise<NatSession[]> {
+  let data = await axiosService.get(`services/nats/${iface_name}/sessions`, {
+    params: query,
+  });
+  return data.data;
+}
+
+export async function evict_nat_session(
+  iface_name: string,
+  key: NatSessionKey
+): Promise<void> {
+  return axiosService.delete(`services/nats/${iface_name}/sessions`, {
+    params: key,
+  });
+}
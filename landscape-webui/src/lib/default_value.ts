@@ -1,3 +1,4 @@
+import { FlowDnsPolicy } from "@/rust_bindings/common/dns";
 import { FlowConfig } from "@/rust_bindings/common/flow";
 
 export function flow_config_default(): FlowConfig {
@@ -8,10 +9,23 @@ export function flow_config_default(): FlowConfig {
     flow_match_rules: [],
     flow_targets: [],
     remark: "",
+    dns_policy: flow_dns_policy_default(),
+    nat_behavior: null,
     update_at: new Date().getTime(),
   };
 }
 
+export function flow_dns_policy_default(): FlowDnsPolicy {
+  return {
+    client_qps: 0,
+    client_burst: 0,
+    flow_qps: 0,
+    flow_burst: 0,
+    rate_limit_action: "drop",
+    qtype_rules: [],
+  };
+}
+
 export enum FlowTargetTypes {
   INTERFACE = "interface",
   NETNS = "netns",
@@ -20,6 +20,7 @@ export interface MacBindingRecord {
   mac: string;
   ip: string;
   expire_time: number;
+  hostname?: string | null;
 }
 
 export class DHCPv4ServerConfig {
@@ -29,6 +30,7 @@ export class DHCPv4ServerConfig {
   ip_range_start: string;
   ip_range_end: string | undefined;
   mac_binding_records: MacBindingRecord[];
+  local_domain: string | null;
 
   constructor(obj?: {
     options?: any[];
@@ -37,6 +39,7 @@ export class DHCPv4ServerConfig {
     ip_range_start?: string;
     ip_range_end?: string;
     mac_binding_records?: MacBindingRecord[];
+    local_domain?: string | null;
   }) {
     this.options = obj?.options ?? [];
     this.server_ip_addr = obj?.server_ip_addr ?? "192.168.5.1";
@@ -48,6 +51,7 @@ export class DHCPv4ServerConfig {
     this.ip_range_start = obj?.ip_range_start ?? start;
     this.ip_range_end = obj?.ip_range_end ?? end;
     this.mac_binding_records = obj?.mac_binding_records ?? [];
+    this.local_domain = obj?.local_domain ?? "lan";
   }
 }
 
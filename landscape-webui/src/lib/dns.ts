@@ -3,6 +3,7 @@ import { FlowDnsMarkType } from "./default_value";
 import {
   DNSResolveMode,
   DNSRuleConfig,
+  DnsAnswerFilter,
   FilterResult,
   RuleSource,
 } from "@/rust_bindings/common/dns";
@@ -18,6 +19,7 @@ export class DnsRule implements DNSRuleConfig {
   flow_id: number;
   filter: FilterResult;
   update_at: number;
+  answer_filter: DnsAnswerFilter;
 
   constructor(obj?: Partial<DNSRuleConfig>) {
     this.id = obj?.id ?? null;
@@ -35,6 +37,11 @@ export class DnsRule implements DNSRuleConfig {
     this.flow_id = obj?.flow_id ?? 0;
     this.filter = obj?.filter ?? "unfilter";
     this.update_at = obj?.update_at ?? new Date().getTime();
+    this.answer_filter = {
+      rebind_protection: obj?.answer_filter?.rebind_protection ?? false,
+      rebind_allow_domains: obj?.answer_filter?.rebind_allow_domains ?? [],
+      bogus_sources: obj?.answer_filter?.bogus_sources ?? [],
+    };
   }
 }
 
@@ -48,6 +55,8 @@ export enum DomainMatchTypeEnum {
 export enum RuleSourceEnum {
   GeoKey = "geo_key",
   Config = "config",
+  Hosts = "hosts",
+  Adblock = "adblock",
 }
 
 // export type RuleSource =
@@ -83,6 +92,7 @@ export function get_dns_filter_options(): {
     { label: "不过滤", value: FilterResultEnum.Unfilter },
     { label: "仅 IPv4", value: FilterResultEnum.OnlyIPv4 },
     { label: "仅 IPv6", value: FilterResultEnum.OnlyIPv6 },
+    { label: "DNS64", value: FilterResultEnum.Dns64 },
   ];
 }
 
@@ -94,6 +104,42 @@ export function get_dns_resolve_mode_options(): {
     { label: "重定向", value: DNSResolveModeEnum.Redirect },
     { label: "自定义上游", value: DNSResolveModeEnum.Upstream },
     { label: "Cloudflare", value: DNSResolveModeEnum.Cloudflare },
+    { label: "上游组", value: DNSResolveModeEnum.Group },
+    { label: "拦截", value: DNSResolveModeEnum.Block },
+  ];
+}
+
+export function get_dns_block_response_options(): {
+  label: string;
+  value: string;
+}[] {
+  return [
+    { label: "NXDOMAIN", value: DnsBlockResponseEnum.NxDomain },
+    { label: "REFUSED", value: DnsBlockResponseEnum.Refused },
+    { label: "0.0.0.0 / ::", value: DnsBlockResponseEnum.Unspecified },
+    { label: "空应答", value: DnsBlockResponseEnum.NoData },
+  ];
+}
+
+export function get_dns_upstream_strategy_options(): {
+  label: string;
+  value: string;
+}[] {
+  return [
+    { label: "按顺序故障转移", value: DnsUpstreamStrategyEnum.Failover },
+    { label: "并发竞速", value: DnsUpstreamStrategyEnum.Race },
+    { label: "轮询", value: DnsUpstreamStrategyEnum.RoundRobin },
+  ];
+}
+
+export function get_dns_ecs_mode_options(): {
+  label: string;
+  value: string;
+}[] {
+  return [
+    { label: "不发送", value: DnsEcsModeEnum.Strip },
+    { label: "转发客户端", value: DnsEcsModeEnum.Forward },
+    { label: "指定子网", value: DnsEcsModeEnum.Inject },
   ];
 }
 
@@ -105,6 +151,19 @@ export function get_dns_upstream_type_options(): {
     { label: "无加密", value: DnsUpstreamTypeEnum.Plaintext },
     { label: "TLS", value: DnsUpstreamTypeEnum.Tls },
     { label: "HTTPS", value: DnsUpstreamTypeEnum.Https },
+    { label: "QUIC", value: DnsUpstreamTypeEnum.Quic },
+    { label: "HTTP/3", value: DnsUpstreamTypeEnum.Http3 },
+  ];
+}
+
+export function get_cloudflare_mode_options(): {
+  label: string;
+  value: string;
+}[] {
+  return [
+    { label: "无加密", value: CloudflareMode.Plaintext },
+    { label: "TLS", value: CloudflareMode.Tls },
+    { label: "HTTPS", value: CloudflareMode.Https },
   ];
 }
 
@@ -112,14 +171,42 @@ export enum DNSResolveModeEnum {
   Redirect = "redirect",
   Upstream = "upstream",
   Cloudflare = "cloudflare",
+  Group = "group",
+  Block = "block",
+}
+
+export enum DnsBlockResponseEnum {
+  NxDomain = "nx_domain",
+  Refused = "refused",
+  Unspecified = "unspecified",
+  NoData = "no_data",
+}
+
+export enum DnsUpstreamStrategyEnum {
+  Failover = "failover",
+  Race = "race",
+  RoundRobin = "round_robin",
 }
 
 export enum DnsUpstreamTypeEnum {
   Plaintext = "plaintext",
   Tls = "tls",
   Https = "https",
+  Quic = "quic",
+  Http3 = "http3",
+}
+
+export enum DnsEcsModeEnum {
+  Strip = "strip",
+  Forward = "forward",
+  Inject = "inject",
 }
 
+export type DnsEcsMode =
+  | { t: DnsEcsModeEnum.Strip }
+  | { t: DnsEcsModeEnum.Forward }
+  | { t: DnsEcsModeEnum.Inject; ip: string; prefix: number };
+
 export enum CloudflareMode {
   Plaintext = "plaintext",
   Tls = "tls",
@@ -129,7 +216,9 @@ export enum CloudflareMode {
 export type DnsUpstreamType =
   | { t: DnsUpstreamTypeEnum.Plaintext }
   | { t: DnsUpstreamTypeEnum.Tls; domain: string }
-  | { t: DnsUpstreamTypeEnum.Https; domain: string };
+  | { t: DnsUpstreamTypeEnum.Https; domain: string }
+  | { t: DnsUpstreamTypeEnum.Quic; domain: string }
+  | { t: DnsUpstreamTypeEnum.Http3; domain: string };
 
 // export type DNSResolveMode =
 //   | { t: DNSResolveModeEnum.Redirect; ips: string[] }
@@ -141,10 +230,12 @@ export type DnsUpstreamMode = {
   upstream: DnsUpstreamType;
   ips: string[];
   port?: number;
+  ecs: DnsEcsMode;
 };
 
 export enum FilterResultEnum {
   Unfilter = "unfilter",
   OnlyIPv4 = "only_ipv4",
   OnlyIPv6 = "only_ipv6",
+  Dns64 = "dns64",
 }
@@ -1,4 +1,9 @@
 import { Range } from "@/lib/common";
+import {
+  Nat6Mode,
+  NatBehavior,
+  SnatPoolConfig,
+} from "@/rust_bindings/common/nat";
 const DEFAULT_RANGE_START = 32768;
 const DEFAULT_RANGE_END = 65535;
 
@@ -6,15 +11,26 @@ export class NatServiceConfig {
   iface_name: string;
   enable: boolean;
   nat_config: NatConfig;
+  nat6_mode: Nat6Mode;
+  snat_pool: SnatPoolConfig;
 
   constructor(obj: {
     iface_name: string;
     enable?: boolean;
     nat_config?: NatConfig;
+    nat6_mode?: Nat6Mode;
+    snat_pool?: SnatPoolConfig;
   }) {
     this.iface_name = obj?.iface_name ?? "";
     this.enable = obj?.enable ?? true;
     this.nat_config = new NatConfig(obj?.nat_config ?? {});
+    this.nat6_mode = obj?.nat6_mode ?? { t: "disabled" };
+    this.snat_pool = obj?.snat_pool ?? {
+      addrs: [],
+      policy: "source_hash",
+      flow_overrides: [],
+      subnet_overrides: [],
+    };
   }
 }
 
@@ -22,11 +38,25 @@ export class NatConfig {
   tcp_range: Range;
   udp_range: Range;
   icmp_in_range: Range;
+  tcp_syn_timeout: number;
+  tcp_established_timeout: number;
+  tcp_closing_timeout: number;
+  udp_timeout: number;
+  icmp_timeout: number;
+  max_mappings_per_host: number;
+  behavior: NatBehavior;
 
   constructor(obj?: {
     tcp_range?: Range;
     udp_range?: Range;
     icmp_in_range?: Range;
+    tcp_syn_timeout?: number;
+    tcp_established_timeout?: number;
+    tcp_closing_timeout?: number;
+    udp_timeout?: number;
+    icmp_timeout?: number;
+    max_mappings_per_host?: number;
+    behavior?: NatBehavior;
   }) {
     this.tcp_range =
       obj?.tcp_range ?? new Range(DEFAULT_RANGE_START, DEFAULT_RANGE_END);
@@ -34,5 +64,15 @@ export class NatConfig {
       obj?.udp_range ?? new Range(DEFAULT_RANGE_START, DEFAULT_RANGE_END);
     this.icmp_in_range =
       obj?.icmp_in_range ?? new Range(DEFAULT_RANGE_START, DEFAULT_RANGE_END);
+    this.tcp_syn_timeout = obj?.tcp_syn_timeout ?? 6;
+    this.tcp_established_timeout = obj?.tcp_established_timeout ?? 600;
+    this.tcp_closing_timeout = obj?.tcp_closing_timeout ?? 60;
+    this.udp_timeout = obj?.udp_timeout ?? 300;
+    this.icmp_timeout = obj?.icmp_timeout ?? 300;
+    this.max_mappings_per_host = obj?.max_mappings_per_host ?? 0;
+    this.behavior = obj?.behavior ?? {
+      mapping: "address_and_port_dependent",
+      filtering: "endpoint_independent",
+    };
   }
 }
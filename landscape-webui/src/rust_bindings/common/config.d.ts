@@ -1,4 +1,5 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { FlowDnsPolicy } from "./dns.d";
 
 /**
  * auth realte config
@@ -22,6 +23,94 @@ export type LandscapeConfig = {
   web: LandscapeWebConfig;
   log: LandscapeLogConfig;
   store: LandscapeStoreConfig;
+  dns: LandscapeDnsConfig;
+};
+
+/**
+ * dns realte config
+ */
+export type LandscapeDnsConfig = {
+  /**
+   * Enable DNS over TLS for LAN clients
+   */
+  dot_enable: boolean | null;
+  /**
+   * Listen DNS over TLS port
+   */
+  dot_port: number | null;
+  /**
+   * Enable DNS over HTTPS (`/dns-query` on the web HTTPS port)
+   */
+  doh_enable: boolean | null;
+  /**
+   * Prefetch frequently queried cache entries once this fraction of
+   * their TTL has elapsed, `1.0` or greater disables prefetch
+   */
+  cache_prefetch_ratio: number | null;
+  /**
+   * Answer with expired cache entries while refreshing them (RFC 8767)
+   */
+  cache_serve_stale: boolean | null;
+  /**
+   * How long (seconds) an expired entry may still be served
+   */
+  cache_stale_max_ttl: number | null;
+  /**
+   * Max cached domains of each flow
+   */
+  cache_capacity: number | null;
+  /**
+   * Lower bound (seconds) of cached record TTLs
+   */
+  cache_min_ttl: number | null;
+  /**
+   * Upper bound (seconds) of cached record TTLs, also caps negative answers
+   */
+  cache_max_ttl: number | null;
+  /**
+   * NAT64 prefix used by DNS64 rules, default `64:ff9b::`
+   */
+  dns64_prefix: string | null;
+  /**
+   * NAT64 prefix length, one of 32, 40, 48, 56, 64, 96 (RFC 6052)
+   */
+  dns64_prefix_len: number | null;
+  /**
+   * Save the DNS cache to disk on shutdown and periodically, and
+   * restore it (with the flow DNS marks) on startup
+   */
+  cache_persist: boolean | null;
+  /**
+   * Interval (seconds) between periodic DNS cache snapshots
+   */
+  cache_snapshot_interval: number | null;
+  /**
+   * Addresses the DNS server listens on, default `[::]`
+   */
+  listen_addresses: Array<string> | null;
+  /**
+   * Port the DNS server listens on (UDP and TCP), default `53`
+   */
+  listen_port: number | null;
+  /**
+   * Only accept queries arriving on these interfaces (`SO_BINDTODEVICE`),
+   * empty means all interfaces
+   */
+  listen_interfaces: Array<string> | null;
+  /**
+   * Point `/etc/resolv.conf` to this DNS server while it is running,
+   * the original file is restored when the DNS server stops
+   */
+  takeover_resolv_conf: boolean | null;
+  /**
+   * Flow used for lookups made by the router itself (queries from loopback)
+   */
+  local_flow_id: number | null;
+  /**
+   * Rate limit and query type policy of the default flow (flow 0),
+   * other flows are configured in the flow config
+   */
+  default_flow_policy: FlowDnsPolicy | null;
 };
 
 export type LandscapeLogConfig = {
@@ -27,6 +27,10 @@ export type DHCPv4ServerConfig = {
    * Static MAC --> IP address binding
    */
   mac_binding_records: Array<MacBindingRecord>;
+  /**
+   * 本地域名 e.g. lan, 设置后将客户端主机名注册到 DNS 中
+   */
+  local_domain: string | null;
 };
 
 export type DHCPv4ServiceConfig = {
@@ -43,4 +47,8 @@ export type MacBindingRecord = {
   mac: MacAddr;
   ip: string;
   expire_time: number;
+  /**
+   * 注册到本地域名中的主机名, 未设置时使用客户端上报的主机名
+   */
+  hostname: string | null;
 };
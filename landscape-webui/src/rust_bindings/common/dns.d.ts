@@ -1,5 +1,5 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
-import type { FlowDnsMark } from "../flow";
+import type { FlowDnsMark, WanIPRuleSource } from "../flow";
 import type { GeoConfigKey } from "./geo.d";
 
 export type CloudflareMode = "plaintext" | "tls" | "https";
@@ -9,7 +9,22 @@ export type DNSResolveMode = { "t": "redirect"; ips: Array<string> } | {
   upstream: DnsUpstreamType;
   ips: Array<string>;
   port: number | null;
-} | { "t": "cloudflare"; mode: CloudflareMode };
+  /**
+   * EDNS Client Subnet 处理方式
+   */
+  ecs: DnsEcsMode;
+} | { "t": "cloudflare"; mode: CloudflareMode } | {
+  "t": "group";
+  group_id: string;
+  /**
+   * EDNS Client Subnet 处理方式
+   */
+  ecs: DnsEcsMode;
+} | { "t": "block"; response: DnsBlockResponse } | {
+  "t": "record_set";
+  records: Array<DnsRecordConfig>;
+  ttl: number;
+};
 
 /**
  * DNS 配置
@@ -43,19 +58,187 @@ export type DNSRuleConfig = {
   source: Array<RuleSource>;
   flow_id: number;
   update_at: number;
+  /**
+   * 上游应答过滤
+   */
+  answer_filter: DnsAnswerFilter;
 };
 
-export type DnsUpstreamType = { "t": "plaintext" } | {
-  "t": "tls";
-  domain: string;
-} | { "t": "https"; domain: string };
+/**
+ * 对上游返回的 A / AAAA 记录进行过滤, 被过滤的记录不会写入缓存以及 flow 的标记
+ */
+export type DnsAnswerFilter = {
+  /**
+   * DNS 重绑定保护: 丢弃非本地域名解析到的私有, 回环以及链路本地地址
+   */
+  rebind_protection: boolean;
+  /**
+   * 允许解析到私有地址的域名, 包含其子域名
+   */
+  rebind_allow_domains: Array<string>;
+  /**
+   * 丢弃落在这些 geoip / CIDR 中的应答, 用于防御 DNS 污染
+   */
+  bogus_sources: Array<WanIPRuleSource>;
+};
+
+/**
+ * 拦截时返回的应答
+ */
+export type DnsBlockResponse = "nx_domain" | "refused" | "unspecified" | "no_data";
+
+/**
+ * 向上游转发请求时 EDNS Client Subnet (RFC 7871) 的处理方式
+ */
+export type DnsEcsMode = { "t": "strip" } | { "t": "forward" } | {
+  "t": "inject";
+  ip: string;
+  prefix: number;
+};
+
+/**
+ * 本地记录
+ */
+export type DnsRecordConfig = {
+  /**
+   * 记录所属的域名, 为空或 `@` 时表示匹配到当前规则的任意域名
+   */
+  name: string;
+  /**
+   * 记录内容
+   */
+  data: DnsRecordData;
+};
+
+export type DnsRecordData =
+  | { "t": "a"; ip: string }
+  | { "t": "aaaa"; ip: string }
+  | { "t": "cname"; target: string }
+  | { "t": "txt"; values: Array<string> }
+  | { "t": "mx"; preference: number; exchange: string }
+  | {
+    "t": "srv";
+    priority: number;
+    weight: number;
+    port: number;
+    target: string;
+  }
+  | { "t": "ptr"; target: string };
+
+/**
+ * 上游 DNS 服务器组
+ */
+export type DnsUpstreamGroupConfig = {
+  id: string | null;
+  name: string;
+  /**
+   * 服务器选择策略
+   */
+  strategy: DnsUpstreamStrategy;
+  /**
+   * 上游服务器, Failover 时按照列表顺序尝试
+   */
+  servers: Array<DnsUpstreamServer>;
+  update_at: number;
+};
+
+export type DnsUpstreamServer = {
+  upstream: DnsUpstreamType;
+  ip: string;
+  port: number | null;
+};
+
+export type DnsUpstreamStrategy =
+  /**
+   * 按顺序使用第一个健康的服务器, 失败后切换至下一个
+   */
+  | "failover"
+  /**
+   * 同时向所有健康的服务器发送请求, 使用最快的结果
+   */
+  | "race"
+  /**
+   * 轮流使用组内的服务器
+   */
+  | "round_robin";
+
+export type DnsUpstreamType =
+  | { "t": "plaintext" }
+  | { "t": "tls"; domain: string }
+  | { "t": "https"; domain: string }
+  | { "t": "quic"; domain: string }
+  | { "t": "http3"; domain: string };
 
 export type DomainConfig = { match_type: DomainMatchType; value: string };
 
 export type DomainMatchType = "plain" | "regex" | "domain" | "full";
 
-export type FilterResult = "unfilter" | "only_ipv4" | "only_ipv6";
+export type FilterResult = "unfilter" | "only_ipv4" | "only_ipv6" | "dns64";
+
+/**
+ * 本地区域中自动生成的记录 (DHCP 租约, Docker 容器等)
+ */
+export type LocalZoneRecord = {
+  /**
+   * 完整域名, 不带末尾的 `.`
+   */
+  name: string;
+  ip: string;
+  source: LocalZoneSource;
+};
+
+/**
+ * 本地区域记录的来源, 同一来源的记录会被整体替换
+ */
+export type LocalZoneSource = { "t": "dhcp"; iface_name: string } | {
+  "t": "docker";
+  container_name: string;
+};
 
 export type RuleSource =
   | { "t": "geo_key" } & GeoConfigKey
-  | { "t": "config" } & DomainConfig;
+  | { "t": "config" } & DomainConfig
+  | { "t": "hosts"; url: string }
+  | { "t": "adblock"; url: string };
+
+/**
+ * flow 内 DNS 请求的限速以及按查询类型处理的策略
+ */
+export type FlowDnsPolicy = {
+  /**
+   * 每个客户端 IP 每秒允许的请求数, 0 表示不限制
+   */
+  client_qps: number;
+  /**
+   * 每个客户端允许的突发请求数, 0 时与 `client_qps` 相同
+   */
+  client_burst: number;
+  /**
+   * 整个 flow 每秒允许的请求数, 0 表示不限制
+   */
+  flow_qps: number;
+  /**
+   * 整个 flow 允许的突发请求数, 0 时与 `flow_qps` 相同
+   */
+  flow_burst: number;
+  /**
+   * 超过限速后的处理方式
+   */
+  rate_limit_action: DnsRateLimitAction;
+  /**
+   * 按查询类型拒绝或者返回空应答
+   */
+  qtype_rules: Array<DnsQtypeRule>;
+};
+
+export type DnsRateLimitAction = "drop" | "refused" | "serv_fail";
+
+export type DnsQtypeRule = {
+  /**
+   * 查询类型, 例如 `HTTPS`, `SVCB`, `ANY`, `AAAA`
+   */
+  qtype: string;
+  action: DnsQtypeAction;
+};
+
+export type DnsQtypeAction = "refused" | "empty";
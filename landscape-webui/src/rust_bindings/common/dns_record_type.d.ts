@@ -1,3 +1,10 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 
-export type LandscapeDnsRecordType = "A" | "AAAA";
+export type LandscapeDnsRecordType = 
+  | "A"
+  | "AAAA"
+  | "CNAME"
+  | "TXT"
+  | "MX"
+  | "SRV"
+  | "PTR";
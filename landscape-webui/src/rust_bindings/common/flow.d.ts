@@ -1,5 +1,7 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 import type { FlowDnsMark, WanIPRuleSource } from "../flow";
+import type { FlowDnsPolicy } from "./dns.d";
+import type { NatBehavior } from "./nat.d";
 
 /**
  * 流控配置结构体
@@ -27,6 +29,14 @@ export type FlowConfig = {
    * 备注
    */
   remark: string;
+  /**
+   * 该 flow 中 DNS 请求的限速以及查询类型策略
+   */
+  dns_policy: FlowDnsPolicy;
+  /**
+   * 覆盖该 flow 经过 WAN NAT 时的映射与过滤行为
+   */
+  nat_behavior: NatBehavior | null;
   update_at: number;
 };
 
@@ -0,0 +1,88 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * 按客户端统计的查询次数
+ */
+export type DnsClientCount = {
+  client_ip: string;
+  count: number;
+  blocked: number;
+  cache_hits: number;
+};
+
+/**
+ * 按域名统计的查询次数
+ */
+export type DnsDomainCount = { domain: string; count: number };
+
+/**
+ * 单个 DNS 问题的处理记录
+ */
+export type DnsQueryLog = {
+  report_time: number;
+  client_ip: string;
+  flow_id: number;
+  domain: string;
+  /**
+   * 例如 `A`, `AAAA`
+   */
+  query_type: string;
+  /**
+   * 匹配到的规则名称, 本地区域应答时为空
+   */
+  rule_name: string | null;
+  /**
+   * 使用的上游, 未向上游查询时为空
+   */
+  upstream: string | null;
+  /**
+   * 例如 `No Error`, `Non-Existent Domain`
+   */
+  response_code: string;
+  latency_ms: number;
+  cache_hit: boolean;
+  /**
+   * 是否被规则拦截
+   */
+  blocked: boolean;
+};
+
+/**
+ * 查询日志的搜索条件, 未设置的条件不进行过滤
+ */
+export type DnsQueryLogFilter = {
+  /**
+   * 起始时间 (毫秒)
+   */
+  start_time: number | null;
+  /**
+   * 结束时间 (毫秒)
+   */
+  end_time: number | null;
+  client_ip: string | null;
+  flow_id: number | null;
+  /**
+   * 包含该字符串的域名
+   */
+  domain: string | null;
+  rule_name: string | null;
+  blocked: boolean | null;
+  limit: number | null;
+};
+
+/**
+ * 每个 flow 被限速以及按查询类型处理的请求数
+ */
+export type DnsPolicyStats = {
+  flow_id: number;
+  /**
+   * 超过单个客户端限速的请求
+   */
+  client_limited: number;
+  /**
+   * 超过 flow 限速的请求
+   */
+  flow_limited: number;
+  qtype_refused: number;
+  qtype_empty: number;
+};
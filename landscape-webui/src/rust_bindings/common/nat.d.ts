@@ -4,11 +4,214 @@ export type NatConfig = {
   tcp_range: { start: number; end: number };
   udp_range: { start: number; end: number };
   icmp_in_range: { start: number; end: number };
+  /**
+   * 未完成握手的 TCP 连接超时, 单位: 秒
+   */
+  tcp_syn_timeout: number;
+  /**
+   * unit: s
+   */
+  tcp_established_timeout: number;
+  /**
+   * 收到 FIN 之后的 TCP 连接超时, 单位: 秒
+   */
+  tcp_closing_timeout: number;
+  /**
+   * unit: s
+   */
+  udp_timeout: number;
+  /**
+   * unit: s
+   */
+  icmp_timeout: number;
+  /**
+   * 单个内网主机可同时占用的动态映射数量, 0 表示不限制
+   */
+  max_mappings_per_host: number;
+  /**
+   * 动态映射的映射与过滤行为, 可被 Flow 配置覆盖
+   */
+  behavior: NatBehavior;
+};
+
+/**
+ * RFC 4787 中映射与过滤行为所依赖的对端范围
+ */
+export type NatEndpointPolicy =
+  | "endpoint_independent"
+  | "address_dependent"
+  | "address_and_port_dependent";
+
+/**
+ * NAT 的映射与过滤行为
+ */
+export type NatBehavior = {
+  /**
+   * 同一个内网端点访问哪些目的端点时使用同一个映射
+   * 不同的目的端点会分配各自的映射以及外部端口
+   * Flow 的 AllowReusePort 标记总是使用 Endpoint-Independent 映射
+   */
+  mapping: NatEndpointPolicy;
+  /**
+   * 允许哪些外部端点通过已有映射访问内网端点
+   */
+  filtering: NatEndpointPolicy;
+};
+
+/**
+ * NAT 行为检测的请求
+ */
+export type NatBehaviorTestRequest = {
+  /**
+   * 支持 RFC 5780 (OTHER-ADDRESS / CHANGE-REQUEST) 的 STUN 服务器, 例如 `stun.example.com:3478`
+   */
+  server: string;
+};
+
+/**
+ * 通过 STUN 观察到的 NAT 行为
+ * 服务器不支持 RFC 5780 时只能得到映射地址
+ */
+export type NatBehaviorTestResult = {
+  server_addr: string;
+  local_port: number;
+  mapped_addr: string | null;
+  other_addr: string | null;
+  /**
+   * 向其他地址发送的请求没有响应时无法判断, 为空
+   */
+  mapping: NatEndpointPolicy | null;
+  filtering: NatEndpointPolicy | null;
+};
+
+/**
+ * 当前的动态 NAT 映射
+ */
+export type NatSession = {
+  /**
+   * TCP / UDP / ICMP
+   */
+  l4proto: number;
+  internal_addr: string;
+  internal_port: number;
+  mapped_addr: string;
+  mapped_port: number;
+  /**
+   * 触发创建映射的对端
+   */
+  remote_addr: string;
+  remote_port: number;
+  flow_id: number;
+  /**
+   * 创建至今的时间, 单位: 秒
+   */
+  age: number;
+  /**
+   * 距离超时的时间, 单位: 秒
+   */
+  remaining_timeout: number;
+};
+
+/**
+ * NAT 会话的过滤条件, 未设置的条件不参与过滤
+ */
+export type NatSessionQuery = {
+  /**
+   * 匹配内网, 映射或对端地址
+   */
+  ip: string | null;
+  /**
+   * 匹配内网, 映射或对端端口
+   */
+  port: number | null;
+  l4proto: number | null;
+};
+
+/**
+ * 通过内网端点定位动态映射
+ */
+export type NatSessionKey = {
+  l4proto: number;
+  internal_addr: string;
+  internal_port: number;
 };
 
 export type NatServiceConfig = {
   iface_name: string;
   enable: boolean;
   nat_config: NatConfig;
+  nat6_mode: Nat6Mode;
+  /**
+   * 动态映射使用的 SNAT 地址池, 为空时使用 WAN 网卡的地址
+   */
+  snat_pool: SnatPoolConfig;
+  update_at: number;
+};
+
+/**
+ * 从地址池中选择 SNAT 地址的方式
+ */
+export type SnatPoolPolicy = "source_hash" | "round_robin";
+
+/**
+ * 指定 flow 使用的 SNAT 地址
+ */
+export type SnatFlowOverride = { flow_id: number; addr: string };
+
+/**
+ * 指定内网网段使用的 SNAT 地址
+ */
+export type SnatSubnetOverride = {
+  subnet: string;
+  prefix_len: number;
+  addr: string;
+};
+
+/**
+ * SNAT 地址池
+ * 地址需要已经配置在 WAN 网卡上, 或由上游路由到 WAN 网卡
+ * 优先级: flow 指定 > 网段指定 > 地址池 > WAN 网卡地址
+ * 路由器自身发出的数据包始终使用 WAN 网卡地址
+ */
+export type SnatPoolConfig = {
+  addrs: Array<string>;
+  policy: SnatPoolPolicy;
+  flow_overrides: Array<SnatFlowOverride>;
+  subnet_overrides: Array<SnatSubnetOverride>;
+};
+
+/**
+ * IPv6 地址转换模式
+ */
+export type Nat6Mode =
+  | { t: "disabled" }
+  | { t: "nptv6"; ula_prefix: string }
+  | { t: "masquerade" };
+
+export type PortForwardProtocol = "tcp" | "udp" | "both";
+
+export type PortForwardRuleConfig = {
+  id: string | null;
+  enable: boolean;
+  remark: string;
+  /**
+   * 外部端口所在的 WAN 网卡
+   */
+  wan_iface_name: string;
+  protocol: PortForwardProtocol;
+  /**
+   * 外部端口范围, 包含结束端口, 单个端口时两者相同
+   */
+  external_port_start: number;
+  external_port_end: number;
+  internal_ip: string;
+  /**
+   * 内部起始端口, 端口范围按相同的偏移进行映射
+   */
+  internal_port: number;
+  /**
+   * LAN 客户端通过 WAN 地址访问时回流到内部主机
+   */
+  hairpin: boolean;
   update_at: number;
 };
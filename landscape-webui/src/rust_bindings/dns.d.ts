@@ -1,5 +1,7 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 import type { LandscapeDnsRecordType } from "./common/dns_record_type.d";
+import type { DomainConfig, RuleSource } from "./common/dns.d";
+import type { FlowDnsMark } from "./flow";
 
 export type CheckDnsReq = {
   flow_id: number;
@@ -7,8 +9,111 @@ export type CheckDnsReq = {
   record_type: LandscapeDnsRecordType;
 };
 
+/**
+ * 每个 flow 的 DNS 缓存统计
+ */
+export type DnsCacheStats = {
+  flow_id: number;
+  capacity: number;
+  size: number;
+  /**
+   * NXDOMAIN / NODATA 缓存数量
+   */
+  negative_size: number;
+  hits: number;
+  negative_hits: number;
+  misses: number;
+};
+
 export type CheckDnsResult = {
   config: any | null;
   records: Array<any> | null;
   cache_records: Array<any> | null;
 };
+
+/**
+ * 上游服务器的健康统计
+ */
+export type DnsUpstreamHealth = {
+  group_id: string;
+  /**
+   * 例如 `tls://one.one.one.one@1.1.1.1:853`
+   */
+  server: string;
+  queries: number;
+  errors: number;
+  avg_latency_ms: number;
+  healthy: boolean;
+};
+
+/**
+ * 批量追踪域名的规则匹配过程
+ */
+export type TraceDnsReq = {
+  domains: Array<string>;
+  /**
+   * 未指定时根据 `client_ip` 查找 flow, 都未指定时使用默认 flow
+   */
+  flow_id: number | null;
+  client_ip: string | null;
+  /**
+   * 用于检查缓存状态, 默认为 A
+   */
+  record_type: LandscapeDnsRecordType | null;
+};
+
+export type TraceDnsResult = {
+  /**
+   * 实际使用的 flow
+   */
+  flow_id: number;
+  domains: Array<DomainTrace>;
+};
+
+/**
+ * 单个域名的匹配过程
+ */
+export type DomainTrace = {
+  domain: string;
+  /**
+   * 本地区域中存在记录, 查询时直接应答不经过规则
+   */
+  local_zone: boolean;
+  /**
+   * 按照优先级依次检查的规则, 最后一条为最终处理的规则
+   */
+  rules: Array<DnsRuleTrace>;
+  mark: FlowDnsMark | null;
+  /**
+   * 写入 eBPF map 中的标记值, 不需要写入时为空
+   */
+  mark_value: number | null;
+  upstream: string | null;
+  cache: DnsCacheTrace;
+};
+
+export type DnsRuleTraceResult = "matched" | "excluded" | "not_matched";
+
+export type DnsRuleTrace = {
+  index: number;
+  name: string;
+  result: DnsRuleTraceResult;
+  /**
+   * 命中的配置来源 (geo key / 内联配置), 规则未配置来源时匹配所有域名, 此时为空
+   */
+  source: RuleSource | null;
+  /**
+   * 命中的具体条目
+   */
+  domain_config: DomainConfig | null;
+};
+
+export type DnsCacheState = "miss" | "fresh" | "stale" | "negative";
+
+export type DnsCacheTrace = {
+  state: DnsCacheState;
+  /**
+   * 剩余的 TTL, 过期后为负数
+   */
+  remaining_ttl: number | null;
+};
@@ -11,6 +11,7 @@ use bollard::Docker;
 use landscape::docker::create_docker_event_spawn;
 use landscape::route::IpRouteService;
 use landscape_database::provider::LandscapeDBServiceProvider;
+use landscape_dns::local_zone::LocalZone;
 use tokio::sync::mpsc;
 
 /// cargo run --package landscape --bin docker_test
@@ -30,7 +31,7 @@ async fn main() {
     let (_, route_rx) = mpsc::channel(1);
     let route_service = IpRouteService::new(route_rx, flow_repo);
 
-    create_docker_event_spawn(route_service).await;
+    create_docker_event_spawn(route_service, LocalZone::new()).await;
     let running = Arc::new(AtomicBool::new(true));
     let r = running.clone();
     ctrlc::set_handler(move || {
@@ -0,0 +1,50 @@
+use landscape_common::{
+    config::dns::DnsUpstreamGroupConfig, event::dns::DnsEvent,
+    service::controller_service::ConfigController,
+};
+use landscape_database::{
+    dns_upstream_group::repository::DNSUpstreamGroupRepository,
+    provider::LandscapeDBServiceProvider,
+};
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+#[derive(Clone)]
+pub struct DNSUpstreamGroupService {
+    store: DNSUpstreamGroupRepository,
+    dns_events_tx: mpsc::Sender<DnsEvent>,
+}
+
+impl DNSUpstreamGroupService {
+    pub async fn new(
+        store: LandscapeDBServiceProvider,
+        dns_events_tx: mpsc::Sender<DnsEvent>,
+    ) -> Self {
+        let store = store.dns_upstream_group_store();
+        Self { store, dns_events_tx }
+    }
+}
+
+#[async_trait::async_trait]
+impl ConfigController for DNSUpstreamGroupService {
+    type Id = Uuid;
+
+    type Config = DnsUpstreamGroupConfig;
+
+    type DatabseAction = DNSUpstreamGroupRepository;
+
+    fn get_repository(&self) -> &Self::DatabseAction {
+        &self.store
+    }
+
+    // 上游组可能被多个 flow 的规则引用, 全部刷新
+    async fn update_one_config(&self, _config: Self::Config) {
+        let _ = self.dns_events_tx.send(DnsEvent::RuleUpdated { flow_id: None }).await;
+    }
+    async fn delete_one_config(&self, _config: Self::Config) {
+        let _ = self.dns_events_tx.send(DnsEvent::RuleUpdated { flow_id: None }).await;
+    }
+    async fn update_many_config(&self, _configs: Vec<Self::Config>) {
+        let _ = self.dns_events_tx.send(DnsEvent::RuleUpdated { flow_id: None }).await;
+    }
+}
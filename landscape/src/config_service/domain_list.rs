@@ -0,0 +1,381 @@
+use std::{
+    collections::HashSet,
+    net::IpAddr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use landscape_common::{
+    args::LAND_HOME_PATH,
+    config::dns::{DomainConfig, DomainMatchType, RuleSource},
+    database::LandscapeDBTrait,
+    event::dns::DnsEvent,
+    store::storev3::{LandscapeStoreTrait, StoreFileManager},
+    utils::time::{get_f64_timestamp, MILL_A_DAY},
+    LANDSCAPE_GEO_CACHE_TMP_DIR,
+};
+use landscape_database::{
+    dns_rule::repository::DNSRuleRepository, provider::LandscapeDBServiceProvider,
+};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{mpsc, Mutex, Notify};
+
+/// 检查列表是否需要更新的间隔
+const CHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// hosts 文件中常见的本机主机名, 不作为拦截域名
+const HOSTS_IGNORED_NAMES: [&str; 11] = [
+    "localhost",
+    "localhost.localdomain",
+    "local",
+    "broadcasthost",
+    "ip6-localhost",
+    "ip6-loopback",
+    "ip6-localnet",
+    "ip6-mcastprefix",
+    "ip6-allnodes",
+    "ip6-allrouters",
+    "ip6-allhosts",
+];
+
+#[derive(Serialize, Deserialize, Debug, Clone, Hash, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DomainListFormat {
+    Hosts,
+    Adblock,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Hash, PartialEq, Eq)]
+pub struct DomainListKey {
+    pub url: String,
+    pub format: DomainListFormat,
+}
+
+impl DomainListFormat {
+    /// 解析列表内容, 返回 (拦截, 例外)
+    /// 没有任何拦截域名时返回 None, 避免错误的下载内容 (例如 HTML 页面) 覆盖之前的列表
+    pub fn parse(&self, content: &str) -> Option<(Vec<DomainConfig>, Vec<DomainConfig>)> {
+        let (block, allow) = match self {
+            DomainListFormat::Hosts => (parse_hosts(content), vec![]),
+            DomainListFormat::Adblock => parse_adblock(content),
+        };
+        if block.is_empty() {
+            return None;
+        }
+        Some((block, allow))
+    }
+}
+
+impl DomainListKey {
+    pub fn from_source(source: &RuleSource) -> Option<Self> {
+        match source {
+            RuleSource::Hosts { url } => {
+                Some(DomainListKey { url: url.clone(), format: DomainListFormat::Hosts })
+            }
+            RuleSource::Adblock { url } => Some(DomainListKey {
+                url: url.clone(),
+                format: DomainListFormat::Adblock,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// 下载并解析后的列表, 存储在 file cache 中
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DomainListCache {
+    pub url: String,
+    pub format: DomainListFormat,
+    pub update_at: f64,
+    /// 需要匹配的域名
+    pub block: Vec<DomainConfig>,
+    /// 例外的域名
+    pub allow: Vec<DomainConfig>,
+}
+
+impl LandscapeStoreTrait for DomainListCache {
+    type K = DomainListKey;
+    fn get_store_key(&self) -> DomainListKey {
+        DomainListKey { url: self.url.clone(), format: self.format.clone() }
+    }
+}
+
+/// 规范化域名, 非法的域名返回 None
+fn normalize_domain(name: &str) -> Option<String> {
+    let name = name.trim().trim_end_matches('.').to_ascii_lowercase();
+    if name.is_empty()
+        || name.starts_with('.')
+        || !name.contains('.')
+        || !name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    {
+        return None;
+    }
+    Some(name)
+}
+
+/// 解析 hosts 文件, 也兼容每行一个域名的列表
+pub fn parse_hosts(content: &str) -> Vec<DomainConfig> {
+    let mut domains = HashSet::new();
+    for line in content.lines() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        let mut parts = line.split_whitespace();
+        let Some(first) = parts.next() else {
+            continue;
+        };
+
+        let names: Vec<&str> =
+            if first.parse::<IpAddr>().is_ok() { parts.collect() } else { vec![first] };
+        for name in names {
+            if HOSTS_IGNORED_NAMES.contains(&name) {
+                continue;
+            }
+            if let Some(domain) = normalize_domain(name) {
+                domains.insert(domain);
+            }
+        }
+    }
+
+    domains
+        .into_iter()
+        .map(|value| DomainConfig { match_type: DomainMatchType::Full, value })
+        .collect()
+}
+
+/// 解析 adblock 语法的列表, 返回 (拦截, 例外)
+/// 仅支持域名级别的规则: `||example.com^`, `@@||example.com^`, 以及 hosts 格式的行
+/// 带有 `$important` 以外修饰符的规则会被忽略
+pub fn parse_adblock(content: &str) -> (Vec<DomainConfig>, Vec<DomainConfig>) {
+    let mut block = HashSet::new();
+    let mut allow = HashSet::new();
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty()
+            || line.starts_with('!')
+            || line.starts_with('[')
+            || line.starts_with('#')
+        {
+            continue;
+        }
+
+        let (rule, is_allow) = match line.strip_prefix("@@") {
+            Some(rule) => (rule, true),
+            None => (line, false),
+        };
+        let rule = match rule.split_once('$') {
+            Some((rule, modifiers)) if modifiers.split(',').all(|m| m == "important") => rule,
+            Some(_) => continue,
+            None => rule,
+        };
+
+        let config = if let Some(domain) = rule.strip_prefix("||") {
+            let domain = domain.strip_suffix('|').unwrap_or(domain);
+            let domain = domain.strip_suffix('^').unwrap_or(domain);
+            normalize_domain(domain)
+                .map(|value| DomainConfig { match_type: DomainMatchType::Domain, value })
+        } else {
+            let mut parts = rule.split_whitespace();
+            match (parts.next(), parts.next()) {
+                // hosts 格式
+                (Some(ip), Some(name)) if ip.parse::<IpAddr>().is_ok() => normalize_domain(name)
+                    .map(|value| DomainConfig { match_type: DomainMatchType::Full, value }),
+                // 仅有域名时匹配域名及其子域名
+                (Some(name), None) => normalize_domain(name)
+                    .map(|value| DomainConfig { match_type: DomainMatchType::Domain, value }),
+                _ => None,
+            }
+        };
+
+        if let Some(config) = config {
+            if is_allow {
+                allow.insert(config);
+            } else {
+                block.insert(config);
+            }
+        }
+    }
+    (block.into_iter().collect(), allow.into_iter().collect())
+}
+
+/// 定时下载 DNS 规则中引用的 hosts / adblock 列表
+#[derive(Clone)]
+pub struct DomainListService {
+    rule_store: DNSRuleRepository,
+    file_cache: Arc<Mutex<StoreFileManager<DomainListKey, DomainListCache>>>,
+    refresh_notify: Arc<Notify>,
+    dns_events_tx: mpsc::Sender<DnsEvent>,
+}
+
+impl DomainListService {
+    pub fn new(store: LandscapeDBServiceProvider, dns_events_tx: mpsc::Sender<DnsEvent>) -> Self {
+        let rule_store = store.dns_rule_store();
+        let file_cache = Arc::new(Mutex::new(StoreFileManager::new(
+            LAND_HOME_PATH.join(LANDSCAPE_GEO_CACHE_TMP_DIR),
+            "domain_list".to_string(),
+        )));
+
+        let service = Self {
+            rule_store,
+            file_cache,
+            refresh_notify: Arc::new(Notify::new()),
+            dns_events_tx,
+        };
+        let service_clone = service.clone();
+        tokio::spawn(async move {
+            loop {
+                service_clone.refresh().await;
+                tokio::select! {
+                    _ = tokio::time::sleep(CHECK_INTERVAL) => {}
+                    _ = service_clone.refresh_notify.notified() => {}
+                }
+            }
+        });
+        service
+    }
+
+    /// 读取已经下载的列表, 不存在时在后台进行下载
+    pub async fn get(&self, key: &DomainListKey) -> Option<DomainListCache> {
+        let result = self.file_cache.lock().await.get(key);
+        if result.is_none() {
+            self.refresh_notify.notify_one();
+        }
+        result
+    }
+
+    /// 下载缺失以及过期的列表, 并删除不再被规则引用的列表
+    pub async fn refresh(&self) {
+        let rules = match self.rule_store.list().await {
+            Ok(rules) => rules,
+            Err(e) => {
+                tracing::error!("load dns rules error: {e:?}");
+                return;
+            }
+        };
+        let keys: HashSet<DomainListKey> = rules
+            .iter()
+            .flat_map(|rule| rule.source.iter())
+            .filter_map(DomainListKey::from_source)
+            .collect();
+
+        let client = Client::new();
+        let mut updated = false;
+        for key in keys.iter() {
+            let expired = match self.file_cache.lock().await.get(key) {
+                Some(cache) => cache.update_at + (MILL_A_DAY as f64) < get_f64_timestamp(),
+                None => true,
+            };
+            if !expired {
+                continue;
+            }
+
+            tracing::debug!("download domain list: {}", key.url);
+            let time = Instant::now();
+            let content = match client.get(&key.url).send().await {
+                Ok(resp) if resp.status().is_success() => match resp.text().await {
+                    Ok(content) => content,
+                    Err(e) => {
+                        tracing::error!("read {} response error: {}", key.url, e);
+                        continue;
+                    }
+                },
+                Ok(resp) => {
+                    tracing::error!("download {} error, HTTP status: {}", key.url, resp.status());
+                    continue;
+                }
+                Err(e) => {
+                    tracing::error!("request {} error: {}", key.url, e);
+                    continue;
+                }
+            };
+
+            let Some((block, allow)) = key.format.parse(&content) else {
+                tracing::error!("domain list {} contains no domains, keep the last one", key.url);
+                continue;
+            };
+            tracing::info!(
+                "domain list {} loaded, block: {}, allow: {}, time: {}s",
+                key.url,
+                block.len(),
+                allow.len(),
+                time.elapsed().as_secs()
+            );
+            self.file_cache.lock().await.set(DomainListCache {
+                url: key.url.clone(),
+                format: key.format.clone(),
+                update_at: get_f64_timestamp(),
+                block,
+                allow,
+            });
+            updated = true;
+        }
+
+        {
+            let mut file_cache_lock = self.file_cache.lock().await;
+            let unused: Vec<DomainListKey> =
+                file_cache_lock.keys().into_iter().filter(|key| !keys.contains(key)).collect();
+            for key in unused {
+                file_cache_lock.del(&key);
+            }
+        }
+
+        if updated {
+            let _ = self.dns_events_tx.send(DnsEvent::DomainListUpdated).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use landscape_common::config::dns::{DomainConfig, DomainMatchType};
+
+    use super::{parse_adblock, parse_hosts, DomainListFormat};
+
+    fn sorted(mut configs: Vec<DomainConfig>) -> Vec<(DomainMatchType, String)> {
+        configs.sort_by(|a, b| a.value.cmp(&b.value));
+        configs.into_iter().map(|c| (c.match_type, c.value)).collect()
+    }
+
+    #[test]
+    fn test_parse_hosts() {
+        let content = "# comment\n127.0.0.1 localhost\n0.0.0.0 ads.example.com tracker.example.com # inline\n::1 ip6-localhost\nbare.example.org\n";
+        assert_eq!(
+            sorted(parse_hosts(content)),
+            vec![
+                (DomainMatchType::Full, "ads.example.com".to_string()),
+                (DomainMatchType::Full, "bare.example.org".to_string()),
+                (DomainMatchType::Full, "tracker.example.com".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_adblock() {
+        let content = "[Adblock Plus 2.0]\n! comment\n||ads.example.com^\n||track.example.com^$important\n||img.example.com^$third-party\n@@||good.ads.example.com^\n/banner/*\n0.0.0.0 hosts.example.net\n";
+        let (block, allow) = parse_adblock(content);
+        assert_eq!(
+            sorted(block),
+            vec![
+                (DomainMatchType::Domain, "ads.example.com".to_string()),
+                (DomainMatchType::Full, "hosts.example.net".to_string()),
+                (DomainMatchType::Domain, "track.example.com".to_string()),
+            ]
+        );
+        assert_eq!(
+            sorted(allow),
+            vec![(DomainMatchType::Domain, "good.ads.example.com".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_parse_empty_list() {
+        let html = "<!DOCTYPE html>\n<html><body>Not Found</body></html>\n";
+        for format in [DomainListFormat::Hosts, DomainListFormat::Adblock] {
+            assert!(format.parse("").is_none());
+            assert!(format.parse("# comment only\n").is_none());
+            assert!(format.parse(html).is_none());
+        }
+        // 只有例外规则时同样视为空列表
+        assert!(DomainListFormat::Adblock.parse("@@||good.example.com^\n").is_none());
+        assert!(DomainListFormat::Hosts.parse("0.0.0.0 ads.example.com\n").is_some());
+    }
+}
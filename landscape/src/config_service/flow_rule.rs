@@ -9,7 +9,7 @@ use landscape_database::{
 use tokio::sync::mpsc;
 use uuid::Uuid;
 
-use crate::flow::update_flow_matchs;
+use crate::flow::{update_flow_matchs, update_flow_nat_behavior};
 
 #[derive(Clone)]
 pub struct FlowRuleService {
@@ -64,6 +64,7 @@ impl ConfigController for FlowRuleService {
         new_configs: Vec<Self::Config>,
         old_configs: Vec<Self::Config>,
     ) {
+        update_flow_nat_behavior(&new_configs, &old_configs);
         update_flow_matchs(new_configs, old_configs).await;
         let _ = self.dns_events_tx.send(DnsEvent::FlowUpdated).await;
     }
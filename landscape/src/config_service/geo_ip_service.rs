@@ -2,7 +2,7 @@ use landscape_common::store::storev3::LandscapeStoreTrait;
 use landscape_common::{
     config::geo::{GeoFileCacheKey, GeoIpConfig, GeoIpSourceConfig},
     database::LandscapeDBTrait,
-    ip_mark::{IpMarkInfo, WanIPRuleSource, WanIpRuleConfig},
+    ip_mark::{IpConfig, IpMarkInfo, WanIPRuleSource, WanIpRuleConfig},
     service::controller_service::ConfigController,
     utils::time::{get_f64_timestamp, MILL_A_DAY},
 };
@@ -15,7 +15,9 @@ use std::{
 };
 
 use landscape_common::{
-    args::LAND_HOME_PATH, event::dns::DstIpEvent, store::storev3::StoreFileManager,
+    args::LAND_HOME_PATH,
+    event::dns::{DnsEvent, DstIpEvent},
+    store::storev3::StoreFileManager,
     LANDSCAPE_GEO_CACHE_TMP_DIR,
 };
 use landscape_database::{
@@ -28,17 +30,40 @@ const A_DAY: u64 = 60 * 60 * 24;
 
 pub type GeoDomainCacheStore = Arc<Mutex<StoreFileManager<GeoFileCacheKey, GeoIpConfig>>>;
 
+fn expand_ip_sources(
+    cache: &mut StoreFileManager<GeoFileCacheKey, GeoIpConfig>,
+    sources: &[WanIPRuleSource],
+) -> Vec<IpConfig> {
+    let mut result = vec![];
+    for each in sources.iter() {
+        match each {
+            WanIPRuleSource::GeoKey(config_key) => {
+                if let Some(ips) = cache.get(&config_key.get_file_cache_key()) {
+                    result.extend(ips.values.iter().cloned());
+                }
+            }
+            WanIPRuleSource::Config(c) => {
+                result.push(c.clone());
+            }
+        }
+    }
+    result
+}
+
 #[derive(Clone)]
 pub struct GeoIpService {
     store: GeoIpSourceConfigRepository,
     file_cache: GeoDomainCacheStore,
     dst_ip_events_tx: mpsc::Sender<DstIpEvent>,
+    /// DNS 规则的应答过滤同样引用了 geoip
+    dns_events_tx: mpsc::Sender<DnsEvent>,
 }
 
 impl GeoIpService {
     pub async fn new(
         store: LandscapeDBServiceProvider,
         dst_ip_events_tx: mpsc::Sender<DstIpEvent>,
+        dns_events_tx: mpsc::Sender<DnsEvent>,
     ) -> Self {
         let store = store.geo_ip_rule_store();
 
@@ -47,7 +72,7 @@ impl GeoIpService {
             "ip".to_string(),
         )));
 
-        let service = Self { store, file_cache, dst_ip_events_tx };
+        let service = Self { store, file_cache, dst_ip_events_tx, dns_events_tx };
         let service_clone = service.clone();
         tokio::spawn(async move {
             //
@@ -68,19 +93,7 @@ impl GeoIpService {
         let mut lock = self.file_cache.lock().await;
         let mut result = vec![];
         for config in configs.into_iter() {
-            let mut source = vec![];
-            for each in config.source.iter() {
-                match each {
-                    WanIPRuleSource::GeoKey(config_key) => {
-                        if let Some(ips) = lock.get(&config_key.get_file_cache_key()) {
-                            source.extend(ips.values.iter().cloned());
-                        }
-                    }
-                    WanIPRuleSource::Config(c) => {
-                        source.push(c.clone());
-                    }
-                }
-            }
+            let source = expand_ip_sources(&mut lock, &config.source);
 
             let ip_marks = source.into_iter().map(|cidr| IpMarkInfo {
                 mark: config.mark,
@@ -92,6 +105,17 @@ impl GeoIpService {
         result
     }
 
+    async fn notify_updated(&self) {
+        let _ = self.dst_ip_events_tx.send(DstIpEvent::GeoIpUpdated).await;
+        let _ = self.dns_events_tx.send(DnsEvent::GeoIpUpdated).await;
+    }
+
+    /// 将 geoip key 以及 CIDR 展开为网段列表
+    pub async fn expand_sources(&self, sources: &[WanIPRuleSource]) -> Vec<IpConfig> {
+        let mut lock = self.file_cache.lock().await;
+        expand_ip_sources(&mut lock, sources)
+    }
+
     pub async fn refresh(&self, force: bool) {
         // 读取当前规则
         let mut configs: Vec<GeoIpSourceConfig> = self.store.list().await.unwrap();
@@ -147,7 +171,7 @@ impl GeoIpService {
                             url,
                             time.elapsed().as_secs()
                         );
-                        let _ = self.dst_ip_events_tx.send(DstIpEvent::GeoIpUpdated).await;
+                        self.notify_updated().await;
                     }
                     Err(e) => tracing::error!("read {} response error: {}", url, e),
                 },
@@ -167,9 +191,14 @@ impl GeoIpService {
                 .into_iter()
                 .filter(|k| !config_names.contains(&k.name))
                 .collect::<HashSet<GeoFileCacheKey>>();
+            let removed = !need_to_remove.is_empty();
             for key in need_to_remove {
                 file_cache_lock.del(&key);
             }
+            drop(file_cache_lock);
+            if removed {
+                self.notify_updated().await;
+            }
         }
     }
 }
@@ -202,7 +231,7 @@ impl GeoIpService {
                 file_cache_lock.set(info);
             }
         }
-        let _ = self.dst_ip_events_tx.send(DstIpEvent::GeoIpUpdated).await;
+        self.notify_updated().await;
     }
 }
 
@@ -1,7 +1,7 @@
 use landscape_common::{
     config::{
-        dns::{DNSRuleConfig, DNSRuntimeRule, RuleSource},
-        geo::{GeoDomainConfig, GeoFileCacheKey, GeoSiteFileConfig},
+        dns::{DNSRuleConfig, DNSRuntimeRule, RuleSource, RuntimeSourceOrigin},
+        geo::{GeoConfigKey, GeoDomainConfig, GeoFileCacheKey, GeoSiteFileConfig},
     },
     database::LandscapeDBTrait,
     service::controller_service::ConfigController,
@@ -26,6 +26,8 @@ use landscape_database::{
 use reqwest::Client;
 use tokio::sync::{mpsc, Mutex};
 
+use crate::config_service::domain_list::{DomainListKey, DomainListService};
+
 const A_DAY: u64 = 60 * 60 * 24;
 
 pub type GeoDomainCacheStore = Arc<Mutex<StoreFileManager<GeoFileCacheKey, GeoDomainConfig>>>;
@@ -34,6 +36,8 @@ pub type GeoDomainCacheStore = Arc<Mutex<StoreFileManager<GeoFileCacheKey, GeoDo
 pub struct GeoSiteService {
     store: GeoSiteConfigRepository,
     file_cache: GeoDomainCacheStore,
+    /// 规则中引用的 hosts / adblock 列表
+    domain_list: DomainListService,
     dns_events_tx: mpsc::Sender<DnsEvent>,
 }
 
@@ -42,6 +46,7 @@ impl GeoSiteService {
         store: LandscapeDBServiceProvider,
         dns_events_tx: mpsc::Sender<DnsEvent>,
     ) -> Self {
+        let domain_list = DomainListService::new(store.clone(), dns_events_tx.clone());
         let store = store.geo_site_rule_store();
 
         let file_cache = Arc::new(Mutex::new(StoreFileManager::new(
@@ -49,7 +54,7 @@ impl GeoSiteService {
             "site".to_string(),
         )));
 
-        let service = Self { store, file_cache, dns_events_tx };
+        let service = Self { store, file_cache, domain_list, dns_events_tx };
         let service_clone = service.clone();
         tokio::spawn(async move {
             //
@@ -73,9 +78,14 @@ impl GeoSiteService {
         for config in configs.into_iter() {
             let mut usage_keys = HashSet::new();
             let mut source = vec![];
+            let mut source_origins = vec![];
+            let mut exclude = vec![];
+            let mut list_source = false;
 
             let mut inverse_keys: HashMap<String, HashSet<String>> = HashMap::new();
             for each in config.source.into_iter() {
+                let origin = each.clone();
+                let start = source.len();
                 match each {
                     RuleSource::GeoKey(k) if k.inverse => {
                         inverse_keys.entry(k.name).or_default().insert(k.key);
@@ -101,6 +111,19 @@ impl GeoSiteService {
                     RuleSource::Config(c) => {
                         source.push(c);
                     }
+                    RuleSource::Hosts { .. } | RuleSource::Adblock { .. } => {
+                        let Some(key) = DomainListKey::from_source(&each) else {
+                            continue;
+                        };
+                        list_source = true;
+                        if let Some(list) = self.domain_list.get(&key).await {
+                            source.extend(list.block);
+                            exclude.extend(list.allow);
+                        }
+                    }
+                }
+                if source.len() > start {
+                    source_origins.push(RuntimeSourceOrigin { source: origin, end: source.len() });
                 }
             }
 
@@ -115,6 +138,15 @@ impl GeoSiteService {
                                 if !usage_keys.contains(key) {
                                     usage_keys.insert(key.clone());
                                     source.extend(domains.values.into_iter().map(Into::into));
+                                    source_origins.push(RuntimeSourceOrigin {
+                                        source: RuleSource::GeoKey(GeoConfigKey {
+                                            name: key.name.clone(),
+                                            key: key.key.clone(),
+                                            inverse: true,
+                                            attribute_key: None,
+                                        }),
+                                        end: source.len(),
+                                    });
                                 }
                             }
                             // } else {
@@ -125,8 +157,19 @@ impl GeoSiteService {
                 tracing::debug!("using key len: {:#?}", usage_keys.len());
             }
 
+            // 空的匹配规则会匹配所有域名, 列表还未下载完成或者没有内容时跳过该规则
+            if source.is_empty() && list_source {
+                tracing::warn!(
+                    "dns rule {} is skipped, its domain lists are not loaded or empty",
+                    config.name
+                );
+                continue;
+            }
+
             result.push(DNSRuntimeRule {
                 source,
+                source_origins,
+                exclude,
                 id: config.id,
                 name: config.name,
                 index: config.index,
@@ -135,6 +178,9 @@ impl GeoSiteService {
                 resolve_mode: config.resolve_mode,
                 mark: config.mark,
                 flow_id: config.flow_id,
+                upstream_group: None,
+                answer_filter: config.answer_filter,
+                bogus_nets: vec![],
             });
         }
         tracing::debug!("covert config time: {:?}s", time.elapsed().as_secs());
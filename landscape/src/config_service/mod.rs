@@ -1,6 +1,9 @@
 pub mod dns_rule;
+pub mod dns_upstream_group;
+pub mod domain_list;
 pub mod dst_ip_rule;
 pub mod firewall_rule;
 pub mod flow_rule;
 pub mod geo_ip_service;
 pub mod geo_site_service;
+pub mod port_forward;
@@ -0,0 +1,133 @@
+use std::{
+    collections::{HashMap, HashSet},
+    net::Ipv4Addr,
+    sync::Arc,
+};
+
+use landscape_common::{
+    config::nat::{PortForwardRuleConfig, StaticNatMappingItem, STATIC_NAT_MAPPING_CAPACITY},
+    service::controller_service::ConfigController,
+};
+use landscape_database::{
+    port_forward::repository::PortForwardRuleRepository, provider::LandscapeDBServiceProvider,
+};
+use landscape_ebpf::map_setting::nat::{add_static_nat_mappings, del_static_nat_mappings};
+use tokio::sync::{watch, Mutex};
+use uuid::Uuid;
+
+use crate::route::IpRouteService;
+
+#[derive(Clone)]
+pub struct PortForwardService {
+    store: PortForwardRuleRepository,
+    wan_ips: watch::Receiver<HashMap<String, Ipv4Addr>>,
+    /// 当前已经写入 static_nat_mappings 的映射
+    applied: Arc<Mutex<HashSet<StaticNatMappingItem>>>,
+}
+
+impl PortForwardService {
+    pub async fn new(store: LandscapeDBServiceProvider, route_service: IpRouteService) -> Self {
+        let store = store.port_forward_rule_store();
+        let service = Self {
+            store,
+            wan_ips: route_service.subscribe_ipv4_wan_ips(),
+            applied: Arc::new(Mutex::new(HashSet::new())),
+        };
+        service.refresh_mappings().await;
+
+        // WAN 地址变化时重新写入映射
+        let watch_service = service.clone();
+        tokio::spawn(async move {
+            let mut wan_ips = watch_service.wan_ips.clone();
+            while wan_ips.changed().await.is_ok() {
+                watch_service.refresh_mappings().await;
+            }
+        });
+        service
+    }
+
+    /// 检查与现有规则是否冲突
+    pub async fn check_conflict(&self, config: &PortForwardRuleConfig) -> Result<(), String> {
+        config.check()?;
+        if !config.enable {
+            return Ok(());
+        }
+        let mut map_entries = config.map_entries();
+        for exist in self.list().await {
+            if !exist.enable || exist.id == config.id {
+                continue;
+            }
+            if exist.is_conflict(config) {
+                return Err(format!(
+                    "conflict with port forward rule: {} ({}:{}-{})",
+                    exist.remark,
+                    exist.wan_iface_name,
+                    exist.external_port_start,
+                    exist.external_port_end
+                ));
+            }
+            map_entries += exist.map_entries();
+        }
+        // 所有规则共用同一个 map
+        if map_entries > STATIC_NAT_MAPPING_CAPACITY {
+            return Err(format!(
+                "port forward rules need {map_entries} static mappings, at most {STATIC_NAT_MAPPING_CAPACITY} are supported"
+            ));
+        }
+        Ok(())
+    }
+
+    async fn refresh_mappings(&self) {
+        let rules = self.list().await;
+        let wan_ips = self.wan_ips.borrow().clone();
+
+        let mut new_mappings = HashSet::new();
+        for rule in rules.iter() {
+            if let Some(wan_addr) = wan_ips.get(&rule.wan_iface_name) {
+                new_mappings.extend(rule.mappings(*wan_addr));
+            }
+        }
+
+        let mut applied = self.applied.lock().await;
+        let del: Vec<_> = applied.difference(&new_mappings).cloned().collect();
+        let add: Vec<_> = new_mappings.difference(&applied).cloned().collect();
+        tracing::info!(
+            "refresh port forward mappings, add: {}, del: {}, wan ips: {wan_ips:?}",
+            add.len(),
+            del.len()
+        );
+        del_static_nat_mappings(&del);
+        for item in del.iter() {
+            applied.remove(item);
+        }
+        // 写入成功后才记录, 失败时删除可能已经写入的部分, 下次刷新时重试
+        match add_static_nat_mappings(&add) {
+            Ok(()) => applied.extend(add),
+            Err(e) => {
+                tracing::error!("add static nat mappings error: {e:?}");
+                del_static_nat_mappings(&add);
+            }
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl ConfigController for PortForwardService {
+    type Id = Uuid;
+
+    type Config = PortForwardRuleConfig;
+
+    type DatabseAction = PortForwardRuleRepository;
+
+    fn get_repository(&self) -> &Self::DatabseAction {
+        &self.store
+    }
+
+    async fn after_update_config(
+        &self,
+        _new_configs: Vec<Self::Config>,
+        _old_configs: Vec<Self::Config>,
+    ) {
+        self.refresh_mappings().await;
+    }
+}
@@ -13,10 +13,12 @@ use crate::dump::udp_packet::dhcp::{
 use cidr::Ipv4Inet;
 use futures::TryStreamExt;
 use landscape_common::config::dhcp_v4_server::DHCPv4ServerConfig;
+use landscape_common::config::dns::{LocalZoneRecord, LocalZoneSource};
 use landscape_common::dhcp::{DHCPv4OfferInfo, DHCPv4OfferInfoItem};
 use landscape_common::net::MacAddr;
 use landscape_common::service::{DefaultWatchServiceStatus, ServiceStatus};
 use landscape_common::LANDSCAPE_DHCP_DEFAULT_ADDRESS_LEASE_TIME;
+use landscape_dns::local_zone::LocalZone;
 use netlink_packet_route::address::AddressAttribute;
 use rtnetlink::{new_connection, Handle};
 use socket2::{Domain, Protocol, Type};
@@ -72,12 +74,13 @@ async fn add_address(link_name: &str, ip: IpAddr, prefix_length: u8, handle: Han
     }
 }
 
-#[instrument(skip(config, service_status, assigned_ips))]
+#[instrument(skip(config, service_status, assigned_ips, local_zone))]
 pub async fn dhcp_v4_server(
     iface_name: String,
     config: DHCPv4ServerConfig,
     service_status: DefaultWatchServiceStatus,
     assigned_ips: Arc<RwLock<DHCPv4OfferInfo>>,
+    local_zone: LocalZone,
 ) {
     service_status.just_change_status(ServiceStatus::Staring);
 
@@ -142,6 +145,13 @@ pub async fn dhcp_v4_server(
     let timeout_timer = tokio::time::sleep(tokio::time::Duration::from_secs(IP_EXPIRE_INTERVAL));
     tokio::pin!(timeout_timer);
     let mut dhcp_server = DHCPv4Server::init(config);
+    let local_zone_source = LocalZoneSource::Dhcp { iface_name: iface_name.clone() };
+    local_zone
+        .replace_source(
+            local_zone_source.clone(),
+            dhcp_server.get_local_zone_records(&local_zone_source),
+        )
+        .await;
 
     loop {
         tokio::select! {
@@ -152,6 +162,7 @@ pub async fn dhcp_v4_server(
                         let need_update_data = handle_dhcp_message(&mut dhcp_server, &send_socket, message).await;
                         if need_update_data {
                             update_assign_info(assigned_ips.clone(), dhcp_server.get_offered_info()).await;
+                            local_zone.replace_source(local_zone_source.clone(), dhcp_server.get_local_zone_records(&local_zone_source)).await;
                         }
                     },
                     None => {
@@ -164,7 +175,9 @@ pub async fn dhcp_v4_server(
             _ = &mut timeout_timer => {
                 // dhcp_status.expire_check();
                 timeout_timer.as_mut().reset(tokio::time::Instant::now() + tokio::time::Duration::from_secs(IP_EXPIRE_INTERVAL));
-                            update_assign_info(assigned_ips.clone(), dhcp_server.get_offered_info()).await;
+                update_assign_info(assigned_ips.clone(), dhcp_server.get_offered_info()).await;
+                // 移除过期租约的记录
+                local_zone.replace_source(local_zone_source.clone(), dhcp_server.get_local_zone_records(&local_zone_source)).await;
             }
             // 处理外部关闭服务通知
             change_result = dhcp_server_service_status.changed() => {
@@ -180,6 +193,7 @@ pub async fn dhcp_v4_server(
         }
     }
 
+    local_zone.remove_source(&local_zone_source).await;
     tracing::info!("DHCPv4 Server Stop: {:#?}", service_status);
 
     if !service_status.is_stop() {
@@ -255,6 +269,7 @@ async fn handle_dhcp_message(
                 // DhcpOptionMessageType::Nak => todo!(),
                 DhcpOptionMessageType::Release => {
                     tracing::info!("req: Release, {dhcp:?}");
+                    return dhcp_server.release(&dhcp.chaddr, dhcp.ciaddr);
                 }
                 DhcpOptionMessageType::Inform => {
                     tracing::info!("req: Inform, {dhcp:?}");
@@ -285,6 +300,8 @@ struct DHCPv4ServerOfferedCache {
     relative_offer_time: u64,
     valid_time: u32,
     is_static: bool,
+    /// 客户端主机名, 仅在 ACK 后记录
+    hostname: Option<String>,
 }
 
 impl DHCPv4ServerOfferedCache {
@@ -312,6 +329,9 @@ pub struct DHCPv4Server {
     options_map: HashMap<u8, DhcpOptions>,
 
     pub address_lease_time: u32,
+
+    /// 主机名注册使用的本地域名
+    local_domain: Option<String>,
 }
 
 impl DHCPv4Server {
@@ -347,6 +367,14 @@ impl DHCPv4Server {
         options.push(DhcpOptions::ServerIdentifier(config.server_ip_addr));
         options.push(DhcpOptions::DomainNameServer(vec![config.server_ip_addr]));
         options.push(DhcpOptions::BroadcastAddr(Ipv4Addr::from(broadcast_u32)));
+        let local_domain = config
+            .local_domain
+            .as_deref()
+            .map(|domain| domain.trim().trim_matches('.').to_lowercase())
+            .filter(|domain| !domain.is_empty());
+        if let Some(local_domain) = &local_domain {
+            options.push(DhcpOptions::DomainName(local_domain.clone()));
+        }
         // options_map.push(DhcpOptions::AddressLeaseTime(LANDSCAPE_DHCP_DEFAULT_ADDRESS_LEASE_TIME));
 
         tracing::debug!("dhcp v4 server options: {:#?}", options);
@@ -367,6 +395,7 @@ impl DHCPv4Server {
                     relative_offer_time: 0,
                     valid_time: each.expire_time,
                     is_static: true,
+                    hostname: each.hostname.as_deref().and_then(sanitize_hostname),
                 },
             );
         }
@@ -383,6 +412,7 @@ impl DHCPv4Server {
             offered_ip,
             options_map,
             address_lease_time,
+            local_domain,
         }
     }
 
@@ -420,6 +450,7 @@ impl DHCPv4Server {
                         relative_offer_time: self.relative_boot_time.elapsed().as_secs(),
                         valid_time: OFFER_VALID_TIME,
                         is_static: false,
+                        hostname: None,
                     },
                 );
                 self.allocated_host.insert(address, true);
@@ -461,13 +492,24 @@ impl DHCPv4Server {
     }
 
     /// 检查是否存在过, 存在过直接刷新时间
-    pub fn ack_request(&mut self, mac_addr: &MacAddr, ip_addr: Ipv4Addr) -> bool {
+    pub fn ack_request(
+        &mut self,
+        mac_addr: &MacAddr,
+        ip_addr: Ipv4Addr,
+        hostname: Option<String>,
+    ) -> bool {
         if let Some(offered_cache) = self.offered_ip.get_mut(mac_addr) {
             if offered_cache.ip == ip_addr {
                 if !offered_cache.is_static {
                     // 非静态刷新掉 offer 时间
                     offered_cache.valid_time = self.address_lease_time;
                 }
+                // 静态绑定中配置的主机名优先
+                if !offered_cache.is_static || offered_cache.hostname.is_none() {
+                    if hostname.is_some() {
+                        offered_cache.hostname = hostname;
+                    }
+                }
                 // 静态和非静态都刷新相对分配时间
                 offered_cache.relative_offer_time = self.relative_boot_time.elapsed().as_secs();
                 return true;
@@ -490,6 +532,7 @@ impl DHCPv4Server {
                 is_static: false,
                 valid_time: self.address_lease_time,
                 relative_offer_time: self.relative_boot_time.elapsed().as_secs(),
+                hostname,
             };
 
             self.offered_ip.insert(*mac_addr, lease_cache);
@@ -502,6 +545,42 @@ impl DHCPv4Server {
         false
     }
 
+    /// 客户端主动释放 IP
+    /// 静态绑定的记录保留
+    pub fn release(&mut self, mac_addr: &MacAddr, ip_addr: Ipv4Addr) -> bool {
+        let Some(offered_cache) = self.offered_ip.get(mac_addr) else {
+            return false;
+        };
+        if offered_cache.is_static || offered_cache.ip != ip_addr {
+            return false;
+        }
+        self.offered_ip.remove(mac_addr);
+        self.allocated_host.remove(&ip_addr);
+        tracing::info!("client: {mac_addr:?} release ip: {ip_addr:?}");
+        true
+    }
+
+    /// 有效租约与静态绑定对应的本地区域记录
+    pub fn get_local_zone_records(&self, source: &LocalZoneSource) -> Vec<LocalZoneRecord> {
+        let Some(local_domain) = &self.local_domain else {
+            return vec![];
+        };
+        let current_time = self.relative_boot_time.elapsed().as_secs();
+        let mut result = vec![];
+        for cache in self.offered_ip.values() {
+            let Some(hostname) = &cache.hostname else { continue };
+            if !cache.is_static && current_time > cache.get_expire_time() {
+                continue;
+            }
+            result.push(LocalZoneRecord {
+                name: format!("{hostname}.{local_domain}"),
+                ip: IpAddr::V4(cache.ip),
+                source: source.clone(),
+            });
+        }
+        result
+    }
+
     pub fn get_offered_info(&self) -> DHCPv4OfferInfo {
         let mut offered_ips = Vec::with_capacity(self.offered_ip.len());
         let relative_boot_time = self.relative_boot_time.elapsed().as_secs();
@@ -519,6 +598,22 @@ impl DHCPv4Server {
     }
 }
 
+/// 将客户端上报的主机名转换为合法的 DNS 标签
+fn sanitize_hostname(hostname: &str) -> Option<String> {
+    let label = hostname.split('.').next().unwrap_or_default();
+    let label: String = label
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '-')
+        .collect::<String>()
+        .to_lowercase();
+    let label = label.trim_matches('-');
+    if label.is_empty() || label.len() > 63 {
+        None
+    } else {
+        Some(label.to_string())
+    }
+}
+
 async fn update_assign_info(assigned_ips: Arc<RwLock<DHCPv4OfferInfo>>, info: DHCPv4OfferInfo) {
     match tokio::time::timeout(tokio::time::Duration::from_secs(5), assigned_ips.write()).await {
         Ok(mut write_lock) => {
@@ -615,7 +710,12 @@ fn gen_ack(server: &mut DHCPv4Server, frame: DhcpEthFrame) -> Option<DhcpEthFram
         return None;
     };
 
-    let (message_type, client_addr) = if server.ack_request(&frame.chaddr, client_ip) {
+    let hostname = match frame.options.has_option(12) {
+        Some(DhcpOptions::Hostname(hostname)) => sanitize_hostname(&hostname),
+        _ => None,
+    };
+
+    let (message_type, client_addr) = if server.ack_request(&frame.chaddr, client_ip, hostname) {
         (DhcpOptionMessageType::Ack, client_ip)
     } else {
         (DhcpOptionMessageType::Nak, Ipv4Addr::UNSPECIFIED)
@@ -652,9 +752,12 @@ mod tests {
     use std::{net::Ipv4Addr, thread::sleep, time::Duration};
 
     use cidr::Ipv4Inet;
-    use landscape_common::{config::dhcp_v4_server::DHCPv4ServerConfig, net::MacAddr};
+    use landscape_common::{
+        config::{dhcp_v4_server::DHCPv4ServerConfig, dns::LocalZoneSource},
+        net::MacAddr,
+    };
 
-    use crate::dhcp_server::dhcp_server_new::DHCPv4Server;
+    use crate::dhcp_server::dhcp_server_new::{sanitize_hostname, DHCPv4Server};
 
     #[tokio::test]
     pub async fn test_ip_alloc() {
@@ -666,13 +769,13 @@ mod tests {
         let ip = Ipv4Addr::new(192, 168, 5, 226);
         let mac1 = MacAddr::from_str("00:00:00:00:00:01").unwrap();
 
-        let result = dhcp_server.ack_request(&mac1, ip);
+        let result = dhcp_server.ack_request(&mac1, ip, None);
         tracing::debug!("result: {:?}", result);
 
         let result = dhcp_server.offer_ip(&mac1);
         tracing::debug!("result: {:?}", result);
 
-        let result = dhcp_server.ack_request(&mac1, ip);
+        let result = dhcp_server.ack_request(&mac1, ip, None);
         tracing::debug!("result: {:?}", result);
     }
 
@@ -704,4 +807,20 @@ mod tests {
         let result = dhcp_server.offer_ip(&mac1);
         tracing::debug!("result: {:?}", result);
     }
+
+    #[test]
+    pub fn test_local_zone_records() {
+        let mut dhcp_server = DHCPv4Server::init(DHCPv4ServerConfig::default());
+        let ip = Ipv4Addr::new(192, 168, 5, 120);
+        let mac = MacAddr::from_str("00:00:00:00:00:01").unwrap();
+
+        assert!(dhcp_server.ack_request(&mac, ip, sanitize_hostname("My_NAS.local")));
+        let source = LocalZoneSource::Dhcp { iface_name: "br_lan".into() };
+        let records = dhcp_server.get_local_zone_records(&source);
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].name, "mynas.lan");
+
+        assert!(dhcp_server.release(&mac, ip));
+        assert!(dhcp_server.get_local_zone_records(&source).is_empty());
+    }
 }
@@ -1,16 +1,21 @@
 use bollard::{
-    secret::{ContainerSummary, EventMessageTypeEnum},
+    secret::{ContainerInspectResponse, ContainerSummary, EventMessageTypeEnum},
     Docker,
 };
+use landscape_common::config::dns::{LocalZoneRecord, LocalZoneSource};
 use landscape_common::NAMESPACE_REGISTER_SOCK_PATH;
-use landscape_common::{docker::DockerTargetEnroll, NAMESPACE_REGISTER_SOCK};
+use landscape_common::{
+    docker::{DockerTargetEnroll, DOCKER_DNS_NAME_LABEL_KEY},
+    NAMESPACE_REGISTER_SOCK,
+};
 use landscape_common::{
     route::RouteTargetInfo,
     service::{DefaultWatchServiceStatus, ServiceStatus},
 };
+use landscape_dns::local_zone::LocalZone;
 use regex::Regex;
 use serde::Serialize;
-use std::{fs::File, io::BufRead, path::PathBuf};
+use std::{fs::File, io::BufRead, net::IpAddr, path::PathBuf};
 use tokio::net::UnixListener;
 use tokio::net::UnixStream;
 use tokio::{io::AsyncWriteExt, net::unix::SocketAddr};
@@ -28,6 +33,8 @@ pub struct LandscapeDockerService {
     route_service: IpRouteService,
     #[serde(skip)]
     home_path: PathBuf,
+    #[serde(skip)]
+    local_zone: LocalZone,
 }
 
 pub async fn listen_unix_sock(home_path: PathBuf) -> UnixListener {
@@ -50,9 +57,9 @@ pub async fn listen_unix_sock(home_path: PathBuf) -> UnixListener {
 }
 
 impl LandscapeDockerService {
-    pub fn new(home_path: PathBuf, route_service: IpRouteService) -> Self {
+    pub fn new(home_path: PathBuf, route_service: IpRouteService, local_zone: LocalZone) -> Self {
         let status = DefaultWatchServiceStatus::new();
-        LandscapeDockerService { status, route_service, home_path }
+        LandscapeDockerService { status, route_service, home_path, local_zone }
     }
 
     pub async fn start_to_listen_event(&self) {
@@ -61,6 +68,7 @@ impl LandscapeDockerService {
         let status = self.status.clone();
         let route_service = self.route_service.clone();
         let path = self.home_path.clone();
+        let local_zone = self.local_zone.clone();
         tokio::spawn(async move {
             status.just_change_status(ServiceStatus::Staring);
             let docker = Docker::connect_with_socket_defaults();
@@ -70,6 +78,7 @@ impl LandscapeDockerService {
 
             route_service.remove_all_wan_docker().await;
             // scan_and_set_all_docker(&route_service, &docker).await;
+            scan_and_set_all_docker_dns(&local_zone, &docker).await;
 
             let mut event_stream = docker.events::<String>(None);
             let mut receiver = status.subscribe();
@@ -81,7 +90,7 @@ impl LandscapeDockerService {
                     event_msg = event_stream.next() => {
                         if let Some(e) = event_msg {
                             if let Ok(msg) = e {
-                                handle_event(&route_service ,&docker, msg).await;
+                                handle_event(&route_service, &local_zone, &docker, msg).await;
                             } else {
                                 tracing::error!("err event loop: event_msg");
                             }
@@ -125,11 +134,95 @@ impl LandscapeDockerService {
                 };
             }
 
+            remove_all_docker_dns(&local_zone).await;
             status.just_change_status(ServiceStatus::Stop);
         });
     }
 }
 
+/// 启动时扫描正在运行的容器, 注册带有 DNS label 的容器
+async fn scan_and_set_all_docker_dns(local_zone: &LocalZone, docker: &Docker) {
+    remove_all_docker_dns(local_zone).await;
+    for container in get_docker_continer_summary(docker).await {
+        let has_label = container
+            .labels
+            .as_ref()
+            .is_some_and(|labels| labels.contains_key(DOCKER_DNS_NAME_LABEL_KEY));
+        if !has_label {
+            continue;
+        }
+        if let Some(id) = container.id {
+            inspect_container_and_set_dns(&id, local_zone, docker).await;
+        }
+    }
+}
+
+async fn remove_all_docker_dns(local_zone: &LocalZone) {
+    local_zone.retain_sources(|source| !matches!(source, LocalZoneSource::Docker { .. })).await;
+}
+
+async fn inspect_container_and_set_dns(id_or_name: &str, local_zone: &LocalZone, docker: &Docker) {
+    let Ok(container_info) = docker.inspect_container(id_or_name, None).await else {
+        tracing::error!("can not inspect container: {id_or_name}");
+        return;
+    };
+
+    let Some(container_name) =
+        container_info.name.as_deref().map(|name| name.trim_start_matches('/').to_string())
+    else {
+        return;
+    };
+
+    let records = get_container_dns_records(&container_name, &container_info);
+    if !records.is_empty() {
+        tracing::info!("register container: {container_name} dns records: {records:?}");
+    }
+    local_zone.replace_source(LocalZoneSource::Docker { container_name }, records).await;
+}
+
+/// 通过容器 label 生成指向容器网络 IP 的记录
+fn get_container_dns_records(
+    container_name: &str,
+    container_info: &ContainerInspectResponse,
+) -> Vec<LocalZoneRecord> {
+    let Some(label_value) = container_info
+        .config
+        .as_ref()
+        .and_then(|config| config.labels.as_ref())
+        .and_then(|labels| labels.get(DOCKER_DNS_NAME_LABEL_KEY))
+    else {
+        return vec![];
+    };
+
+    let mut ips: Vec<IpAddr> = vec![];
+    if let Some(networks) =
+        container_info.network_settings.as_ref().and_then(|settings| settings.networks.as_ref())
+    {
+        for endpoint in networks.values() {
+            for ip in [&endpoint.ip_address, &endpoint.global_ipv6_address] {
+                if let Some(ip) = ip.as_deref().and_then(|ip| ip.parse::<IpAddr>().ok()) {
+                    if !ips.contains(&ip) {
+                        ips.push(ip);
+                    }
+                }
+            }
+        }
+    }
+
+    let source = LocalZoneSource::Docker { container_name: container_name.to_string() };
+    let mut result = vec![];
+    for name in label_value.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+        for ip in ips.iter() {
+            result.push(LocalZoneRecord {
+                name: name.to_string(),
+                ip: *ip,
+                source: source.clone(),
+            });
+        }
+    }
+    result
+}
+
 pub async fn scan_and_set_all_docker(ip_route: &IpRouteService, docker: &Docker) {
     let containers = get_docker_continer_summary(&docker).await;
 
@@ -212,7 +305,8 @@ pub async fn accept_docker_info(
 
 pub async fn handle_event(
     ip_route_service: &IpRouteService,
-    _docker: &Docker,
+    local_zone: &LocalZone,
+    docker: &Docker,
     emsg: bollard::secret::EventMessage,
 ) {
     match emsg.typ {
@@ -221,6 +315,11 @@ pub async fn handle_event(
             // println!("{:?}", emsg);
             if let Some(action) = emsg.action {
                 match action.as_str() {
+                    "start" => {
+                        if let Some(id) = emsg.actor.and_then(|actor| actor.id) {
+                            inspect_container_and_set_dns(&id, local_zone, docker).await;
+                        }
+                    }
                     // "start" => {
                     //     if let Some(actor) = emsg.actor {
                     //         if let Some(attr) = actor.attributes {
@@ -241,10 +340,26 @@ pub async fn handle_event(
                                     // tracing::info!("docker stop name: {name}");
                                     ip_route_service.remove_ipv4_wan_route(name).await;
                                     ip_route_service.remove_ipv6_wan_route(name).await;
+                                    local_zone
+                                        .remove_source(&LocalZoneSource::Docker {
+                                            container_name: name.clone(),
+                                        })
+                                        .await;
                                 }
                             }
                         }
                     }
+                    "die" => {
+                        if let Some(name) = emsg
+                            .actor
+                            .and_then(|actor| actor.attributes)
+                            .and_then(|attr| attr.get("name").cloned())
+                        {
+                            local_zone
+                                .remove_source(&LocalZoneSource::Docker { container_name: name })
+                                .await;
+                        }
+                    }
                     _ => {}
                 }
             }
@@ -289,7 +404,7 @@ pub async fn handle_event(
     }
 }
 
-pub async fn create_docker_event_spawn(ip_route_service: IpRouteService) {
+pub async fn create_docker_event_spawn(ip_route_service: IpRouteService, local_zone: LocalZone) {
     let docker = Docker::connect_with_socket_defaults();
     let docker = docker.unwrap();
 
@@ -302,7 +417,7 @@ pub async fn create_docker_event_spawn(ip_route_service: IpRouteService) {
         while let Some(e) = event_stream.next().await {
             if let Ok(msg) = e {
                 // println!("{:?}", msg);
-                handle_event(&ip_route_service, &docker, msg).await;
+                handle_event(&ip_route_service, &local_zone, &docker, msg).await;
             }
         }
     });
@@ -391,3 +506,47 @@ fn read_igmp_index(file_path: &str) -> std::io::Result<Option<u32>> {
 
     Ok(result)
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use bollard::secret::{
+        ContainerConfig, ContainerInspectResponse, EndpointSettings, NetworkSettings,
+    };
+    use landscape_common::docker::DOCKER_DNS_NAME_LABEL_KEY;
+
+    use super::get_container_dns_records;
+
+    #[test]
+    fn test_container_dns_records() {
+        let mut labels = HashMap::new();
+        labels
+            .insert(DOCKER_DNS_NAME_LABEL_KEY.to_string(), "grafana.lan, metrics.lan".to_string());
+        let mut networks = HashMap::new();
+        networks.insert(
+            "bridge".to_string(),
+            EndpointSettings {
+                ip_address: Some("172.17.0.2".to_string()),
+                global_ipv6_address: Some("".to_string()),
+                ..Default::default()
+            },
+        );
+        let info = ContainerInspectResponse {
+            config: Some(ContainerConfig { labels: Some(labels), ..Default::default() }),
+            network_settings: Some(NetworkSettings {
+                networks: Some(networks),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        let records = get_container_dns_records("grafana", &info);
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].name, "grafana.lan");
+        assert_eq!(records[1].name, "metrics.lan");
+
+        let records = get_container_dns_records("grafana", &ContainerInspectResponse::default());
+        assert!(records.is_empty());
+    }
+}
@@ -43,6 +43,25 @@ pub async fn update_flow_matchs(rules: Vec<FlowConfig>, old_rules: Vec<FlowConfi
     // }
 }
 
+/// 同步 flow 覆盖的 NAT 映射与过滤行为
+pub fn update_flow_nat_behavior(rules: &[FlowConfig], old_rules: &[FlowConfig]) {
+    let mut behaviors = HashMap::new();
+    for rule in rules.iter().filter(|r| r.enable) {
+        if let Some(behavior) = rule.nat_behavior {
+            behaviors.insert(rule.flow_id, behavior);
+        }
+    }
+
+    for old_rule in old_rules {
+        if !behaviors.contains_key(&old_rule.flow_id) {
+            landscape_ebpf::map_setting::nat::del_flow_nat_behavior(old_rule.flow_id);
+        }
+    }
+    for (flow_id, behavior) in behaviors {
+        landscape_ebpf::map_setting::nat::set_flow_nat_behavior(flow_id, &behavior);
+    }
+}
+
 fn flow_rule_into_hash(rules: Vec<FlowConfig>) -> HashMap<PacketMatchMark, u32> {
     let mut new_mark_infos = HashMap::new();
 
@@ -32,6 +32,7 @@ pub mod pppoe_client;
 pub mod route;
 pub mod routerstatus;
 pub mod service;
+pub mod stun;
 pub mod sys_service;
 pub mod wifi;
 
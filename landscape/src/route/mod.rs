@@ -1,5 +1,9 @@
 use core::mem::drop;
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::HashMap,
+    net::{IpAddr, Ipv4Addr},
+    sync::Arc,
+};
 
 use landscape_common::{
     config::FlowId,
@@ -9,7 +13,7 @@ use landscape_common::{
 };
 use landscape_database::flow_rule::repository::FlowConfigRepository;
 use landscape_ebpf::map_setting::route::{add_lan_route, del_lan_route};
-use tokio::sync::{mpsc, RwLock};
+use tokio::sync::{mpsc, watch, RwLock};
 
 use landscape_common::database::LandscapeDBTrait;
 
@@ -22,6 +26,9 @@ pub struct IpRouteService {
 
     ipv4_lan_ifaces: ShareRwLock<HashMap<String, LanRouteInfo>>,
     ipv6_lan_ifaces: ShareRwLock<HashMap<String, LanRouteInfo>>,
+
+    /// WAN 网卡名称 -> 当前 IPv4 地址
+    ipv4_wan_ips: watch::Sender<HashMap<String, Ipv4Addr>>,
 }
 
 impl IpRouteService {
@@ -35,6 +42,7 @@ impl IpRouteService {
             ipv6_wan_ifaces: Arc::new(RwLock::new(HashMap::new())),
             ipv4_lan_ifaces: Arc::new(RwLock::new(HashMap::new())),
             ipv6_lan_ifaces: Arc::new(RwLock::new(HashMap::new())),
+            ipv4_wan_ips: watch::channel(HashMap::new()).0,
         };
         let route_service = service.clone();
         tokio::spawn(async move {
@@ -82,6 +90,30 @@ impl IpRouteService {
         service
     }
 
+    /// 监听 WAN 网卡 IPv4 地址的变化
+    pub fn subscribe_ipv4_wan_ips(&self) -> watch::Receiver<HashMap<String, Ipv4Addr>> {
+        self.ipv4_wan_ips.subscribe()
+    }
+
+    fn notify_ipv4_wan_ips(&self, wan_ifaces: &HashMap<String, RouteTargetInfo>) {
+        let ips: HashMap<String, Ipv4Addr> = wan_ifaces
+            .values()
+            .filter(|info| !info.is_docker)
+            .filter_map(|info| match info.iface_ip {
+                IpAddr::V4(ip) if !ip.is_unspecified() => Some((info.iface_name.clone(), ip)),
+                _ => None,
+            })
+            .collect();
+        self.ipv4_wan_ips.send_if_modified(|current| {
+            if *current == ips {
+                false
+            } else {
+                *current = ips;
+                true
+            }
+        });
+    }
+
     pub async fn remove_all_wan_docker(&self) {
         {
             let mut lock = self.ipv4_wan_ifaces.write().await;
@@ -106,6 +138,15 @@ impl IpRouteService {
         }
     }
 
+    /// 地址是否属于某个开启了 LAN 路由的网卡网段
+    pub async fn is_lan_addr(&self, addr: IpAddr) -> bool {
+        let lan_ifaces = match addr.to_canonical() {
+            IpAddr::V4(_) => &self.ipv4_lan_ifaces,
+            IpAddr::V6(_) => &self.ipv6_lan_ifaces,
+        };
+        lan_ifaces.read().await.values().any(|info| info.contains(addr))
+    }
+
     pub async fn insert_ipv6_lan_route(&self, key: &str, info: LanRouteInfo) {
         let mut lock = self.ipv6_lan_ifaces.write().await;
         add_lan_route(info.clone());
@@ -159,6 +200,7 @@ impl IpRouteService {
         if let Some(old_info) = lock.insert(key.to_string(), info) {
             refresh_default_router = refresh_default_router || old_info.default_route;
         }
+        self.notify_ipv4_wan_ips(&lock);
         drop(lock);
         self.refresh_ipv4_target_map(target).await;
         if refresh_default_router {
@@ -169,6 +211,7 @@ impl IpRouteService {
     pub async fn remove_ipv4_wan_route(&self, key: &str) {
         let mut lock = self.ipv4_wan_ifaces.write().await;
         let result = lock.remove(key);
+        self.notify_ipv4_wan_ips(&lock);
         drop(lock);
         if let Some(info) = result {
             self.refresh_ipv4_target_map(info.get_flow_target()).await;
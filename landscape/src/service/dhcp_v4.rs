@@ -16,6 +16,7 @@ use landscape_common::{
 };
 use landscape_database::dhcp_v4_server::repository::DHCPv4ServerRepository;
 use landscape_database::provider::LandscapeDBServiceProvider;
+use landscape_dns::local_zone::LocalZone;
 use tokio::sync::broadcast;
 use tokio::sync::RwLock;
 
@@ -27,13 +28,15 @@ use crate::route::IpRouteService;
 pub struct DHCPv4ServerStarter {
     iface_lease_map: Arc<RwLock<HashMap<String, Arc<RwLock<DHCPv4OfferInfo>>>>>,
     route_service: IpRouteService,
+    local_zone: LocalZone,
 }
 
 impl DHCPv4ServerStarter {
-    pub fn new(route_service: IpRouteService) -> DHCPv4ServerStarter {
+    pub fn new(route_service: IpRouteService, local_zone: LocalZone) -> DHCPv4ServerStarter {
         DHCPv4ServerStarter {
             route_service,
             iface_lease_map: Arc::new(RwLock::new(HashMap::new())),
+            local_zone,
         }
     }
 }
@@ -58,6 +61,7 @@ impl ServiceStarterTrait for DHCPv4ServerStarter {
                 };
 
                 let route_service = self.route_service.clone();
+                let local_zone = self.local_zone.clone();
                 let status = service_status.clone();
                 tokio::spawn(async move {
                     let info = LanRouteInfo {
@@ -74,6 +78,7 @@ impl ServiceStarterTrait for DHCPv4ServerStarter {
                         config.config,
                         status,
                         assigned_ips,
+                        local_zone,
                     )
                     .await;
                     route_service.remove_ipv4_lan_route(&iface_name).await;
@@ -118,9 +123,10 @@ impl DHCPv4ServerManagerService {
         route_service: IpRouteService,
         store_service: LandscapeDBServiceProvider,
         mut dev_observer: broadcast::Receiver<IfaceObserverAction>,
+        local_zone: LocalZone,
     ) -> Self {
         let store = store_service.dhcp_v4_server_store();
-        let server_starter = DHCPv4ServerStarter::new(route_service);
+        let server_starter = DHCPv4ServerStarter::new(route_service, local_zone);
         let service =
             ServiceManager::init(store.list().await.unwrap(), server_starter.clone()).await;
 
@@ -1,9 +1,16 @@
+use std::net::{IpAddr, Ipv6Addr};
+use std::time::Duration;
+
 use landscape_common::database::{LandscapeDBTrait, LandscapeServiceDBTrait};
+use landscape_common::global_const::{LDIAPrefix, LD_PD_WATCHES};
 use landscape_common::observer::IfaceObserverAction;
 use landscape_common::service::controller_service::ControllerService;
 use landscape_common::service::service_manager::ServiceManager;
 use landscape_common::{
-    config::nat::{NatConfig, NatServiceConfig},
+    config::nat::{
+        Nat6Mode, NatBehaviorTestResult, NatConfig, NatServiceConfig, NatSession, NatSessionKey,
+        NatSessionQuery, Nptv6Translation, SnatPoolConfig,
+    },
     service::{
         service_manager::ServiceHandler, DefaultServiceStatus, DefaultWatchServiceStatus,
         ServiceStatus,
@@ -11,9 +18,17 @@ use landscape_common::{
 };
 use landscape_database::nat::repository::NatServiceRepository;
 use landscape_database::provider::LandscapeDBServiceProvider;
-use tokio::sync::{broadcast, oneshot};
+use landscape_ebpf::map_setting::nat::{
+    del_nat6_config, del_snat_pool, set_nat66_masquerade_config, set_nat_ct_config,
+    set_nptv6_config, set_snat_pool,
+};
+use tokio::sync::{broadcast, oneshot, watch};
 
 use crate::iface::get_iface_by_name;
+use crate::iface::ip::addresses_by_iface_name;
+
+/// Masquerade 模式下重新检查 WAN 地址的间隔
+const NAT66_WAN_ADDR_CHECK_INTERVAL: Duration = Duration::from_secs(30);
 
 #[derive(Clone)]
 pub struct NatService;
@@ -32,9 +47,12 @@ impl ServiceHandler for NatService {
                 let status_clone = service_status.clone();
                 tokio::spawn(async move {
                     create_nat_service(
+                        config.iface_name,
                         iface.index as i32,
                         iface.mac.is_some(),
                         config.nat_config,
+                        config.nat6_mode,
+                        config.snat_pool,
                         status_clone,
                     )
                     .await
@@ -49,12 +67,16 @@ impl ServiceHandler for NatService {
 }
 
 pub async fn create_nat_service(
+    iface_name: String,
     ifindex: i32,
     has_mac: bool,
     nat_config: NatConfig,
+    nat6_mode: Nat6Mode,
+    snat_pool: SnatPoolConfig,
     service_status: DefaultWatchServiceStatus,
 ) {
     service_status.just_change_status(ServiceStatus::Staring);
+    set_snat_pool(ifindex as u32, &snat_pool);
     let (tx, rx) = oneshot::channel::<()>();
     let (other_tx, other_rx) = oneshot::channel::<()>();
     service_status.just_change_status(ServiceStatus::Running);
@@ -72,17 +94,118 @@ pub async fn create_nat_service(
         tracing::info!("向外部线程发送解除阻塞信号");
         let _ = other_tx.send(());
     });
+    if nat6_mode != Nat6Mode::Disabled {
+        tokio::spawn(nat6_binding_loop(
+            iface_name,
+            ifindex as u32,
+            nat6_mode,
+            service_status.clone(),
+        ));
+    }
     let _ = other_rx.await;
     tracing::info!("结束外部线程阻塞");
+    del_snat_pool(ifindex as u32, &snat_pool);
     service_status.just_change_status(ServiceStatus::Stop);
 }
 
+/// 在 WAN 网卡的委托前缀 (或全局地址) 变化时重新绑定 IPv6 转换配置
+async fn nat6_binding_loop(
+    iface_name: String,
+    ifindex: u32,
+    nat6_mode: Nat6Mode,
+    service_status: DefaultWatchServiceStatus,
+) {
+    let mut ia_prefix_watch = LD_PD_WATCHES.get_ia_prefix(&iface_name).await;
+    let mut service_status_subscribe = service_status.subscribe();
+    let mut interval = tokio::time::interval(NAT66_WAN_ADDR_CHECK_INTERVAL);
+    let mut current_wan_addr: Option<Ipv6Addr> = None;
+
+    apply_nptv6_prefix(&iface_name, ifindex, &nat6_mode, &ia_prefix_watch);
+    loop {
+        tokio::select! {
+            change_result = ia_prefix_watch.changed() => {
+                if let Err(_) = change_result {
+                    tracing::error!("get change result error. exit loop");
+                    break;
+                }
+                tracing::info!("{iface_name} IA_PREFIX update, rebind nat6");
+                apply_nptv6_prefix(&iface_name, ifindex, &nat6_mode, &ia_prefix_watch);
+                interval.reset_immediately();
+            }
+            _ = interval.tick(), if nat6_mode == Nat6Mode::Masquerade => {
+                let wan_addr = find_global_ipv6_addr(&iface_name).await;
+                if wan_addr != current_wan_addr {
+                    tracing::info!("{iface_name} nat66 wan address: {wan_addr:?}");
+                    match wan_addr {
+                        Some(addr) => set_nat66_masquerade_config(ifindex, addr),
+                        None => del_nat6_config(ifindex),
+                    }
+                    current_wan_addr = wan_addr;
+                }
+            }
+            change_result = service_status_subscribe.changed() => {
+                if let Err(_) = change_result {
+                    break;
+                }
+                if service_status.is_exit() {
+                    break;
+                }
+            }
+        }
+    }
+    del_nat6_config(ifindex);
+}
+
+fn apply_nptv6_prefix(
+    iface_name: &str,
+    ifindex: u32,
+    nat6_mode: &Nat6Mode,
+    ia_prefix_watch: &watch::Receiver<Option<LDIAPrefix>>,
+) {
+    let Nat6Mode::Nptv6 { ula_prefix } = nat6_mode else {
+        return;
+    };
+    let ia_prefix = ia_prefix_watch.borrow().clone();
+    let Some(ia_prefix) = ia_prefix else {
+        del_nat6_config(ifindex);
+        return;
+    };
+    match Nptv6Translation::new(*ula_prefix, ia_prefix.prefix_ip, ia_prefix.prefix_len) {
+        Ok(translation) => {
+            tracing::info!("{iface_name} NPTv6 binding: {translation:?}");
+            set_nptv6_config(ifindex, &translation);
+        }
+        Err(e) => {
+            tracing::error!("{iface_name} NPTv6 binding error: {e}");
+            del_nat6_config(ifindex);
+        }
+    }
+}
+
+/// WAN 网卡上的全局单播 IPv6 地址 (排除链路本地地址与 ULA)
+async fn find_global_ipv6_addr(iface_name: &str) -> Option<Ipv6Addr> {
+    addresses_by_iface_name(iface_name.to_string()).await.into_iter().find_map(|info| {
+        match info.address {
+            IpAddr::V6(addr)
+                if !addr.is_unicast_link_local()
+                    && !addr.is_unique_local()
+                    && !addr.is_loopback()
+                    && !addr.is_multicast() =>
+            {
+                Some(addr)
+            }
+            _ => None,
+        }
+    })
+}
+
 #[derive(Clone)]
 pub struct NatServiceManagerService {
     store: NatServiceRepository,
     service: ServiceManager<NatService>,
 }
 
+#[async_trait::async_trait]
 impl ControllerService for NatServiceManagerService {
     type Id = String;
     type Config = NatServiceConfig;
@@ -96,6 +219,33 @@ impl ControllerService for NatServiceManagerService {
     fn get_repository(&self) -> &Self::DatabseAction {
         &self.store
     }
+
+    /// 只修改了连接跟踪参数时直接更新运行中的服务, 避免重启丢失已有的映射
+    async fn handle_service_config(&self, config: NatServiceConfig) {
+        let old_config = self.store.find_by_iface_name(config.iface_name.clone()).await.unwrap();
+        let is_running = self
+            .service
+            .get_all_status()
+            .await
+            .get(&config.iface_name)
+            .map(|status| status.is_running())
+            .unwrap_or(false);
+
+        if let Some(old_config) = old_config {
+            if is_running && config.enable && config.is_ct_only_change(&old_config) {
+                if let Some(iface) = get_iface_by_name(&config.iface_name).await {
+                    tracing::info!("update {} nat ct config without restart", config.iface_name);
+                    set_nat_ct_config(iface.index, &config.nat_config);
+                    self.store.set(config).await.unwrap();
+                    return;
+                }
+            }
+        }
+
+        if let Ok(()) = self.service.update_service(config.clone()).await {
+            self.store.set(config).await.unwrap();
+        }
+    }
 }
 
 impl NatServiceManagerService {
@@ -130,4 +280,54 @@ impl NatServiceManagerService {
         let store = store_service.nat_service_store();
         Self { service, store }
     }
+
+    /// 使用 STUN 服务器检测当前 NAT 的映射与过滤行为
+    pub async fn behavior_test(
+        &self,
+        iface_name: String,
+        server: String,
+    ) -> Result<NatBehaviorTestResult, String> {
+        let Some(config) = self.store.find_by_iface_name(iface_name.clone()).await.unwrap() else {
+            return Err(format!("{iface_name} nat service config not found"));
+        };
+        if !config.enable {
+            return Err(format!("{iface_name} nat service is disabled"));
+        }
+        // 使用 NAT 端口范围内的随机端口, 保证数据包经过 NAT 处理
+        let range = config.nat_config.udp_range;
+        if range.start >= range.end {
+            return Err("invalid nat udp range".to_string());
+        }
+        let local_port = rand::random_range(range.start..range.end);
+        crate::stun::nat_behavior_test(&iface_name, &server, local_port).await
+    }
+
+    /// 列出网卡 NAT 服务当前的动态映射, 服务未运行时返回 None
+    pub async fn list_sessions(
+        &self,
+        iface_name: String,
+        query: NatSessionQuery,
+    ) -> Option<Vec<NatSession>> {
+        let iface = get_iface_by_name(&iface_name).await?;
+        // 需要遍历整个映射表, 不能阻塞异步运行时
+        tokio::task::spawn_blocking(move || {
+            landscape_ebpf::nat::list_nat_sessions(iface.index, &query)
+        })
+        .await
+        .ok()
+        .flatten()
+    }
+
+    /// 删除一条动态映射, 返回是否找到了该映射
+    pub async fn evict_session(&self, iface_name: String, key: NatSessionKey) -> bool {
+        let Some(iface) = get_iface_by_name(&iface_name).await else {
+            return false;
+        };
+        // 删除后需要遍历 timer 重新统计主机的映射数量
+        tokio::task::spawn_blocking(move || {
+            landscape_ebpf::nat::evict_nat_session(iface.index, &key)
+        })
+        .await
+        .unwrap_or(false)
+    }
 }
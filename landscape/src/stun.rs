@@ -0,0 +1,307 @@
+//! 简单的 STUN Binding 客户端, 用于按照 RFC 5780 检测 NAT 的映射与过滤行为
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+
+use landscape_common::config::nat::{NatBehaviorTestResult, NatEndpointPolicy};
+use socket2::{Domain, Protocol, Socket, Type};
+use tokio::net::UdpSocket;
+
+const STUN_MAGIC_COOKIE: u32 = 0x2112_A442;
+const STUN_HEADER_LEN: usize = 20;
+
+const BINDING_REQUEST: u16 = 0x0001;
+const BINDING_SUCCESS_RESPONSE: u16 = 0x0101;
+
+const ATTR_MAPPED_ADDRESS: u16 = 0x0001;
+const ATTR_CHANGE_REQUEST: u16 = 0x0003;
+const ATTR_CHANGED_ADDRESS: u16 = 0x0005;
+const ATTR_XOR_MAPPED_ADDRESS: u16 = 0x0020;
+const ATTR_OTHER_ADDRESS: u16 = 0x802C;
+
+const CHANGE_IP: u32 = 0x04;
+const CHANGE_PORT: u32 = 0x02;
+
+const FAMILY_IPV4: u8 = 0x01;
+const FAMILY_IPV6: u8 = 0x02;
+
+/// 单次请求的等待时间与重传次数
+const REQUEST_TIMEOUT: Duration = Duration::from_millis(800);
+const REQUEST_RETRIES: usize = 3;
+
+#[derive(Debug, Default, Clone, PartialEq)]
+struct BindingResponse {
+    mapped: Option<SocketAddr>,
+    other: Option<SocketAddr>,
+}
+
+fn build_binding_request(transaction_id: &[u8; 12], change: u32) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(STUN_HEADER_LEN + 8);
+    buf.extend_from_slice(&BINDING_REQUEST.to_be_bytes());
+    let attr_len: u16 = if change != 0 { 8 } else { 0 };
+    buf.extend_from_slice(&attr_len.to_be_bytes());
+    buf.extend_from_slice(&STUN_MAGIC_COOKIE.to_be_bytes());
+    buf.extend_from_slice(transaction_id);
+    if change != 0 {
+        buf.extend_from_slice(&ATTR_CHANGE_REQUEST.to_be_bytes());
+        buf.extend_from_slice(&4u16.to_be_bytes());
+        buf.extend_from_slice(&change.to_be_bytes());
+    }
+    buf
+}
+
+fn parse_address(value: &[u8], xor: bool, transaction_id: &[u8; 12]) -> Option<SocketAddr> {
+    if value.len() < 4 {
+        return None;
+    }
+    let cookie = STUN_MAGIC_COOKIE.to_be_bytes();
+    let mut port = u16::from_be_bytes([value[2], value[3]]);
+    if xor {
+        port ^= (STUN_MAGIC_COOKIE >> 16) as u16;
+    }
+    let ip = match value[1] {
+        FAMILY_IPV4 if value.len() >= 8 => {
+            let mut octets: [u8; 4] = value[4..8].try_into().ok()?;
+            if xor {
+                for (i, b) in octets.iter_mut().enumerate() {
+                    *b ^= cookie[i];
+                }
+            }
+            IpAddr::V4(Ipv4Addr::from(octets))
+        }
+        FAMILY_IPV6 if value.len() >= 20 => {
+            let mut octets: [u8; 16] = value[4..20].try_into().ok()?;
+            if xor {
+                let mask = cookie.iter().chain(transaction_id.iter());
+                for (b, m) in octets.iter_mut().zip(mask) {
+                    *b ^= m;
+                }
+            }
+            IpAddr::V6(Ipv6Addr::from(octets))
+        }
+        _ => return None,
+    };
+    Some(SocketAddr::new(ip, port))
+}
+
+fn parse_binding_response(buf: &[u8], transaction_id: &[u8; 12]) -> Option<BindingResponse> {
+    if buf.len() < STUN_HEADER_LEN {
+        return None;
+    }
+    if u16::from_be_bytes([buf[0], buf[1]]) != BINDING_SUCCESS_RESPONSE
+        || u32::from_be_bytes([buf[4], buf[5], buf[6], buf[7]]) != STUN_MAGIC_COOKIE
+        || &buf[8..20] != transaction_id
+    {
+        return None;
+    }
+    let msg_len = u16::from_be_bytes([buf[2], buf[3]]) as usize;
+    let end = (STUN_HEADER_LEN + msg_len).min(buf.len());
+
+    let mut response = BindingResponse::default();
+    let mut offset = STUN_HEADER_LEN;
+    while offset + 4 <= end {
+        let attr_type = u16::from_be_bytes([buf[offset], buf[offset + 1]]);
+        let attr_len = u16::from_be_bytes([buf[offset + 2], buf[offset + 3]]) as usize;
+        let value_start = offset + 4;
+        if value_start + attr_len > end {
+            break;
+        }
+        let value = &buf[value_start..value_start + attr_len];
+        match attr_type {
+            ATTR_XOR_MAPPED_ADDRESS => {
+                response.mapped = parse_address(value, true, transaction_id).or(response.mapped);
+            }
+            ATTR_MAPPED_ADDRESS if response.mapped.is_none() => {
+                response.mapped = parse_address(value, false, transaction_id);
+            }
+            ATTR_OTHER_ADDRESS => {
+                response.other = parse_address(value, false, transaction_id).or(response.other);
+            }
+            ATTR_CHANGED_ADDRESS if response.other.is_none() => {
+                response.other = parse_address(value, false, transaction_id);
+            }
+            _ => {}
+        }
+        // 属性按 4 字节对齐
+        offset = value_start + attr_len.div_ceil(4) * 4;
+    }
+    Some(response)
+}
+
+/// 发送 Binding 请求, 响应可以来自任意地址 (CHANGE-REQUEST)
+async fn binding(socket: &UdpSocket, server: SocketAddr, change: u32) -> Option<BindingResponse> {
+    let transaction_id: [u8; 12] = rand::random();
+    let request = build_binding_request(&transaction_id, change);
+    let mut buf = vec![0u8; 1500];
+
+    for _ in 0..REQUEST_RETRIES {
+        if let Err(e) = socket.send_to(&request, server).await {
+            tracing::error!("send stun request to {server} error: {e:?}");
+            return None;
+        }
+        let result = tokio::time::timeout(REQUEST_TIMEOUT, async {
+            loop {
+                let Ok((len, _)) = socket.recv_from(&mut buf).await else {
+                    return None;
+                };
+                if let Some(response) = parse_binding_response(&buf[..len], &transaction_id) {
+                    return Some(response);
+                }
+            }
+        })
+        .await;
+        if let Ok(response) = result {
+            return response;
+        }
+    }
+    None
+}
+
+/// RFC 5780 4.3 的映射行为检测
+/// 按照向其他地址与端口发送请求时得到的映射地址判断, 没有响应时无法判断
+fn classify_mapping(
+    mapped: SocketAddr,
+    other_ip_mapped: Option<SocketAddr>,
+    other_port_mapped: Option<SocketAddr>,
+) -> Option<NatEndpointPolicy> {
+    let other_ip_mapped = other_ip_mapped?;
+    if other_ip_mapped == mapped {
+        return Some(NatEndpointPolicy::EndpointIndependent);
+    }
+    let other_port_mapped = other_port_mapped?;
+    Some(if other_port_mapped == mapped {
+        NatEndpointPolicy::AddressDependent
+    } else {
+        NatEndpointPolicy::AddressAndPortDependent
+    })
+}
+
+/// 通过 WAN 网卡检测 NAT 行为
+/// 本地端口需要位于 NAT 的端口范围中, 否则数据包不会经过 NAT
+pub async fn nat_behavior_test(
+    iface_name: &str,
+    server: &str,
+    local_port: u16,
+) -> Result<NatBehaviorTestResult, String> {
+    let server_addr = tokio::net::lookup_host(server)
+        .await
+        .map_err(|e| format!("resolve {server} error: {e}"))?
+        .find(|addr| addr.is_ipv4())
+        .ok_or_else(|| format!("{server} has no ipv4 address"))?;
+
+    let socket =
+        Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP)).map_err(|e| e.to_string())?;
+    socket.set_nonblocking(true).map_err(|e| e.to_string())?;
+    socket.bind_device(Some(iface_name.as_bytes())).map_err(|e| e.to_string())?;
+    let bind_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), local_port);
+    socket.bind(&bind_addr.into()).map_err(|e| format!("bind {bind_addr} error: {e}"))?;
+    let socket = UdpSocket::from_std(socket.into()).map_err(|e| e.to_string())?;
+
+    let mut result = NatBehaviorTestResult {
+        server_addr: server_addr.to_string(),
+        local_port,
+        mapped_addr: None,
+        other_addr: None,
+        mapping: None,
+        filtering: None,
+    };
+
+    let Some(first) = binding(&socket, server_addr, 0).await else {
+        return Err(format!("no response from {server_addr}"));
+    };
+    let Some(mapped) = first.mapped else {
+        return Err("stun response has no mapped address".to_string());
+    };
+    result.mapped_addr = Some(mapped.to_string());
+    // 服务器不支持 RFC 5780 时无法继续检测
+    let Some(other) = first.other else {
+        return Ok(result);
+    };
+    result.other_addr = Some(other.to_string());
+
+    // 过滤行为需要在向其他地址发送数据之前检测, 否则会产生额外的访问许可
+    result.filtering =
+        Some(if binding(&socket, server_addr, CHANGE_IP | CHANGE_PORT).await.is_some() {
+            NatEndpointPolicy::EndpointIndependent
+        } else if binding(&socket, server_addr, CHANGE_PORT).await.is_some() {
+            NatEndpointPolicy::AddressDependent
+        } else {
+            NatEndpointPolicy::AddressAndPortDependent
+        });
+
+    let other_ip = SocketAddr::new(other.ip(), server_addr.port());
+    let other_ip_mapped = binding(&socket, other_ip, 0).await.and_then(|r| r.mapped);
+    let other_port_mapped = match other_ip_mapped {
+        Some(addr) if addr != mapped => {
+            let other_port = SocketAddr::new(server_addr.ip(), other.port());
+            binding(&socket, other_port, 0).await.and_then(|r| r.mapped)
+        }
+        _ => None,
+    };
+    result.mapping = classify_mapping(mapped, other_ip_mapped, other_port_mapped);
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use landscape_common::config::nat::NatEndpointPolicy;
+
+    use super::{
+        build_binding_request, classify_mapping, parse_binding_response, STUN_MAGIC_COOKIE,
+    };
+
+    #[test]
+    fn test_parse_binding_response() {
+        let tid = [7u8; 12];
+        let request = build_binding_request(&tid, 0x06);
+        assert_eq!(request.len(), 28);
+        assert_eq!(&request[8..20], &tid);
+
+        let mapped: SocketAddr = "203.0.113.5:40000".parse().unwrap();
+        let other: SocketAddr = "198.51.100.2:3479".parse().unwrap();
+
+        let mut resp = vec![0x01, 0x01, 0x00, 24];
+        resp.extend_from_slice(&STUN_MAGIC_COOKIE.to_be_bytes());
+        resp.extend_from_slice(&tid);
+        // XOR-MAPPED-ADDRESS
+        resp.extend_from_slice(&[0x00, 0x20, 0x00, 0x08, 0x00, 0x01]);
+        resp.extend_from_slice(&(40000u16 ^ (STUN_MAGIC_COOKIE >> 16) as u16).to_be_bytes());
+        resp.extend_from_slice(
+            &(u32::from_be_bytes([203, 0, 113, 5]) ^ STUN_MAGIC_COOKIE).to_be_bytes(),
+        );
+        // OTHER-ADDRESS
+        resp.extend_from_slice(&[0x80, 0x2C, 0x00, 0x08, 0x00, 0x01]);
+        resp.extend_from_slice(&3479u16.to_be_bytes());
+        resp.extend_from_slice(&[198, 51, 100, 2]);
+
+        let parsed = parse_binding_response(&resp, &tid).unwrap();
+        assert_eq!(parsed.mapped, Some(mapped));
+        assert_eq!(parsed.other, Some(other));
+
+        // 事务 ID 不匹配
+        assert!(parse_binding_response(&resp, &[0u8; 12]).is_none());
+    }
+    #[test]
+    fn test_classify_mapping() {
+        let mapped: SocketAddr = "203.0.113.5:40000".parse().unwrap();
+        let changed: SocketAddr = "203.0.113.5:40001".parse().unwrap();
+
+        assert_eq!(
+            classify_mapping(mapped, Some(mapped), None),
+            Some(NatEndpointPolicy::EndpointIndependent)
+        );
+        assert_eq!(
+            classify_mapping(mapped, Some(changed), Some(mapped)),
+            Some(NatEndpointPolicy::AddressDependent)
+        );
+        assert_eq!(
+            classify_mapping(mapped, Some(changed), Some(changed)),
+            Some(NatEndpointPolicy::AddressAndPortDependent)
+        );
+        // 没有响应时不能判断
+        assert_eq!(classify_mapping(mapped, None, None), None);
+        assert_eq!(classify_mapping(mapped, Some(changed), None), None);
+    }
+}
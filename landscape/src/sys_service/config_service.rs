@@ -19,10 +19,12 @@ impl LandscapeConfigService {
             ifaces: self.store.iface_store().list().await.unwrap(),
             ipconfigs: self.store.iface_ip_service_store().list().await.unwrap(),
             nats: self.store.nat_service_store().list().await.unwrap(),
+            port_forwards: self.store.port_forward_rule_store().list().await.unwrap(),
             marks: self.store.flow_wan_service_store().list().await.unwrap(),
             pppds: self.store.pppd_service_store().list().await.unwrap(),
             flow_rules: self.store.flow_rule_store().list().await.unwrap(),
             dns_rules: self.store.dns_rule_store().list().await.unwrap(),
+            dns_upstream_groups: self.store.dns_upstream_group_store().list().await.unwrap(),
             dst_ip_mark: self.store.dst_ip_rule_store().list().await.unwrap(),
             dhcpv6pds: self.store.dhcp_v6_client_store().list().await.unwrap(),
             icmpras: self.store.ra_service_store().list().await.unwrap(),
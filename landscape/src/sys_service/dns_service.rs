@@ -1,25 +1,74 @@
-use std::time::Instant;
+use std::{collections::HashMap, net::SocketAddr, time::Instant};
 
 use landscape_common::{
+    config::{
+        dns::{DNSResolveMode, DNSRuntimeRule, LocalZoneRecord},
+        DnsRuntimeConfig,
+    },
     event::dns::DnsEvent,
+    metric::dns::DnsMetricManager,
     service::{
         controller_service::{ConfigController, FlowConfigController},
         DefaultWatchServiceStatus,
     },
 };
-use landscape_dns::diff_server::{CheckDnsReq, CheckDnsResult, LandscapeFiffFlowDnsService};
+use landscape_dns::{
+    diff_server::{
+        CheckDnsReq, CheckDnsResult, DnsCacheStats, LandscapeFiffFlowDnsService, TraceDnsReq,
+        TraceDnsResult,
+    },
+    local_zone::LocalZone,
+    upstream::DnsUpstreamHealth,
+};
+use rustls::ServerConfig;
 use tokio::sync::mpsc;
 
 use crate::config_service::{
-    dns_rule::DNSRuleService, flow_rule::FlowRuleService, geo_site_service::GeoSiteService,
+    dns_rule::DNSRuleService, dns_upstream_group::DNSUpstreamGroupService,
+    flow_rule::FlowRuleService, geo_ip_service::GeoIpService, geo_site_service::GeoSiteService,
 };
 
+/// 为使用上游组的规则填充组配置
+async fn attach_upstream_groups(
+    upstream_group_service: &DNSUpstreamGroupService,
+    mut dns_rules: Vec<DNSRuntimeRule>,
+) -> Vec<DNSRuntimeRule> {
+    let groups: HashMap<_, _> = upstream_group_service
+        .list()
+        .await
+        .into_iter()
+        .filter_map(|group| group.id.map(|id| (id, group)))
+        .collect();
+    for rule in dns_rules.iter_mut() {
+        if let DNSResolveMode::Group { group_id, .. } = &rule.resolve_mode {
+            rule.upstream_group = groups.get(group_id).cloned();
+        }
+    }
+    dns_rules
+}
+
+/// 展开规则中用于过滤应答的 geoip / CIDR
+async fn attach_bogus_nets(
+    geo_ip_service: &GeoIpService,
+    mut dns_rules: Vec<DNSRuntimeRule>,
+) -> Vec<DNSRuntimeRule> {
+    for rule in dns_rules.iter_mut() {
+        if !rule.answer_filter.bogus_sources.is_empty() {
+            rule.bogus_nets =
+                geo_ip_service.expand_sources(&rule.answer_filter.bogus_sources).await;
+        }
+    }
+    dns_rules
+}
+
 #[derive(Clone)]
 pub struct LandscapeDnsService {
     dns_service: LandscapeFiffFlowDnsService,
     dns_rule_service: DNSRuleService,
     flow_rule_service: FlowRuleService,
     geo_site_service: GeoSiteService,
+    upstream_group_service: DNSUpstreamGroupService,
+    geo_ip_service: GeoIpService,
 }
 
 impl LandscapeDnsService {
@@ -28,23 +77,37 @@ impl LandscapeDnsService {
         dns_rule_service: DNSRuleService,
         flow_rule_service: FlowRuleService,
         geo_site_service: GeoSiteService,
+        upstream_group_service: DNSUpstreamGroupService,
+        geo_ip_service: GeoIpService,
+        dns_config: DnsRuntimeConfig,
+        tls_config: ServerConfig,
+        dns_metric: DnsMetricManager,
     ) -> Self {
-        let dns_service = LandscapeFiffFlowDnsService::new().await;
+        let dns_service =
+            LandscapeFiffFlowDnsService::new(dns_config, Some(tls_config), dns_metric).await;
         let dns_rules = dns_rule_service.list().await;
         let dns_rules = geo_site_service.convert_config_to_runtime_rule(dns_rules).await;
+        let dns_rules = attach_upstream_groups(&upstream_group_service, dns_rules).await;
+        let dns_rules = attach_bogus_nets(&geo_ip_service, dns_rules).await;
 
-        dns_service.restart(53).await;
+        // 先恢复缓存以及 flow 的 DNS 标记, 再开始接收请求
         dns_service.init_handle(dns_rules).await;
         dns_service.update_flow_map(&flow_rule_service.list().await).await;
+        dns_service.restart().await;
 
         let dns_rule_service_clone = dns_rule_service.clone();
         let flow_rule_service_clone = flow_rule_service.clone();
         let dns_service_clone = dns_service.clone();
         let geo_site_service_clone = geo_site_service.clone();
+        let upstream_group_service_clone = upstream_group_service.clone();
+        let geo_ip_service_clone = geo_ip_service.clone();
         tokio::spawn(async move {
             while let Some(event) = receiver.recv().await {
                 match event {
-                    DnsEvent::RuleUpdated { flow_id: None } | DnsEvent::GeositeUpdated => {
+                    DnsEvent::RuleUpdated { flow_id: None }
+                    | DnsEvent::GeositeUpdated
+                    | DnsEvent::DomainListUpdated
+                    | DnsEvent::GeoIpUpdated => {
                         tracing::info!("refresh dns rule");
                         let time = Instant::now();
                         let dns_rules = dns_rule_service_clone.list().await;
@@ -52,6 +115,9 @@ impl LandscapeDnsService {
 
                         let dns_rules =
                             geo_site_service_clone.convert_config_to_runtime_rule(dns_rules).await;
+                        let dns_rules =
+                            attach_upstream_groups(&upstream_group_service_clone, dns_rules).await;
+                        let dns_rules = attach_bogus_nets(&geo_ip_service_clone, dns_rules).await;
                         tracing::info!("convert rule: {:?}", time.elapsed().as_secs());
 
                         dns_service_clone.init_handle(dns_rules).await;
@@ -67,6 +133,9 @@ impl LandscapeDnsService {
                         let dns_rules = geo_site_service_clone
                             .convert_config_to_runtime_rule(flow_dns_rules)
                             .await;
+                        let dns_rules =
+                            attach_upstream_groups(&upstream_group_service_clone, dns_rules).await;
+                        let dns_rules = attach_bogus_nets(&geo_ip_service_clone, dns_rules).await;
                         tracing::info!("convert rule: {:?}", time.elapsed().as_secs());
 
                         dns_service_clone.init_handle(dns_rules).await;
@@ -86,6 +155,8 @@ impl LandscapeDnsService {
             dns_rule_service,
             flow_rule_service,
             geo_site_service,
+            upstream_group_service,
+            geo_ip_service,
         }
     }
 
@@ -97,17 +168,51 @@ impl LandscapeDnsService {
         let dns_rules = self.dns_rule_service.list().await;
         let flow_rules = self.flow_rule_service.list().await;
         let dns_rules = self.geo_site_service.convert_config_to_runtime_rule(dns_rules).await;
+        let dns_rules = attach_upstream_groups(&self.upstream_group_service, dns_rules).await;
+        let dns_rules = attach_bogus_nets(&self.geo_ip_service, dns_rules).await;
         // TODO 重置 Flow 相关 map 信息
         self.dns_service.init_handle(dns_rules).await;
         self.dns_service.update_flow_map(&flow_rules).await;
-        self.dns_service.restart(53).await;
+        self.dns_service.restart().await;
     }
 
     pub async fn stop(&self) {
         self.dns_service.stop();
     }
 
+    pub async fn shutdown(&self) {
+        self.dns_service.shutdown().await;
+    }
+
     pub async fn check_domain(&self, req: CheckDnsReq) -> CheckDnsResult {
         self.dns_service.check_domain(req).await
     }
+
+    pub async fn trace_domains(&self, req: TraceDnsReq) -> TraceDnsResult {
+        self.dns_service.trace_domains(req).await
+    }
+
+    pub async fn handle_https_message(
+        &self,
+        message: Vec<u8>,
+        src_addr: SocketAddr,
+    ) -> Option<Vec<u8>> {
+        self.dns_service.handle_https_message(message, src_addr).await
+    }
+
+    pub fn local_zone(&self) -> LocalZone {
+        self.dns_service.local_zone()
+    }
+
+    pub async fn list_local_zone_records(&self) -> Vec<LocalZoneRecord> {
+        self.dns_service.local_zone().list().await
+    }
+
+    pub async fn cache_stats(&self) -> Vec<DnsCacheStats> {
+        self.dns_service.cache_stats().await
+    }
+
+    pub fn upstream_health(&self) -> Vec<DnsUpstreamHealth> {
+        self.dns_service.upstream_health()
+    }
 }